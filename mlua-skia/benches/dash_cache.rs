@@ -0,0 +1,104 @@
+//! Counts heap allocations of 600 frames of marching ants, drawn with
+//! effects of an `AnimatedDash` and with a new dash effect every frame.
+//!
+//! Run with `cargo bench --bench dash_cache`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use mlua::prelude::*;
+use mlua_skia::LuaCanvas;
+use skia_safe::surfaces;
+
+/// Number of animated frames per measured run.
+const FRAMES: usize = 600;
+
+/// Forwards to the system allocator, counting allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Strokes a rect outline with dashes moving by half a unit every frame,
+/// taking the effect of each frame from `effect(phase)`.
+const SCRIPT: &str = r#"
+    local canvas, effect = ...
+    local path = Path():addRect({x = 4, y = 4, width = 56, height = 56})
+    local paint = Paint.make({a = 1}):setStyle({stroke = true}):setStrokeWidth(1)
+    return function(frames)
+        for frame = 1, frames do
+            paint:setPathEffect(effect(frame * 0.5))
+            canvas:drawPath(path, paint)
+        end
+    end
+"#;
+
+fn measure(lua: &Lua, name: &str, effect: &str) -> LuaResult<()> {
+    let surface = surfaces::raster_n32_premul((64, 64)).expect("unable to create surface");
+    let effect: LuaFunction = lua.load(effect).eval()?;
+    let draw: LuaFunction = lua
+        .load(SCRIPT)
+        .call((LuaCanvas::Owned(surface, None), effect))?;
+
+    // grows Lua stacks before measuring
+    draw.call::<_, ()>(1)?;
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    draw.call::<_, ()>(FRAMES)?;
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{:<14} {:>6} frames {:>10.2?} {:>8} allocations ({:.2} per frame)",
+        name,
+        FRAMES,
+        elapsed,
+        allocations,
+        allocations as f64 / FRAMES as f64
+    );
+    Ok(())
+}
+
+fn main() -> LuaResult<()> {
+    let lua = Lua::new();
+    mlua_skia::setup(&lua)?;
+
+    measure(
+        &lua,
+        "AnimatedDash",
+        r#"
+        local dashes = PathEffect.makeDashAnimated({4, 2})
+        return function(phase) return dashes:withPhase(phase) end
+        "#,
+    )?;
+    measure(
+        &lua,
+        "makeDash",
+        r#"
+        return function(phase) return PathEffect.makeDash({4, 2}, phase) end
+        "#,
+    )?;
+    Ok(())
+}
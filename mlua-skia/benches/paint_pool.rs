@@ -0,0 +1,90 @@
+//! Counts heap allocations of `Canvas:drawRect` calls with paint tables,
+//! which take paints from a pool instead of constructing one per call.
+//!
+//! Run with `cargo bench --bench paint_pool`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use mlua::prelude::*;
+use mlua_skia::LuaCanvas;
+use skia_safe::surfaces;
+
+/// Number of draw calls per measured run.
+const CALLS: usize = 10_000;
+
+/// Forwards to the system allocator, counting allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Draws a rect `calls` times with `paint`, which is Lua code evaluating to
+/// a paint argument.
+const SCRIPT: &str = r#"
+    local canvas, paint = ...
+    local rect = {x = 0, y = 0, width = 16, height = 16}
+    return function(calls)
+        for _ = 1, calls do
+            canvas:drawRect(rect, paint)
+        end
+    end
+"#;
+
+fn measure<'lua>(lua: &'lua Lua, name: &str, paint: LuaValue<'lua>) -> LuaResult<()> {
+    let surface = surfaces::raster_n32_premul((64, 64)).expect("unable to create surface");
+    let draw: LuaFunction = lua
+        .load(SCRIPT)
+        .call((LuaCanvas::Owned(surface, None), paint))?;
+
+    // fills the pool and grows Lua stacks before measuring
+    draw.call::<_, ()>(100)?;
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    draw.call::<_, ()>(CALLS)?;
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    println!(
+        "{:<12} {:>8} calls {:>10.2?} {:>8} allocations ({:.2} per call)",
+        name,
+        CALLS,
+        elapsed,
+        allocations,
+        allocations as f64 / CALLS as f64
+    );
+    Ok(())
+}
+
+fn main() -> LuaResult<()> {
+    let lua = Lua::new();
+    mlua_skia::setup(&lua)?;
+
+    let table: LuaTable = lua.load("{r = 1, a = 1}").eval()?;
+    measure(&lua, "table paint", LuaValue::Table(table))?;
+    let handle: LuaValue = lua.load("Paint.make({r = 1, a = 1})").eval()?;
+    measure(&lua, "Paint handle", handle)?;
+    Ok(())
+}
@@ -23,6 +23,11 @@ pub fn lua_methods(
         Err(err) => return err.to_compile_error().into_token_stream().into(),
     };
 
+    match model.generate_help(&options) {
+        Ok(it) => result.extend(it.into_token_stream()),
+        Err(err) => return err.to_compile_error().into_token_stream().into(),
+    };
+
     let register_fn = match model.generate_register_fn(&options) {
         Ok(it) => it,
         Err(err) => return err.to_compile_error().into_token_stream().into(),
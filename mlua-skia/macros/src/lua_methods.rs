@@ -42,6 +42,74 @@ impl MethodSignature {
             .unwrap_or_else(|| snake_to_camel(&self.name))
     }
 
+    /// Name of the call used in argument errors, e.g. `Canvas:drawRect` for
+    /// methods and `Image.load` for static functions.
+    pub fn call_name(&self, type_name: Option<&str>) -> String {
+        let type_name = match type_name {
+            Some(it) => it,
+            None => return self.lua_name(),
+        };
+        if self.options.constructor {
+            return type_name.to_string();
+        }
+        let separator = match self.kind {
+            SignatureKind::Method { .. } => ':',
+            SignatureKind::Function { .. } => '.',
+        };
+        format!("{}{}{}", type_name, separator, self.lua_name())
+    }
+
+    /// Lua names and type labels of arguments, e.g. `("rect", "Rect")`.
+    pub fn params(&self) -> Vec<(String, String)> {
+        self.args()
+            .filter_map(|(pat, ty)| match pat {
+                Pat::Ident(it) => Some((snake_to_camel(&it.ident), lua_type_label(&ty))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Type label of returned values, `None` if nothing is returned.
+    pub fn returns(&self, output: &ReturnType, type_name: Option<&str>) -> Option<String> {
+        match (output, type_name) {
+            (_, Some(type_name)) if self.options.chain => Some(type_name.to_string()),
+            (ReturnType::Type(_, ty), _) => Some(lua_type_label(ty)).filter(|it| it != "nil"),
+            (ReturnType::Default, _) => None,
+        }
+    }
+
+    /// Readable signature of the call, e.g. `drawRect(rect: Rect, paint: Paint)`.
+    pub fn signature(&self, output: &ReturnType, type_name: Option<&str>) -> String {
+        let name = match type_name {
+            Some(type_name) if self.options.constructor => type_name.to_string(),
+            _ => self.lua_name(),
+        };
+        let args: Vec<_> = self
+            .params()
+            .into_iter()
+            .map(|(name, ty)| format!("{}: {}", name, ty))
+            .collect();
+        let mut result = format!("{}({})", name, args.join(", "));
+
+        if let Some(output) = self.returns(output, type_name) {
+            result.push_str(" -> ");
+            result.push_str(&output);
+        }
+        result
+    }
+
+    /// Variant of `crate::help::MethodKind` describing how the call is
+    /// registered.
+    pub fn help_kind(&self) -> Ident {
+        let kind = match self.kind {
+            _ if self.options.constructor => "Constructor",
+            _ if self.is_meta => "MetaMethod",
+            SignatureKind::Method { .. } => "Method",
+            SignatureKind::Function { .. } => "Function",
+        };
+        Ident::new(kind, Span::call_site())
+    }
+
     pub fn register_with(&self) -> Ident {
         let mut result = String::with_capacity(25);
         result.push_str("add");
@@ -207,11 +275,14 @@ impl MethodSignature {
         })
     }
 
-    fn block_setup_statements(&self, ctx_name: &str, skip_table: bool) -> Result<Vec<Stmt>> {
+    fn block_setup_statements(
+        &self,
+        ctx_name: &str,
+        skip_table: bool,
+        call_name: &str,
+    ) -> Result<Vec<Stmt>> {
         let mut result = Vec::with_capacity(3);
 
-        let name = snake_to_camel(&self.name);
-
         let init = Expr::Call(ExprCall {
             attrs: vec![],
             func: Box::new(Expr::ident_segments([
@@ -226,7 +297,7 @@ impl MethodSignature {
                 Expr::ident(ctx_name),
                 some_value(Expr::Lit(ExprLit {
                     attrs: vec![],
-                    lit: Lit::Str(LitStr::new(name.as_str(), Span::call_site())),
+                    lit: Lit::Str(LitStr::new(call_name, Span::call_site())),
                 })),
                 some_value(Expr::Reference(ExprReference {
                     attrs: vec![],
@@ -421,7 +492,7 @@ impl MethodSignature {
                 Expr::Lit(ExprLit {
                     attrs: vec![],
                     lit: Lit::Str(LitStr::new(
-                        it.ident.to_string().as_str(),
+                        snake_to_camel(&it.ident).as_str(),
                         Span::call_site(),
                     )),
                 })
@@ -468,7 +539,6 @@ impl MethodSignature {
 }
 
 struct LuaMethod {
-    #[allow(unused)]
     source: ImplItemFn,
     signature: MethodSignature,
     ctx_lifetime: Option<Lifetime>,
@@ -521,7 +591,7 @@ impl LuaMethod {
         })
     }
 
-    pub fn closure(&self, skip_table: bool) -> Result<ExprClosure> {
+    pub fn closure(&self, skip_table: bool, call_name: &str) -> Result<ExprClosure> {
         let mut inputs = Punctuated::new();
 
         let ctx_name = if let Some((_, ctx)) = &self.signature.lua_ctx {
@@ -577,11 +647,50 @@ impl LuaMethod {
         if insert_mapping {
             let mut modified = self
                 .signature
-                .block_setup_statements(&ctx_name, skip_table)?;
+                .block_setup_statements(&ctx_name, skip_table, call_name)?;
             modified.append(&mut block.stmts);
             block.stmts = modified;
         }
 
+        // handles created by the binding count towards its context
+        // userdata can outlive its context's scripts, so every call checks
+        // the context wasn't torn down before touching skia
+        let ctx = Ident::new(&ctx_name, Span::call_site());
+        block.stmts.insert(
+            0,
+            parse_quote! {
+                let _stats = crate::stats::enter(#ctx);
+            },
+        );
+        block.stmts.insert(
+            1,
+            parse_quote! {
+                crate::teardown::ensure_alive(#ctx)?;
+            },
+        );
+        // times the call until the closure returns when profiling is active
+        block.stmts.insert(
+            2,
+            parse_quote! {
+                let __profile_timer = crate::profile::time_binding();
+            },
+        );
+        // stale userdata (e.g. canvases of resized surfaces) fails before
+        // its arguments are converted
+        if let SignatureKind::Method { .. } = self.signature.kind {
+            let this = Ident::new(SELF_MAPPED, Span::call_site());
+            block.stmts.insert(
+                3,
+                parse_quote! {
+                    {
+                        #[allow(unused_imports)]
+                        use crate::lua::CheckValid as _;
+                        Self::check_valid(&*#this)?;
+                    }
+                },
+            );
+        }
+
         let body = Box::new(Expr::Block(ExprBlock {
             attrs: vec![],
             label: None,
@@ -602,6 +711,102 @@ impl LuaMethod {
             body,
         })
     }
+
+    /// Returns the doc comment of the method, without the space rustdoc
+    /// leaves at the start of each line.
+    fn doc(&self) -> String {
+        let lines: Vec<String> = self
+            .source
+            .attrs
+            .iter()
+            .filter_map(|attr| match &attr.meta {
+                Meta::NameValue(MetaNameValue {
+                    path,
+                    value:
+                        Expr::Lit(ExprLit {
+                            lit: Lit::Str(line),
+                            ..
+                        }),
+                    ..
+                }) if path.is_ident("doc") => Some(line.value()),
+                _ => None,
+            })
+            .map(|line| match line.strip_prefix(' ') {
+                Some(it) => it.to_string(),
+                None => line,
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// Returns `cfg` attributes of the method, which have to be repeated on
+    /// generated registration statements.
+    fn cfg_attrs(&self) -> Vec<Attribute> {
+        self.source
+            .attrs
+            .iter()
+            .filter(|it| it.path().is_ident("cfg"))
+            .cloned()
+            .collect()
+    }
+
+    /// Builds a function closure which calls the method body and returns
+    /// the receiver userdata, allowing `value:a():b()` chaining from Lua.
+    pub fn chained_closure(&self, call_name: &str) -> Result<ExprClosure> {
+        let recv = match &self.signature.kind {
+            SignatureKind::Method { recv } => recv,
+            SignatureKind::Function { .. } => {
+                return Err(Error::new_spanned(
+                    &self.source.sig,
+                    "'chain' option requires a method receiver",
+                ))
+            }
+        };
+        if !matches!(self.source.sig.output, ReturnType::Default)
+            && !matches!(&self.source.sig.output, ReturnType::Type(_, ty) if matches!(ty.as_ref(), Type::Tuple(it) if it.elems.is_empty()))
+        {
+            return Err(Error::new_spanned(
+                &self.source.sig.output,
+                "'chain' option requires a method returning ()",
+            ));
+        }
+
+        let inner = self.closure(false, call_name)?;
+        let mut inner_inputs = inner.inputs.into_iter();
+        let ctx = inner_inputs.next().expect("closure has a context argument");
+        let args = inner_inputs
+            .nth(1)
+            .expect("closure has an arguments argument");
+        let args_ty: Type = if matches!(args, Pat::Tuple(_)) {
+            parse_quote!(())
+        } else {
+            parse_quote!(mlua::MultiValue<'lua>)
+        };
+        let body = inner.body;
+        let this = Ident::new(SELF_MAPPED, Span::call_site());
+        let borrow = if recv.mutability.is_some() {
+            quote::quote! {
+                let mut __cb_this_ref = __cb_this_ud.borrow_mut::<Self>()?;
+                let #this = &mut *__cb_this_ref;
+            }
+        } else {
+            quote::quote! {
+                let __cb_this_ref = __cb_this_ud.borrow::<Self>()?;
+                let #this = &*__cb_this_ref;
+            }
+        };
+
+        Ok(parse_quote! {
+            |#ctx, (__cb_this_ud, #args): (mlua::AnyUserData<'lua>, #args_ty)| {
+                {
+                    #borrow
+                    let __chained: mlua::Result<()> = (|| #body)();
+                    __chained?;
+                }
+                Ok(__cb_this_ud)
+            }
+        })
+    }
 }
 
 pub struct UserDataMetods {
@@ -638,10 +843,24 @@ fn globals(ctx_name: Ident) -> Expr {
 }
 
 impl UserDataMetods {
-    fn method_register_calls(&self, recv: Expr) -> impl Iterator<Item = Result<Expr>> + '_ {
+    /// Name of the type in Lua, if it can be determined.
+    fn lua_type_name(&self, options: &AttributeOptions) -> Option<String> {
+        options
+            .lua_name
+            .clone()
+            .or_else(|| ty_base_name(&self.self_ty))
+    }
+
+    fn method_register_calls<'a>(
+        &'a self,
+        recv: Expr,
+        options: &'a AttributeOptions,
+    ) -> impl Iterator<Item = Result<Expr>> + 'a {
+        let type_name = self.lua_type_name(options);
         self.methods.iter().map(move |m| {
             let sig = &m.signature;
             let name = sig.lua_name();
+            let call_name = sig.call_name(type_name.as_deref());
 
             let name = if sig.options.constructor {
                 Expr::Lit(ExprLit {
@@ -655,9 +874,23 @@ impl UserDataMetods {
                 })
             };
 
-            m.closure(false).map(|c| {
+            if sig.options.chain {
+                return m.chained_closure(&call_name).map(|c| {
+                    Expr::MethodCall(ExprMethodCall {
+                        attrs: m.cfg_attrs(),
+                        receiver: Box::new(recv.clone()),
+                        dot_token: Default::default(),
+                        method: Ident::new("add_function", Span::call_site()),
+                        turbofish: None,
+                        paren_token: Default::default(),
+                        args: Punctuated::from_iter([name, Expr::Closure(c)]),
+                    })
+                });
+            }
+
+            m.closure(false, &call_name).map(|c| {
                 Expr::MethodCall(ExprMethodCall {
-                    attrs: vec![],
+                    attrs: m.cfg_attrs(),
                     receiver: Box::new(recv.clone()),
                     dot_token: Default::default(),
                     method: sig.register_with(),
@@ -718,19 +951,33 @@ impl UserDataMetods {
         result
     }
 
-    pub fn generate_userdata_impl(&self, _options: &AttributeOptions) -> Result<ItemImpl> {
+    pub fn generate_userdata_impl(&self, options: &AttributeOptions) -> Result<ItemImpl> {
         let method_registry = Ident::new("__lua_methods", Span::call_site());
 
-        let block = Block {
-            brace_token: Default::default(),
-            stmts: self
-                .method_register_calls(Expr::Path(ExprPath {
+        let mut stmts = self
+            .method_register_calls(
+                Expr::Path(ExprPath {
                     attrs: vec![],
                     qself: None,
                     path: Path::from(method_registry.clone()),
-                }))
-                .map(|it| it.map(|it| Stmt::Expr(it, Some(Default::default()))))
-                .collect::<Result<Vec<_>>>()?,
+                }),
+                options,
+            )
+            .map(|it| it.map(|it| Stmt::Expr(it, Some(Default::default()))))
+            .collect::<Result<Vec<_>>>()?;
+        // wrapped skia handles shadow the no-op trait function with an
+        // inherent one, other types fall back to it
+        stmts.push(parse_quote! {
+            {
+                #[allow(unused_imports)]
+                use crate::lua::HandleMethods as _;
+                Self::add_handle_methods(#method_registry);
+            }
+        });
+
+        let block = Block {
+            brace_token: Default::default(),
+            stmts,
         };
 
         let add_methods = parse_quote! {
@@ -754,6 +1001,61 @@ impl UserDataMetods {
         })
     }
 
+    /// Generates a `LUA_HELP` constant describing the methods, which
+    /// `crate::help` registers into the `Help` table.
+    pub fn generate_help(&self, options: &AttributeOptions) -> Result<ItemImpl> {
+        let type_name = self.lua_type_name(options).ok_or_else(|| {
+            Error::new(
+                self.self_ty.span(),
+                "lua_methods attribute only works for named types",
+            )
+        })?;
+        let entries = self.methods.iter().map(|m| {
+            let cfg = m.cfg_attrs();
+            let name = m.signature.lua_name();
+            let doc = m.doc();
+            let signature = m
+                .signature
+                .signature(&m.source.sig.output, Some(&type_name));
+            let kind = m.signature.help_kind();
+            let params = m.signature.params().into_iter().map(|(name, ty)| {
+                quote::quote! {
+                    crate::help::ParamHelp { name: #name, ty: #ty }
+                }
+            });
+            let returns = match m.signature.returns(&m.source.sig.output, Some(&type_name)) {
+                Some(it) => quote::quote! { Some(#it) },
+                None => quote::quote! { None },
+            };
+            quote::quote! {
+                #(#cfg)*
+                crate::help::MethodHelp {
+                    name: #name,
+                    doc: #doc,
+                    signature: #signature,
+                    kind: crate::help::MethodKind::#kind,
+                    params: &[#(#params),*],
+                    returns: #returns,
+                }
+            }
+        });
+
+        let (impl_generics, _, where_clause) = self.generics.split_for_impl();
+        let self_ty = &self.self_ty;
+        Ok(parse_quote! {
+            impl #impl_generics #self_ty #where_clause {
+                pub const LUA_HELP: crate::help::TypeHelp = crate::help::TypeHelp {
+                    name: #type_name,
+                    methods: &[#(#entries),*],
+                    handle_methods: {
+                        use crate::lua::HandleMethods as _;
+                        Self::HANDLE_HELP
+                    },
+                };
+            }
+        })
+    }
+
     pub fn generate_register_fn(&self, options: &AttributeOptions) -> Result<Option<ItemImpl>> {
         let lua_ctx = Ident::new("__lua_context", Span::call_site());
 
@@ -792,10 +1094,11 @@ impl UserDataMetods {
             .filter(|it| matches!(it.signature.kind, SignatureKind::Function { .. }));
 
         let mut found_any = false;
+        let type_name = self.lua_type_name(options);
 
         for m in statics {
             let sig = &m.signature;
-            let c = m.closure(true)?;
+            let c = m.closure(true, &sig.call_name(type_name.as_deref()))?;
 
             let function_reg = Expr::MethodCall(ExprMethodCall {
                 attrs: vec![],
@@ -845,7 +1148,7 @@ impl UserDataMetods {
             });
 
             let table_insert = Expr::Try(ExprTry {
-                attrs: vec![],
+                attrs: m.cfg_attrs(),
                 expr: Box::new(table_insert),
                 question_token: Default::default(),
             });
@@ -877,16 +1180,12 @@ impl UserDataMetods {
         });
         stmts.push(Stmt::Expr(set_metatable, Some(Default::default())));
 
-        let base_name = options
-            .lua_name
-            .clone()
-            .or_else(|| ty_base_name(&self.self_ty))
-            .ok_or_else(|| {
-                Error::new(
-                    self.self_ty.span(),
-                    "lua_methods attribute only works for named types",
-                )
-            })?;
+        let base_name = type_name.ok_or_else(|| {
+            Error::new(
+                self.self_ty.span(),
+                "lua_methods attribute only works for named types",
+            )
+        })?;
 
         let set_table = Expr::MethodCall(ExprMethodCall {
             attrs: vec![],
@@ -916,7 +1215,7 @@ impl UserDataMetods {
         };
 
         let globals_fn = parse_quote! {
-            fn register_globals<'lua>(#lua_ctx: &'lua mlua::Lua) -> Result<(), mlua::Error> #block
+            pub fn register_globals<'lua>(#lua_ctx: &'lua mlua::Lua) -> Result<(), mlua::Error> #block
         };
 
         Ok(Some(ItemImpl {
@@ -979,6 +1278,30 @@ impl Parse for UserDataMetods {
             }
         }
 
+        // a copy-pasted name would silently replace the other method
+        let mut registered: Vec<(String, &LuaMethod)> = Vec::new();
+        for method in &result.methods {
+            let sig = &method.signature;
+            let name = if sig.options.constructor {
+                "__call".to_string()
+            } else {
+                sig.lua_name()
+            };
+            let conditional = !method.cfg_attrs().is_empty();
+            match registered.iter().find(|(it, _)| *it == name) {
+                Some((_, other)) if !conditional || other.cfg_attrs().is_empty() => {
+                    errors.push(Error::new_spanned(
+                        &method.source.sig.ident,
+                        format!(
+                            "Lua name '{}' is already used by '{}'",
+                            name, other.source.sig.ident
+                        ),
+                    ));
+                }
+                _ => registered.push((name, method)),
+            }
+        }
+
         if let Some(combined) = Error::from_many(errors) {
             Err(combined)
         } else {
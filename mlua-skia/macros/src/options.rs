@@ -160,6 +160,8 @@ pub struct ItemOptions {
     pub metamethod: Option<Path>,
     pub skip: bool,
     pub constructor: bool,
+    /// Method returns its userdata so calls can be chained from Lua.
+    pub chain: bool,
     pub rename: Option<String>,
 }
 
@@ -209,6 +211,9 @@ impl Parse for ItemOptions {
                 "constructor" => {
                     options.constructor = true;
                 }
+                "chain" => {
+                    options.chain = true;
+                }
                 other => {
                     return Err(Error::new(
                         it.name.span(),
@@ -212,3 +212,58 @@ pub fn cloned_value(value: Expr) -> Expr {
         args: Punctuated::new(),
     })
 }
+
+/// Returns a short, Lua oriented description of an argument type, used in
+/// generated signatures.
+pub fn lua_type_label(ty: &Type) -> String {
+    match ty {
+        Type::Reference(it) => lua_type_label(&it.elem),
+        Type::Paren(it) => lua_type_label(&it.elem),
+        Type::Group(it) => lua_type_label(&it.elem),
+        Type::Tuple(it) if it.elems.is_empty() => "nil".to_string(),
+        Type::Tuple(it) => {
+            let items: Vec<_> = it.elems.iter().map(lua_type_label).collect();
+            format!("({})", items.join(", "))
+        }
+        Type::Array(it) => format!("{}[]", lua_type_label(&it.elem)),
+        Type::Slice(it) => format!("{}[]", lua_type_label(&it.elem)),
+        Type::Path(it) => {
+            let last = match it.path.segments.last() {
+                Some(it) => it,
+                None => return "any".to_string(),
+            };
+            let inner = match &last.arguments {
+                PathArguments::AngleBracketed(args) => args.args.iter().find_map(|it| match it {
+                    GenericArgument::Type(ty) => Some(ty),
+                    _ => None,
+                }),
+                _ => None,
+            };
+            let name = last.ident.to_string();
+            match (name.as_str(), inner) {
+                ("Option" | "LuaFallible", Some(inner)) => format!("{}?", lua_type_label(inner)),
+                ("Vec" | "NoneOrMany", Some(inner)) => format!("{}[]", lua_type_label(inner)),
+                ("Unpacked" | "MaybeUnpacked" | "Box", Some(inner)) => lua_type_label(inner),
+                ("f32" | "f64", _) => "number".to_string(),
+                (
+                    "i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize",
+                    _,
+                ) => "integer".to_string(),
+                ("bool", _) => "boolean".to_string(),
+                ("String" | "str" | "LuaString", _) => "string".to_string(),
+                ("Value" | "LuaValue" | "MultiValue" | "LuaMultiValue", _) => "any".to_string(),
+                _ => {
+                    let name = ["Lua", "Like"]
+                        .iter()
+                        .find_map(|prefix| {
+                            name.strip_prefix(prefix)
+                                .filter(|it| it.starts_with(char::is_uppercase))
+                        })
+                        .unwrap_or(&name);
+                    name.to_string()
+                }
+            }
+        }
+        _ => "any".to_string(),
+    }
+}
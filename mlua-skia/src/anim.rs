@@ -0,0 +1,430 @@
+//! Easing curves and interpolation of skia values.
+
+use std::f32::consts::PI;
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::{Color4f, Matrix, Rect};
+
+use crate::{LuaColor, LuaColorSpace, LuaFallible, LuaMatrix, LuaPath, LuaRect};
+
+const BACK_C1: f32 = 1.70158;
+const BACK_C2: f32 = BACK_C1 * 1.525;
+const BACK_C3: f32 = BACK_C1 + 1.;
+const ELASTIC_C4: f32 = (2. * PI) / 3.;
+const ELASTIC_C5: f32 = (2. * PI) / 4.5;
+
+fn out_bounce(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1. / D {
+        N * t * t
+    } else if t < 2. / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Supported easing curves; all map `0` to `0` and `1` to `1`.
+const EASINGS: &[(&str, fn(f32) -> f32)] = &[
+    ("linear", |t| t),
+    ("in_quad", |t| t * t),
+    ("out_quad", |t| 1. - (1. - t) * (1. - t)),
+    ("in_out_quad", |t| {
+        if t < 0.5 {
+            2. * t * t
+        } else {
+            1. - (-2. * t + 2.).powi(2) / 2.
+        }
+    }),
+    ("in_cubic", |t| t * t * t),
+    ("out_cubic", |t| 1. - (1. - t).powi(3)),
+    ("in_out_cubic", |t| {
+        if t < 0.5 {
+            4. * t * t * t
+        } else {
+            1. - (-2. * t + 2.).powi(3) / 2.
+        }
+    }),
+    ("in_sine", |t| 1. - (t * PI / 2.).cos()),
+    ("out_sine", |t| (t * PI / 2.).sin()),
+    ("in_out_sine", |t| -((PI * t).cos() - 1.) / 2.),
+    ("in_expo", |t| {
+        if t <= 0. {
+            0.
+        } else {
+            2f32.powf(10. * t - 10.)
+        }
+    }),
+    ("out_expo", |t| {
+        if t >= 1. {
+            1.
+        } else {
+            1. - 2f32.powf(-10. * t)
+        }
+    }),
+    ("in_out_expo", |t| {
+        if t <= 0. {
+            0.
+        } else if t >= 1. {
+            1.
+        } else if t < 0.5 {
+            2f32.powf(20. * t - 10.) / 2.
+        } else {
+            (2. - 2f32.powf(-20. * t + 10.)) / 2.
+        }
+    }),
+    ("in_back", |t| BACK_C3 * t * t * t - BACK_C1 * t * t),
+    ("out_back", |t| {
+        1. + BACK_C3 * (t - 1.).powi(3) + BACK_C1 * (t - 1.).powi(2)
+    }),
+    ("in_out_back", |t| {
+        if t < 0.5 {
+            ((2. * t).powi(2) * ((BACK_C2 + 1.) * 2. * t - BACK_C2)) / 2.
+        } else {
+            ((2. * t - 2.).powi(2) * ((BACK_C2 + 1.) * (t * 2. - 2.) + BACK_C2) + 2.) / 2.
+        }
+    }),
+    ("in_elastic", |t| {
+        if t <= 0. {
+            0.
+        } else if t >= 1. {
+            1.
+        } else {
+            -(2f32.powf(10. * t - 10.)) * ((t * 10. - 10.75) * ELASTIC_C4).sin()
+        }
+    }),
+    ("out_elastic", |t| {
+        if t <= 0. {
+            0.
+        } else if t >= 1. {
+            1.
+        } else {
+            2f32.powf(-10. * t) * ((t * 10. - 0.75) * ELASTIC_C4).sin() + 1.
+        }
+    }),
+    ("in_out_elastic", |t| {
+        if t <= 0. {
+            0.
+        } else if t >= 1. {
+            1.
+        } else if t < 0.5 {
+            -(2f32.powf(20. * t - 10.) * ((20. * t - 11.125) * ELASTIC_C5).sin()) / 2.
+        } else {
+            (2f32.powf(-20. * t + 10.) * ((20. * t - 11.125) * ELASTIC_C5).sin()) / 2. + 1.
+        }
+    }),
+    ("in_bounce", |t| 1. - out_bounce(1. - t)),
+    ("out_bounce", out_bounce),
+    ("in_out_bounce", |t| {
+        if t < 0.5 {
+            (1. - out_bounce(1. - 2. * t)) / 2.
+        } else {
+            (1. + out_bounce(2. * t - 1.)) / 2.
+        }
+    }),
+];
+
+/// Returns easing curve with provided `name`.
+pub fn easing(name: &str) -> Option<fn(f32) -> f32> {
+    let name = name.to_ascii_lowercase();
+    EASINGS
+        .iter()
+        .find(|(it, _)| *it == name)
+        .map(|(_, curve)| *curve)
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// Affine matrix split into components that can be interpolated
+/// independently.
+struct Decomposed {
+    rotation: f32,
+    scale_x: f32,
+    scale_y: f32,
+    shear: f32,
+    translate_x: f32,
+    translate_y: f32,
+}
+
+impl Decomposed {
+    /// Decomposes `matrix` into `rotate * shear_and_scale` followed by
+    /// translation.
+    fn new(matrix: &Matrix) -> Self {
+        let (a, b) = (matrix.scale_x(), matrix.skew_y());
+        let (c, d) = (matrix.skew_x(), matrix.scale_y());
+
+        let scale_x = a.hypot(b);
+        let rotation = b.atan2(a);
+        let (sin, cos) = rotation.sin_cos();
+        Decomposed {
+            rotation,
+            scale_x,
+            scale_y: -sin * c + cos * d,
+            shear: cos * c + sin * d,
+            translate_x: matrix.translate_x(),
+            translate_y: matrix.translate_y(),
+        }
+    }
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        // rotate along the shorter arc
+        let mut delta = other.rotation - self.rotation;
+        if delta > PI {
+            delta -= 2. * PI;
+        } else if delta < -PI {
+            delta += 2. * PI;
+        }
+        Decomposed {
+            rotation: self.rotation + delta * t,
+            scale_x: lerp(self.scale_x, other.scale_x, t),
+            scale_y: lerp(self.scale_y, other.scale_y, t),
+            shear: lerp(self.shear, other.shear, t),
+            translate_x: lerp(self.translate_x, other.translate_x, t),
+            translate_y: lerp(self.translate_y, other.translate_y, t),
+        }
+    }
+
+    fn recompose(&self) -> Matrix {
+        let (sin, cos) = self.rotation.sin_cos();
+        Matrix::new_all(
+            self.scale_x * cos,
+            self.shear * cos - self.scale_y * sin,
+            self.translate_x,
+            self.scale_x * sin,
+            self.shear * sin + self.scale_y * cos,
+            self.translate_y,
+            0.,
+            0.,
+            1.,
+        )
+    }
+}
+
+/// Animation helpers exposed as the global `Anim` table.
+pub struct Anim;
+
+#[lua_methods(lua_name: Anim)]
+impl Anim {
+    /// Applies named easing curve to `t`, which is clamped to `[0, 1]`.
+    pub fn ease(name: String, t: f32) -> f32 {
+        let curve = easing(&name).ok_or_else(|| {
+            let names: Vec<&str> = EASINGS.iter().map(|(it, _)| *it).collect();
+            LuaError::RuntimeError(format!(
+                "unknown easing '{}'; expected one of: {}",
+                name,
+                names.join(", ")
+            ))
+        })?;
+        Ok(curve(t.clamp(0., 1.)))
+    }
+
+    /// Interpolates between colors `a` and `b`.
+    ///
+    /// If `color_space` has linear gamma, color channels are interpolated in
+    /// linear light which avoids dark bands between saturated colors.
+    pub fn lerp_color(
+        a: LuaColor,
+        b: LuaColor,
+        t: f32,
+        color_space: LuaFallible<LuaColorSpace>,
+    ) -> LuaColor {
+        let a: Color4f = a.into();
+        let b: Color4f = b.into();
+        let linear = color_space
+            .map(|it| it.0.gamma_is_linear())
+            .unwrap_or_default();
+
+        let channel = |a: f32, b: f32| {
+            if linear {
+                linear_to_srgb(lerp(srgb_to_linear(a), srgb_to_linear(b), t))
+            } else {
+                lerp(a, b, t)
+            }
+        };
+        Ok(LuaColor::from(Color4f::new(
+            channel(a.r, b.r),
+            channel(a.g, b.g),
+            channel(a.b, b.b),
+            lerp(a.a, b.a, t),
+        )))
+    }
+
+    /// Interpolates edges of rects `a` and `b`.
+    pub fn lerp_rect(a: LuaRect, b: LuaRect, t: f32) -> LuaRect {
+        let a: Rect = a.into();
+        let b: Rect = b.into();
+        Ok(LuaRect::from(Rect::new(
+            lerp(a.left, b.left, t),
+            lerp(a.top, b.top, t),
+            lerp(a.right, b.right, t),
+            lerp(a.bottom, b.bottom, t),
+        )))
+    }
+
+    /// Interpolates 3x3 matrices by decomposing them into rotation, scale,
+    /// shear and translation.
+    ///
+    /// Matrices with perspective are interpolated component-wise.
+    pub fn lerp_matrix(a: LuaMatrix, b: LuaMatrix, t: f32) -> LuaMatrix {
+        let a: Matrix = a.into();
+        let b: Matrix = b.into();
+
+        if a.has_perspective() || b.has_perspective() {
+            let mut result = Matrix::new_identity();
+            for i in 0..9 {
+                result[i] = lerp(a[i], b[i], t);
+            }
+            return Ok(LuaMatrix::Three(result));
+        }
+
+        let result = Decomposed::new(&a).lerp(&Decomposed::new(&b), t);
+        Ok(LuaMatrix::Three(result.recompose()))
+    }
+
+    /// Interpolates between paths `a` and `b`, returning `nil` if they don't
+    /// have the same structure (see `Path:isInterpolatable`).
+    pub fn lerp_path(a: LuaPath, b: LuaPath, t: f32) -> Option<LuaPath> {
+        Ok(b.0.interpolate(&a.0, t).map(LuaPath::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "got {}, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    fn as_matrix(value: LuaMatrix) -> Matrix {
+        value.into()
+    }
+
+    #[test]
+    fn easings_keep_endpoints() {
+        for (name, curve) in EASINGS {
+            assert!(curve(0.).abs() < 1e-4, "{} doesn't start at 0", name);
+            assert!((curve(1.) - 1.).abs() < 1e-4, "{} doesn't end at 1", name);
+        }
+    }
+
+    #[test]
+    fn symmetric_easings_pass_through_midpoint() {
+        for name in [
+            "linear",
+            "in_out_quad",
+            "in_out_cubic",
+            "in_out_sine",
+            "in_out_expo",
+            "in_out_back",
+            "in_out_bounce",
+        ] {
+            assert_near(Anim::ease(name.to_string(), 0.5).unwrap(), 0.5);
+        }
+        assert_near(Anim::ease("in_quad".to_string(), 0.5).unwrap(), 0.25);
+        assert_near(Anim::ease("OUT_QUAD".to_string(), 0.5).unwrap(), 0.75);
+        // input is clamped
+        assert_near(Anim::ease("linear".to_string(), 2.).unwrap(), 1.);
+    }
+
+    #[test]
+    fn unknown_easing_lists_valid_names() {
+        let message = Anim::ease("wobble".to_string(), 0.5)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("'wobble'"), "{}", message);
+        assert!(message.contains("in_out_bounce"), "{}", message);
+    }
+
+    #[test]
+    fn srgb_transfer_round_trips() {
+        for value in [0., 0.01, 0.2, 0.5, 0.8, 1.] {
+            assert_near(linear_to_srgb(srgb_to_linear(value)), value);
+        }
+    }
+
+    #[test]
+    fn lerp_rect_interpolates_edges() {
+        let a = LuaRect::from(Rect::new(0., 0., 10., 10.));
+        let b = LuaRect::from(Rect::new(10., 20., 30., 50.));
+        let mid: Rect = Anim::lerp_rect(a, b, 0.5).unwrap().into();
+        assert_eq!(mid, Rect::new(5., 10., 20., 30.));
+    }
+
+    #[test]
+    fn lerp_matrix_keeps_endpoints_and_rotates_through_midpoint() {
+        let a = Matrix::scale((2., 3.));
+        let b = *Matrix::rotate_deg(90.).post_translate((10., 20.));
+
+        for (t, expected) in [(0., a), (1., b)] {
+            let result =
+                as_matrix(Anim::lerp_matrix(LuaMatrix::Three(a), LuaMatrix::Three(b), t).unwrap());
+            for i in 0..9 {
+                assert_near(result[i], expected[i]);
+            }
+        }
+
+        let from = Matrix::new_identity();
+        let to = Matrix::rotate_deg(90.);
+        let mid = as_matrix(
+            Anim::lerp_matrix(LuaMatrix::Three(from), LuaMatrix::Three(to), 0.5).unwrap(),
+        );
+        let expected = Matrix::rotate_deg(45.);
+        for i in 0..9 {
+            assert_near(mid[i], expected[i]);
+        }
+    }
+
+    #[test]
+    fn lerp_color_in_linear_space() {
+        let lua = Lua::new();
+        crate::setup(&lua).unwrap();
+        let (plain, linear, end): (f32, f32, f32) = lua
+            .load(
+                r#"
+                local black, white = {0, 0, 0, 1}, {1, 1, 1, 1}
+                return Anim.lerpColor(black, white, 0.5).r,
+                    Anim.lerpColor(black, white, 0.5, ColorSpace.makeSrgbLinear()).r,
+                    Anim.lerpColor(black, white, 1, ColorSpace.makeSrgbLinear()).r
+            "#,
+            )
+            .eval()
+            .unwrap();
+        assert_near(plain, 0.5);
+        // half of linear light is brighter than half of sRGB encoded value
+        assert_near(linear, linear_to_srgb(0.5));
+        assert!(linear > 0.7);
+        assert_near(end, 1.);
+    }
+}
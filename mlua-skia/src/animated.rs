@@ -0,0 +1,304 @@
+//! Decoding of animated images such as GIF and animated WebP.
+//!
+//! Only the composed current frame is kept in memory. Frames are decoded from
+//! the encoded data on demand, on top of the previous frame when the format
+//! allows it, so disposal and blending of frames is handled by Skia's codec.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::{
+    codec::{self, codec_animation::DisposalMethod, FrameInfo, ZeroInitialized},
+    images, AlphaType, Codec, Data, Image, ImageInfo,
+};
+
+use crate::{lua::ConstructorResult, util::PathOrData, LuaImage};
+
+/// Duration used for frames which don't specify one, matching browsers.
+const DEFAULT_FRAME_DURATION: i32 = 100;
+
+pub struct AnimatedDecoder {
+    codec: Codec<'static>,
+    info: ImageInfo,
+    frames: Vec<FrameInfo>,
+    /// Composed pixels of the `current` frame.
+    pixels: Vec<u8>,
+    current: Option<usize>,
+    /// Image of the current frame, created when first requested.
+    image: Option<Image>,
+}
+
+impl AnimatedDecoder {
+    pub fn new(data: &[u8]) -> Option<Self> {
+        let mut codec = Codec::from_data(Data::new_copy(data))?;
+        let info = ImageInfo::new_n32(
+            codec.dimensions(),
+            AlphaType::Premul,
+            codec.info().color_space(),
+        );
+        let count = codec.get_frame_count().max(1);
+        let frames = (0..count)
+            .map(|index| codec.get_frame_info(index).unwrap_or_default())
+            .collect();
+        let pixels = vec![0; info.compute_min_byte_size()];
+        Some(AnimatedDecoder {
+            codec,
+            info,
+            frames,
+            pixels,
+            current: None,
+            image: None,
+        })
+    }
+
+    #[inline]
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Display duration of frame at `index` in milliseconds.
+    pub fn frame_duration(&self, index: usize) -> i32 {
+        match self.frames.get(index) {
+            Some(frame) if frame.duration > 0 => frame.duration,
+            _ => DEFAULT_FRAME_DURATION,
+        }
+    }
+
+    /// Duration of a single loop of the animation in milliseconds.
+    pub fn duration(&self) -> i64 {
+        (0..self.frame_count())
+            .map(|index| self.frame_duration(index) as i64)
+            .sum()
+    }
+
+    #[inline]
+    pub fn current_index(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// Decodes frame at `index`, composing it on top of frames it depends
+    /// on.
+    pub fn decode_frame(&mut self, index: usize) -> bool {
+        if index >= self.frame_count() {
+            return false;
+        }
+        if self.current == Some(index) {
+            return true;
+        }
+
+        let required = self.frames[index].required_frame;
+        let prior_frame = if required < 0 {
+            self.pixels.fill(0);
+            None
+        } else {
+            let required = required as usize;
+            match self.current {
+                // frames disposed by restoring the previous one can't be
+                // drawn over
+                Some(current)
+                    if current >= required
+                        && current < index
+                        && self.frames[current].disposal_method
+                            != DisposalMethod::RestorePrevious =>
+                {
+                    Some(current)
+                }
+                _ => {
+                    if !self.decode_frame(required) {
+                        return false;
+                    }
+                    Some(required)
+                }
+            }
+        };
+
+        let options = codec::Options {
+            zero_initialized: ZeroInitialized::No,
+            subset: None,
+            frame_index: index,
+            prior_frame,
+        };
+        let row_bytes = self.info.min_row_bytes();
+        let result = self.codec.get_pixels_with_options(
+            &self.info,
+            &mut self.pixels,
+            row_bytes,
+            Some(&options),
+        );
+        self.image = None;
+        match result {
+            codec::Result::Success | codec::Result::IncompleteInput => {
+                self.current = Some(index);
+                true
+            }
+            _ => {
+                self.current = None;
+                false
+            }
+        }
+    }
+
+    /// Returns image of the current frame, decoding the first frame if none
+    /// was decoded yet.
+    pub fn current_frame(&mut self) -> Option<Image> {
+        if self.current.is_none() && !self.decode_frame(0) {
+            return None;
+        }
+        if self.image.is_none() {
+            self.image = images::raster_from_data(
+                &self.info,
+                Data::new_copy(&self.pixels),
+                self.info.min_row_bytes(),
+            );
+        }
+        self.image.clone()
+    }
+
+    /// Returns index of the frame shown `time` milliseconds after the
+    /// animation started, looping it.
+    pub fn frame_at(&self, time: i64) -> usize {
+        let duration = self.duration();
+        if duration <= 0 {
+            return 0;
+        }
+        let mut time = time.rem_euclid(duration);
+        for index in 0..self.frame_count() {
+            let frame_duration = self.frame_duration(index) as i64;
+            if time < frame_duration {
+                return index;
+            }
+            time -= frame_duration;
+        }
+        self.frame_count() - 1
+    }
+
+    /// Drops the current frame so the next access starts from the first one.
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.image = None;
+    }
+}
+
+pub struct LuaAnimatedImage(pub AnimatedDecoder);
+
+#[lua_methods(lua_name: AnimatedImage)]
+impl LuaAnimatedImage {
+    /// Loads an animated image from a file path or encoded data.
+    pub fn load<'lua>(lua: &'lua Lua, path_or_data: LuaString<'lua>) -> LuaAnimatedImage {
+        let source =
+            PathOrData::new(path_or_data.as_bytes()).check_read(lua, "AnimatedImage.load")?;
+        let data = source
+            .read()
+            .map_err(|err| LuaError::RuntimeError(format!("AnimatedImage.load failed: {}", err)))?;
+        Ok(AnimatedDecoder::new(&data)
+            .map(LuaAnimatedImage)
+            .or_failed("AnimatedImage.load", || match source {
+                PathOrData::Path(path) => format!("unsupported image file '{}'", path.display()),
+                PathOrData::Data(data) => format!("unsupported image data ({} bytes)", data.len()),
+            })?)
+    }
+    pub fn frame_count(&self) -> usize {
+        Ok(self.0.frame_count())
+    }
+    /// Duration of one loop in milliseconds.
+    pub fn duration(&self) -> i64 {
+        Ok(self.0.duration())
+    }
+    /// Index of the decoded frame, starting at `0`.
+    pub fn current_frame_index(&self) -> usize {
+        Ok(self.0.current_index().unwrap_or_default())
+    }
+    pub fn current_frame(&mut self) -> Option<LuaImage> {
+        Ok(self.0.current_frame().map(LuaImage::new))
+    }
+    /// Decodes the next frame, wrapping to the first one after the last,
+    /// and returns its index.
+    pub fn decode_next_frame(&mut self) -> Option<usize> {
+        let next = match self.0.current_index() {
+            Some(current) => (current + 1) % self.0.frame_count(),
+            None => 0,
+        };
+        Ok(self.0.decode_frame(next).then_some(next))
+    }
+    pub fn reset(&mut self) {
+        self.0.reset();
+        Ok(())
+    }
+    /// Decodes the frame shown `time` milliseconds after the animation
+    /// started, looping it, and returns its index.
+    pub fn seek_time(&mut self, time: f64) -> Option<usize> {
+        let index = self.0.frame_at(time as i64);
+        Ok(self.0.decode_frame(index).then_some(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x4 GIF with solid red, green and blue frames shown for 100, 200 and
+    /// 300 ms.
+    const THREE_FRAMES: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/three_frames.gif"
+    ));
+
+    fn pixel(image: &Image) -> [u8; 4] {
+        let info = ImageInfo::new(
+            (1, 1),
+            skia_safe::ColorType::RGBA8888,
+            AlphaType::Unpremul,
+            None,
+        );
+        let mut result = [0; 4];
+        assert!(image.read_pixels(
+            &info,
+            &mut result,
+            4,
+            (1, 1),
+            skia_safe::image::CachingHint::Allow
+        ));
+        result
+    }
+
+    #[test]
+    fn frames_follow_durations_and_loop() {
+        let decoder = AnimatedDecoder::new(THREE_FRAMES).unwrap();
+        assert_eq!(decoder.frame_count(), 3);
+        assert_eq!(decoder.duration(), 600);
+
+        for (time, index) in [(0, 0), (99, 0), (100, 1), (299, 1), (300, 2), (599, 2)] {
+            assert_eq!(decoder.frame_at(time), index, "frame at {} ms", time);
+        }
+        // time wraps around the loop in both directions
+        assert_eq!(decoder.frame_at(650), 0);
+        assert_eq!(decoder.frame_at(-50), 2);
+    }
+
+    #[test]
+    fn decoded_frames_change_and_wrap() {
+        let mut image = LuaAnimatedImage(AnimatedDecoder::new(THREE_FRAMES).unwrap());
+        let first = image.current_frame().unwrap().unwrap();
+        assert_eq!(pixel(&first.0), [255, 0, 0, 255]);
+
+        let expected = [[0, 255, 0, 255], [0, 0, 255, 255], [255, 0, 0, 255]];
+        for (index, color) in [1, 2, 0].into_iter().zip(expected) {
+            assert_eq!(image.decode_next_frame().unwrap(), Some(index));
+            assert_eq!(image.current_frame_index().unwrap(), index);
+            let frame = image.current_frame().unwrap().unwrap();
+            assert_eq!(pixel(&frame.0), color, "frame {}", index);
+        }
+
+        assert_eq!(image.seek_time(1500.).unwrap(), Some(2));
+        image.reset().unwrap();
+        assert_eq!(image.current_frame_index().unwrap(), 0);
+        assert_eq!(
+            pixel(&image.current_frame().unwrap().unwrap().0),
+            [255, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn invalid_data_fails_to_load() {
+        assert!(AnimatedDecoder::new(b"not an image").is_none());
+    }
+}
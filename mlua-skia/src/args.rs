@@ -1,15 +1,19 @@
 //! This module contains representations of skia types that are used as
 //! arguments.
 
-use std::{collections::VecDeque, sync::Arc};
+use std::{cell::RefCell, collections::VecDeque, sync::Arc};
 
 use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
 use skia_safe::{
-    font_style::{Weight, Width},
-    Color, Color4f, IPoint, IRect, ISize, Point, Point3, Rect,
+    font_style::{Slant, Weight, Width},
+    Color, Color4f, FontStyle, IPoint, IRect, ISize, Matrix, Paint, Point, Point3, RSXform, Rect,
+    Size,
 };
 
-use crate::{from_lua_argpack, ArgumentContext, FromArgPack, LuaType};
+use crate::{
+    from_lua_argpack, lua::WrapperT, ArgumentContext, FromArgPack, LuaFallible, LuaPaint, LuaType,
+};
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct LuaColor {
@@ -185,6 +189,34 @@ impl<'lua> FromLua<'lua> for LuaRect {
                 })
         }
 
+        let centered_format = rect.contains_key("cx")? || rect.contains_key("cy")?;
+
+        if centered_format {
+            for conflicting in ["x", "y", "left", "top", "right", "bottom"] {
+                if rect.contains_key(conflicting)? {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "table",
+                        to: "Rect",
+                        message: Some(format!(
+                            "Rect table with 'cx' and 'cy' fields can't have a '{}' field",
+                            conflicting
+                        )),
+                    });
+                }
+            }
+            let cx: f32 = required_field(&rect, "cx")?;
+            let cy: f32 = required_field(&rect, "cy")?;
+            let width: f32 = required_field(&rect, "w").or(required_field(&rect, "width"))?;
+            let height: f32 = required_field(&rect, "h").or(required_field(&rect, "height"))?;
+
+            return Ok(LuaRect::from(Rect::from_xywh(
+                cx - width / 2.0,
+                cy - height / 2.0,
+                width,
+                height,
+            )));
+        }
+
         let skia_format = rect.contains_key("right")? || rect.contains_key("bottom")?;
 
         if skia_format {
@@ -238,17 +270,18 @@ impl<'lua> FromLua<'lua> for LuaRect {
         Err(LuaError::FromLuaConversionError {
             from: "table",
             to: "Rect",
-            message: Some("unknown Rect format; expected one of:\n- { left, top, right, bottom }\n- { x, y, width, height }\n- { from, to }".to_string()),
+            message: Some("unknown Rect format; expected one of:\n- { left, top, right, bottom }\n- { x, y, width, height }\n- { cx, cy, width, height }\n- { from, to }".to_string()),
         })
     }
 }
 from_lua_argpack!(LuaRect);
 
+/// Rects are always returned to Lua in `{ left, top, right, bottom }` form.
 impl<'lua> IntoLua<'lua> for LuaRect {
     fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
         let result = lua.create_table()?;
-        result.set("top", self.from.x())?;
-        result.set("left", self.from.y())?;
+        result.set("left", self.from.x())?;
+        result.set("top", self.from.y())?;
         result.set("right", self.to.x())?;
         result.set("bottom", self.to.y())?;
         result.into_lua(lua)
@@ -295,6 +328,120 @@ impl From<LuaRect> for IRect {
     }
 }
 
+/// Either a [`LuaPoint`] or a [`LuaRect`].
+#[derive(Clone, Copy)]
+pub enum PointOrRect {
+    Point(Point),
+    Rect(Rect),
+}
+
+impl<'lua> FromArgPack<'lua> for PointOrRect {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(table) = args.pop_typed::<LuaTable<'lua>>() {
+            if let Ok(rect) = LuaRect::from_lua(LuaValue::Table(table.clone()), lua) {
+                return Ok(PointOrRect::Rect(rect.into()));
+            }
+            args.revert(table);
+        }
+        LuaPoint::convert(args, lua).map(|it| PointOrRect::Point(it.into()))
+    }
+}
+
+impl<'lua> IntoLua<'lua> for PointOrRect {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            PointOrRect::Point(it) => LuaPoint::from(it).into_lua(lua),
+            PointOrRect::Rect(it) => LuaRect::from(it).into_lua(lua),
+        }
+    }
+}
+
+/// Rect utility functions exposed as the global `Rect` table.
+///
+/// All functions accept any supported Rect format and return rects in
+/// `{ left, top, right, bottom }` form.
+pub struct RectFns;
+
+#[lua_methods(lua_name: Rect)]
+impl RectFns {
+    /// Creates a rect from position and size.
+    pub fn make(x: f32, y: f32, width: f32, height: f32) -> LuaRect {
+        Ok(LuaRect::from(Rect::from_xywh(x, y, width, height)))
+    }
+    /// Creates a rect from its edges.
+    #[lua(rename: "makeLTRB")]
+    pub fn make_ltrb(left: f32, top: f32, right: f32, bottom: f32) -> LuaRect {
+        Ok(LuaRect::from(Rect::new(left, top, right, bottom)))
+    }
+    /// Returns intersection of `a` and `b`, or `nil` if they don't overlap.
+    pub fn intersect(a: LuaRect, b: LuaRect) -> Option<LuaRect> {
+        let mut result: Rect = a.into();
+        let b: Rect = b.into();
+        if result.intersect(b) {
+            Ok(Some(LuaRect::from(result)))
+        } else {
+            Ok(None)
+        }
+    }
+    /// Returns the smallest rect containing both `a` and `b`. Empty rects
+    /// are ignored.
+    pub fn union(a: LuaRect, b: LuaRect) -> LuaRect {
+        let mut result: Rect = a.into();
+        let b: Rect = b.into();
+        result.join(b);
+        Ok(LuaRect::from(result))
+    }
+    /// Returns whether `value` point or rect is contained within `rect`.
+    pub fn contains(rect: LuaRect, value: PointOrRect) -> bool {
+        let rect: Rect = rect.into();
+        Ok(match value {
+            PointOrRect::Point(it) => rect.contains(it),
+            PointOrRect::Rect(it) => rect.contains(it),
+        })
+    }
+    /// Returns `rect` outset by `dx` horizontally and `dy` vertically (`dx`
+    /// if omitted).
+    pub fn inflate(rect: LuaRect, dx: f32, dy: Option<f32>) -> LuaRect {
+        let rect: Rect = rect.into();
+        Ok(LuaRect::from(rect.with_outset((dx, dy.unwrap_or(dx)))))
+    }
+    /// Returns center point of `rect`.
+    pub fn center(rect: LuaRect) -> LuaPoint {
+        let rect: Rect = rect.into();
+        Ok(LuaPoint::from(rect.center()))
+    }
+    /// Returns `rect` inset by `dx` horizontally and `dy` vertically (`dx`
+    /// if omitted). Negative values outset the rect.
+    pub fn inset(rect: LuaRect, dx: f32, dy: Option<f32>) -> LuaRect {
+        let rect: Rect = rect.into();
+        Ok(LuaRect::from(rect.with_inset((dx, dy.unwrap_or(dx)))))
+    }
+    /// Creates a rect of `size` centered on `center`.
+    pub fn centered(center: LuaPoint, size: LuaSize) -> LuaRect {
+        Ok(LuaRect::from(Rect::from_xywh(
+            center.x() - size.width() / 2.0,
+            center.y() - size.height() / 2.0,
+            size.width(),
+            size.height(),
+        )))
+    }
+    /// Maps `rect` given in fractions of `parent` size, relative to its
+    /// top-left corner, into the coordinates of `parent`.
+    ///
+    /// E.g. `{x = 0.1, y = 0.1, w = 0.8, h = 0.3}` is the upper part of the
+    /// parent with 10% margins.
+    pub fn relative(parent: LuaRect, rect: LuaRect) -> LuaRect {
+        let parent: Rect = parent.into();
+        let rect: Rect = rect.into();
+        Ok(LuaRect::from(Rect::new(
+            parent.left + rect.left * parent.width(),
+            parent.top + rect.top * parent.height(),
+            parent.left + rect.right * parent.width(),
+            parent.top + rect.bottom * parent.height(),
+        )))
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub struct LuaSize<const N: usize = 2> {
     value: [f32; N],
@@ -325,6 +472,13 @@ impl From<ISize> for LuaSize {
         }
     }
 }
+impl From<Size> for LuaSize {
+    fn from(value: Size) -> Self {
+        LuaSize {
+            value: [value.width, value.height],
+        }
+    }
+}
 impl From<LuaSize> for ISize {
     fn from(val: LuaSize) -> Self {
         ISize {
@@ -581,6 +735,101 @@ impl<'lua, const N: usize> IntoLua<'lua> for LuaPoint<N> {
     }
 }
 
+/// Vector math functions exposed as the global `Point` table.
+///
+/// All functions accept any supported Point format and return points in
+/// `{ x, y }` form.
+pub struct PointFns;
+
+#[lua_methods(lua_name: Point)]
+impl PointFns {
+    pub fn add(a: LuaPoint, b: LuaPoint) -> LuaPoint {
+        Ok(LuaPoint {
+            value: [a.x() + b.x(), a.y() + b.y()],
+        })
+    }
+    pub fn sub(a: LuaPoint, b: LuaPoint) -> LuaPoint {
+        Ok(LuaPoint {
+            value: [a.x() - b.x(), a.y() - b.y()],
+        })
+    }
+    pub fn scale(p: LuaPoint, factor: f32) -> LuaPoint {
+        Ok(LuaPoint {
+            value: [p.x() * factor, p.y() * factor],
+        })
+    }
+    pub fn dot(a: LuaPoint, b: LuaPoint) -> f32 {
+        Ok(a.x() * b.x() + a.y() * b.y())
+    }
+    pub fn length(p: LuaPoint) -> f32 {
+        Ok(p.x().hypot(p.y()))
+    }
+    /// Returns `p` scaled to unit length, or a zero vector if `p` has no
+    /// length.
+    pub fn normalize(p: LuaPoint) -> LuaPoint {
+        let length = p.x().hypot(p.y());
+        if length <= f32::EPSILON || !length.is_finite() {
+            return Ok(LuaPoint { value: [0.0, 0.0] });
+        }
+        Ok(LuaPoint {
+            value: [p.x() / length, p.y() / length],
+        })
+    }
+    /// Linearly interpolates between `a` and `b`; `t` isn't clamped.
+    pub fn lerp(a: LuaPoint, b: LuaPoint, t: f32) -> LuaPoint {
+        Ok(LuaPoint {
+            value: [a.x() + (b.x() - a.x()) * t, a.y() + (b.y() - a.y()) * t],
+        })
+    }
+    /// Rotates `p` by `degrees` (clockwise on screen, same as
+    /// `Canvas:rotate`) around `pivot`, or origin if omitted.
+    pub fn rotate(p: LuaPoint, degrees: f32, pivot: LuaFallible<LuaPoint>) -> LuaPoint {
+        let pivot: Point = pivot.map(LuaPoint::into).unwrap_or_default();
+        let matrix = Matrix::rotate_deg_pivot(degrees, pivot);
+        Ok(LuaPoint::from(matrix.map_point(p)))
+    }
+    pub fn distance(a: LuaPoint, b: LuaPoint) -> f32 {
+        Ok((b.x() - a.x()).hypot(b.y() - a.y()))
+    }
+}
+
+/// Rotation and scale (`scos`, `ssin`) followed by translation (`tx`, `ty`)
+/// applied to a single glyph.
+#[derive(Clone, Copy, PartialEq)]
+pub struct LuaRSXform(pub RSXform);
+
+impl<'lua> FromLua<'lua> for LuaRSXform {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(it) => it,
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "RSXform",
+                    message: Some("expected a {scos, ssin, tx, ty} table".to_string()),
+                })
+            }
+        };
+
+        let mut value = [0.0; 4];
+        for (i, name) in ["scos", "ssin", "tx", "ty"].iter().enumerate() {
+            value[i] = match table.get::<_, Option<f32>>(*name)? {
+                Some(it) => it,
+                None => table
+                    .get(i + 1)
+                    .map_err(|_| LuaError::FromLuaConversionError {
+                        from: "table",
+                        to: "RSXform",
+                        message: Some(format!("RSXform table missing '{}' field", name)),
+                    })?,
+            };
+        }
+        let [scos, ssin, tx, ty] = value;
+        Ok(LuaRSXform(RSXform::new(scos, ssin, (tx, ty))))
+    }
+}
+from_lua_argpack!(LuaRSXform);
+
 #[derive(Clone)]
 pub struct LuaLine<const N: usize = 2> {
     pub from: LuaPoint<N>,
@@ -802,6 +1051,101 @@ impl<'lua> TryFrom<LuaTable<'lua>> for SidePack {
     }
 }
 
+/// Scale factors with an optional pivot point, accepted as
+/// `sx, sy?, pivot?` or `{x, y}, pivot?`.
+///
+/// A number following `sx` is always read as `sy`, so `sx, {x, y}` scales
+/// uniformly about the pivot instead of being misread as two factors.
+#[derive(Clone, Copy)]
+pub struct ScalePack {
+    pub sx: f32,
+    pub sy: f32,
+    pub pivot: Option<Point>,
+}
+
+impl<'lua> FromArgPack<'lua> for ScalePack {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        args.assert_next_type(&[LuaType::Integer, LuaType::Number, LuaType::Table])?;
+
+        let (sx, sy) = match args.pop_typed::<f32>() {
+            Some(sx) => (sx, args.pop_typed::<f32>().unwrap_or(sx)),
+            None => {
+                let factors = LuaPoint::convert(args, lua)?;
+                (factors.x(), factors.y())
+            }
+        };
+        let pivot = Option::<LuaPoint>::convert(args, lua)?.map(LuaPoint::into);
+
+        Ok(ScalePack { sx, sy, pivot })
+    }
+}
+
+const WEIGHT_NAMES: &[(&str, Weight)] = &[
+    ("invisible", Weight::INVISIBLE),
+    ("thin", Weight::THIN),
+    ("extra_light", Weight::EXTRA_LIGHT),
+    ("light", Weight::LIGHT),
+    ("normal", Weight::NORMAL),
+    ("medium", Weight::MEDIUM),
+    ("semi_bold", Weight::SEMI_BOLD),
+    ("bold", Weight::BOLD),
+    ("extra_bold", Weight::EXTRA_BOLD),
+    ("black", Weight::BLACK),
+    ("extra_black", Weight::EXTRA_BLACK),
+];
+
+const WIDTH_NAMES: &[(&str, Width)] = &[
+    ("ultra_condensed", Width::ULTRA_CONDENSED),
+    ("extra_condensed", Width::EXTRA_CONDENSED),
+    ("condensed", Width::CONDENSED),
+    ("semi_condensed", Width::SEMI_CONDENSED),
+    ("normal", Width::NORMAL),
+    ("semi_expanded", Width::SEMI_EXPANDED),
+    ("expanded", Width::EXPANDED),
+    ("extra_expanded", Width::EXTRA_EXPANDED),
+    ("ultra_expanded", Width::ULTRA_EXPANDED),
+];
+
+const SLANT_NAMES: &[(&str, Slant)] = &[
+    ("upright", Slant::Upright),
+    ("italic", Slant::Italic),
+    ("oblique", Slant::Oblique),
+];
+
+fn lookup_name<T: Copy>(names: &[(&str, T)], name: &str) -> Option<T> {
+    names
+        .iter()
+        .find(|(it, _)| *it == name)
+        .map(|(_, value)| *value)
+}
+
+fn reverse_name<T: PartialEq>(names: &[(&'static str, T)], value: &T) -> Option<&'static str> {
+    names
+        .iter()
+        .find(|(_, it)| it == value)
+        .map(|(name, _)| *name)
+}
+
+fn weight_name(weight: Weight) -> String {
+    reverse_name(WEIGHT_NAMES, &weight)
+        .map(str::to_string)
+        .unwrap_or_else(|| (*weight).to_string())
+}
+
+fn width_name(width: Width) -> String {
+    reverse_name(WIDTH_NAMES, &width)
+        .map(str::to_string)
+        .unwrap_or_else(|| (*width).to_string())
+}
+
+fn expected_names<T>(names: &[(&str, T)]) -> String {
+    names
+        .iter()
+        .map(|(name, _)| format!("'{}'", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Clone, Copy)]
 pub struct LuaFontWeight(pub i32);
 
@@ -813,7 +1157,6 @@ impl LuaFontWeight {
 
 impl<'lua> FromArgPack<'lua> for LuaFontWeight {
     fn convert(args: &mut ArgumentContext<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
-        static EXPECTED: &str = "'invisible', 'thin', 'extra_light', 'light', 'normal', 'medium', 'semi_bold', 'bold', 'extra_bold', 'black', 'extra_black'";
         match args.pop() {
             LuaNil => Ok(LuaFontWeight(*Weight::NORMAL)),
             LuaValue::Integer(number) => {
@@ -842,26 +1185,21 @@ impl<'lua> FromArgPack<'lua> for LuaFontWeight {
                 }
                 Ok(LuaFontWeight(number.floor() as i32))
             }
-            LuaValue::String(name) => match name.to_str()? {
-                "invisible" => Ok(LuaFontWeight(*Weight::INVISIBLE)),
-                "thin" => Ok(LuaFontWeight(*Weight::THIN)),
-                "extra_light" => Ok(LuaFontWeight(*Weight::EXTRA_LIGHT)),
-                "light" => Ok(LuaFontWeight(*Weight::LIGHT)),
-                "normal" => Ok(LuaFontWeight(*Weight::NORMAL)),
-                "medium" => Ok(LuaFontWeight(*Weight::MEDIUM)),
-                "semi_bold" => Ok(LuaFontWeight(*Weight::SEMI_BOLD)),
-                "bold" => Ok(LuaFontWeight(*Weight::BOLD)),
-                "extra_bold" => Ok(LuaFontWeight(*Weight::EXTRA_BOLD)),
-                "black" => Ok(LuaFontWeight(*Weight::BLACK)),
-                "extra_black" => Ok(LuaFontWeight(*Weight::EXTRA_BLACK)),
-                other => Err(LuaError::RuntimeError(format!(
-                    "unknown weight name: '{}'; expected a number or one of: {}",
-                    other, EXPECTED
-                ))),
-            },
+            LuaValue::String(name) => {
+                let name = name.to_str()?;
+                match lookup_name(WEIGHT_NAMES, name) {
+                    Some(weight) => Ok(LuaFontWeight(*weight)),
+                    None => Err(LuaError::RuntimeError(format!(
+                        "unknown weight name: '{}'; expected a number or one of: {}",
+                        name,
+                        expected_names(WEIGHT_NAMES)
+                    ))),
+                }
+            }
             other => Err(LuaError::RuntimeError(format!(
                 "invalid font weight: '{:?}'; expected a number or name ({})",
-                other, EXPECTED
+                other,
+                expected_names(WEIGHT_NAMES)
             ))),
         }
     }
@@ -884,7 +1222,6 @@ impl LuaFontWidth {
 
 impl<'lua> FromArgPack<'lua> for LuaFontWidth {
     fn convert(args: &mut ArgumentContext<'lua>, _lua: &'lua Lua) -> LuaResult<Self> {
-        static EXPECTED: &str = "'invisible', 'thin', 'extra_light', 'light', 'normal', 'medium', 'semi_bold', 'bold', 'extra_bold', 'black', 'extra_black'";
         match args.pop() {
             LuaNil => Ok(LuaFontWidth(*Width::NORMAL)),
             LuaValue::Integer(number) => {
@@ -913,31 +1250,28 @@ impl<'lua> FromArgPack<'lua> for LuaFontWidth {
                 }
                 Ok(LuaFontWidth(number.floor() as i32))
             }
-            LuaValue::String(name) => match name.to_str()? {
-                "ultra_condensed" => Ok(LuaFontWidth(*Width::ULTRA_CONDENSED)),
-                "extra_condensed" => Ok(LuaFontWidth(*Width::EXTRA_CONDENSED)),
-                "condensed" => Ok(LuaFontWidth(*Width::CONDENSED)),
-                "semi_condensed" => Ok(LuaFontWidth(*Width::SEMI_CONDENSED)),
-                "normal" => Ok(LuaFontWidth(*Width::NORMAL)),
-                "semi_expanded" => Ok(LuaFontWidth(*Width::SEMI_EXPANDED)),
-                "expanded" => Ok(LuaFontWidth(*Width::EXPANDED)),
-                "extra_expanded" => Ok(LuaFontWidth(*Width::EXTRA_EXPANDED)),
-                "ultra_expanded" => Ok(LuaFontWidth(*Width::ULTRA_EXPANDED)),
-                other => Err(LuaError::FromLuaConversionError {
-                    from: "string",
-                    to: "Width",
-                    message: Some(format!(
-                        "unknown width name: '{}'; expected a number or one of: {}",
-                        other, EXPECTED
-                    )),
-                }),
-            },
+            LuaValue::String(name) => {
+                let name = name.to_str()?;
+                match lookup_name(WIDTH_NAMES, name) {
+                    Some(width) => Ok(LuaFontWidth(*width)),
+                    None => Err(LuaError::FromLuaConversionError {
+                        from: "string",
+                        to: "Width",
+                        message: Some(format!(
+                            "unknown width name: '{}'; expected a number or one of: {}",
+                            name,
+                            expected_names(WIDTH_NAMES)
+                        )),
+                    }),
+                }
+            }
             other => Err(LuaError::FromLuaConversionError {
                 from: other.type_name(),
                 to: "Width",
                 message: Some(format!(
                     "invalid font width: '{:?}'; expected a number or name ({})",
-                    other, EXPECTED
+                    other,
+                    expected_names(WIDTH_NAMES)
                 )),
             }),
         }
@@ -949,3 +1283,414 @@ impl<'lua> IntoLua<'lua> for LuaFontWidth {
         Ok(LuaValue::Integer(self.0 as i64))
     }
 }
+
+/// Parses a font style descriptor such as `"semi_bold condensed italic"`.
+///
+/// Weight, width and slant names can appear in any order; weights can also
+/// be numbers. `"normal"` sets the weight, or the width if the weight was
+/// already given.
+pub fn parse_font_style(descriptor: &str) -> Result<FontStyle, String> {
+    let mut weight: Option<Weight> = None;
+    let mut width: Option<Width> = None;
+    let mut slant: Option<Slant> = None;
+
+    let understood = |weight: Option<Weight>, width: Option<Width>, slant: Option<Slant>| {
+        let parts: Vec<String> = [
+            weight.map(|it| format!("weight '{}'", weight_name(it))),
+            width.map(|it| format!("width '{}'", width_name(it))),
+            slant.and_then(|it| reverse_name(SLANT_NAMES, &it).map(|it| format!("slant '{}'", it))),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if parts.is_empty() {
+            "nothing".to_string()
+        } else {
+            parts.join(", ")
+        }
+    };
+
+    for token in descriptor.split_whitespace() {
+        let token = token.to_ascii_lowercase();
+        let numeric_weight = token
+            .parse::<i32>()
+            .ok()
+            .filter(|it| *it >= 0)
+            .map(Weight::from);
+        if let Some(value) = lookup_name(WEIGHT_NAMES, &token).or(numeric_weight) {
+            if weight.is_none() {
+                weight = Some(value);
+                continue;
+            }
+        }
+        if let Some(value) = lookup_name(WIDTH_NAMES, &token) {
+            if width.is_none() {
+                width = Some(value);
+                continue;
+            }
+        }
+        if let Some(value) = lookup_name(SLANT_NAMES, &token) {
+            if slant.is_none() {
+                slant = Some(value);
+                continue;
+            }
+        }
+        let is_known = lookup_name(WEIGHT_NAMES, &token).is_some()
+            || numeric_weight.is_some()
+            || lookup_name(WIDTH_NAMES, &token).is_some()
+            || lookup_name(SLANT_NAMES, &token).is_some();
+        let problem = if is_known { "repeated" } else { "unknown" };
+        return Err(format!(
+            "{} token '{}' in font style '{}'; understood {}",
+            problem,
+            token,
+            descriptor,
+            understood(weight, width, slant)
+        ));
+    }
+
+    Ok(FontStyle::new(
+        weight.unwrap_or(Weight::NORMAL),
+        width.unwrap_or(Width::NORMAL),
+        slant.unwrap_or(Slant::Upright),
+    ))
+}
+
+/// Returns the canonical descriptor of `style`, accepted by
+/// [`parse_font_style`]. Default components are left out, so the normal
+/// style is described as `"normal"`.
+pub fn describe_font_style(style: &FontStyle) -> String {
+    let mut parts = Vec::with_capacity(3);
+    if style.weight() != Weight::NORMAL {
+        parts.push(weight_name(style.weight()));
+    }
+    if style.width() != Width::NORMAL {
+        parts.push(width_name(style.width()));
+    }
+    if style.slant() != Slant::Upright {
+        if let Some(name) = reverse_name(SLANT_NAMES, &style.slant()) {
+            parts.push(name.to_string());
+        }
+    }
+    if parts.is_empty() {
+        return "normal".to_string();
+    }
+    parts.join(" ")
+}
+
+/// Font style given either as separate weight, width and slant arguments or
+/// as a single descriptor string, e.g. `"bold italic"`.
+pub struct FontStyleArgs(pub FontStyle);
+
+impl<'lua> FromArgPack<'lua> for FontStyleArgs {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if args.len() == 1 {
+            if let Some(descriptor) = args.pop_typed::<LuaString>() {
+                let descriptor = descriptor.to_str()?;
+                return parse_font_style(descriptor)
+                    .map(FontStyleArgs)
+                    .map_err(LuaError::RuntimeError);
+            }
+        }
+        let weight = Option::<LuaFontWeight>::convert(args, lua)?;
+        let width = Option::<LuaFontWidth>::convert(args, lua)?;
+        let slant = Option::<crate::LuaSlant>::convert(args, lua)?;
+        Ok(FontStyleArgs(FontStyle::new(
+            weight.map_or(Weight::NORMAL, |it| it.to_skia_weight()),
+            width.map_or(Width::NORMAL, |it| it.to_skia_width()),
+            slant.map_or(Slant::Upright, |it| it.0),
+        )))
+    }
+}
+
+/// Maximum number of paints kept by the [`LikePaint`] pool.
+const PAINT_POOL_SIZE: usize = 16;
+
+thread_local! {
+    static PAINT_POOL: RefCell<Vec<Paint>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Resets `paint` and returns it to the [`LikePaint`] pool, unless the pool
+/// is full.
+fn release_paint(mut paint: Paint) {
+    paint.reset();
+    PAINT_POOL.with(|it| {
+        let mut pool = it.borrow_mut();
+        if pool.len() < PAINT_POOL_SIZE {
+            pool.push(paint);
+        }
+    });
+}
+
+/// Drops paints kept by the [`LikePaint`] pool.
+pub(crate) fn clear_paint_pool() {
+    PAINT_POOL.with(|it| it.borrow_mut().clear());
+}
+
+/// A `Paint` handle or a paint constructor table.
+///
+/// Paints built from tables are taken from a pool and returned to it when the
+/// argument is dropped, so the common `canvas:drawRect(r, {color = c})` style
+/// doesn't construct a new paint for every draw call.
+pub struct LikePaint {
+    paint: Option<Paint>,
+    pooled: bool,
+}
+
+impl LikePaint {
+    fn from_pool<'lua>(table: LuaTable<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let mut paint = PAINT_POOL
+            .with(|it| it.borrow_mut().pop())
+            .unwrap_or_default();
+        if let Err(err) = LuaPaint::apply_table(&mut paint, table, lua) {
+            release_paint(paint);
+            return Err(err);
+        }
+        Ok(LikePaint {
+            paint: Some(paint),
+            pooled: true,
+        })
+    }
+
+    #[inline]
+    pub fn paint(&self) -> &Paint {
+        self.paint.as_ref().expect("paint is only taken on unwrap")
+    }
+}
+
+impl Clone for LikePaint {
+    fn clone(&self) -> Self {
+        LikePaint {
+            paint: self.paint.clone(),
+            pooled: false,
+        }
+    }
+}
+
+impl Drop for LikePaint {
+    fn drop(&mut self) {
+        if !self.pooled {
+            return;
+        }
+        if let Some(paint) = self.paint.take() {
+            release_paint(paint);
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for LikePaint {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::UserData(ud) if ud.is::<LuaPaint>() => Ok(LikePaint {
+                paint: Some(ud.borrow::<LuaPaint>()?.0.clone()),
+                pooled: false,
+            }),
+            LuaValue::Table(it) => LikePaint::from_pool(it, lua),
+            other => Err(LuaError::FromLuaConversionError {
+                from: other.type_name(),
+                to: "Paint",
+                message: Some("expected Paint or constructor Table".to_string()),
+            }),
+        }
+    }
+}
+from_lua_argpack!(LikePaint);
+
+impl<'lua> WrapperT<'lua> for LikePaint {
+    type Wrapped = Paint;
+
+    /// Takes the paint out of the argument; it won't be returned to the pool.
+    #[inline]
+    fn unwrap(mut self) -> Paint {
+        self.paint.take().expect("paint is only taken on unwrap")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lua::FromArgs;
+
+    fn pooled_paints() -> usize {
+        PAINT_POOL.with(|it| it.borrow().len())
+    }
+
+    fn scale_pack(lua: &Lua, source: &str) -> LuaResult<ScalePack> {
+        let values: LuaMultiValue = lua.load(source).eval().unwrap();
+        <(ScalePack,)>::from_arguments(values, lua, None, None).map(|(it,)| it)
+    }
+
+    fn like_paint(lua: &Lua, source: &str) -> LuaResult<LikePaint> {
+        let value: LuaValue = lua.load(source).eval().unwrap();
+        LikePaint::from_lua(value, lua)
+    }
+
+    #[test]
+    fn table_paints_are_reused_from_pool() {
+        let lua = Lua::new();
+        // each test runs on its own thread, so the pool starts empty
+        assert_eq!(pooled_paints(), 0);
+
+        let paint = like_paint(&lua, "{r = 1, antiAlias = true}").unwrap();
+        assert!(paint.paint().is_anti_alias());
+        drop(paint);
+        assert_eq!(pooled_paints(), 1);
+
+        let mut hits = 0;
+        for _ in 0..1000 {
+            let available = pooled_paints();
+            let paint = like_paint(&lua, "{g = 1, strokeWidth = 2}").unwrap();
+            if pooled_paints() < available {
+                hits += 1;
+            }
+            // returned paints are reset before reuse
+            assert!(!paint.paint().is_anti_alias());
+            assert_eq!(paint.paint().stroke_width(), 2.);
+        }
+        assert_eq!(hits, 1000);
+        assert_eq!(pooled_paints(), 1);
+
+        // unwrapped paints are owned by the caller and leave the pool
+        let owned = like_paint(&lua, "{b = 1}").unwrap().unwrap();
+        assert_eq!(owned.color4f().b, 1.);
+        assert_eq!(pooled_paints(), 0);
+    }
+
+    #[test]
+    fn failed_table_conversion_returns_paint_to_pool() {
+        let lua = Lua::new();
+        drop(like_paint(&lua, "{r = 1}").unwrap());
+        assert_eq!(pooled_paints(), 1);
+
+        // antiAlias is applied before style fails to convert
+        assert!(like_paint(&lua, "{antiAlias = true, style = 'bogus'}").is_err());
+        assert_eq!(pooled_paints(), 1);
+
+        let paint = like_paint(&lua, "{r = 1}").unwrap();
+        assert!(!paint.paint().is_anti_alias());
+    }
+
+    #[test]
+    fn scale_pack_disambiguates_factors_and_pivot() {
+        let lua = Lua::new();
+        let parse = |source| {
+            let it = scale_pack(&lua, source).unwrap();
+            (it.sx, it.sy, it.pivot)
+        };
+        assert_eq!(parse("return 2"), (2., 2., None));
+        assert_eq!(parse("return 2, 3"), (2., 3., None));
+        assert_eq!(
+            parse("return 2, {x = 5, y = 6}"),
+            (2., 2., Some(Point::new(5., 6.)))
+        );
+        assert_eq!(
+            parse("return 2, 3, {5, 6}"),
+            (2., 3., Some(Point::new(5., 6.)))
+        );
+        assert_eq!(parse("return {x = 2, y = 3}"), (2., 3., None));
+        assert_eq!(
+            parse("return {2, 3}, {5, 6}"),
+            (2., 3., Some(Point::new(5., 6.)))
+        );
+        assert!(scale_pack(&lua, "return 'big'").is_err());
+    }
+
+    fn style_parts(style: FontStyle) -> (Weight, Width, Slant) {
+        (style.weight(), style.width(), style.slant())
+    }
+
+    #[test]
+    fn font_style_descriptors_parse_in_any_order() {
+        let parse = |it| style_parts(parse_font_style(it).unwrap());
+        assert_eq!(
+            parse("semi_bold condensed italic"),
+            (Weight::SEMI_BOLD, Width::CONDENSED, Slant::Italic)
+        );
+        assert_eq!(
+            parse("Italic  BOLD"),
+            (Weight::BOLD, Width::NORMAL, Slant::Italic)
+        );
+        assert_eq!(
+            parse("expanded 350"),
+            (Weight::from(350), Width::EXPANDED, Slant::Upright)
+        );
+        // the second "normal" can only be a width
+        assert_eq!(
+            parse("normal normal oblique"),
+            (Weight::NORMAL, Width::NORMAL, Slant::Oblique)
+        );
+        assert_eq!(parse(""), (Weight::NORMAL, Width::NORMAL, Slant::Upright));
+    }
+
+    #[test]
+    fn font_style_descriptor_errors_list_understood_parts() {
+        assert_eq!(
+            parse_font_style("bold bold").unwrap_err(),
+            "repeated token 'bold' in font style 'bold bold'; understood weight 'bold'"
+        );
+        assert_eq!(
+            parse_font_style("italic condensed wavy").unwrap_err(),
+            "unknown token 'wavy' in font style 'italic condensed wavy'; \
+             understood width 'condensed', slant 'italic'"
+        );
+        assert_eq!(
+            parse_font_style("heavy").unwrap_err(),
+            "unknown token 'heavy' in font style 'heavy'; understood nothing"
+        );
+    }
+
+    #[test]
+    fn font_style_descriptors_round_trip() {
+        for style in [
+            FontStyle::normal(),
+            FontStyle::bold_italic(),
+            FontStyle::new(Weight::from(450), Width::ULTRA_EXPANDED, Slant::Oblique),
+            FontStyle::new(Weight::NORMAL, Width::CONDENSED, Slant::Upright),
+        ] {
+            let descriptor = describe_font_style(&style);
+            let parsed = parse_font_style(&descriptor).unwrap();
+            assert_eq!(style_parts(parsed), style_parts(style), "{}", descriptor);
+        }
+        assert_eq!(describe_font_style(&FontStyle::normal()), "normal");
+        assert_eq!(
+            describe_font_style(&FontStyle::bold_italic()),
+            "bold italic"
+        );
+    }
+
+    #[test]
+    fn rect_tables_accept_each_spelling() {
+        let lua = Lua::new();
+        let rect = |source: &str| -> LuaResult<Rect> {
+            let value: LuaValue = lua.load(source).eval().unwrap();
+            LuaRect::from_lua(value, &lua).map(Rect::from)
+        };
+        let expected = Rect::new(10., 20., 50., 80.);
+        assert_eq!(
+            rect("{left = 10, top = 20, right = 50, bottom = 80}").unwrap(),
+            expected
+        );
+        assert_eq!(
+            rect("{x = 10, y = 20, width = 40, height = 60}").unwrap(),
+            expected
+        );
+        assert_eq!(rect("{x = 10, y = 20, w = 40, h = 60}").unwrap(), expected);
+        assert_eq!(
+            rect("{cx = 30, cy = 50, w = 40, h = 60}").unwrap(),
+            expected
+        );
+        assert_eq!(
+            rect("{cx = 30, cy = 50, width = 40, height = 60}").unwrap(),
+            expected
+        );
+
+        let err = rect("{x = 10, cx = 30, cy = 50, w = 40, h = 60}")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("can't have a 'x' field"), "{}", err);
+        let err = rect("{cx = 30, w = 40, h = 60}").unwrap_err().to_string();
+        assert!(err.contains("missing 'cy'"), "{}", err);
+        let err = rect("{cx = 30, cy = 50, w = 40}").unwrap_err().to_string();
+        assert!(err.contains("missing 'height'"), "{}", err);
+    }
+}
@@ -0,0 +1,185 @@
+//! Geometry for borders with different widths per side.
+//!
+//! Each side is the part of the ring between outer and inner shape that lies
+//! within its wedge. Wedges are split along lines going from outer to inner
+//! corners, which produces mitered joins between sides, and continue past the
+//! inner corner through the area cut out by inner corner radii.
+
+use skia_safe::{rrect::Corner, Path, PathFillType, PathOp, Point, RRect, Rect, Vector};
+
+/// Side order used by [`side_paths`].
+pub const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+/// Corner order matching [`RRect`] radii: upper-left, upper-right,
+/// lower-right, lower-left.
+const CORNERS: [Corner; 4] = [
+    Corner::UpperLeft,
+    Corner::UpperRight,
+    Corner::LowerRight,
+    Corner::LowerLeft,
+];
+
+/// Returns the shape left after insetting `outer` by `widths` (top, right,
+/// bottom, left), with corner radii reduced by adjacent widths.
+pub fn inner_rrect(outer: &RRect, widths: [f32; 4]) -> RRect {
+    let [top, right, bottom, left] = widths;
+    let bounds = outer.rect();
+
+    let mut rect = Rect::new(
+        bounds.left + left,
+        bounds.top + top,
+        bounds.right - right,
+        bounds.bottom - bottom,
+    );
+    // collapse into a line when widths overlap
+    if rect.left > rect.right {
+        let x = (rect.left + rect.right) / 2.0;
+        rect.left = x;
+        rect.right = x;
+    }
+    if rect.top > rect.bottom {
+        let y = (rect.top + rect.bottom) / 2.0;
+        rect.top = y;
+        rect.bottom = y;
+    }
+
+    // horizontal and vertical widths adjacent to each corner
+    let adjacent = [(left, top), (right, top), (right, bottom), (left, bottom)];
+    let mut radii = [Vector::default(); 4];
+    for (i, corner) in CORNERS.iter().enumerate() {
+        let outer_radii = outer.radii(*corner);
+        radii[i] = Vector::new(
+            (outer_radii.x - adjacent[i].0).max(0.0),
+            (outer_radii.y - adjacent[i].1).max(0.0),
+        );
+    }
+
+    RRect::new_rect_radii(rect, &radii)
+}
+
+/// Returns corner points of `rect` in [`CORNERS`] order.
+fn corner_points(rect: &Rect) -> [Point; 4] {
+    [
+        Point::new(rect.left, rect.top),
+        Point::new(rect.right, rect.top),
+        Point::new(rect.right, rect.bottom),
+        Point::new(rect.left, rect.bottom),
+    ]
+}
+
+/// Continues the line from `outer` through `inner` corner until it leaves
+/// the area of `radii` cut out from inner corner.
+fn extend_miter(outer: Point, inner: Point, radii: Vector) -> Point {
+    let direction = inner - outer;
+    let mut t = f32::INFINITY;
+    if direction.x.abs() > f32::EPSILON {
+        t = t.min(radii.x / direction.x.abs());
+    }
+    if direction.y.abs() > f32::EPSILON {
+        t = t.min(radii.y / direction.y.abs());
+    }
+    if !t.is_finite() {
+        return inner;
+    }
+    inner + direction * t
+}
+
+fn polygon(points: &[Point]) -> Path {
+    let mut path = Path::new();
+    path.add_poly(points, true);
+    path
+}
+
+/// Returns fill paths of each border side in [`SIDES`] order, or `None` for
+/// sides with no width.
+pub fn side_paths(outer: &RRect, widths: [f32; 4]) -> [Option<Path>; 4] {
+    let inner = inner_rrect(outer, widths);
+
+    let mut ring = Path::new();
+    ring.add_rrect(outer, None);
+    ring.add_rrect(inner, None);
+    ring.set_fill_type(PathFillType::EvenOdd);
+
+    let outer_points = corner_points(outer.rect());
+    let inner_points = corner_points(inner.rect());
+    let mut miter_points = [Point::default(); 4];
+    for (i, corner) in CORNERS.iter().enumerate() {
+        miter_points[i] = extend_miter(outer_points[i], inner_points[i], inner.radii(*corner));
+    }
+
+    let mut result: [Option<Path>; 4] = Default::default();
+    for (side, result) in result.iter_mut().enumerate() {
+        if widths[side] <= 0.0 {
+            continue;
+        }
+        // side `i` lies between corners `i` and `i + 1`
+        let (a, b) = (side, (side + 1) % 4);
+        let wedge = polygon(&[
+            outer_points[a],
+            outer_points[b],
+            inner_points[b],
+            miter_points[b],
+            miter_points[a],
+            inner_points[a],
+        ]);
+        *result = ring.op(&wedge, PathOp::Intersect);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inner_rrect_reduces_radii_by_adjacent_widths() {
+        let outer = RRect::new_rect_xy(Rect::new(0., 0., 20., 20.), 6., 6.);
+        let inner = inner_rrect(&outer, [4., 2., 1., 8.]);
+
+        assert_eq!(*inner.rect(), Rect::new(8., 4., 18., 19.));
+        // skia squares off corners with either radius at zero
+        assert_eq!(inner.radii(Corner::UpperLeft), Vector::new(0., 0.));
+        assert_eq!(inner.radii(Corner::UpperRight), Vector::new(4., 2.));
+        assert_eq!(inner.radii(Corner::LowerRight), Vector::new(4., 5.));
+    }
+
+    #[test]
+    fn inner_rrect_collapses_overlapping_widths() {
+        let outer = RRect::new_rect(Rect::new(0., 0., 10., 10.));
+        let inner = inner_rrect(&outer, [8., 8., 8., 8.]);
+        assert_eq!(*inner.rect(), Rect::new(5., 5., 5., 5.));
+    }
+
+    #[test]
+    fn side_paths_meet_at_mitered_corners() {
+        let outer = RRect::new_rect(Rect::new(0., 0., 20., 20.));
+        let [top, right, bottom, left] = side_paths(&outer, [4., 2., 0., 4.]);
+        let (top, right, left) = (top.unwrap(), right.unwrap(), left.unwrap());
+        assert!(
+            bottom.is_none(),
+            "sides without width shouldn't have a path"
+        );
+
+        assert!(top.contains((10., 2.)));
+        assert!(!top.contains((10., 5.)));
+        assert!(right.contains((19., 10.)));
+        assert!(left.contains((2., 10.)));
+
+        // the upper-left corner is split along its diagonal
+        assert!(top.contains((3., 1.)) && !left.contains((3., 1.)));
+        assert!(left.contains((1., 3.)) && !top.contains((1., 3.)));
+    }
+
+    #[test]
+    fn side_paths_follow_rounded_corners() {
+        let outer = RRect::new_rect_xy(Rect::new(0., 0., 40., 40.), 10., 10.);
+        let [top, ..] = side_paths(&outer, [4., 4., 4., 4.]);
+        let top = top.unwrap();
+
+        // outside of the outer arc and inside of the inner arc
+        assert!(!top.contains((1., 1.)));
+        assert!(!top.contains((6., 6.)));
+        // on the ring near the middle of the arc, above the miter line
+        assert!(top.contains((5., 2.5)));
+    }
+}
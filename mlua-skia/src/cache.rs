@@ -0,0 +1,139 @@
+//! Memoization of preset shaders that are expensive to build but only depend
+//! on a few parameters.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+};
+
+use skia_safe::Shader;
+
+/// Number of shaders kept by caches created with [`ShaderCache::new`].
+pub const DEFAULT_CAPACITY: usize = 32;
+
+/// Shaders keyed by parameters they were built from.
+///
+/// Caches are meant to be stored in `thread_local!` statics, one per preset.
+/// Once a cache is full, the oldest shader is evicted to make room for a new
+/// one, so scripts drawing with ever changing parameters don't grow it
+/// without bounds.
+pub struct ShaderCache<K> {
+    entries: RefCell<HashMap<K, Shader>>,
+    /// Keys of `entries` in insertion order.
+    order: RefCell<VecDeque<K>>,
+    capacity: usize,
+}
+
+impl<K: Hash + Eq + Clone> ShaderCache<K> {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        ShaderCache {
+            entries: RefCell::new(HashMap::new()),
+            order: RefCell::new(VecDeque::new()),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns shader cached for `key`, calling `build` to create it if it's
+    /// missing. Failed builds aren't cached.
+    pub fn get_or_build(&self, key: K, build: impl FnOnce() -> Option<Shader>) -> Option<Shader> {
+        if let Some(shader) = self.entries.borrow().get(&key) {
+            return Some(shader.clone());
+        }
+        let shader = build()?;
+        let mut entries = self.entries.borrow_mut();
+        let mut order = self.order.borrow_mut();
+        if entries.len() >= self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                entries.remove(&oldest);
+            }
+        }
+        order.push_back(key.clone());
+        entries.insert(key, shader.clone());
+        Some(shader)
+    }
+
+    /// Number of cached shaders.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+        self.order.borrow_mut().clear();
+    }
+}
+
+impl<K: Hash + Eq + Clone> Default for ShaderCache<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use skia_safe::{shaders, Color};
+
+    use super::*;
+
+    #[test]
+    fn builds_each_key_once() {
+        let cache = ShaderCache::new();
+        let builds = Cell::new(0);
+        let build = || {
+            builds.set(builds.get() + 1);
+            Some(shaders::color(Color::RED))
+        };
+
+        assert!(cache.get_or_build(1, build).is_some());
+        assert!(cache.get_or_build(1, build).is_some());
+        assert_eq!(builds.get(), 1);
+        assert!(cache.get_or_build(2, build).is_some());
+        assert_eq!(builds.get(), 2);
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn evicts_oldest_shader_when_full() {
+        let cache = ShaderCache::with_capacity(2);
+        let builds = Cell::new(0);
+        let build = || {
+            builds.set(builds.get() + 1);
+            Some(shaders::color(Color::RED))
+        };
+
+        for key in 0..3 {
+            assert!(cache.get_or_build(key, build).is_some());
+        }
+        assert_eq!(cache.len(), 2);
+        // 1 and 2 are still cached, 0 was evicted
+        assert!(cache.get_or_build(2, build).is_some());
+        assert_eq!(builds.get(), 3);
+        assert!(cache.get_or_build(0, build).is_some());
+        assert_eq!(builds.get(), 4);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn doesnt_cache_failed_builds() {
+        let cache = ShaderCache::new();
+        assert!(cache.get_or_build("missing", || None).is_none());
+        assert!(cache.is_empty());
+        assert!(cache
+            .get_or_build("missing", || Some(shaders::color(Color::BLUE)))
+            .is_some());
+    }
+}
@@ -0,0 +1,258 @@
+//! Geometry for plotting numeric series.
+
+use skia_safe::{Path, Point, Rect};
+
+/// Returns value range of `values`, ignoring non-finite values.
+pub fn value_range(values: &[f32]) -> Option<(f32, f32)> {
+    values
+        .iter()
+        .copied()
+        .filter(|it| it.is_finite())
+        .fold(None, |range, it| match range {
+            None => Some((it, it)),
+            Some((min, max)) => Some((min.min(it), max.max(it))),
+        })
+}
+
+/// Maps `values` into `bounds` and splits them into runs of consecutive finite
+/// samples. Non-finite samples produce gaps.
+pub fn map_values(values: &[f32], bounds: Rect, min: f32, max: f32) -> Vec<Vec<Point>> {
+    let step = if values.len() > 1 {
+        bounds.width() / (values.len() - 1) as f32
+    } else {
+        0.
+    };
+    let span = max - min;
+
+    let mut result = Vec::new();
+    let mut current = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        if !value.is_finite() {
+            if !current.is_empty() {
+                result.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+
+        let t = if span.abs() > f32::EPSILON {
+            (value - min) / span
+        } else {
+            0.5
+        };
+        current.push(Point::new(
+            bounds.left + step * i as f32,
+            bounds.bottom - t * bounds.height(),
+        ));
+    }
+    if !current.is_empty() {
+        result.push(current);
+    }
+    result
+}
+
+/// Appends a contour through `points` to `path`, smoothing it with a
+/// Catmull-Rom spline if `smooth` is set.
+fn add_run(path: &mut Path, points: &[Point], smooth: bool, start_new: bool) {
+    let first = match points.first() {
+        Some(it) => *it,
+        None => return,
+    };
+    if start_new {
+        path.move_to(first);
+    } else {
+        path.line_to(first);
+    }
+
+    if !smooth || points.len() < 3 {
+        for point in &points[1..] {
+            path.line_to(*point);
+        }
+        return;
+    }
+
+    for i in 0..points.len() - 1 {
+        let p0 = points[i.saturating_sub(1)];
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = points[(i + 2).min(points.len() - 1)];
+
+        // uniform Catmull-Rom segment expressed as a cubic Bézier
+        let c1 = p1 + (p2 - p0) * (1. / 6.);
+        let c2 = p2 - (p3 - p1) * (1. / 6.);
+        path.cubic_to(c1, c2, p2);
+    }
+}
+
+/// Builds the line through `runs` and area under it closed at the bottom of
+/// `bounds`.
+pub fn plot_paths(runs: &[Vec<Point>], bounds: Rect, smooth: bool) -> (Path, Path) {
+    let mut line = Path::new();
+    let mut area = Path::new();
+
+    for run in runs {
+        add_run(&mut line, run, smooth, true);
+
+        let (first, last) = match (run.first(), run.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => continue,
+        };
+        area.move_to((first.x, bounds.bottom));
+        add_run(&mut area, run, smooth, false);
+        area.line_to((last.x, bounds.bottom));
+        area.close();
+    }
+
+    (line, area)
+}
+
+/// Returns cumulative fractions of `values` bounding pie segments, starting
+/// with 0 and ending with exactly 1, or `None` if values aren't finite and
+/// non-negative with a positive sum.
+pub fn pie_boundaries(values: &[f32]) -> Option<Vec<f32>> {
+    if values.iter().any(|it| !it.is_finite() || *it < 0.0) {
+        return None;
+    }
+    let total: f32 = values.iter().sum();
+    if !(total > 0.0 && total.is_finite()) {
+        return None;
+    }
+
+    let mut result = Vec::with_capacity(values.len() + 1);
+    let mut covered = 0.0;
+    result.push(0.0);
+    for value in values {
+        covered += value;
+        result.push((covered / total).min(1.0));
+    }
+    // avoid a sliver of the last color wrapping around due to rounding
+    if let Some(last) = result.last_mut() {
+        *last = 1.0;
+    }
+    Some(result)
+}
+
+/// Returns gradient stops as `(position, segment index)` for pie segments
+/// bounded by `boundaries`, skipping empty segments.
+///
+/// Adjacent segments share a boundary position, which Skia draws as a hard
+/// stop. With a positive `feather` the colors are instead blended over that
+/// fraction of the circle, limited to half of either adjacent segment so
+/// stops stay ordered.
+pub fn pie_stops(boundaries: &[f32], feather: f32) -> Vec<(f32, usize)> {
+    let segments: Vec<(usize, f32, f32)> = boundaries
+        .windows(2)
+        .enumerate()
+        .filter(|(_, it)| it[1] > it[0])
+        .map(|(index, it)| (index, it[0], it[1]))
+        .collect();
+
+    let mut result = Vec::with_capacity(segments.len() * 2);
+    for (i, &(index, start, end)) in segments.iter().enumerate() {
+        let half = |other: Option<&(usize, f32, f32)>| match other {
+            Some((_, s, e)) if feather > 0.0 => {
+                (feather * 0.5).min((e - s) * 0.5).min((end - start) * 0.5)
+            }
+            _ => 0.0,
+        };
+        let before = if i > 0 {
+            half(segments.get(i - 1))
+        } else {
+            0.0
+        };
+        let after = half(segments.get(i + 1));
+        result.push((start + before, index));
+        result.push((end - after, index));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value_range_ignores_non_finite() {
+        assert_eq!(
+            value_range(&[3., f32::NAN, -1., f32::INFINITY, 2.]),
+            Some((-1., 3.))
+        );
+        assert_eq!(value_range(&[f32::NAN]), None);
+        assert_eq!(value_range(&[]), None);
+    }
+
+    #[test]
+    fn map_values_splits_runs_at_gaps() {
+        let bounds = Rect::from_xywh(10., 0., 40., 100.);
+        let runs = map_values(&[0., 1., f32::NAN, 0.5, 1.], bounds, 0., 1.);
+
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0], vec![Point::new(10., 100.), Point::new(20., 0.)]);
+        assert_eq!(runs[1], vec![Point::new(40., 50.), Point::new(50., 0.)]);
+    }
+
+    #[test]
+    fn map_values_centers_flat_series() {
+        let bounds = Rect::from_xywh(0., 0., 10., 20.);
+        let runs = map_values(&[4., 4.], bounds, 4., 4.);
+        assert_eq!(runs, vec![vec![Point::new(0., 10.), Point::new(10., 10.)]]);
+    }
+
+    #[test]
+    fn plot_paths_close_area_at_bottom() {
+        let bounds = Rect::from_xywh(0., 0., 30., 30.);
+        let runs = map_values(&[0., 1., 0.5, 1.], bounds, 0., 1.);
+        for smooth in [false, true] {
+            let (line, area) = plot_paths(&runs, bounds, smooth);
+            // a move followed by a line or cubic per segment
+            assert_eq!(line.count_verbs(), 4);
+            assert_eq!(line.get_point(0), Some(Point::new(0., 30.)));
+            assert_eq!(area.bounds().bottom, bounds.bottom);
+            assert_eq!(line.last_pt(), Some(Point::new(30., 0.)));
+        }
+    }
+
+    fn assert_stops(actual: &[(f32, usize)], expected: &[(f32, usize)]) {
+        assert_eq!(actual.len(), expected.len(), "{:?}", actual);
+        for ((position, index), (expected_position, expected_index)) in actual.iter().zip(expected)
+        {
+            assert_eq!(index, expected_index, "{:?}", actual);
+            assert!((position - expected_position).abs() < 1e-6, "{:?}", actual);
+        }
+    }
+
+    #[test]
+    fn pie_boundaries_normalize_values() {
+        assert_eq!(pie_boundaries(&[1., 1., 2.]), Some(vec![0., 0.25, 0.5, 1.]));
+        assert_eq!(pie_boundaries(&[0.1; 3]).unwrap().last(), Some(&1.));
+        assert_eq!(pie_boundaries(&[]), None);
+        assert_eq!(pie_boundaries(&[0., 0.]), None);
+        assert_eq!(pie_boundaries(&[-1., 2.]), None);
+        assert_eq!(pie_boundaries(&[f32::NAN, 1.]), None);
+        assert_eq!(pie_boundaries(&[f32::MAX, f32::MAX]), None);
+    }
+
+    #[test]
+    fn pie_stops_duplicate_boundaries_and_skip_empty_segments() {
+        let stops = pie_stops(&[0., 0.25, 0.5, 1.], 0.);
+        assert_stops(
+            &stops,
+            &[(0., 0), (0.25, 0), (0.25, 1), (0.5, 1), (0.5, 2), (1., 2)],
+        );
+        let stops = pie_stops(&pie_boundaries(&[1., 0., 1.]).unwrap(), 0.);
+        assert_stops(&stops, &[(0., 0), (0.5, 0), (0.5, 2), (1., 2)]);
+    }
+
+    #[test]
+    fn feathered_pie_stops_stay_ordered() {
+        let stops = pie_stops(&[0., 0.25, 0.5, 1.], 0.1);
+        // the seam between the last and the first segment isn't blended
+        assert_stops(
+            &stops,
+            &[(0., 0), (0.2, 0), (0.3, 1), (0.45, 1), (0.55, 2), (1., 2)],
+        );
+        // blending is limited to half of narrow segments
+        let stops = pie_stops(&[0., 0.02, 1.], 0.1);
+        assert_stops(&stops, &[(0., 0), (0.01, 0), (0.03, 1), (1., 1)]);
+        assert!(stops.windows(2).all(|it| it[0].0 <= it[1].0));
+    }
+}
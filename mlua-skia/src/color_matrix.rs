@@ -0,0 +1,360 @@
+//! Named color adjustments as 4x5 color matrices, exposed to Lua as
+//! `ColorMatrix`.
+//!
+//! Matrices are 20 floats in row major order, as `ColorFilter.matrix`
+//! takes them: each row computes one of red, green, blue and alpha from
+//! unpremultiplied input channels, with the last column added as an offset
+//! in 0-1 range. Presets follow the CSS filter effects specification, using
+//! Rec. 709 luma coefficients.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+
+/// Rec. 709 luma coefficients of red, green and blue.
+pub const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+pub type Matrix = [f32; 20];
+
+pub const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, 0.0, //
+    0.0, 1.0, 0.0, 0.0, 0.0, //
+    0.0, 0.0, 1.0, 0.0, 0.0, //
+    0.0, 0.0, 0.0, 1.0, 0.0, //
+];
+
+/// Returns a matrix applying `rgb` to color channels and keeping alpha.
+fn rgb_matrix(rgb: [[f32; 3]; 3], offset: f32) -> Matrix {
+    let mut result = IDENTITY;
+    for (row, values) in rgb.iter().enumerate() {
+        result[row * 5..row * 5 + 3].copy_from_slice(values);
+        result[row * 5 + 4] = offset;
+    }
+    result
+}
+
+/// Scales saturation by `amount`; 0 is grayscale and 1 keeps colors.
+pub fn saturation(amount: f32) -> Matrix {
+    let [r, g, b] = LUMA.map(|it| it * (1.0 - amount));
+    rgb_matrix(
+        [[r + amount, g, b], [r, g + amount, b], [r, g, b + amount]],
+        0.0,
+    )
+}
+
+/// Scales distance of channels from mid gray (0.5) by `amount`.
+pub fn contrast(amount: f32) -> Matrix {
+    let d = amount;
+    rgb_matrix(
+        [[d, 0.0, 0.0], [0.0, d, 0.0], [0.0, 0.0, d]],
+        0.5 * (1.0 - amount),
+    )
+}
+
+/// Multiplies color channels by `amount`.
+pub fn brightness(amount: f32) -> Matrix {
+    let d = amount;
+    rgb_matrix([[d, 0.0, 0.0], [0.0, d, 0.0], [0.0, 0.0, d]], 0.0)
+}
+
+/// Rotates hue by `degrees` while keeping luma.
+pub fn hue_rotate(degrees: f32) -> Matrix {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let [lr, lg, lb] = LUMA;
+    rgb_matrix(
+        [
+            [
+                lr + cos * (1.0 - lr) - sin * lr,
+                lg - cos * lg - sin * lg,
+                lb - cos * lb + sin * (1.0 - lb),
+            ],
+            [
+                lr - cos * lr + sin * 0.143,
+                lg + cos * (1.0 - lg) + sin * 0.140,
+                lb - cos * lb - sin * 0.283,
+            ],
+            [
+                lr - cos * lr - sin * (1.0 - lr),
+                lg - cos * lg + sin * lg,
+                lb + cos * (1.0 - lb) + sin * lb,
+            ],
+        ],
+        0.0,
+    )
+}
+
+/// Tints colors brown by `amount` in 0-1 range.
+pub fn sepia(amount: f32) -> Matrix {
+    let keep = 1.0 - amount.clamp(0.0, 1.0);
+    rgb_matrix(
+        [
+            [
+                0.393 + 0.607 * keep,
+                0.769 - 0.769 * keep,
+                0.189 - 0.189 * keep,
+            ],
+            [
+                0.349 - 0.349 * keep,
+                0.686 + 0.314 * keep,
+                0.168 - 0.168 * keep,
+            ],
+            [
+                0.272 - 0.272 * keep,
+                0.534 - 0.534 * keep,
+                0.131 + 0.869 * keep,
+            ],
+        ],
+        0.0,
+    )
+}
+
+/// Inverts color channels by `amount` in 0-1 range; 0.5 is mid gray.
+pub fn invert(amount: f32) -> Matrix {
+    let amount = amount.clamp(0.0, 1.0);
+    let d = 1.0 - 2.0 * amount;
+    rgb_matrix([[d, 0.0, 0.0], [0.0, d, 0.0], [0.0, 0.0, d]], amount)
+}
+
+/// Returns a matrix equivalent to applying `first` and then `second`.
+pub fn concat(first: &Matrix, second: &Matrix) -> Matrix {
+    let mut result = [0.0; 20];
+    for row in 0..4 {
+        for col in 0..5 {
+            let mut value: f32 = (0..4)
+                .map(|i| second[row * 5 + i] * first[i * 5 + col])
+                .sum();
+            if col == 4 {
+                value += second[row * 5 + 4];
+            }
+            result[row * 5 + col] = value;
+        }
+    }
+    result
+}
+
+/// Adjustments applied by `ColorFilter.adjust`, in order of fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Adjustments {
+    pub brightness: Option<f32>,
+    pub contrast: Option<f32>,
+    pub saturation: Option<f32>,
+    pub hue_rotate: Option<f32>,
+    pub sepia: Option<f32>,
+    pub invert: Option<f32>,
+}
+
+impl Adjustments {
+    /// Returns a single matrix applying set adjustments in order of fields.
+    pub fn to_matrix(&self) -> Matrix {
+        let steps: [(Option<f32>, fn(f32) -> Matrix); 6] = [
+            (self.brightness, brightness),
+            (self.contrast, contrast),
+            (self.saturation, saturation),
+            (self.hue_rotate, hue_rotate),
+            (self.sepia, sepia),
+            (self.invert, invert),
+        ];
+        steps
+            .into_iter()
+            .filter_map(|(amount, build)| amount.map(build))
+            .fold(IDENTITY, |result, it| concat(&result, &it))
+    }
+}
+
+impl<'lua> FromLua<'lua> for Adjustments {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(it) => it,
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "Adjustments",
+                    message: Some("expected a table of adjustment amounts".to_string()),
+                })
+            }
+        };
+        Ok(Adjustments {
+            brightness: table.get("brightness")?,
+            contrast: table.get("contrast")?,
+            saturation: table.get("saturation")?,
+            hue_rotate: table.get("hueRotate")?,
+            sepia: table.get("sepia")?,
+            invert: table.get("invert")?,
+        })
+    }
+}
+crate::from_lua_argpack!(Adjustments);
+
+pub struct ColorMatrixFns;
+
+#[lua_methods(lua_name: ColorMatrix)]
+impl ColorMatrixFns {
+    /// Returns a matrix that leaves colors unchanged.
+    pub fn identity() -> [f32; 20] {
+        Ok(IDENTITY)
+    }
+    /// Returns a matrix scaling saturation by `amount`; 0 is grayscale.
+    pub fn saturation(amount: f32) -> [f32; 20] {
+        Ok(saturation(amount))
+    }
+    /// Returns a matrix scaling contrast by `amount` around mid gray.
+    pub fn contrast(amount: f32) -> [f32; 20] {
+        Ok(contrast(amount))
+    }
+    /// Returns a matrix multiplying color channels by `amount`.
+    pub fn brightness(amount: f32) -> [f32; 20] {
+        Ok(brightness(amount))
+    }
+    /// Returns a matrix rotating hue by `degrees`.
+    pub fn hue_rotate(degrees: f32) -> [f32; 20] {
+        Ok(hue_rotate(degrees))
+    }
+    /// Returns a matrix tinting colors brown by `amount` (1 by default).
+    pub fn sepia(amount: Option<f32>) -> [f32; 20] {
+        Ok(sepia(amount.unwrap_or(1.0)))
+    }
+    /// Returns a matrix inverting colors by `amount` (1 by default).
+    pub fn invert(amount: Option<f32>) -> [f32; 20] {
+        Ok(invert(amount.unwrap_or(1.0)))
+    }
+    /// Returns a matrix applying `first` and then `second`.
+    pub fn concat(first: [f32; 20], second: [f32; 20]) -> [f32; 20] {
+        Ok(concat(&first, &second))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns unpremultiplied `color` transformed by `matrix`.
+    fn apply(matrix: &Matrix, color: [f32; 4]) -> [f32; 4] {
+        let mut result = [0.0; 4];
+        for (row, channel) in result.iter_mut().enumerate() {
+            let row = &matrix[row * 5..row * 5 + 5];
+            *channel = (0..4).map(|i| row[i] * color[i]).sum::<f32>() + row[4];
+        }
+        result
+    }
+
+    fn assert_near(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-3,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn saturation_mixes_with_rec709_luma() {
+        assert_near(&saturation(1.0), &IDENTITY);
+        assert_near(
+            &apply(&saturation(0.0), [1.0, 0.0, 0.0, 1.0]),
+            &[0.2126, 0.2126, 0.2126, 1.0],
+        );
+        assert_near(
+            &apply(&saturation(0.0), [0.0, 1.0, 0.0, 0.5]),
+            &[0.7152, 0.7152, 0.7152, 0.5],
+        );
+        // CSS saturate(0.5)
+        assert_near(&saturation(0.5)[..5], &[0.6063, 0.3576, 0.0361, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn contrast_pivots_around_mid_gray() {
+        let matrix = contrast(2.0);
+        assert_near(
+            &apply(&matrix, [0.5, 0.25, 0.75, 1.0]),
+            &[0.5, 0.0, 1.0, 1.0],
+        );
+        assert_near(
+            &apply(&contrast(0.0), [0.1, 0.9, 0.3, 1.0]),
+            &[0.5, 0.5, 0.5, 1.0],
+        );
+        assert_near(&contrast(1.0), &IDENTITY);
+    }
+
+    #[test]
+    fn brightness_and_invert_scale_channels() {
+        assert_near(
+            &apply(&brightness(0.5), [1.0, 0.5, 0.0, 0.8]),
+            &[0.5, 0.25, 0.0, 0.8],
+        );
+        assert_near(
+            &apply(&invert(1.0), [1.0, 0.25, 0.0, 0.8]),
+            &[0.0, 0.75, 1.0, 0.8],
+        );
+        assert_near(
+            &apply(&invert(0.5), [1.0, 0.25, 0.0, 1.0]),
+            &[0.5, 0.5, 0.5, 1.0],
+        );
+        assert_near(&invert(0.0), &IDENTITY);
+    }
+
+    #[test]
+    fn hue_rotation_keeps_grays_and_full_turns() {
+        assert_near(&hue_rotate(0.0), &IDENTITY);
+        assert_near(&hue_rotate(360.0), &IDENTITY);
+        for degrees in [45.0, 90.0, 180.0, 270.0] {
+            let gray = apply(&hue_rotate(degrees), [0.4, 0.4, 0.4, 1.0]);
+            assert_near(&gray, &[0.4, 0.4, 0.4, 1.0]);
+        }
+        // CSS hue-rotate(180deg) of red
+        assert_near(
+            &apply(&hue_rotate(180.0), [1.0, 0.0, 0.0, 1.0]),
+            &[-0.5748, 0.4252, 0.4252, 1.0],
+        );
+    }
+
+    #[test]
+    fn sepia_interpolates_towards_reference_tint() {
+        assert_near(&sepia(0.0), &IDENTITY);
+        assert_near(
+            &apply(&sepia(1.0), [1.0, 1.0, 1.0, 1.0]),
+            &[1.351, 1.203, 0.937, 1.0],
+        );
+        assert_near(&sepia(1.0)[..3], &[0.393, 0.769, 0.189]);
+        // amounts are clamped
+        assert_near(&sepia(2.0), &sepia(1.0));
+    }
+
+    #[test]
+    fn concat_applies_first_matrix_first() {
+        let darker_then_contrast = concat(&brightness(0.5), &contrast(2.0));
+        let contrast_then_darker = concat(&contrast(2.0), &brightness(0.5));
+        let color = [0.5, 0.5, 0.5, 1.0];
+        assert_near(&apply(&darker_then_contrast, color), &[0.0, 0.0, 0.0, 1.0]);
+        assert_near(
+            &apply(&contrast_then_darker, color),
+            &[0.25, 0.25, 0.25, 1.0],
+        );
+        assert_near(&concat(&IDENTITY, &sepia(1.0)), &sepia(1.0));
+        assert_near(&concat(&sepia(1.0), &IDENTITY), &sepia(1.0));
+    }
+
+    #[test]
+    fn adjustments_compose_in_field_order() {
+        assert_near(&Adjustments::default().to_matrix(), &IDENTITY);
+        let adjustments = Adjustments {
+            brightness: Some(0.5),
+            contrast: Some(2.0),
+            ..Default::default()
+        };
+        assert_near(
+            &adjustments.to_matrix(),
+            &concat(&brightness(0.5), &contrast(2.0)),
+        );
+        let adjustments = Adjustments {
+            saturation: Some(0.0),
+            invert: Some(1.0),
+            ..Default::default()
+        };
+        assert_near(
+            &apply(&adjustments.to_matrix(), [1.0, 0.0, 0.0, 1.0]),
+            &[0.7874, 0.7874, 0.7874, 1.0],
+        );
+    }
+}
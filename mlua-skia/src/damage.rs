@@ -0,0 +1,230 @@
+//! Tracking of canvas areas changed by draw calls.
+//!
+//! Damage is tracked per surface rather than per Lua handle, so all
+//! `Canvas` handles drawing into the same surface share it and the host can
+//! read it with [`take_damage`] without going through Lua.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use mlua::Lua;
+use skia_safe::{Canvas, IRect, Paint, Rect, Surface};
+
+use crate::{tiled::TiledSurface, util::draw_device_bounds};
+
+/// Number of rects damage is simplified to; beyond it, rects adding the
+/// least area are merged.
+pub const MAX_DAMAGE_RECTS: usize = 8;
+
+/// Surface whose damage is tracked.
+#[derive(Clone)]
+pub enum Target {
+    Surface(Surface),
+    Tiled(Rc<TiledSurface>),
+}
+
+impl Target {
+    /// Returns identity of the surface, which stays unique while tracked
+    /// because tracking holds a reference to it.
+    fn id(&self) -> usize {
+        match self {
+            Target::Surface(surface) => {
+                // SAFETY: Surface is a transparent wrapper of a non-null
+                // pointer to the native surface.
+                unsafe { std::mem::transmute_copy::<Surface, usize>(surface) }
+            }
+            Target::Tiled(tiled) => Rc::as_ptr(tiled) as usize,
+        }
+    }
+}
+
+struct Tracked {
+    /// Keeps the surface alive so its identity isn't reused.
+    _target: Target,
+    rects: Vec<IRect>,
+}
+
+thread_local! {
+    static DAMAGE: RefCell<HashMap<usize, Tracked>> = RefCell::new(HashMap::new());
+}
+
+/// Starts accumulating damage for `target`, discarding any already
+/// accumulated.
+///
+/// The surface is kept alive until tracking ends.
+pub fn begin_tracking(target: &Target) {
+    let tracked = Tracked {
+        _target: target.clone(),
+        rects: Vec::new(),
+    };
+    DAMAGE.with(|it| it.borrow_mut().insert(target.id(), tracked));
+}
+
+/// Stops accumulating damage for `target`.
+pub fn end_tracking(target: &Target) {
+    DAMAGE.with(|it| it.borrow_mut().remove(&target.id()));
+}
+
+/// Stops accumulating damage for all surfaces, releasing them.
+pub fn clear() {
+    DAMAGE.with(|it| it.borrow_mut().clear());
+}
+
+/// Targets scripts of a context started tracking, kept as its app data so
+/// [`teardown`](crate::teardown()) can release them.
+#[derive(Default)]
+struct ContextTargets(RefCell<Vec<Target>>);
+
+/// Starts tracking `target` on behalf of scripts of `lua`.
+pub(crate) fn begin_context_tracking(lua: &Lua, target: &Target) {
+    begin_tracking(target);
+    if lua.app_data_ref::<ContextTargets>().is_none() {
+        lua.set_app_data(ContextTargets::default());
+    }
+    let targets = lua
+        .app_data_ref::<ContextTargets>()
+        .expect("targets were just set");
+    let mut targets = targets.0.borrow_mut();
+    if !targets.iter().any(|it| it.id() == target.id()) {
+        targets.push(target.clone());
+    }
+}
+
+/// Stops tracking `target` on behalf of scripts of `lua`.
+pub(crate) fn end_context_tracking(lua: &Lua, target: &Target) {
+    end_tracking(target);
+    if let Some(targets) = lua.app_data_ref::<ContextTargets>() {
+        targets.0.borrow_mut().retain(|it| it.id() != target.id());
+    }
+}
+
+/// Stops tracking of all targets scripts of `lua` started tracking.
+pub(crate) fn clear_context(lua: &Lua) {
+    if let Some(targets) = lua.remove_app_data::<ContextTargets>() {
+        for target in targets.0.into_inner() {
+            end_tracking(&target);
+        }
+    }
+}
+
+pub fn is_tracking(target: &Target) -> bool {
+    DAMAGE.with(|it| it.borrow().contains_key(&target.id()))
+}
+
+/// Returns whether damage of any surface is tracked.
+#[inline]
+pub(crate) fn is_active() -> bool {
+    DAMAGE.with(|it| !it.borrow().is_empty())
+}
+
+/// Returns device space rects changed since tracking started or the last
+/// call, or `None` if `target` isn't tracked.
+pub fn take_damage(target: &Target) -> Option<Vec<IRect>> {
+    DAMAGE.with(|it| {
+        it.borrow_mut()
+            .get_mut(&target.id())
+            .map(|it| std::mem::take(&mut it.rects))
+    })
+}
+
+/// Records damage of drawing `bounds` in local coordinates of `canvas`,
+/// which draws into `target`, with `paint`.
+///
+/// `None` bounds, or paints with effects whose bounds can't be computed,
+/// damage the whole clip.
+pub(crate) fn add(target: &Target, canvas: &Canvas, bounds: Option<Rect>, paint: Option<&Paint>) {
+    DAMAGE.with(|it| {
+        let mut damage = it.borrow_mut();
+        let tracked = match damage.get_mut(&target.id()) {
+            Some(it) => it,
+            None => return,
+        };
+        if let Some(device) = draw_device_bounds(canvas, bounds, paint) {
+            insert(&mut tracked.rects, device);
+        }
+    })
+}
+
+fn join(a: &IRect, b: &IRect) -> IRect {
+    IRect::new(
+        a.left.min(b.left),
+        a.top.min(b.top),
+        a.right.max(b.right),
+        a.bottom.max(b.bottom),
+    )
+}
+
+fn overlaps(a: &IRect, b: &IRect) -> bool {
+    a.left < b.right && b.left < a.right && a.top < b.bottom && b.top < a.bottom
+}
+
+fn contains(outer: &IRect, inner: &IRect) -> bool {
+    outer.left <= inner.left
+        && outer.top <= inner.top
+        && outer.right >= inner.right
+        && outer.bottom >= inner.bottom
+}
+
+fn area(rect: &IRect) -> i64 {
+    rect.width() as i64 * rect.height() as i64
+}
+
+/// Adds `rect` to `rects`, merging overlapping rects and keeping at most
+/// [`MAX_DAMAGE_RECTS`].
+fn insert(rects: &mut Vec<IRect>, mut rect: IRect) {
+    if rect.is_empty() || rects.iter().any(|it| contains(it, &rect)) {
+        return;
+    }
+    // merging can make the result overlap other rects
+    while let Some(i) = rects.iter().position(|it| overlaps(it, &rect)) {
+        rect = join(&rects.swap_remove(i), &rect);
+    }
+    rects.push(rect);
+
+    while rects.len() > MAX_DAMAGE_RECTS {
+        let mut best = (0, 1, i64::MAX);
+        for i in 0..rects.len() {
+            for j in (i + 1)..rects.len() {
+                let joined = join(&rects[i], &rects[j]);
+                let growth = area(&joined) - area(&rects[i]) - area(&rects[j]);
+                if growth < best.2 {
+                    best = (i, j, growth);
+                }
+            }
+        }
+        let (i, j, _) = best;
+        let b = rects.swap_remove(j);
+        rects[i] = join(&rects[i], &b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_merges_overlapping_rects() {
+        let mut rects = Vec::new();
+        insert(&mut rects, IRect::new(0, 0, 10, 10));
+        insert(&mut rects, IRect::new(20, 0, 30, 10));
+        assert_eq!(rects.len(), 2);
+
+        // contained rects don't add damage
+        insert(&mut rects, IRect::new(2, 2, 8, 8));
+        assert_eq!(rects.len(), 2);
+
+        // bridging rect merges both into one
+        insert(&mut rects, IRect::new(5, 0, 25, 5));
+        assert_eq!(rects, vec![IRect::new(0, 0, 30, 10)]);
+    }
+
+    #[test]
+    fn insert_limits_rect_count() {
+        let mut rects = Vec::new();
+        for i in 0..(MAX_DAMAGE_RECTS as i32 + 4) {
+            insert(&mut rects, IRect::new(i * 20, 0, i * 20 + 10, 10));
+        }
+        assert_eq!(rects.len(), MAX_DAMAGE_RECTS);
+        let covered = rects.iter().fold(rects[0], |acc, it| join(&acc, it));
+        assert_eq!(covered, IRect::new(0, 0, 230, 10));
+    }
+}
@@ -0,0 +1,461 @@
+//! Dashing fitted to path geometry.
+//!
+//! Regular dash patterns run out wherever the path ends, leaving partial
+//! dashes at corners. Here paths are split into pieces between sharp corners
+//! and each piece gets its own intervals, stretched so that a whole number of
+//! dashes fits it exactly and open pieces start and end with a dash.
+//!
+//! Animated dashes reuse effects built for a few phases through
+//! [`PhaseCache`] instead of building one every frame.
+
+use skia_safe::{
+    dash_path_effect, path, path::Verb, ContourMeasureIter, Path, PathEffect, Point, Vector,
+};
+
+/// Sine of the largest angle between tangents of a join still considered
+/// smooth.
+const SMOOTH_JOIN_SINE: f32 = 0.01;
+
+/// Dash pattern fitted to a piece of a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FittedDash {
+    pub dash: f32,
+    pub gap: f32,
+    pub count: usize,
+}
+
+impl FittedDash {
+    #[inline]
+    pub fn intervals(&self) -> [f32; 2] {
+        [self.dash, self.gap]
+    }
+}
+
+/// Fits dashes of roughly `dash_length`, separated by gaps of
+/// `dash_length * gap_ratio`, into `length`.
+///
+/// Open pieces start and end with a dash, closed ones repeat the pattern
+/// without a seam. Returns `None` for empty pieces and invalid dash lengths.
+pub fn fit(length: f32, dash_length: f32, gap_ratio: f32, closed: bool) -> Option<FittedDash> {
+    let valid = length.is_finite()
+        && length > 0.0
+        && dash_length.is_finite()
+        && dash_length > 0.0
+        && gap_ratio.is_finite()
+        && gap_ratio >= 0.0;
+    if !valid {
+        return None;
+    }
+    let period = dash_length * (1.0 + gap_ratio);
+    if closed {
+        let count = (length / period).round().max(1.0);
+        let dash = length / count / (1.0 + gap_ratio);
+        Some(FittedDash {
+            dash,
+            gap: dash * gap_ratio,
+            count: count as usize,
+        })
+    } else {
+        // n dashes separated by n - 1 gaps
+        let count = ((length + dash_length * gap_ratio) / period)
+            .round()
+            .max(1.0);
+        let dash = length / (count + (count - 1.0) * gap_ratio);
+        Some(FittedDash {
+            dash,
+            gap: dash * gap_ratio,
+            count: count as usize,
+        })
+    }
+}
+
+struct Segment {
+    verb: Verb,
+    /// Points of the segment, including its start point.
+    points: Vec<Point>,
+    weight: f32,
+}
+
+impl Segment {
+    fn start_tangent(&self) -> Option<Vector> {
+        let start = self.points[0];
+        self.points[1..]
+            .iter()
+            .map(|it| *it - start)
+            .find(|it| it.length() > f32::EPSILON)
+    }
+
+    fn end_tangent(&self) -> Option<Vector> {
+        let end = *self.points.last()?;
+        self.points[..self.points.len() - 1]
+            .iter()
+            .rev()
+            .map(|it| end - *it)
+            .find(|it| it.length() > f32::EPSILON)
+    }
+
+    fn add_to(&self, path: &mut Path) {
+        let p = &self.points;
+        match self.verb {
+            Verb::Line => path.line_to(p[1]),
+            Verb::Quad => path.quad_to(p[1], p[2]),
+            Verb::Conic => path.conic_to(p[1], p[2], self.weight),
+            Verb::Cubic => path.cubic_to(p[1], p[2], p[3]),
+            _ => path,
+        };
+    }
+}
+
+fn is_smooth_join(from: &Segment, to: &Segment) -> bool {
+    let (a, b) = match (from.end_tangent(), to.start_tangent()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return false,
+    };
+    let (a, b) = (a * (1.0 / a.length()), b * (1.0 / b.length()));
+    a.dot(b) > 0.0 && a.cross(b).abs() < SMOOTH_JOIN_SINE
+}
+
+fn piece_path(segments: &[Segment], closed: bool) -> Path {
+    let mut result = Path::new();
+    result.move_to(segments[0].points[0]);
+    for segment in segments {
+        segment.add_to(&mut result);
+    }
+    if closed {
+        result.close();
+    }
+    result
+}
+
+/// Splits a contour into pieces between sharp corners.
+fn split_contour(mut segments: Vec<Segment>, closed: bool, pieces: &mut Vec<(Path, bool)>) {
+    if segments.is_empty() {
+        return;
+    }
+    let corners = |segments: &[Segment]| -> Vec<usize> {
+        (1..segments.len())
+            .filter(|&i| !is_smooth_join(&segments[i - 1], &segments[i]))
+            .collect()
+    };
+
+    if closed && is_smooth_join(&segments[segments.len() - 1], &segments[0]) {
+        match corners(&segments).first() {
+            // a smooth loop, such as a circle
+            None => {
+                pieces.push((piece_path(&segments, true), true));
+                return;
+            }
+            // start at a corner so no piece wraps around the contour start
+            Some(&first) => segments.rotate_left(first),
+        }
+    }
+
+    let mut start = 0;
+    for corner in corners(&segments).into_iter().chain([segments.len()]) {
+        pieces.push((piece_path(&segments[start..corner], false), false));
+        start = corner;
+    }
+}
+
+/// Splits `path` into pieces between sharp corners, each paired with whether
+/// it's a smooth closed loop.
+pub fn split_at_corners(path: &Path) -> Vec<(Path, bool)> {
+    let mut pieces = Vec::new();
+    let mut segments = Vec::new();
+    let mut iter = path::Iter::new(path, false);
+    while let Some((verb, points)) = iter.next() {
+        match verb {
+            Verb::Move => {
+                split_contour(std::mem::take(&mut segments), false, &mut pieces);
+            }
+            Verb::Close => {
+                split_contour(std::mem::take(&mut segments), true, &mut pieces);
+            }
+            Verb::Line | Verb::Quad | Verb::Conic | Verb::Cubic => segments.push(Segment {
+                verb,
+                points,
+                weight: iter.conic_weight().unwrap_or(1.0),
+            }),
+            _ => {}
+        }
+    }
+    split_contour(segments, false, &mut pieces);
+    pieces
+}
+
+/// Returns dashes along `path` with each piece between sharp corners
+/// dashed separately, so dashes meet at corners.
+pub fn fitted_dashes(path: &Path, dash_length: f32, gap_ratio: f32) -> Path {
+    let mut result = Path::new();
+    for (piece, closed) in split_at_corners(path) {
+        for contour in ContourMeasureIter::new(&piece, false, None) {
+            let fitted = match fit(contour.length(), dash_length, gap_ratio, closed) {
+                Some(it) => it,
+                None => continue,
+            };
+            let period = fitted.dash + fitted.gap;
+            for index in 0..fitted.count {
+                let start = index as f32 * period;
+                let end = (start + fitted.dash).min(contour.length());
+                if let Some(dash) = contour.segment(start, end, true) {
+                    result.add_path(&dash, (0.0, 0.0), None);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Granularity of phases [`PhaseCache`] builds effects for. Finer offsets
+/// aren't visible with antialiased strokes.
+pub const PHASE_STEP: f32 = 0.25;
+/// Most effects a [`PhaseCache`] keeps. Patterns longer than 64 units are
+/// split into this many coarser steps instead.
+pub const MAX_CACHED_PHASES: usize = 256;
+
+/// Dash effects of a single pattern built for different phases.
+///
+/// The pattern period is split into steps of at most [`PHASE_STEP`] and
+/// phases are wrapped into the period and rounded to a step, so phases a
+/// whole period apart share an effect. Effects are kept in a slot per step,
+/// so every phase is looked up in constant time and no effect is ever
+/// evicted while the cache holds at most [`MAX_CACHED_PHASES`] of them.
+pub struct PhaseCache {
+    intervals: Vec<f32>,
+    period: f32,
+    /// Length of a phase step; divides the period evenly.
+    step: f32,
+    /// Effect of each phase step, built on first use.
+    slots: Vec<Option<PathEffect>>,
+    len: usize,
+}
+
+impl PhaseCache {
+    /// Returns `None` unless `intervals` are an even number of non-negative
+    /// lengths with a positive sum.
+    pub fn new(intervals: Vec<f32>) -> Option<Self> {
+        let period: f32 = intervals.iter().sum();
+        let valid = intervals.len() >= 2
+            && intervals.len() % 2 == 0
+            && intervals.iter().all(|it| it.is_finite() && *it >= 0.0)
+            && period.is_finite()
+            && period > 0.0;
+        if !valid {
+            return None;
+        }
+        let steps = ((period / PHASE_STEP).ceil() as usize).clamp(1, MAX_CACHED_PHASES);
+        Some(PhaseCache {
+            intervals,
+            period,
+            step: period / steps as f32,
+            slots: vec![None; steps],
+            len: 0,
+        })
+    }
+
+    #[inline]
+    pub fn intervals(&self) -> &[f32] {
+        &self.intervals
+    }
+
+    /// Sum of intervals; phases this far apart produce the same dashes.
+    #[inline]
+    pub fn period(&self) -> f32 {
+        self.period
+    }
+
+    /// Distance between phases effects are built for.
+    #[inline]
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Number of cached effects.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(None);
+        self.len = 0;
+    }
+
+    /// Returns the slot of the step `phase` is drawn with.
+    fn slot(&self, phase: f32) -> usize {
+        let step = (phase.rem_euclid(self.period) / self.step).round() as usize;
+        // phases rounded up to the period wrap to its start
+        step % self.slots.len()
+    }
+
+    /// Returns the dash effect for `phase`, building it if it isn't cached.
+    pub fn effect(&mut self, phase: f32) -> Option<PathEffect> {
+        if !phase.is_finite() {
+            return None;
+        }
+        let slot = self.slot(phase);
+        if let Some(effect) = &self.slots[slot] {
+            return Some(effect.clone());
+        }
+
+        let effect = dash_path_effect::new(&self.intervals, slot as f32 * self.step)?;
+        self.slots[slot] = Some(effect.clone());
+        self.len += 1;
+        Some(effect)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::Rect;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    /// Returns start and end points of each contour in `path`.
+    fn contour_ends(path: &Path) -> Vec<(Point, Point)> {
+        let mut result: Vec<(Point, Point)> = Vec::new();
+        for (verb, points) in path::Iter::new(path, false) {
+            match verb {
+                Verb::Move => result.push((points[0], points[0])),
+                Verb::Line | Verb::Quad | Verb::Conic | Verb::Cubic => {
+                    if let Some(last) = result.last_mut() {
+                        last.1 = *points.last().unwrap();
+                    }
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn open_pieces_start_and_end_with_a_dash() {
+        let fitted = fit(100.0, 10.0, 0.5, false).unwrap();
+        assert_eq!(fitted.count, 7);
+        let count = fitted.count as f32;
+        assert_near(count * fitted.dash + (count - 1.0) * fitted.gap, 100.0);
+        assert_near(fitted.gap, fitted.dash * 0.5);
+    }
+
+    #[test]
+    fn closed_pieces_repeat_without_a_seam() {
+        let fitted = fit(100.0, 10.0, 0.5, true).unwrap();
+        assert_eq!(fitted.count, 7);
+        assert_near(fitted.count as f32 * (fitted.dash + fitted.gap), 100.0);
+        // shorter than a single period still gets one dash
+        assert_eq!(fit(4.0, 10.0, 1.0, true).unwrap().count, 1);
+        assert_eq!(fit(4.0, 10.0, 1.0, false).unwrap().dash, 4.0);
+    }
+
+    #[test]
+    fn fit_rejects_invalid_input() {
+        assert_eq!(fit(0.0, 10.0, 0.5, false), None);
+        assert_eq!(fit(100.0, 0.0, 0.5, false), None);
+        assert_eq!(fit(100.0, 10.0, -1.0, false), None);
+        assert_eq!(fit(f32::NAN, 10.0, 0.5, true), None);
+        assert_eq!(fit(100.0, f32::INFINITY, 0.5, true), None);
+    }
+
+    #[test]
+    fn rects_split_at_corners_and_circles_stay_whole() {
+        let rect = Path::rect(Rect::from_xywh(0.0, 0.0, 100.0, 50.0), None);
+        let pieces = split_at_corners(&rect);
+        assert_eq!(pieces.len(), 4);
+        assert!(pieces.iter().all(|(_, closed)| !closed));
+
+        let circle = Path::circle((50.0, 50.0), 40.0, None);
+        let pieces = split_at_corners(&circle);
+        assert_eq!(pieces.len(), 1);
+        assert!(pieces[0].1);
+    }
+
+    #[test]
+    fn rect_side_dashes_touch_corners() {
+        let rect = Path::rect(Rect::from_xywh(0.0, 0.0, 100.0, 50.0), None);
+        let dashes = contour_ends(&fitted_dashes(&rect, 10.0, 0.5));
+        let corners = [
+            Point::new(0.0, 0.0),
+            Point::new(100.0, 0.0),
+            Point::new(100.0, 50.0),
+            Point::new(0.0, 50.0),
+        ];
+        for side in 0..4 {
+            let (from, to) = (corners[side], corners[(side + 1) % 4]);
+            let starts = dashes
+                .iter()
+                .any(|(start, _)| (*start - from).length() < 1e-3);
+            let ends = dashes.iter().any(|(_, end)| (*end - to).length() < 1e-3);
+            assert!(starts, "no dash starts at {:?}", from);
+            assert!(ends, "no dash ends at {:?}", to);
+        }
+        // 7 dashes on long sides, 4 on short ones
+        assert_eq!(dashes.len(), 2 * 7 + 2 * 4);
+    }
+
+    fn cached_phase(cache: &mut PhaseCache, phase: f32) -> f32 {
+        cache.effect(phase).unwrap().as_a_dash().unwrap().phase
+    }
+
+    #[test]
+    fn phases_wrap_into_period_and_round_to_steps() {
+        let mut cache = PhaseCache::new(vec![4.0, 2.0]).unwrap();
+        assert_eq!(cache.period(), 6.0);
+        assert_near(cached_phase(&mut cache, 1.1), 1.0);
+        assert_near(cached_phase(&mut cache, 7.0), 1.0);
+        assert_near(cached_phase(&mut cache, -5.0), 1.0);
+        assert_eq!(cache.len(), 1);
+
+        // rounding up to the period wraps to the start
+        assert_near(cached_phase(&mut cache, 5.9), 0.0);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.effect(f32::NAN).is_none());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn phase_cache_is_sized_from_the_period() {
+        let mut cache = PhaseCache::new(vec![4.0, 2.0]).unwrap();
+        assert_eq!(cache.step(), PHASE_STEP);
+        for frame in 0..600 {
+            cache.effect(frame as f32 * 0.1).unwrap();
+        }
+        assert_eq!(cache.len(), 24);
+
+        // long patterns get coarser steps rather than evicting effects
+        let mut cache = PhaseCache::new(vec![500.0, 500.0]).unwrap();
+        assert_near(cache.step() * MAX_CACHED_PHASES as f32, 1000.0);
+        for frame in 0..4000 {
+            let phase = cached_phase(&mut cache, frame as f32 * 0.5);
+            assert_near((phase / cache.step()).round() * cache.step(), phase);
+        }
+        assert_eq!(cache.len(), MAX_CACHED_PHASES);
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_near(cached_phase(&mut cache, 1001.0), 0.0);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn phase_cache_rejects_invalid_intervals() {
+        assert!(PhaseCache::new(vec![]).is_none());
+        assert!(PhaseCache::new(vec![4.0]).is_none());
+        assert!(PhaseCache::new(vec![4.0, 2.0, 1.0]).is_none());
+        assert!(PhaseCache::new(vec![0.0, 0.0]).is_none());
+        assert!(PhaseCache::new(vec![-1.0, 2.0]).is_none());
+        assert!(PhaseCache::new(vec![f32::INFINITY, 2.0]).is_none());
+    }
+}
@@ -0,0 +1,78 @@
+//! Draw command recording for debug canvases.
+//!
+//! Debug canvases draw into a null surface and log every operation issued
+//! through `Canvas` methods, which allows scripts to be tested by asserting
+//! what they draw instead of comparing pixels.
+
+use std::cell::RefCell;
+
+use mlua::{Lua, Result as LuaResult, Table as LuaTable};
+use skia_safe::{paint::Style as PaintStyle, Paint};
+
+use crate::{provenance::Param, LuaBlendMode, LuaColor};
+
+/// A single recorded canvas operation.
+pub struct DebugCommand {
+    /// Lua name of the canvas method.
+    pub op: &'static str,
+    pub params: Vec<(&'static str, Param)>,
+    pub paint: Option<Paint>,
+}
+
+impl DebugCommand {
+    pub fn to_table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        let result = lua.create_table()?;
+        result.set("op", self.op)?;
+        for (name, value) in &self.params {
+            result.set(*name, value.clone())?;
+        }
+        if let Some(paint) = &self.paint {
+            result.set("paintSummary", paint_summary(lua, paint)?)?;
+        }
+        Ok(result)
+    }
+}
+
+/// Returns a table of paint properties relevant to tests.
+pub fn paint_summary<'lua>(lua: &'lua Lua, paint: &Paint) -> LuaResult<LuaTable<'lua>> {
+    let result = lua.create_table()?;
+    result.set("color", LuaColor::from(paint.color4f()))?;
+    let style = match paint.style() {
+        PaintStyle::Fill => "fill",
+        PaintStyle::Stroke => "stroke",
+        PaintStyle::StrokeAndFill => "stroke_and_fill",
+    };
+    result.set("style", style)?;
+    result.set("strokeWidth", paint.stroke_width())?;
+    result.set("antiAlias", paint.is_anti_alias())?;
+    if let Some(mode) = paint.as_blend_mode() {
+        result.set("blendMode", LuaBlendMode(mode))?;
+    }
+    result.set("hasShader", paint.shader().is_some())?;
+    result.set("hasImageFilter", paint.image_filter().is_some())?;
+    Ok(result)
+}
+
+/// Commands recorded by a debug canvas.
+#[derive(Default)]
+pub struct CommandLog {
+    commands: RefCell<Vec<DebugCommand>>,
+}
+
+impl CommandLog {
+    pub fn push(&self, command: DebugCommand) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    pub fn clear(&self) {
+        self.commands.borrow_mut().clear();
+    }
+
+    pub fn to_table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        let result = lua.create_table()?;
+        for command in self.commands.borrow().iter() {
+            result.raw_push(command.to_table(lua)?)?;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,340 @@
+//! Construction parameters of gradient shaders, recorded so they can be
+//! described as plain tables and rebuilt from them.
+//!
+//! Theme editors save descriptor tables returned by `Shader:describe()` and
+//! pass them to `Shader.fromDescriptor()` when loading. Tables only contain
+//! strings, numbers and nested tables, so they can be serialized as is.
+
+use mlua::prelude::*;
+use skia_safe::{
+    gradient_shader::{
+        interpolation::{ColorSpace as InColorSpace, HueMethod, InPremul},
+        Interpolation,
+    },
+    Color4f, ColorSpace, Matrix, Point, Shader, TileMode,
+};
+
+use crate::{
+    ext::skia::MatrixExt,
+    lua::{TableExt, TableWrapperExt},
+    LuaColor, LuaHueMethod, LuaInColorSpace, LuaInPremul, LuaPoint, LuaTileMode,
+};
+
+/// Shape of a gradient and the points defining it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientGeometry {
+    Linear {
+        from: Point,
+        to: Point,
+    },
+    Radial {
+        center: Point,
+        radius: f32,
+    },
+    Sweep {
+        center: Point,
+        start_angle: f32,
+        end_angle: f32,
+    },
+    TwoPointConical {
+        start: Point,
+        start_radius: f32,
+        end: Point,
+        end_radius: f32,
+    },
+}
+
+impl GradientGeometry {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            GradientGeometry::Linear { .. } => "linear",
+            GradientGeometry::Radial { .. } => "radial",
+            GradientGeometry::Sweep { .. } => "sweep",
+            GradientGeometry::TwoPointConical { .. } => "twoPointConical",
+        }
+    }
+}
+
+/// Parameters of a gradient shader constructor.
+#[derive(Clone)]
+pub struct ShaderDescriptor {
+    pub geometry: GradientGeometry,
+    pub positions: Vec<f32>,
+    pub colors: Vec<Color4f>,
+    pub color_space: Option<ColorSpace>,
+    pub tile_mode: TileMode,
+    pub interpolation: Interpolation,
+    pub local: Option<Matrix>,
+}
+
+/// Returns the descriptor name of `color_space`, or `None` if it isn't one
+/// of the named color spaces that can be rebuilt.
+fn color_space_name(color_space: &ColorSpace) -> Option<&'static str> {
+    if color_space.is_srgb() {
+        Some("srgb")
+    } else if *color_space == ColorSpace::new_srgb_linear() {
+        Some("srgb_linear")
+    } else {
+        None
+    }
+}
+
+impl ShaderDescriptor {
+    /// Creates the described gradient, or returns `None` if Skia rejects
+    /// its parameters.
+    pub fn build(&self) -> Option<Shader> {
+        let colors = (self.colors.as_slice(), self.color_space.clone());
+        let positions = Some(self.positions.as_slice());
+        let local = self.local.as_ref();
+        match self.geometry {
+            GradientGeometry::Linear { from, to } => Shader::linear_gradient_with_interpolation(
+                (from, to),
+                colors,
+                positions,
+                self.tile_mode,
+                self.interpolation,
+                local,
+            ),
+            GradientGeometry::Radial { center, radius } => {
+                Shader::radial_gradient_with_interpolation(
+                    (center, radius),
+                    colors,
+                    positions,
+                    self.tile_mode,
+                    self.interpolation,
+                    local,
+                )
+            }
+            GradientGeometry::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => Shader::sweep_gradient_with_interpolation(
+                center,
+                colors,
+                positions,
+                self.tile_mode,
+                (start_angle, end_angle),
+                self.interpolation,
+                local,
+            ),
+            GradientGeometry::TwoPointConical {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => Shader::two_point_conical_gradient_with_interpolation(
+                (start, start_radius),
+                (end, end_radius),
+                colors,
+                positions,
+                self.tile_mode,
+                self.interpolation,
+                local,
+            ),
+        }
+    }
+
+    /// Returns a copy with `matrix` applied after the local matrix, like
+    /// `Shader::with_local_matrix` does.
+    pub fn with_local_matrix(&self, matrix: &Matrix) -> Self {
+        let local = match &self.local {
+            Some(it) => Matrix::concat(matrix, it),
+            None => *matrix,
+        };
+        ShaderDescriptor {
+            local: Some(local),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the descriptor table, or `None` if the gradient uses a color
+    /// space that can't be described.
+    pub fn to_table<'lua>(&self, lua: &'lua Lua) -> LuaResult<Option<LuaTable<'lua>>> {
+        let color_space = match &self.color_space {
+            Some(it) => match color_space_name(it) {
+                Some(name) => Some(name),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
+        let result = lua.create_table()?;
+        result.set("type", self.geometry.type_name())?;
+        match self.geometry {
+            GradientGeometry::Linear { from, to } => {
+                result.set("from", LuaPoint::from(from))?;
+                result.set("to", LuaPoint::from(to))?;
+            }
+            GradientGeometry::Radial { center, radius } => {
+                result.set("center", LuaPoint::from(center))?;
+                result.set("radius", radius)?;
+            }
+            GradientGeometry::Sweep {
+                center,
+                start_angle,
+                end_angle,
+            } => {
+                result.set("center", LuaPoint::from(center))?;
+                result.set("startAngle", start_angle)?;
+                result.set("endAngle", end_angle)?;
+            }
+            GradientGeometry::TwoPointConical {
+                start,
+                start_radius,
+                end,
+                end_radius,
+            } => {
+                result.set("start", LuaPoint::from(start))?;
+                result.set("startRadius", start_radius)?;
+                result.set("end", LuaPoint::from(end))?;
+                result.set("endRadius", end_radius)?;
+            }
+        }
+
+        let colors: Vec<LuaColor> = self.colors.iter().copied().map(LuaColor::from).collect();
+        result.set("colors", colors)?;
+        result.set("positions", self.positions.clone())?;
+        result.set("colorSpace", color_space)?;
+        result.set("tileMode", LuaTileMode(self.tile_mode))?;
+
+        let interpolation = lua.create_table()?;
+        interpolation.set("in_premul", LuaInPremul(self.interpolation.in_premul))?;
+        interpolation.set(
+            "color_space",
+            LuaInColorSpace(self.interpolation.color_space),
+        )?;
+        interpolation.set("hue_method", LuaHueMethod(self.interpolation.hue_method))?;
+        result.set("interpolation", interpolation)?;
+
+        if let Some(local) = &self.local {
+            result.set("localMatrix", local.to_vec())?;
+        }
+        Ok(Some(result))
+    }
+}
+
+impl<'lua> FromLua<'lua> for ShaderDescriptor {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(it) => it,
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "ShaderDescriptor",
+                    message: Some("expected a table returned by Shader:describe()".to_string()),
+                })
+            }
+        };
+        let invalid = |message: String| LuaError::FromLuaConversionError {
+            from: "table",
+            to: "ShaderDescriptor",
+            message: Some(message),
+        };
+        let point = |key: &str| -> LuaResult<Point> {
+            table
+                .try_get::<_, LuaPoint>(key, lua)?
+                .map(Point::from)
+                .ok_or_else(|| invalid(format!("missing '{}' point", key)))
+        };
+        let number = |key: &str| -> LuaResult<f32> {
+            table
+                .get::<_, Option<f32>>(key)?
+                .ok_or_else(|| invalid(format!("missing '{}' number", key)))
+        };
+
+        let kind: String = table.get("type")?;
+        let geometry = match kind.as_str() {
+            "linear" => GradientGeometry::Linear {
+                from: point("from")?,
+                to: point("to")?,
+            },
+            "radial" => GradientGeometry::Radial {
+                center: point("center")?,
+                radius: number("radius")?,
+            },
+            "sweep" => GradientGeometry::Sweep {
+                center: point("center")?,
+                start_angle: table.get::<_, Option<f32>>("startAngle")?.unwrap_or(0.0),
+                end_angle: table.get::<_, Option<f32>>("endAngle")?.unwrap_or(360.0),
+            },
+            "twoPointConical" => GradientGeometry::TwoPointConical {
+                start: point("start")?,
+                start_radius: number("startRadius")?,
+                end: point("end")?,
+                end_radius: number("endRadius")?,
+            },
+            other => {
+                return Err(invalid(format!(
+                    "unknown gradient type '{}'; expected one of: linear, radial, sweep, twoPointConical",
+                    other
+                )))
+            }
+        };
+
+        let colors: Vec<Color4f> = table
+            .get::<_, Vec<LuaColor>>("colors")?
+            .into_iter()
+            .map(Color4f::from)
+            .collect();
+        let positions: Vec<f32> = table.get("positions")?;
+        if positions.len() != colors.len() {
+            return Err(invalid(format!(
+                "got {} colors and {} positions",
+                colors.len(),
+                positions.len()
+            )));
+        }
+
+        let color_space = match table.get::<_, Option<String>>("colorSpace")?.as_deref() {
+            None => None,
+            Some("srgb") => Some(ColorSpace::new_srgb()),
+            Some("srgb_linear") => Some(ColorSpace::new_srgb_linear()),
+            Some(other) => {
+                return Err(invalid(format!(
+                    "unknown color space '{}'; expected one of: srgb, srgb_linear",
+                    other
+                )))
+            }
+        };
+
+        let interpolation = match table.get::<_, Option<LuaTable>>("interpolation")? {
+            Some(it) => Interpolation {
+                in_premul: it.try_get_or_t::<_, LuaInPremul>("in_premul", lua, InPremul::No)?,
+                color_space: it.try_get_or_t::<_, LuaInColorSpace>(
+                    "color_space",
+                    lua,
+                    InColorSpace::Destination,
+                )?,
+                hue_method: it.try_get_or_t::<_, LuaHueMethod>(
+                    "hue_method",
+                    lua,
+                    HueMethod::Shorter,
+                )?,
+            },
+            None => Interpolation {
+                in_premul: InPremul::No,
+                color_space: InColorSpace::Destination,
+                hue_method: HueMethod::Shorter,
+            },
+        };
+
+        let local = match table.get::<_, Option<Vec<f32>>>("localMatrix")? {
+            Some(values) => Some(
+                Matrix::from_vec(values).map_err(|err| invalid(format!("localMatrix: {}", err)))?,
+            ),
+            None => None,
+        };
+
+        Ok(ShaderDescriptor {
+            geometry,
+            positions,
+            colors,
+            color_space,
+            tile_mode: table.try_get_or_t::<_, LuaTileMode>("tileMode", lua, TileMode::Clamp)?,
+            interpolation,
+            local,
+        })
+    }
+}
+crate::from_lua_argpack!(ShaderDescriptor);
@@ -2,6 +2,7 @@ use std::str::FromStr;
 use std::sync::OnceLock;
 
 use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
 use phf::phf_map;
 
 use skia_safe::{
@@ -13,16 +14,28 @@ use skia_safe::{
     matrix::{ScaleToFit, TypeMask},
     paint::{Cap as PaintCap, Join as PaintJoin, Style as PaintStyle},
     path::{AddPathMode, ArcSize, SegmentMask, Verb},
+    region::RegionOp,
     rrect::{Corner as RRectCorner, Type as RRectType},
     stroke_rec::{InitStyle as StrokeRecInitStyle, Style as StrokeRecStyle},
     trim_path_effect::Mode as TrimMode,
     *,
 };
 
-use crate::{FromArgPack, WrapperT};
+use crate::{
+    help::{MethodHelp, TypeHelp},
+    marker::{ArrowHead, MarkerStyle},
+    FromArgPack, WrapperT,
+};
 
+/// Declares a `Lua<kind>` wrapper converted from and into listed names.
+///
+/// Optional `aliases` are only accepted when converting from Lua, values
+/// are always converted into their primary name.
 macro_rules! named_enum {
-    ($kind: ty: [$($value: expr => $name: literal,)+]) => {paste::paste!{
+    (
+        $kind: ty: [$($value: expr => $name: literal,)+]
+        $(aliases: [$($alias_value: expr => $alias: literal,)+])?
+    ) => {paste::paste!{
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
         pub struct [<Lua $kind>](pub $kind);
 
@@ -30,20 +43,67 @@ macro_rules! named_enum {
         static [<NAME_TO_ $kind:snake:upper>]: phf::Map<&'static str, $kind> = phf_map! {
             $($name => $value),
             +
+            $(, $($alias => $alias_value),+)?
         };
 
         impl [<Lua $kind>] {
+            /// Values in declaration order; their positions are integer
+            /// discriminants accepted in place of names.
+            const VALUES: &'static [$kind] = &[$($value),+];
+            const NAMES: &'static [&'static str] = &[$($name),+];
+
             fn expected_values() -> &'static str {
                 static EXPECTED: OnceLock<String> = OnceLock::new();
 
                 EXPECTED.get_or_init(|| [
                     $(concat!("'", $name, "'")),+
+                    $(, $(concat!("'", $alias, "'")),+)?
                 ].join(", "))
             }
 
+            /// Returns value with integer discriminant `index`.
+            pub fn from_index(index: i64) -> LuaResult<Self> {
+                match usize::try_from(index).ok().and_then(|i| Self::VALUES.get(i)) {
+                    Some(it) => Ok([<Lua $kind>](*it)),
+                    None => Err(LuaError::FromLuaConversionError {
+                        from: "integer",
+                        to: stringify!($kind),
+                        message: Some(format!(
+                            concat!["invalid ", stringify!($kind), " value: {}; expected an integer in range 0-{}"],
+                            index,
+                            Self::VALUES.len() - 1
+                        )),
+                    }),
+                }
+            }
+
+            /// Returns integer discriminant of the value.
+            pub fn index(&self) -> Option<usize> {
+                Self::VALUES.iter().position(|it| *it == self.0)
+            }
+
+            /// Returns a table mapping names to integer discriminants.
+            pub fn index_table(lua: &Lua) -> LuaResult<LuaTable> {
+                let result = lua.create_table()?;
+                for (i, name) in Self::NAMES.iter().enumerate() {
+                    result.set(*name, i)?;
+                }
+                Ok(result)
+            }
+
             pub fn unwrap(&self) -> $kind {
                 self.0
             }
+
+            /// Returns the Lua name of the value.
+            #[allow(unreachable_patterns)]
+            pub fn name(&self) -> Option<&'static str> {
+                match self.0 {
+                    $($value => Some($name),)
+                    +
+                    _ => None,
+                }
+            }
         }
 
         impl<'lua> $crate::lua::WrapperT<'lua> for [<Lua $kind>] {
@@ -80,8 +140,18 @@ macro_rules! named_enum {
             type Err = LuaError;
 
             fn from_str(value: &str) -> Result<Self, Self::Err> {
-                let value = match [<NAME_TO_ $kind:snake:upper>].get(value.to_ascii_lowercase().as_str()) {
-                    Some(it) => *it,
+                // CSS style names separate words with dashes
+                let key = value.to_ascii_lowercase().replace('-', "_");
+                let found = [<NAME_TO_ $kind:snake:upper>].get(key.as_str()).copied().or_else(|| {
+                    // camelCase spelling of names, e.g. "initWithPrevious"
+                    let key = key.replace('_', "");
+                    [<NAME_TO_ $kind:snake:upper>]
+                        .entries()
+                        .find(|(name, _)| name.replace('_', "") == key)
+                        .map(|(_, it)| *it)
+                });
+                let value = match found {
+                    Some(it) => it,
                     None => return Err(LuaError::FromLuaConversionError {
                         from: "string",
                         to: stringify!($kind),
@@ -119,12 +189,16 @@ macro_rules! named_enum {
             fn from_lua(text: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
                 let text = match text {
                     LuaValue::String(it) => it,
+                    LuaValue::Integer(it) => return Self::from_index(it as i64),
+                    LuaValue::Number(it) if it.fract() == 0.0 => {
+                        return Self::from_index(it as i64)
+                    }
                     other => {
                         return Err(LuaError::FromLuaConversionError {
                             from: other.type_name(),
-                            to: "PaintStyle",
+                            to: stringify!($kind),
                             message: Some(format!(
-                                "expected a PaintStyle string value; one of: {}",
+                                concat!["expected a ", stringify!($kind), " name or integer; one of: {}"],
                                 Self::expected_values()
                             )),
                         })
@@ -181,6 +255,121 @@ named_enum! { BlendMode: [
     BlendMode::Luminosity => "luminosity",
 ]}
 
+named_enum! { BlendModeCoeff: [
+    BlendModeCoeff::Zero => "zero",
+    BlendModeCoeff::One => "one",
+    BlendModeCoeff::SC => "sc",
+    BlendModeCoeff::ISC => "isc",
+    BlendModeCoeff::DC => "dc",
+    BlendModeCoeff::IDC => "idc",
+    BlendModeCoeff::SA => "sa",
+    BlendModeCoeff::ISA => "isa",
+    BlendModeCoeff::DA => "da",
+    BlendModeCoeff::IDA => "ida",
+]}
+
+impl LuaBlendMode {
+    /// Whether the mode is one of the Porter-Duff compositing operators
+    /// (`clear` through `xor`, and `plus`).
+    pub fn is_porter_duff(&self) -> bool {
+        matches!(
+            self.0,
+            BlendMode::Clear
+                | BlendMode::Src
+                | BlendMode::Dst
+                | BlendMode::SrcOver
+                | BlendMode::DstOver
+                | BlendMode::SrcIn
+                | BlendMode::DstIn
+                | BlendMode::SrcOut
+                | BlendMode::DstOut
+                | BlendMode::SrcATop
+                | BlendMode::DstATop
+                | BlendMode::Xor
+                | BlendMode::Plus
+        )
+    }
+
+    /// Returns source and destination coefficients of the mode, or `None`
+    /// if it can't be expressed as `src * srcCoeff + dst * dstCoeff`.
+    ///
+    /// Mirrors `SkBlendMode_AsCoeff`, which skia-safe doesn't expose.
+    pub fn coeffs(&self) -> Option<(BlendModeCoeff, BlendModeCoeff)> {
+        use BlendModeCoeff as C;
+        Some(match self.0 {
+            BlendMode::Clear => (C::Zero, C::Zero),
+            BlendMode::Src => (C::One, C::Zero),
+            BlendMode::Dst => (C::Zero, C::One),
+            BlendMode::SrcOver => (C::One, C::ISA),
+            BlendMode::DstOver => (C::IDA, C::One),
+            BlendMode::SrcIn => (C::DA, C::Zero),
+            BlendMode::DstIn => (C::Zero, C::SA),
+            BlendMode::SrcOut => (C::IDA, C::Zero),
+            BlendMode::DstOut => (C::Zero, C::ISA),
+            BlendMode::SrcATop => (C::DA, C::ISA),
+            BlendMode::DstATop => (C::IDA, C::SA),
+            BlendMode::Xor => (C::IDA, C::ISA),
+            BlendMode::Plus => (C::One, C::One),
+            BlendMode::Modulate => (C::Zero, C::SC),
+            BlendMode::Screen => (C::One, C::ISC),
+            _ => return None,
+        })
+    }
+}
+
+/// Global `BlendMode` table functions.
+pub struct BlendModeFns;
+
+#[lua_methods(lua_name: BlendMode)]
+impl BlendModeFns {
+    /// Blend mode recommended for combining patch corner colors with a paint
+    /// shader. `drawPatch` uses it by default when both are present.
+    pub fn for_patch() -> LuaBlendMode {
+        Ok(LuaBlendMode(BlendMode::Modulate))
+    }
+    /// Returns names of all blend modes in declaration order.
+    pub fn all() -> Vec<&'static str> {
+        Ok(LuaBlendMode::NAMES.to_vec())
+    }
+    pub fn is_porter_duff(mode: LuaBlendMode) -> bool {
+        Ok(mode.is_porter_duff())
+    }
+    /// Returns a `{srcCoeff, dstCoeff}` table of coefficient names, or `nil`
+    /// for modes which aren't coefficient based (e.g. `"multiply"`).
+    pub fn coeffs<'lua>(lua: &'lua Lua, mode: LuaBlendMode) -> Option<LuaTable<'lua>> {
+        let (src, dst) = match mode.coeffs() {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        let result = lua.create_table()?;
+        result.set("srcCoeff", LuaBlendModeCoeff(src))?;
+        result.set("dstCoeff", LuaBlendModeCoeff(dst))?;
+        Ok(Some(result))
+    }
+}
+
+named_enum! { RegionOp: [
+    RegionOp::Difference => "difference",
+    RegionOp::Intersect => "intersect",
+    RegionOp::Union => "union",
+    RegionOp::XOR => "xor",
+    RegionOp::ReverseDifference => "reverse_difference",
+    RegionOp::Replace => "replace",
+]}
+
+named_enum! { ArrowHead: [
+    ArrowHead::Filled => "filled",
+    ArrowHead::Open => "open",
+    ArrowHead::Dot => "dot",
+]}
+
+named_enum! { MarkerStyle: [
+    MarkerStyle::Cross => "cross",
+    MarkerStyle::Circle => "circle",
+    MarkerStyle::Diamond => "diamond",
+    MarkerStyle::Square => "square",
+]}
+
 named_enum! { PaintCap : [
     PaintCap::Butt => "butt",
     PaintCap::Round => "round",
@@ -366,6 +555,11 @@ named_enum! { ColorChannel: [
     ColorChannel::G => "g",
     ColorChannel::B => "b",
     ColorChannel::A => "a",
+] aliases: [
+    ColorChannel::R => "red",
+    ColorChannel::G => "green",
+    ColorChannel::B => "blue",
+    ColorChannel::A => "alpha",
 ]}
 
 named_enum! { HueMethod: [
@@ -394,8 +588,69 @@ named_enum! { BlurStyle: [
     BlurStyle::Inner => "inner",
 ]}
 
+macro_rules! enum_tables {
+    ($($t: ident),* $(,)?) => {paste::paste!{
+        /// Registers the `Enums` global which maps value names of each enum to
+        /// integer discriminants that can be passed instead of names.
+        pub fn register_enums(lua: &Lua) -> LuaResult<()> {
+            let result = lua.create_table()?;
+            $(
+                result.set(stringify!($t), [<Lua $t>]::index_table(lua)?)?;
+            )*
+            lua.globals().set("Enums", result)
+        }
+
+        /// Documentation of the `Enums` global, listing the same enums
+        /// [`register_enums`] registers.
+        pub const LUA_HELP: TypeHelp = TypeHelp::table("Enums", &[$(
+            MethodHelp::value(
+                stringify!($t),
+                concat!("Enums.", stringify!($t), ": table"),
+                "table",
+                concat!("Maps names of `", stringify!($t), "` values to their discriminants."),
+            ),
+        )*]);
+    }};
+}
+
+enum_tables!(
+    AddPathMode,
+    AlphaType,
+    ArcSize,
+    ArrowHead,
+    BlendMode,
+    BlendModeCoeff,
+    BlurStyle,
+    ColorChannel,
+    ColorType,
+    FilterMode,
+    FontEdging,
+    FontHinting,
+    HueMethod,
+    InColorSpace,
+    MapDirection,
+    MarkerStyle,
+    MipmapMode,
+    PaintCap,
+    PaintJoin,
+    PathDirection,
+    PathFillType,
+    PixelGeometry,
+    RegionOp,
+    RRectCorner,
+    RRectType,
+    ScaleToFit,
+    Slant,
+    StrokeRecInitStyle,
+    StrokeRecStyle,
+    TextEncoding,
+    TileMode,
+    TrimMode,
+    Verb,
+);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct LuaInPremul(InPremul);
+pub struct LuaInPremul(pub InPremul);
 
 #[allow(unused)]
 static NAME_TO_IN_PREMUL: phf::Map<&'static str, InPremul> = phf_map! {
@@ -523,18 +778,32 @@ macro_rules! named_bitflags {
         ]}
 
         impl [<Lua $kind>] {
+            /// Reads flags from an array of names, or a map of names to
+            /// booleans (e.g. `{initWithPrevious = true}`), or a mix of
+            /// both.
             pub fn from_table(table: LuaTable) -> Result<Self, LuaError> {
                 let mut result = $kind::empty();
-                for pair in table.pairs::<usize, String>() {
-                    if let Ok((_, name)) = pair {
-                        result |= [<Lua $kind>]::try_from(name)?.0;
-                    } else {
-                        return Err(LuaError::FromLuaConversionError {
+                for pair in table.pairs::<LuaValue, LuaValue>() {
+                    let name = match pair? {
+                        (LuaValue::Integer(_), LuaValue::String(name)) => name,
+                        (LuaValue::String(name), LuaValue::Boolean(set)) => {
+                            if !set {
+                                // still validate the name
+                                [<Lua $kind>]::try_from(name)?;
+                                continue;
+                            }
+                            name
+                        }
+                        _ => return Err(LuaError::FromLuaConversionError {
                             from: "table",
                             to: stringify!($kind),
-                            message: Some(concat!("expected ", stringify!($kind), " array to be an array of strings").to_string()),
-                        });
-                    }
+                            message: Some(format!(
+                                concat!("expected an array of ", stringify!($kind), " names or a map of names to booleans; names are: {}"),
+                                Self::expected_values()
+                            )),
+                        }),
+                    };
+                    result |= [<Lua $kind>]::try_from(name)?.0;
                 }
                 Ok(Self(result))
             }
@@ -729,3 +998,99 @@ impl<'lua> IntoLua<'lua> for LuaPaintStyle {
         .map(LuaValue::String)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enums_accept_integer_discriminants() {
+        let lua = Lua::new();
+        let from_int = |value: i64| LuaValue::Integer(value as _);
+
+        assert_eq!(
+            LuaBlendMode::from_lua(from_int(0), &lua).unwrap().unwrap(),
+            BlendMode::Clear
+        );
+        assert_eq!(
+            LuaBlendMode::from_lua(from_int(3), &lua).unwrap().unwrap(),
+            BlendMode::SrcOver
+        );
+        assert_eq!(
+            LuaTileMode::from_lua(from_int(3), &lua).unwrap().unwrap(),
+            TileMode::Decal
+        );
+        assert_eq!(
+            LuaPaintCap::from_lua(from_int(1), &lua).unwrap().unwrap(),
+            PaintCap::Round
+        );
+        // integral floats are accepted as well
+        assert_eq!(
+            LuaPaintCap::from_lua(LuaValue::Number(2.0), &lua)
+                .unwrap()
+                .unwrap(),
+            PaintCap::Square
+        );
+
+        // discriminants round trip
+        for (i, value) in LuaBlendMode::VALUES.iter().enumerate() {
+            assert_eq!(LuaBlendMode(*value).index(), Some(i));
+            assert_eq!(LuaBlendMode::from_index(i as i64).unwrap().0, *value);
+        }
+    }
+
+    #[test]
+    fn enums_reject_out_of_range_integers() {
+        let lua = Lua::new();
+        let error = |value: LuaValue| match LuaTileMode::from_lua(value, &lua) {
+            Err(LuaError::FromLuaConversionError { message, .. }) => message.unwrap_or_default(),
+            other => panic!("expected a conversion error, got {:?}", other),
+        };
+
+        assert!(error(LuaValue::Integer(4)).contains("range 0-3"));
+        assert!(error(LuaValue::Integer(-1)).contains("range 0-3"));
+        assert!(LuaBlendMode::from_lua(LuaValue::Integer(29), &lua).is_err());
+        assert!(LuaPaintCap::from_lua(LuaValue::Integer(3), &lua).is_err());
+        assert!(LuaPaintCap::from_lua(LuaValue::Number(1.5), &lua).is_err());
+    }
+
+    #[test]
+    fn enums_table_maps_names_to_discriminants() {
+        let lua = Lua::new();
+        register_enums(&lua).unwrap();
+        let (clamp, decal, round): (usize, usize, usize) = lua
+            .load("return Enums.TileMode.clamp, Enums.TileMode.decal, Enums.PaintCap.round")
+            .eval()
+            .unwrap();
+        assert_eq!((clamp, decal, round), (0, 3, 1));
+    }
+
+    #[test]
+    fn color_channels_accept_shorthands_and_full_names() {
+        let channel = |name: &str| LuaColorChannel::from_str(name).map(|it| it.unwrap());
+
+        for (names, expected) in [
+            (["r", "R", "red", "Red"], ColorChannel::R),
+            (["g", "G", "green", "GREEN"], ColorChannel::G),
+            (["b", "B", "blue", "Blue"], ColorChannel::B),
+            (["a", "A", "alpha", "Alpha"], ColorChannel::A),
+        ] {
+            for name in names {
+                assert_eq!(channel(name).unwrap(), expected, "{}", name);
+            }
+        }
+        // values convert into shorthands
+        assert_eq!(LuaColorChannel(ColorChannel::G).name(), Some("g"));
+
+        let message = match channel("x") {
+            Err(LuaError::FromLuaConversionError { message, .. }) => message.unwrap_or_default(),
+            other => panic!("expected a conversion error, got {:?}", other),
+        };
+        assert!(
+            message.contains("unknown ColorChannel name: 'x'"),
+            "{}",
+            message
+        );
+        assert!(message.contains("'r', 'g', 'b', 'a', 'red', 'green', 'blue', 'alpha'"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Splitting of text into runs of the first font in a chain that covers
+//! them, exposed to Lua as `FontChain`.
+//!
+//! Text is split into grapheme clusters and each cluster uses the first
+//! font with a glyph for its first character, so combining marks, variation
+//! selectors and joined emoji stay with the character they modify. Clusters
+//! no font covers use the first font, which draws them as missing glyphs.
+
+use std::{collections::HashMap, ops::Range};
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::{Font, Unichar};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{lua::ConstructorResult, LuaFont};
+
+pub struct FontChain {
+    fonts: Vec<Font>,
+    /// Whether font at an index has a glyph for a character, for characters
+    /// queried so far.
+    coverage: HashMap<(usize, char), bool>,
+}
+
+impl FontChain {
+    /// Returns `None` if `fonts` is empty.
+    pub fn new(fonts: Vec<Font>) -> Option<Self> {
+        if fonts.is_empty() {
+            return None;
+        }
+        Some(FontChain {
+            fonts,
+            coverage: HashMap::new(),
+        })
+    }
+
+    pub fn fonts(&self) -> &[Font] {
+        &self.fonts
+    }
+
+    fn covers(&mut self, font: usize, c: char) -> bool {
+        let fonts = &self.fonts;
+        *self
+            .coverage
+            .entry((font, c))
+            .or_insert_with(|| fonts[font].unichar_to_glyph(c as Unichar) != 0)
+    }
+
+    /// Returns index of the first font covering `c`.
+    pub fn font_for(&mut self, c: char) -> Option<usize> {
+        (0..self.fonts.len()).find(|it| self.covers(*it, c))
+    }
+
+    /// Splits `text` into byte ranges of consecutive clusters using the same
+    /// font, each returned with index of the font.
+    pub fn runs(&mut self, text: &str) -> Vec<(usize, Range<usize>)> {
+        let mut result: Vec<(usize, Range<usize>)> = Vec::new();
+        for (start, cluster) in text.grapheme_indices(true) {
+            let base = match cluster.chars().next() {
+                Some(it) => it,
+                None => continue,
+            };
+            let font = match result.last() {
+                // spaces between scripts don't start a run of their own
+                Some((current, _)) if base.is_whitespace() && self.covers(*current, base) => {
+                    *current
+                }
+                _ => self.font_for(base).unwrap_or(0),
+            };
+
+            let end = start + cluster.len();
+            match result.last_mut() {
+                Some((current, range)) if *current == font => range.end = end,
+                _ => result.push((font, start..end)),
+            }
+        }
+        result
+    }
+}
+
+pub struct LuaFontChain(pub FontChain);
+
+#[lua_methods(lua_name: FontChain)]
+impl LuaFontChain {
+    /// Creates a chain trying `fonts` in order for each character, e.g. a
+    /// text font followed by an emoji font.
+    pub fn new(fonts: Vec<LuaFont>) -> LuaFontChain {
+        Ok(
+            FontChain::new(fonts.into_iter().map(|it| it.0.clone()).collect())
+                .map(LuaFontChain)
+                .or_failed("FontChain.new", || "no fonts given")?,
+        )
+    }
+    pub fn fonts(&self) -> Vec<LuaFont> {
+        Ok(self.0.fonts().iter().cloned().map(LuaFont::new).collect())
+    }
+    /// Splits `text` into runs covered by the same font, returned as
+    /// `{font, text, width}` tables in order. Drawing each run with its
+    /// font and advancing by its width draws the whole text.
+    pub fn shape<'lua>(&mut self, lua: &'lua Lua, text: String) -> Vec<LuaTable<'lua>> {
+        let runs = self.0.runs(&text);
+        runs.into_iter()
+            .map(|(font, range)| {
+                let font = &self.0.fonts()[font];
+                let text = &text[range];
+                let (width, _) = font.measure_str(text, None);
+
+                let run = lua.create_table()?;
+                run.set("font", LuaFont::new(font.clone()))?;
+                run.set("text", text)?;
+                run.set("width", width)?;
+                Ok(run)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::{FontMgr, FontStyle};
+
+    /// DejaVu Sans Mono followed by DejaVu Sans, which also covers some
+    /// emoji. Neither has a glyph for U+2705.
+    fn chain() -> FontChain {
+        let fonts = ["DejaVu Sans Mono", "DejaVu Sans"].map(|family| {
+            let typeface = FontMgr::default()
+                .match_family_style(family, FontStyle::normal())
+                .expect("DejaVu fonts should be installed");
+            Font::from_typeface(typeface, 20.0)
+        });
+        FontChain::new(fonts.to_vec()).unwrap()
+    }
+
+    fn runs<'a>(chain: &mut FontChain, text: &'a str) -> Vec<(usize, &'a str)> {
+        chain
+            .runs(text)
+            .into_iter()
+            .map(|(font, range)| (font, &text[range]))
+            .collect()
+    }
+
+    #[test]
+    fn mixed_scripts_split_at_font_boundaries() {
+        let mut chain = chain();
+        assert_eq!(
+            runs(&mut chain, "Status: доступно 😀 ok"),
+            vec![(0, "Status: доступно "), (1, "😀 "), (0, "ok")]
+        );
+        // uncovered characters stay in the run of the first font
+        assert_eq!(
+            runs(&mut chain, "ok ✅ 😀✅"),
+            vec![(0, "ok ✅ "), (1, "😀"), (0, "✅")]
+        );
+        assert!(runs(&mut chain, "").is_empty());
+    }
+
+    #[test]
+    fn clusters_stay_in_one_run() {
+        let mut chain = chain();
+        // combining acute accent and emoji variation selector
+        assert_eq!(runs(&mut chain, "е\u{301}д"), vec![(0, "е\u{301}д")]);
+        assert_eq!(
+            runs(&mut chain, "a😀\u{fe0f}b"),
+            vec![(0, "a"), (1, "😀\u{fe0f}"), (0, "b")]
+        );
+    }
+
+    #[test]
+    fn coverage_is_cached_per_character() {
+        let mut chain = chain();
+        runs(&mut chain, "abba 😀");
+        let queried = chain.coverage.len();
+        // 'a', 'b' and ' ' are covered by the first font, the emoji isn't
+        assert_eq!(queried, 5);
+        runs(&mut chain, "baab 😀😀");
+        assert_eq!(chain.coverage.len(), queried);
+        assert_eq!(chain.coverage.get(&(1, '😀')), Some(&true));
+        assert!(FontChain::new(Vec::new()).is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Frame clock driven by the host application, exposed to Lua as `Frame`.
+//!
+//! Scripts should use `Frame.time()` and `Frame.delta()` for animations
+//! instead of `os.clock()`, which doesn't match compositor frame timing and
+//! keeps advancing while rendering is paused.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+
+/// Registry key of the table holding the current frame state.
+pub(crate) const FRAME_KEY: &str = "mlua_skia.frame";
+
+/// Frame state as last reported by the host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameState {
+    /// Seconds since an arbitrary host chosen origin.
+    pub time: f64,
+    /// Seconds since the previous frame; `0` for the first one.
+    pub delta: f64,
+    pub index: u64,
+    /// Whether the host is actively rendering frames.
+    pub animating: bool,
+}
+
+impl Default for FrameState {
+    fn default() -> Self {
+        FrameState {
+            time: 0.0,
+            delta: 0.0,
+            index: 0,
+            animating: true,
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for FrameState {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        let result = lua.create_table()?;
+        result.set("time", self.time)?;
+        result.set("delta", self.delta)?;
+        result.set("index", self.index)?;
+        result.set("animating", self.animating)?;
+        Ok(LuaValue::Table(result))
+    }
+}
+
+impl<'lua> FromLua<'lua> for FrameState {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = LuaTable::from_lua(value, lua)?;
+        Ok(FrameState {
+            time: table.get("time")?,
+            delta: table.get("delta")?,
+            index: table.get("index")?,
+            animating: table.get("animating")?,
+        })
+    }
+}
+
+/// Returns the frame state last set by the host, or the default one if
+/// [`set_frame_time`] wasn't called yet.
+pub fn frame_state(lua: &Lua) -> LuaResult<FrameState> {
+    let state: Option<FrameState> = lua.named_registry_value(FRAME_KEY)?;
+    Ok(state.unwrap_or_default())
+}
+
+/// Advances the frame clock to `seconds` and marks the host as animating.
+///
+/// Should be called by the host application before invoking script callbacks
+/// for a frame. Delta is clamped to `0` if time goes backwards, and is `0`
+/// for the first frame after a pause so animations don't skip the time spent
+/// paused.
+pub fn set_frame_time(lua: &Lua, seconds: f64, frame_index: u64) -> LuaResult<()> {
+    let previous: Option<FrameState> = lua.named_registry_value(FRAME_KEY)?;
+    let delta = match previous {
+        Some(previous) if previous.animating => (seconds - previous.time).max(0.0),
+        _ => 0.0,
+    };
+    lua.set_named_registry_value(
+        FRAME_KEY,
+        FrameState {
+            time: seconds,
+            delta,
+            index: frame_index,
+            animating: true,
+        },
+    )
+}
+
+/// Sets whether the host is rendering frames; scripts can check it with
+/// `Frame.isAnimating()` to skip work while paused.
+pub fn set_animating(lua: &Lua, animating: bool) -> LuaResult<()> {
+    let mut state = frame_state(lua)?;
+    state.animating = animating;
+    lua.set_named_registry_value(FRAME_KEY, state)
+}
+
+pub struct Frame;
+
+#[lua_methods(lua_name: Frame)]
+impl Frame {
+    /// Time of the current frame in seconds.
+    pub fn time<'lua>(lua: &'lua Lua) -> f64 {
+        Ok(frame_state(lua)?.time)
+    }
+    /// Seconds elapsed since the previous frame.
+    pub fn delta<'lua>(lua: &'lua Lua) -> f64 {
+        Ok(frame_state(lua)?.delta)
+    }
+    pub fn index<'lua>(lua: &'lua Lua) -> u64 {
+        Ok(frame_state(lua)?.index)
+    }
+    pub fn is_animating<'lua>(lua: &'lua Lua) -> bool {
+        Ok(frame_state(lua)?.animating)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lua() -> Lua {
+        let lua = Lua::new();
+        Frame::register_globals(&lua).unwrap();
+        lua
+    }
+
+    fn read(lua: &Lua) -> (f64, f64, u64, bool) {
+        lua.load("return Frame.time(), Frame.delta(), Frame.index(), Frame.isAnimating()")
+            .eval()
+            .unwrap()
+    }
+
+    #[test]
+    fn frame_defaults_before_host_sets_time() {
+        assert_eq!(read(&lua()), (0.0, 0.0, 0, true));
+    }
+
+    #[test]
+    fn frame_reports_host_time_and_delta() {
+        let lua = lua();
+        set_frame_time(&lua, 10.0, 1).unwrap();
+        assert_eq!(read(&lua), (10.0, 0.0, 1, true));
+
+        set_frame_time(&lua, 10.25, 2).unwrap();
+        assert_eq!(read(&lua), (10.25, 0.25, 2, true));
+
+        // time going backwards doesn't produce negative deltas
+        set_frame_time(&lua, 5.0, 3).unwrap();
+        assert_eq!(read(&lua), (5.0, 0.0, 3, true));
+    }
+
+    #[test]
+    fn frame_reports_paused_host() {
+        let lua = lua();
+        set_frame_time(&lua, 1.0, 7).unwrap();
+        set_animating(&lua, false).unwrap();
+        assert_eq!(read(&lua), (1.0, 0.0, 7, false));
+
+        // next frame resumes animation without counting the pause
+        set_frame_time(&lua, 30.0, 8).unwrap();
+        assert_eq!(read(&lua), (30.0, 0.0, 8, true));
+        set_frame_time(&lua, 30.5, 9).unwrap();
+        assert_eq!(read(&lua), (30.5, 0.5, 9, true));
+    }
+}
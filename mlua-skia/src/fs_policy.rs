@@ -0,0 +1,210 @@
+//! File access policy of bindings that load files, set by the host with
+//! [`set_fs_policy`].
+//!
+//! Hosts running third-party scripts restrict which files bindings such as
+//! `Image.load` and `Typeface.makeFromFile` may read. Paths are resolved
+//! before they're checked, so symlinks and `..` components can't escape
+//! allowed directories. Data passed to bindings directly is never affected.
+
+use std::path::{Path, PathBuf};
+
+use mlua::prelude::*;
+
+/// Files bindings are allowed to read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FsPolicy {
+    /// Any file can be read.
+    #[default]
+    AllowAll,
+    /// No file can be read.
+    DenyAll,
+    /// Only files inside of listed directories (or listed files) can be
+    /// read.
+    AllowList(Vec<PathBuf>),
+}
+
+impl FsPolicy {
+    /// Returns whether `path`, which must be canonical, is allowed.
+    fn allows(&self, path: &Path) -> bool {
+        match self {
+            FsPolicy::AllowAll => true,
+            FsPolicy::DenyAll => false,
+            FsPolicy::AllowList(prefixes) => prefixes.iter().any(|it| path.starts_with(it)),
+        }
+    }
+}
+
+/// Sets the file access policy of bindings in `lua`, replacing the previous
+/// one. [`FsPolicy::AllowAll`] is used until this is called.
+///
+/// Allowed prefixes are resolved when the policy is set, so they must
+/// exist; prefixes that can't be resolved are kept as given and only match
+/// paths that resolve under them.
+pub fn set_fs_policy(lua: &Lua, policy: FsPolicy) {
+    let policy = match policy {
+        FsPolicy::AllowList(prefixes) => FsPolicy::AllowList(
+            prefixes
+                .into_iter()
+                .map(|it| it.canonicalize().unwrap_or(it))
+                .collect(),
+        ),
+        other => other,
+    };
+    lua.set_app_data(policy);
+}
+
+/// Returns the file access policy of bindings in `lua`.
+pub fn fs_policy(lua: &Lua) -> FsPolicy {
+    lua.app_data_ref::<FsPolicy>()
+        .as_deref()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Fails with an error naming `call` if the policy of `lua` doesn't allow
+/// reading `path`, and returns the path to read otherwise. Called by every
+/// binding before it reads a file.
+///
+/// Bindings must read only the returned path: with an allow list it's the
+/// resolved path that was checked, so links changed after the check can't
+/// redirect the read.
+pub(crate) fn check_read(lua: &Lua, call: &str, path: impl AsRef<Path>) -> LuaResult<PathBuf> {
+    let path = path.as_ref();
+    let policy = match lua.app_data_ref::<FsPolicy>() {
+        Some(it) => it,
+        None => return Ok(path.to_path_buf()),
+    };
+    let denied = |reason: String| {
+        Err(LuaError::RuntimeError(format!(
+            "{} failed: {}",
+            call, reason
+        )))
+    };
+    match &*policy {
+        FsPolicy::AllowAll => Ok(path.to_path_buf()),
+        FsPolicy::DenyAll => denied(format!(
+            "reading '{}' is not allowed by file access policy",
+            path.display()
+        )),
+        FsPolicy::AllowList(_) => match path.canonicalize() {
+            Ok(resolved) if policy.allows(&resolved) => Ok(resolved),
+            Ok(resolved) => denied(format!(
+                "reading '{}' (resolved to '{}') is not allowed by file access policy",
+                path.display(),
+                resolved.display()
+            )),
+            Err(err) => denied(format!("unable to resolve '{}': {}", path.display(), err)),
+        },
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Directory with an `allowed` directory containing `inside.txt` and an
+    /// `outside.txt` file next to it, removed when dropped.
+    pub(crate) struct Fixture(PathBuf);
+
+    impl Fixture {
+        pub(crate) fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "mlua-skia-fs-policy-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("allowed")).unwrap();
+            std::fs::write(root.join("allowed/inside.txt"), b"inside").unwrap();
+            std::fs::write(root.join("outside.txt"), b"outside").unwrap();
+            Fixture(root)
+        }
+
+        pub(crate) fn path(&self, relative: &str) -> PathBuf {
+            self.0.join(relative)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn error(result: LuaResult<PathBuf>) -> String {
+        result.unwrap_err().to_string()
+    }
+
+    #[test]
+    fn allow_list_checks_resolved_prefixes() {
+        let fixture = Fixture::new("prefix");
+        let lua = Lua::new();
+        // paths are read as given without a policy
+        let outside = fixture.path("outside.txt");
+        assert_eq!(check_read(&lua, "Image.load", &outside).unwrap(), outside);
+
+        set_fs_policy(&lua, FsPolicy::AllowList(vec![fixture.path("allowed")]));
+        let inside = fixture.path("allowed/inside.txt");
+        assert_eq!(
+            check_read(&lua, "Image.load", &inside).unwrap(),
+            inside.canonicalize().unwrap()
+        );
+
+        let denied = error(check_read(&lua, "Image.load", fixture.path("outside.txt")));
+        assert!(
+            denied.starts_with("Image.load failed: reading"),
+            "{}",
+            denied
+        );
+        assert!(
+            denied.contains("not allowed by file access policy"),
+            "{}",
+            denied
+        );
+        // `..` components are resolved before checking
+        let escaped = fixture.path("allowed/../outside.txt");
+        assert!(check_read(&lua, "Image.load", escaped).is_err());
+        let missing = error(check_read(&lua, "Image.load", fixture.path("allowed/none")));
+        assert!(missing.contains("unable to resolve"), "{}", missing);
+    }
+
+    #[test]
+    fn deny_all_rejects_every_path() {
+        let fixture = Fixture::new("deny");
+        let lua = Lua::new();
+        set_fs_policy(&lua, FsPolicy::DenyAll);
+        assert_eq!(fs_policy(&lua), FsPolicy::DenyAll);
+        let denied = error(check_read(
+            &lua,
+            "Svg.load",
+            fixture.path("allowed/inside.txt"),
+        ));
+        assert!(denied.starts_with("Svg.load failed:"), "{}", denied);
+
+        set_fs_policy(&lua, FsPolicy::AllowAll);
+        let outside = fixture.path("outside.txt");
+        assert_eq!(check_read(&lua, "Svg.load", &outside).unwrap(), outside);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_cant_escape_allowed_directories() {
+        let fixture = Fixture::new("symlink");
+        let link = fixture.path("allowed/link.txt");
+        std::os::unix::fs::symlink(fixture.path("outside.txt"), &link).unwrap();
+        let lua = Lua::new();
+        set_fs_policy(&lua, FsPolicy::AllowList(vec![fixture.path("allowed")]));
+
+        let denied = error(check_read(&lua, "Typeface.makeFromFile", &link));
+        assert!(denied.contains("resolved to"), "{}", denied);
+
+        // links into allowed directories are followed as well
+        let inward = fixture.path("inward.txt");
+        std::os::unix::fs::symlink(fixture.path("allowed/inside.txt"), &inward).unwrap();
+        // and the link target is returned, so it's the file that's read
+        assert_eq!(
+            check_read(&lua, "Typeface.makeFromFile", &inward).unwrap(),
+            fixture.path("allowed/inside.txt").canonicalize().unwrap()
+        );
+    }
+}
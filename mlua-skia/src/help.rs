@@ -0,0 +1,262 @@
+//! Documentation of bindings available to scripts through the `Help` table.
+//!
+//! Descriptions are collected from doc comments by `lua_methods` into
+//! static tables, so they cost nothing until [`register`] builds the Lua
+//! side at setup. The same tables describe the API to tooling through
+//! [`describe`] and, with the `describe` feature, [`api_description`].
+
+use mlua::prelude::*;
+
+use crate::teardown::ensure_alive;
+
+/// How a bound function is called from Lua.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MethodKind {
+    /// Called on a value, as `value:name(...)`.
+    Method,
+    /// Called on the type table, as `Type.name(...)`.
+    Function,
+    /// Called as the type table itself, as `Type(...)`.
+    Constructor,
+    /// Called by Lua operators on a value, e.g. `__eq` for `==`.
+    MetaMethod,
+    /// Not a function, but a value stored in the global table, e.g.
+    /// `Enums.BlendMode`.
+    Value,
+}
+
+impl MethodKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MethodKind::Method => "method",
+            MethodKind::Function => "function",
+            MethodKind::Constructor => "constructor",
+            MethodKind::MetaMethod => "metamethod",
+            MethodKind::Value => "value",
+        }
+    }
+}
+
+/// Lua name and type label of an argument.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamHelp {
+    pub name: &'static str,
+    /// Readable type, e.g. `Rect`, `number?` or `Point[]`.
+    pub ty: &'static str,
+}
+
+/// Documentation of a single bound method or function.
+#[derive(Debug, Clone, Copy)]
+pub struct MethodHelp {
+    /// Name of the method in Lua.
+    pub name: &'static str,
+    /// Doc comment of the method; empty if it's undocumented.
+    pub doc: &'static str,
+    /// Readable signature, e.g. `drawRect(rect: Rect, paint: Paint)`.
+    pub signature: &'static str,
+    pub kind: MethodKind,
+    /// Arguments passed from Lua, without the receiver.
+    pub params: &'static [ParamHelp],
+    /// Readable type of returned values; `None` if nothing is returned.
+    /// Type of the value itself for [`MethodKind::Value`] entries.
+    pub returns: Option<&'static str>,
+}
+
+impl MethodHelp {
+    /// Documents a value of type `ty` stored in a global table.
+    pub const fn value(
+        name: &'static str,
+        signature: &'static str,
+        ty: &'static str,
+        doc: &'static str,
+    ) -> Self {
+        MethodHelp {
+            name,
+            doc,
+            signature,
+            kind: MethodKind::Value,
+            params: &[],
+            returns: Some(ty),
+        }
+    }
+
+    /// Documents a function stored in a global table, which isn't bound
+    /// with `lua_methods`.
+    pub const fn function(
+        name: &'static str,
+        signature: &'static str,
+        params: &'static [ParamHelp],
+        returns: Option<&'static str>,
+        doc: &'static str,
+    ) -> Self {
+        MethodHelp {
+            name,
+            doc,
+            signature,
+            kind: MethodKind::Function,
+            params,
+            returns,
+        }
+    }
+}
+
+/// Documentation of all methods of a bound type.
+#[derive(Debug, Clone, Copy)]
+pub struct TypeHelp {
+    pub name: &'static str,
+    pub methods: &'static [MethodHelp],
+    /// `nativeId` and `__eq` of wrapped skia handles, added to `methods` of
+    /// every handle type by `wrap_skia_handle`.
+    pub handle_methods: &'static [MethodHelp],
+}
+
+impl TypeHelp {
+    /// Documents a global table which isn't bound with `lua_methods`.
+    pub const fn table(name: &'static str, methods: &'static [MethodHelp]) -> Self {
+        TypeHelp {
+            name,
+            methods,
+            handle_methods: &[],
+        }
+    }
+
+    /// Returns all documented methods and values, including handle methods.
+    pub fn all_methods(&self) -> impl Iterator<Item = &'static MethodHelp> {
+        self.methods.iter().chain(self.handle_methods)
+    }
+
+    pub fn method(&self, name: &str) -> Option<&'static MethodHelp> {
+        self.all_methods().find(|it| it.name == name)
+    }
+}
+
+/// Documentation of the `Help` table itself.
+pub const LUA_HELP: TypeHelp = TypeHelp::table(
+    "Help",
+    &[
+        MethodHelp::function(
+            "signature",
+            "Help.signature(path: string) -> string?",
+            &[ParamHelp {
+                name: "path",
+                ty: "string",
+            }],
+            Some("string?"),
+            "Returns readable signature of a method given as `\"Canvas.drawRect\"`.",
+        ),
+        MethodHelp::function(
+            "kind",
+            "Help.kind(path: string) -> string?",
+            &[ParamHelp {
+                name: "path",
+                ty: "string",
+            }],
+            Some("string?"),
+            "Returns kind of a method given as `\"Canvas.drawRect\"`: `\"method\"`, \
+             `\"function\"`, `\"constructor\"`, `\"metamethod\"` or `\"value\"`.",
+        ),
+    ],
+);
+
+/// Finds help of a method given as `Type.method` or `Type:method`.
+pub fn find(types: &[&'static TypeHelp], path: &str) -> Option<&'static MethodHelp> {
+    let (type_name, method) = path.split_once(|c| c == '.' || c == ':')?;
+    types
+        .iter()
+        .find(|it| it.name == type_name)
+        .and_then(|it| it.method(method))
+}
+
+/// Sets up the global `Help` table, which maps type and method names to
+/// doc strings (`Help.Canvas.drawRect`), along with `Help.signature(path)`
+/// and `Help.kind(path)` which return the signature and kind (`"method"`,
+/// `"function"`, `"constructor"`, `"metamethod"` or `"value"`) of a method
+/// given as `"Canvas.drawRect"`.
+pub fn register(lua: &Lua, types: &[&'static TypeHelp]) -> LuaResult<()> {
+    let help = lua.create_table()?;
+    for ty in types {
+        let methods = lua.create_table()?;
+        for method in ty.all_methods() {
+            methods.set(method.name, method.doc)?;
+        }
+        help.set(ty.name, methods)?;
+    }
+
+    let types = types.to_vec();
+    let signature_types = types.clone();
+    help.set(
+        "signature",
+        lua.create_function(move |lua, path: String| {
+            ensure_alive(lua)?;
+            Ok(find(&signature_types, &path).map(|it| it.signature))
+        })?,
+    )?;
+    help.set(
+        "kind",
+        lua.create_function(move |lua, path: String| {
+            ensure_alive(lua)?;
+            Ok(find(&types, &path).map(|it| it.kind.name()))
+        })?,
+    )?;
+
+    lua.globals().set("Help", help)
+}
+
+/// Returns a table mapping type names to tables of their methods, where
+/// each method is described by `{kind, params, returns, signature, doc}`
+/// and `params` is an array of `{name, type}` tables.
+pub fn describe<'lua>(lua: &'lua Lua, types: &[&'static TypeHelp]) -> LuaResult<LuaTable<'lua>> {
+    let result = lua.create_table_with_capacity(0, types.len())?;
+    for ty in types {
+        let methods = lua.create_table()?;
+        for method in ty.all_methods() {
+            let params = lua.create_table_with_capacity(method.params.len(), 0)?;
+            for param in method.params {
+                let entry = lua.create_table_with_capacity(0, 2)?;
+                entry.set("name", param.name)?;
+                entry.set("type", param.ty)?;
+                params.raw_push(entry)?;
+            }
+            let entry = lua.create_table_with_capacity(0, 5)?;
+            entry.set("kind", method.kind.name())?;
+            entry.set("params", params)?;
+            entry.set("returns", method.returns)?;
+            entry.set("signature", method.signature)?;
+            entry.set("doc", method.doc)?;
+            methods.set(method.name, entry)?;
+        }
+        result.set(ty.name, methods)?;
+    }
+    Ok(result)
+}
+
+/// Returns the description [`describe`] returns to scripts as JSON, so build
+/// steps can generate stub files for editors.
+#[cfg(feature = "describe")]
+pub fn api_description() -> serde_json::Value {
+    use serde_json::{json, Map, Value};
+
+    let mut result = Map::new();
+    for ty in crate::API_HELP {
+        let mut methods = Map::new();
+        for method in ty.all_methods() {
+            let params: Vec<Value> = method
+                .params
+                .iter()
+                .map(|it| json!({ "name": it.name, "type": it.ty }))
+                .collect();
+            methods.insert(
+                method.name.to_string(),
+                json!({
+                    "kind": method.kind.name(),
+                    "params": params,
+                    "returns": method.returns,
+                    "signature": method.signature,
+                    "doc": method.doc,
+                }),
+            );
+        }
+        result.insert(ty.name.to_string(), Value::Object(methods));
+    }
+    Value::Object(result)
+}
@@ -0,0 +1,128 @@
+//! Collection of input (clickable) areas, exposed to Lua as `InputRegion`.
+//!
+//! Scripts call `InputRegion.begin(canvas)` while drawing a frame, add
+//! shapes in local coordinates with `InputRegion.add(shape)` and end with
+//! `InputRegion.finish()`. Shapes are mapped to device pixels through the
+//! total matrix of the canvas at the time they're added, and the host reads
+//! the result with [`take_input_region`] to pass it to the compositor.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::{region::RegionOp, Matrix, Path, Region};
+
+use crate::{LikeShape, LuaCanvas, LuaRegion};
+
+/// Registry key of the [`InputRegionState`] userdata.
+pub(crate) const INPUT_REGION_KEY: &str = "mlua_skia.input_region";
+/// Registry key of the canvas shapes are currently added through.
+pub(crate) const INPUT_REGION_CANVAS_KEY: &str = "mlua_skia.input_region.canvas";
+
+#[derive(Default)]
+struct InputRegionState {
+    /// Region being collected; `None` outside of `begin`/`finish`.
+    pending: Option<Region>,
+    /// Last finished region not yet taken by the host.
+    finished: Option<Region>,
+}
+
+impl LuaUserData for InputRegionState {}
+
+fn state(lua: &Lua) -> LuaResult<LuaAnyUserData> {
+    if let Some(state) = lua.named_registry_value::<Option<LuaAnyUserData>>(INPUT_REGION_KEY)? {
+        return Ok(state);
+    }
+    let state = lua.create_userdata(InputRegionState::default())?;
+    lua.set_named_registry_value(INPUT_REGION_KEY, state.clone())?;
+    Ok(state)
+}
+
+/// Returns device space region covered by `shape` drawn with `matrix`.
+fn device_region(shape: &LikeShape, matrix: &Matrix) -> Region {
+    if let LikeShape::Rect(rect) = shape {
+        if matrix.rect_stays_rect() {
+            return Region::from_rect(matrix.map_rect(rect).0.round_out());
+        }
+    }
+
+    let mut path = match shape {
+        LikeShape::Rect(it) => Path::rect(it, None),
+        LikeShape::RRect(it) => Path::rrect(it, None),
+        LikeShape::Path(it) => it.clone(),
+        LikeShape::Circle(center, r) => Path::circle(*center, *r, None),
+    };
+    path.transform(matrix);
+
+    let mut result = Region::new();
+    // inverse fills are limited to the clip, so they can't cover more than
+    // the bounds of their outline here
+    let clip = Region::from_rect(path.bounds().round_out());
+    result.set_path(&path, &clip);
+    result
+}
+
+/// Returns the region of the last `InputRegion.finish()` call in device
+/// pixels, or `None` if no collection was finished since the last call.
+///
+/// Should be called by the host after every frame, as it also discards
+/// collections scripts didn't finish along with the canvas they reference.
+pub fn take_input_region(lua: &Lua) -> Option<Region> {
+    let _ = lua.set_named_registry_value(INPUT_REGION_CANVAS_KEY, LuaNil);
+    let state = state(lua).ok()?;
+    let mut state = state.borrow_mut::<InputRegionState>().ok()?;
+    state.pending = None;
+    state.finished.take()
+}
+
+pub struct InputRegion;
+
+#[lua_methods(lua_name: InputRegion)]
+impl InputRegion {
+    /// Starts collecting input region for shapes drawn on `canvas`,
+    /// discarding any unfinished collection.
+    pub fn begin<'lua>(lua: &'lua Lua, canvas: LuaAnyUserData<'lua>) {
+        if !canvas.is::<LuaCanvas<'static>>() {
+            return Err(LuaError::RuntimeError(
+                "InputRegion.begin expects a Canvas".to_string(),
+            ));
+        }
+        lua.set_named_registry_value(INPUT_REGION_CANVAS_KEY, canvas)?;
+        state(lua)?.borrow_mut::<InputRegionState>()?.pending = Some(Region::new());
+        Ok(())
+    }
+    /// Adds a rect, rrect, path or circle in local coordinates of the canvas
+    /// passed to `begin`, using its current total matrix.
+    pub fn add<'lua>(lua: &'lua Lua, shape: LikeShape) {
+        let canvas: Option<LuaAnyUserData> = lua.named_registry_value(INPUT_REGION_CANVAS_KEY)?;
+        let canvas = canvas.ok_or_else(|| {
+            LuaError::RuntimeError(
+                "InputRegion.add called without matching InputRegion.begin".to_string(),
+            )
+        })?;
+        let matrix = canvas
+            .borrow::<LuaCanvas<'static>>()?
+            .canvas()
+            .local_to_device_as_3x3();
+        let region = device_region(&shape, &matrix);
+
+        let state = state(lua)?;
+        let mut state = state.borrow_mut::<InputRegionState>()?;
+        if let Some(pending) = &mut state.pending {
+            pending.op_region(&region, RegionOp::Union);
+        }
+        Ok(())
+    }
+    /// Ends collection started by `begin`, making the region available to
+    /// the host. Returns a copy of the region for visualization.
+    pub fn finish<'lua>(lua: &'lua Lua) -> LuaRegion {
+        let state = state(lua)?;
+        let mut state = state.borrow_mut::<InputRegionState>()?;
+        let pending = state.pending.take().ok_or_else(|| {
+            LuaError::RuntimeError(
+                "InputRegion.finish called without matching InputRegion.begin".to_string(),
+            )
+        })?;
+        state.finished = Some(pending.clone());
+        lua.set_named_registry_value(INPUT_REGION_CANVAS_KEY, LuaNil)?;
+        Ok(LuaRegion::new(pending))
+    }
+}
@@ -0,0 +1,377 @@
+//! Splitting of rects into rows, columns and grids, exposed to Lua as
+//! `Layout`.
+//!
+//! Piece edges are rounded to whole units relative to the start of the
+//! split rect, so pieces of rects with whole sizes keep whole sizes and don't
+//! change by a pixel between frames. Only the last edge isn't rounded, so
+//! pieces and gaps always cover the split rect exactly.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::Rect;
+
+use crate::{
+    lua::{ArgumentContext, FromArgPack},
+    LuaRect,
+};
+
+/// Largest number of pieces a rect can be split into along one axis.
+pub const MAX_PIECES: usize = 4096;
+
+/// Returns `(start, end)` offsets of pieces splitting `length` in proportion
+/// to `weights`, separated by `gap`.
+///
+/// Weights must be non-negative with a positive sum. Gaps that don't fit
+/// into `length` are shrunk to fit, leaving empty pieces, so pieces always
+/// lie within `[0, length]`.
+pub fn split(length: f32, weights: &[f32], gap: f32) -> Vec<(f32, f32)> {
+    let count = weights.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    let length = length.max(0.0);
+    let gap = match count {
+        1 => gap,
+        _ => gap.min(length / (count - 1) as f32),
+    };
+    let available = (length - gap * (count - 1) as f32).max(0.0);
+    let total: f32 = weights.iter().sum();
+
+    let mut result = Vec::with_capacity(count);
+    let mut covered = 0.0;
+    let mut start = 0.0;
+    for (index, weight) in weights.iter().enumerate() {
+        covered += weight;
+        let end = if index == count - 1 {
+            available
+        } else {
+            (available * covered / total).round().min(available)
+        };
+        let offset = gap * index as f32;
+        result.push((start + offset, end + offset));
+        start = end;
+    }
+    result
+}
+
+/// Number of equal pieces or their relative sizes.
+pub enum SplitSpec {
+    Count(usize),
+    Weights(Vec<f32>),
+}
+
+impl SplitSpec {
+    fn weights(&self) -> Vec<f32> {
+        match self {
+            SplitSpec::Count(count) => vec![1.0; *count],
+            SplitSpec::Weights(it) => it.clone(),
+        }
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for SplitSpec {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(count) = args.pop_typed::<i64>() {
+            if count < 1 || count > MAX_PIECES as i64 {
+                return Err(args.bad_argument(LuaError::RuntimeError(format!(
+                    "piece count must be between 1 and {}; got {}",
+                    MAX_PIECES, count
+                ))));
+            }
+            return Ok(SplitSpec::Count(count as usize));
+        }
+
+        let value = args.pop();
+        let weights = match Vec::<f32>::from_lua(value.clone(), lua) {
+            Ok(it) => it,
+            Err(_) => {
+                let from = value.type_name();
+                args.revert(value);
+                return Err(args.bad_argument(LuaError::FromLuaConversionError {
+                    from,
+                    to: "SplitSpec",
+                    message: Some("expected a piece count or an array of weights".to_string()),
+                }));
+            }
+        };
+        if weights.len() > MAX_PIECES {
+            return Err(args.bad_argument(LuaError::RuntimeError(format!(
+                "at most {} weights are supported; got {}",
+                MAX_PIECES,
+                weights.len()
+            ))));
+        }
+        let valid = !weights.is_empty()
+            && weights.iter().all(|it| it.is_finite() && *it >= 0.0)
+            && weights.iter().sum::<f32>() > 0.0;
+        if !valid {
+            return Err(args.bad_argument(LuaError::RuntimeError(
+                "weights must be non-negative and finite, with a positive sum".to_string(),
+            )));
+        }
+        Ok(SplitSpec::Weights(weights))
+    }
+}
+
+/// Padding of each side of a rect.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Padding {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl<'lua> FromLua<'lua> for Padding {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::Integer(it) => Ok(Padding::uniform(it as f32)),
+            LuaValue::Number(it) => Ok(Padding::uniform(it as f32)),
+            LuaValue::Table(table) => Ok(Padding {
+                left: table.get::<_, Option<f32>>("left")?.unwrap_or_default(),
+                top: table.get::<_, Option<f32>>("top")?.unwrap_or_default(),
+                right: table.get::<_, Option<f32>>("right")?.unwrap_or_default(),
+                bottom: table.get::<_, Option<f32>>("bottom")?.unwrap_or_default(),
+            }),
+            other => Err(LuaError::FromLuaConversionError {
+                from: other.type_name(),
+                to: "Padding",
+                message: Some(
+                    "expected a number or a { left, top, right, bottom } table".to_string(),
+                ),
+            }),
+        }
+    }
+}
+crate::from_lua_argpack!(Padding);
+
+impl Padding {
+    pub fn uniform(value: f32) -> Self {
+        Padding {
+            left: value,
+            top: value,
+            right: value,
+            bottom: value,
+        }
+    }
+
+    /// Returns `rect` shrunk by the padding. Sides that would cross are
+    /// placed in the middle of their padded positions.
+    pub fn apply(&self, rect: Rect) -> Rect {
+        let (left, right) = inset_span(rect.left, rect.right, self.left, self.right);
+        let (top, bottom) = inset_span(rect.top, rect.bottom, self.top, self.bottom);
+        Rect::new(left, top, right, bottom)
+    }
+}
+
+fn inset_span(start: f32, end: f32, a: f32, b: f32) -> (f32, f32) {
+    let (start, end) = (start + a, end - b);
+    if start <= end {
+        (start, end)
+    } else {
+        let middle = (start + end) / 2.0;
+        (middle, middle)
+    }
+}
+
+/// Gap between pieces, given as a number for both axes or an `{x, y}`
+/// table with missing axes being 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Gap {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl<'lua> FromLua<'lua> for Gap {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let gap = match value {
+            LuaValue::Integer(it) => Gap {
+                x: it as f32,
+                y: it as f32,
+            },
+            LuaValue::Number(it) => Gap {
+                x: it as f32,
+                y: it as f32,
+            },
+            LuaValue::Table(table) => Gap {
+                x: table.get::<_, Option<f32>>("x")?.unwrap_or_default(),
+                y: table.get::<_, Option<f32>>("y")?.unwrap_or_default(),
+            },
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "Gap",
+                    message: Some("expected a number or an { x, y } table".to_string()),
+                })
+            }
+        };
+        for it in [gap.x, gap.y] {
+            if !it.is_finite() || it < 0.0 {
+                return Err(LuaError::RuntimeError(format!(
+                    "gap must be a non-negative, finite number; got {}",
+                    it
+                )));
+            }
+        }
+        Ok(gap)
+    }
+}
+crate::from_lua_argpack!(Gap);
+
+pub struct Layout;
+
+#[lua_methods(lua_name: Layout)]
+impl Layout {
+    /// Splits `rect` into rows from top to bottom, given as a count of equal
+    /// rows or an array of weights, separated by the `y` axis of `gap` (0 by
+    /// default).
+    pub fn rows(rect: LuaRect, rows: SplitSpec, gap: Option<Gap>) -> Vec<LuaRect> {
+        let rect: Rect = rect.into();
+        let gap = gap.unwrap_or_default();
+        Ok(split(rect.height(), &rows.weights(), gap.y)
+            .into_iter()
+            .map(|(top, bottom)| {
+                LuaRect::from(Rect::new(
+                    rect.left,
+                    rect.top + top,
+                    rect.right,
+                    rect.top + bottom,
+                ))
+            })
+            .collect())
+    }
+    /// Splits `rect` into columns from left to right, given as a count of
+    /// equal columns or an array of weights, separated by the `x` axis of
+    /// `gap` (0 by default).
+    pub fn columns(rect: LuaRect, columns: SplitSpec, gap: Option<Gap>) -> Vec<LuaRect> {
+        let rect: Rect = rect.into();
+        let gap = gap.unwrap_or_default();
+        Ok(split(rect.width(), &columns.weights(), gap.x)
+            .into_iter()
+            .map(|(left, right)| {
+                LuaRect::from(Rect::new(
+                    rect.left + left,
+                    rect.top,
+                    rect.left + right,
+                    rect.bottom,
+                ))
+            })
+            .collect())
+    }
+    /// Splits `rect` into a grid of cells, returned row by row. Columns and
+    /// rows are given as counts or arrays of weights, separated by `gap` (0
+    /// by default).
+    pub fn grid(
+        rect: LuaRect,
+        columns: SplitSpec,
+        rows: SplitSpec,
+        gap: Option<Gap>,
+    ) -> Vec<LuaRect> {
+        let rect: Rect = rect.into();
+        let gap = gap.unwrap_or_default();
+        let columns = split(rect.width(), &columns.weights(), gap.x);
+        let rows = split(rect.height(), &rows.weights(), gap.y);
+
+        let mut result = Vec::with_capacity(columns.len() * rows.len());
+        for (top, bottom) in &rows {
+            for (left, right) in &columns {
+                result.push(LuaRect::from(Rect::new(
+                    rect.left + left,
+                    rect.top + top,
+                    rect.left + right,
+                    rect.top + bottom,
+                )));
+            }
+        }
+        Ok(result)
+    }
+    /// Returns `rect` shrunk by `padding`, given as a number for all sides
+    /// or a `{left, top, right, bottom}` table with missing sides being 0.
+    pub fn inset(rect: LuaRect, padding: Padding) -> LuaRect {
+        Ok(LuaRect::from(padding.apply(rect.into())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts pieces are in order, separated by exactly `gap` and cover
+    /// `length` together with the gaps.
+    fn assert_covers(pieces: &[(f32, f32)], length: f32, gap: f32) {
+        assert_eq!(pieces[0].0, 0.0);
+        assert_eq!(pieces.last().unwrap().1, length);
+        for pair in pieces.windows(2) {
+            assert_eq!(pair[1].0 - pair[0].1, gap, "{:?}", pieces);
+        }
+        let covered: f32 = pieces.iter().map(|(start, end)| end - start).sum();
+        assert_eq!(covered + gap * (pieces.len() - 1) as f32, length);
+    }
+
+    #[test]
+    fn equal_pieces_distribute_remainders_deterministically() {
+        let pieces = split(100.0, &[1.0; 3], 0.0);
+        assert_eq!(pieces, vec![(0.0, 33.0), (33.0, 67.0), (67.0, 100.0)]);
+        assert_covers(&pieces, 100.0, 0.0);
+        assert_eq!(split(100.0, &[1.0; 3], 0.0), pieces);
+
+        let pieces = split(101.0, &[1.0; 4], 3.0);
+        assert_covers(&pieces, 101.0, 3.0);
+        for (start, end) in &pieces[..3] {
+            assert_eq!(start.fract(), 0.0);
+            assert_eq!(end.fract(), 0.0);
+        }
+    }
+
+    #[test]
+    fn weighted_pieces_keep_proportions() {
+        let pieces = split(200.0, &[1.0, 2.0, 1.0], 10.0);
+        assert_eq!(pieces, vec![(0.0, 45.0), (55.0, 145.0), (155.0, 200.0)]);
+        assert_covers(&pieces, 200.0, 10.0);
+
+        // zero weights get empty pieces
+        let pieces = split(90.0, &[1.0, 0.0, 2.0], 0.0);
+        assert_eq!(pieces, vec![(0.0, 30.0), (30.0, 30.0), (30.0, 90.0)]);
+    }
+
+    #[test]
+    fn oversized_gaps_shrink_to_fit() {
+        let pieces = split(10.0, &[1.0; 3], 8.0);
+        assert!(pieces.iter().all(|(start, end)| start == end));
+        for (start, end) in &pieces {
+            assert!((0.0..=10.0).contains(start), "{:?}", pieces);
+            assert!((0.0..=10.0).contains(end), "{:?}", pieces);
+        }
+        assert_covers(&pieces, 10.0, 5.0);
+        assert!(split(10.0, &[], 1.0).is_empty());
+
+        // pieces of empty and inverted lengths stay at the start
+        for length in [0.0, -5.0] {
+            let pieces = split(length, &[1.0; 2], 3.0);
+            assert!(pieces.iter().all(|it| *it == (0.0, 0.0)), "{:?}", pieces);
+        }
+    }
+
+    #[test]
+    fn padding_shrinks_each_side() {
+        let rect = Rect::new(0.0, 0.0, 100.0, 50.0);
+        assert_eq!(
+            Padding::uniform(5.0).apply(rect),
+            Rect::new(5.0, 5.0, 95.0, 45.0)
+        );
+        let padding = Padding {
+            left: 10.0,
+            top: 0.0,
+            right: 20.0,
+            bottom: 5.0,
+        };
+        assert_eq!(padding.apply(rect), Rect::new(10.0, 0.0, 80.0, 45.0));
+        // crossing sides meet in the middle
+        let padding = Padding {
+            left: 80.0,
+            right: 40.0,
+            ..Default::default()
+        };
+        assert_eq!(padding.apply(rect), Rect::new(70.0, 0.0, 70.0, 50.0));
+    }
+}
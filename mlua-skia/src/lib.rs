@@ -1,10 +1,12 @@
 use std::{
     alloc::Layout,
+    cell::Cell,
     collections::HashMap,
     ffi::OsString,
     mem::{align_of, size_of},
     os::unix::ffi::{OsStrExt, OsStringExt},
     ptr::addr_of,
+    rc::Rc,
     str::FromStr,
     sync::Arc,
 };
@@ -15,30 +17,88 @@ use mlua_skia_macros::lua_methods;
 use skia_safe::{
     canvas::{self, SaveLayerFlags, SaveLayerRec},
     color_filter::color_filters,
-    font_style::{Slant, Weight, Width},
+    font::Edging as FontEdging,
     gradient_shader::interpolation::{ColorSpace as InColorSpace, HueMethod, InPremul},
     gradient_shader::Interpolation,
+    image_filter::MapDirection,
     image_filters::{self, CropRect},
-    paint::Style as PaintStyle,
+    paint::{Join as PaintJoin, Style as PaintStyle},
     path::Verb,
     path_effect::DashInfo,
     stroke_rec::InitStyle as StrokeRecInitStyle,
     typeface::FontTableTag,
     *,
 };
+use unicode_segmentation::UnicodeSegmentation;
 
+/// Animation helpers
+pub mod anim;
+/// Animated image decoding
+pub mod animated;
 /// Skia argument packs
 pub mod args;
+pub(crate) mod border;
+/// Preset shader caches
+pub mod cache;
+pub(crate) mod chart;
+/// Color matrix presets
+pub mod color_matrix;
+/// Damage tracking
+pub mod damage;
+pub(crate) mod dash;
+/// Draw command recording
+pub mod debug;
+/// Gradient shader descriptors
+pub mod descriptor;
 /// Skia enum wrappers
 pub mod enums;
 pub(crate) mod ext;
+/// Font fallback chains
+pub mod font_chain;
+/// Host driven frame clock
+pub mod frame;
+/// File access policy
+pub mod fs_policy;
+/// Runtime API documentation
+pub mod help;
+/// Clickable area collection
+pub mod input_region;
+/// Rect layout helpers
+pub mod layout;
 pub(crate) mod lua;
+pub(crate) mod marker;
+/// Drawing work counters
+pub mod profile;
+/// Construction metadata
+pub mod provenance;
+/// Host controlled render quality
+pub mod quality;
+/// Host provided rendering state
+pub mod session;
+/// Live handle statistics
+pub mod stats;
+/// SVG document rendering
+#[cfg(feature = "svg")]
+pub mod svg;
+/// Context teardown
+pub mod teardown;
+pub(crate) mod tessellate;
+/// Host provided theme
+pub mod theme;
+/// Tiled surfaces
+pub mod tiled;
 pub(crate) mod util;
 
 pub use crate::args::*;
+use crate::cache::ShaderCache;
+use crate::descriptor::{GradientGeometry, ShaderDescriptor};
 pub use crate::enums::*;
 use crate::ext::skia::*;
 use crate::lua::*;
+use crate::provenance::{FilterProvenance, Param, WithProvenance};
+use crate::quality::Quality;
+pub use crate::teardown::teardown;
+use crate::util::PathOrData;
 
 pub trait StructToTable<'lua> {
     fn to_table(&self, lua: &'lua LuaContext) -> LuaResult<LuaTable<'lua>>;
@@ -74,7 +134,26 @@ struct_to_table! { FontMetrics: {
     "cap_height": |metrics, _| metrics.cap_height,
 }}
 
-wrap_skia_handle!(Shader);
+wrap_skia_handle!(Shader, meta: ShaderDescriptor);
+
+impl LuaShader {
+    /// Returns the shader with `matrix` applied after its local matrix,
+    /// keeping its descriptor.
+    fn with_local_matrix(&self, matrix: &Matrix) -> LuaShader {
+        let result = LuaShader::new(self.0.with_local_matrix(matrix));
+        match self.meta() {
+            Some(meta) => result.with_meta(meta.with_local_matrix(matrix)),
+            None => result,
+        }
+    }
+}
+
+/// Creates the gradient described by `descriptor`, recording it.
+fn gradient_shader(descriptor: ShaderDescriptor) -> Option<LuaShader> {
+    descriptor
+        .build()
+        .map(|it| LuaShader::new(it).with_meta(descriptor))
+}
 
 #[lua_methods(lua_name: Shader)]
 impl LuaShader {
@@ -85,6 +164,65 @@ impl LuaShader {
     pub fn is_a_image(&self) -> bool {
         Ok(self.0.is_a_image())
     }
+
+    pub fn make_with_local_matrix(&self, matrix: LuaMatrix) -> LuaShader {
+        let matrix: Matrix = matrix.into();
+        Ok(self.with_local_matrix(&matrix))
+    }
+    /// Returns a table of parameters this shader was constructed with, which
+    /// `Shader.fromDescriptor` rebuilds it from.
+    ///
+    /// Only gradients created by `GradientShader` constructors can be
+    /// described; `nil` is returned for other shaders and gradients in color
+    /// spaces other than sRGB and linear sRGB.
+    pub fn describe<'lua>(&self, lua: &'lua LuaContext) -> Option<LuaTable<'lua>> {
+        match self.meta() {
+            Some(meta) => meta.to_table(lua),
+            None => Ok(None),
+        }
+    }
+    /// Creates a gradient from a table returned by `Shader:describe()`.
+    pub fn from_descriptor(descriptor: ShaderDescriptor) -> LuaShader {
+        Ok(gradient_shader(descriptor)
+            .or_failed("Shader.fromDescriptor", || "invalid gradient parameters")?)
+    }
+
+    /// Returns a shader of alternating `width1` wide `color1` and `width2`
+    /// wide `color2` stripes, rotated clockwise by `angle` degrees (45 by
+    /// default; 0 draws vertical stripes). The first stripe starts at the
+    /// origin.
+    pub fn stripes(
+        width1: f32,
+        color1: LuaColor,
+        width2: f32,
+        color2: LuaColor,
+        angle: Option<f32>,
+    ) -> LuaShader {
+        let valid = |it: f32| it.is_finite() && it > 0.0;
+        if !valid(width1) || !valid(width2) {
+            return Err(LuaError::RuntimeError(format!(
+                "Shader.stripes failed: stripe widths must be positive; got {} and {}",
+                width1, width2
+            )));
+        }
+        let shader = stripe_shader(width1, color1.into(), width2, color2.into())
+            .or_failed("Shader.stripes", || "unable to create stripe image")?;
+        let rotation = Matrix::rotate_deg(angle.unwrap_or(45.0));
+        Ok(LuaShader::new(shader.with_local_matrix(&rotation)))
+    }
+    /// Returns a checkerboard shader with `cellSize` squares, starting with
+    /// a `colorA` cell at the origin.
+    pub fn checker(cell_size: f32, color_a: LuaColor, color_b: LuaColor) -> LuaShader {
+        if !cell_size.is_finite() || cell_size <= 0.0 {
+            return Err(LuaError::RuntimeError(format!(
+                "Shader.checker failed: cell size must be positive; got {}",
+                cell_size
+            )));
+        }
+        Ok(checker_shader(cell_size, color_a.into(), color_b.into())
+            .map(LuaShader::new)
+            .or_failed("Shader.checker", || "unable to create checkerboard image")?)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -123,6 +261,14 @@ impl<'lua> FromLua<'lua> for LuaInterpolation {
                 )?;
                 return Ok(LuaInterpolation(Interpolation::from(flags)));
             }
+            // short form naming only the color space, as in CSS
+            LuaValue::String(name) => {
+                let color_space = LuaInColorSpace::try_from(name)?;
+                return Ok(LuaInterpolation(Interpolation {
+                    color_space: color_space.unwrap(),
+                    ..LuaInterpolation::default().0
+                }));
+            }
             LuaValue::Table(table) => table,
             other => {
                 return Err(LuaError::FromLuaConversionError {
@@ -151,17 +297,91 @@ impl<'lua> FromLua<'lua> for LuaInterpolation {
 }
 from_lua_argpack!(LuaInterpolation);
 
-pub struct ColorStops {
+/// Gradient color stops, sorted by position.
+#[derive(Clone, Default)]
+pub struct LuaColorStops {
     positions: Vec<f32>,
     colors: Vec<Color4f>,
 }
 
+impl LuaColorStops {
+    fn sort(&mut self) {
+        let mut stops: Vec<(f32, Color4f)> = self
+            .positions
+            .drain(..)
+            .zip(self.colors.drain(..))
+            .collect();
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        (self.positions, self.colors) = stops.into_iter().unzip();
+    }
+
+    /// Inserts a stop after existing stops at the same position and returns
+    /// its index.
+    fn insert(&mut self, position: f32, color: Color4f) -> usize {
+        let index = self.positions.partition_point(|it| *it <= position);
+        self.positions.insert(index, position);
+        self.colors.insert(index, color);
+        index
+    }
+
+    /// Converts 1-based Lua `index` into a stop index.
+    fn check_index(&self, index: usize) -> LuaResult<usize> {
+        if index == 0 || index > self.positions.len() {
+            return Err(LuaError::RuntimeError(format!(
+                "color stop index {} out of range 1..={}",
+                index,
+                self.positions.len()
+            )));
+        }
+        Ok(index - 1)
+    }
+
+    /// Returns color at `t` the way Skia gradients interpolate it with
+    /// default settings: linearly between unpremultiplied stop colors,
+    /// clamped to the first and last stop.
+    pub fn sample_at(&self, t: f32) -> Color4f {
+        let (first, last) = match (self.colors.first(), self.colors.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Color4f::new(0.0, 0.0, 0.0, 0.0),
+        };
+        let t = t.clamp(0.0, 1.0);
+
+        let next = self.positions.partition_point(|it| *it <= t);
+        if next == 0 {
+            return first;
+        }
+        if next == self.positions.len() {
+            return last;
+        }
+        let (from, to) = (self.positions[next - 1], self.positions[next]);
+        let (a, b) = (self.colors[next - 1], self.colors[next]);
+        let k = if to > from {
+            (t - from) / (to - from)
+        } else {
+            1.0
+        };
+        Color4f::new(
+            a.r + (b.r - a.r) * k,
+            a.g + (b.g - a.g) * k,
+            a.b + (b.b - a.b) * k,
+            a.a + (b.a - a.a) * k,
+        )
+    }
+}
+
 /// ## Supported formats
+/// - ColorStops userdata
 /// - {pos: color, pos: color, ...}
 /// - {color...}, nil - uniformly spaced
 /// - {color...}, {pos...}
-impl<'lua> FromArgPack<'lua> for ColorStops {
+impl<'lua> FromArgPack<'lua> for LuaColorStops {
     fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let Some(ud) = args.pop_typed::<LuaAnyUserData>() {
+            if let Ok(stops) = ud.borrow::<LuaColorStops>() {
+                return Ok(stops.clone());
+            }
+            args.revert(ud);
+        }
         args.assert_next_type(&[LuaType::Table])?;
 
         let first: LuaTable<'lua> =
@@ -199,7 +419,10 @@ impl<'lua> FromArgPack<'lua> for ColorStops {
             }
 
             let (positions, colors) = stops.into_iter().unzip();
-            return Ok(ColorStops { positions, colors });
+            // pairs aren't traversed in key order
+            let mut result = LuaColorStops { positions, colors };
+            result.sort();
+            return Ok(result);
         }
 
         // TODO: check colors in color stops didn't error
@@ -216,7 +439,7 @@ impl<'lua> FromArgPack<'lua> for ColorStops {
             None => {
                 let step = 1.0 / (colors.len() as f32 - 1.0);
                 let positions = (0..colors.len()).map(|it| it as f32 * step).collect();
-                return Ok(ColorStops { positions, colors });
+                return Ok(LuaColorStops { positions, colors });
             }
         };
 
@@ -235,15 +458,89 @@ impl<'lua> FromArgPack<'lua> for ColorStops {
         };
 
         if let Some(positions) = positions {
-            Ok(ColorStops { positions, colors })
+            Ok(LuaColorStops { positions, colors })
         } else {
             let step = 1.0 / (colors.len() as f32 - 1.0);
             let positions = (0..colors.len()).map(|it| it as f32 * step).collect();
-            Ok(ColorStops { positions, colors })
+            Ok(LuaColorStops { positions, colors })
         }
     }
 }
 
+#[lua_methods(lua_name: ColorStops)]
+impl LuaColorStops {
+    /// Creates color stops from any of the supported table forms.
+    pub fn new(stops: LuaColorStops) -> LuaColorStops {
+        Ok(stops)
+    }
+    pub fn count(&self) -> usize {
+        Ok(self.positions.len())
+    }
+    /// Adds a stop and returns its index.
+    pub fn add(&mut self, position: f32, color: LuaColor) -> usize {
+        Ok(self.insert(position, color.into()) + 1)
+    }
+    pub fn remove(&mut self, index: usize) {
+        let index = self.check_index(index)?;
+        self.positions.remove(index);
+        self.colors.remove(index);
+        Ok(())
+    }
+    /// Changes position and/or color of stop at `index` and returns its new
+    /// index.
+    pub fn set(
+        &mut self,
+        index: usize,
+        position: Option<f32>,
+        color: LuaFallible<LuaColor>,
+    ) -> usize {
+        let index = self.check_index(index)?;
+        let position = position.unwrap_or(self.positions[index]);
+        let color: Color4f = color.map(LuaColor::into).unwrap_or(self.colors[index]);
+        self.positions.remove(index);
+        self.colors.remove(index);
+        Ok(self.insert(position, color) + 1)
+    }
+    /// Returns interpolated color at `t`, matching rendered gradients.
+    pub fn sample(&self, t: f32) -> LuaColor {
+        Ok(LuaColor::from(self.sample_at(t)))
+    }
+    /// Returns colors and positions arrays accepted by gradient constructors.
+    pub fn to_shader_args(&self) -> (Vec<LuaColor>, Vec<f32>) {
+        Ok((
+            self.colors.iter().copied().map(LuaColor::from).collect(),
+            self.positions.clone(),
+        ))
+    }
+}
+
+/// Segment of `GradientShader.makePie`, given as a `{value, color}` table.
+pub struct PieSegment {
+    pub value: f32,
+    pub color: Color4f,
+}
+
+impl<'lua> FromLua<'lua> for PieSegment {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(it) => it,
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "PieSegment",
+                    message: Some("expected a { value, color } table".to_string()),
+                })
+            }
+        };
+        let color: LuaColor = LuaColor::from_lua(table.get("color")?, lua)?;
+        Ok(PieSegment {
+            value: table.get("value")?,
+            color: color.into(),
+        })
+    }
+}
+from_lua_argpack!(PieSegment);
+
 pub struct GradientShader;
 
 #[lua_methods]
@@ -252,140 +549,562 @@ impl GradientShader {
     pub fn make_linear(
         from: LuaPoint,
         to: LuaPoint,
-        stops: ColorStops,
+        stops: LuaColorStops,
         color_space: LuaFallible<LuaColorSpace>,
         tile_mode: LuaFallible<LuaTileMode>,
         interpolation: LuaFallible<LuaInterpolation>,
         local: LuaFallible<LuaMatrix>,
-    ) -> Option<LuaShader> {
-        let tile_mode = tile_mode.unwrap_or_t(TileMode::Clamp);
-        let interpolation = interpolation.unwrap_or_default().0;
-        let local: Option<Matrix> = local.map(LuaMatrix::into);
+    ) -> LuaShader {
+        let color_count = stops.colors.len();
+        let descriptor = ShaderDescriptor {
+            geometry: GradientGeometry::Linear {
+                from: from.into(),
+                to: to.into(),
+            },
+            positions: stops.positions,
+            colors: stops.colors,
+            color_space: color_space.map(LuaColorSpace::unwrap),
+            tile_mode: tile_mode.unwrap_or_t(TileMode::Clamp),
+            interpolation: interpolation.unwrap_or_default().0,
+            local: local.map(LuaMatrix::into),
+        };
 
-        Ok(Shader::linear_gradient_with_interpolation(
-            (from, to),
-            (
-                stops.colors.as_slice(),
-                color_space.map(LuaColorSpace::unwrap),
-            ),
-            Some(stops.positions.as_slice()),
-            tile_mode,
-            interpolation,
-            local.as_ref(),
+        Ok(
+            gradient_shader(descriptor).or_failed("GradientShader.makeLinear", || {
+                format!(
+                    "invalid gradient from {:?} to {:?} with {} stops",
+                    Point::from(from),
+                    Point::from(to),
+                    color_count
+                )
+            })?,
         )
-        .map(LuaShader))
     }
     pub fn make_radial(
         center: LuaPoint,
         radius: f32,
-        stops: ColorStops,
+        stops: LuaColorStops,
         color_space: LuaFallible<LuaColorSpace>,
         tile_mode: LuaFallible<LuaTileMode>,
         interpolation: LuaFallible<LuaInterpolation>,
         local: LuaFallible<LuaMatrix>,
-    ) -> Option<LuaShader> {
-        let tile_mode = tile_mode.unwrap_or_t(TileMode::Clamp);
-        let interpolation = interpolation.unwrap_or_default().0;
-        let local: Option<Matrix> = local.map(LuaMatrix::into);
+    ) -> LuaShader {
+        let color_count = stops.colors.len();
+        let descriptor = ShaderDescriptor {
+            geometry: GradientGeometry::Radial {
+                center: center.into(),
+                radius,
+            },
+            positions: stops.positions,
+            colors: stops.colors,
+            color_space: color_space.map(LuaColorSpace::unwrap),
+            tile_mode: tile_mode.unwrap_or_t(TileMode::Clamp),
+            interpolation: interpolation.unwrap_or_default().0,
+            local: local.map(LuaMatrix::into),
+        };
 
-        Ok(Shader::radial_gradient_with_interpolation(
-            (center, radius),
-            (
-                stops.colors.as_slice(),
-                color_space.map(LuaColorSpace::unwrap),
-            ),
-            Some(stops.positions.as_slice()),
-            tile_mode,
-            interpolation,
-            local.as_ref(),
+        Ok(
+            gradient_shader(descriptor).or_failed("GradientShader.makeRadial", || {
+                format!(
+                    "invalid gradient with radius {} and {} stops",
+                    radius, color_count
+                )
+            })?,
         )
-        .map(LuaShader))
     }
     pub fn make_sweep(
         center: LuaPoint,
-        stops: ColorStops,
+        stops: LuaColorStops,
         color_space: LuaFallible<LuaColorSpace>,
         tile_mode: LuaFallible<LuaTileMode>,
         angles: LuaFallible<(f32, f32)>,
         interpolation: LuaFallible<LuaInterpolation>,
         local: LuaFallible<LuaMatrix>,
-    ) -> Option<LuaShader> {
-        let tile_mode = tile_mode.unwrap_or_t(TileMode::Clamp);
-        let interpolation = interpolation.unwrap_or_default().0;
-        let local: Option<Matrix> = local.map(LuaMatrix::into);
+    ) -> LuaShader {
+        let (start_angle, end_angle) = angles.into_inner().unwrap_or((0.0, 360.0));
+        let color_count = stops.colors.len();
+        let descriptor = ShaderDescriptor {
+            geometry: GradientGeometry::Sweep {
+                center: center.into(),
+                start_angle,
+                end_angle,
+            },
+            positions: stops.positions,
+            colors: stops.colors,
+            color_space: color_space.map(LuaColorSpace::unwrap),
+            tile_mode: tile_mode.unwrap_or_t(TileMode::Clamp),
+            interpolation: interpolation.unwrap_or_default().0,
+            local: local.map(LuaMatrix::into),
+        };
 
-        Ok(Shader::sweep_gradient_with_interpolation(
-            center,
-            (
-                stops.colors.as_slice(),
-                color_space.map(LuaColorSpace::unwrap),
-            ),
-            Some(stops.positions.as_slice()),
-            tile_mode,
-            *angles,
-            interpolation,
-            local.as_ref(),
+        Ok(
+            gradient_shader(descriptor).or_failed("GradientShader.makeSweep", || {
+                format!(
+                    "invalid gradient with angles {:?} and {} stops",
+                    (start_angle, end_angle),
+                    color_count
+                )
+            })?,
         )
-        .map(LuaShader))
+    }
+    /// Creates a sweep gradient filling `segments` of a pie chart, given as
+    /// an array of `{value, color}` tables, with segment angles proportional
+    /// to values. Segments go clockwise from `startAngle` (-90, the top, by
+    /// default).
+    ///
+    /// Colors change at segment boundaries without blending, unless `radius`
+    /// of the drawn pie is given, in which case they're blended over a pixel
+    /// at its edge for anti-aliased boundaries. The boundary where the last
+    /// segment meets the first one is never blended.
+    ///
+    /// Returns the shader and an array of boundary angles in degrees, where
+    /// segment `i` spans from `angles[i]` to `angles[i + 1]`.
+    pub fn make_pie(
+        center: LuaPoint,
+        segments: Vec<PieSegment>,
+        start_angle: Option<f32>,
+        radius: Option<f32>,
+    ) -> (LuaShader, Vec<f32>) {
+        let values: Vec<f32> = segments.iter().map(|it| it.value).collect();
+        let boundaries = chart::pie_boundaries(&values).ok_or_else(|| {
+            LuaError::RuntimeError(
+                "GradientShader.makePie: segment values must be non-negative and finite, with a positive sum"
+                    .to_string(),
+            )
+        })?;
+        let feather = match radius {
+            Some(r) if r.is_finite() && r > 0.0 => 1.0 / (std::f32::consts::TAU * r),
+            _ => 0.0,
+        };
+
+        let stops = chart::pie_stops(&boundaries, feather);
+        let positions: Vec<f32> = stops.iter().map(|(position, _)| *position).collect();
+        let colors: Vec<Color4f> = stops
+            .iter()
+            .map(|(_, index)| segments[*index].color)
+            .collect();
+
+        let start_angle = start_angle.unwrap_or(-90.0);
+        let center = Point::from(center);
+        let descriptor = ShaderDescriptor {
+            geometry: GradientGeometry::Sweep {
+                center,
+                start_angle: 0.0,
+                end_angle: 360.0,
+            },
+            positions,
+            colors,
+            color_space: None,
+            tile_mode: TileMode::Clamp,
+            interpolation: LuaInterpolation::default().0,
+            local: Some(Matrix::rotate_deg_pivot(start_angle, center)),
+        };
+        let shader = gradient_shader(descriptor).or_failed("GradientShader.makePie", || {
+            format!("invalid pie gradient with {} segments", segments.len())
+        })?;
+
+        let angles = boundaries
+            .iter()
+            .map(|it| start_angle + it * 360.0)
+            .collect();
+        Ok((shader, angles))
     }
     pub fn make_two_point_conical(
         start: LuaPoint,
         start_radius: f32,
         end: LuaPoint,
         end_radius: f32,
-        stops: ColorStops,
+        stops: LuaColorStops,
         color_space: LuaFallible<LuaColorSpace>,
         tile_mode: LuaFallible<LuaTileMode>,
         interpolation: LuaFallible<LuaInterpolation>,
         local: LuaFallible<LuaMatrix>,
-    ) -> Option<LuaShader> {
-        let tile_mode = tile_mode.unwrap_or_t(TileMode::Clamp);
-        let interpolation = interpolation.unwrap_or_default().0;
-        let local: Option<Matrix> = local.map(LuaMatrix::into);
-
-        Ok(Shader::two_point_conical_gradient_with_interpolation(
-            (start, start_radius),
-            (end, end_radius),
+    ) -> LuaShader {
+        let color_count = stops.colors.len();
+        let descriptor = ShaderDescriptor {
+            geometry: GradientGeometry::TwoPointConical {
+                start: start.into(),
+                start_radius,
+                end: end.into(),
+                end_radius,
+            },
+            positions: stops.positions,
+            colors: stops.colors,
+            color_space: color_space.map(LuaColorSpace::unwrap),
+            tile_mode: tile_mode.unwrap_or_t(TileMode::Clamp),
+            interpolation: interpolation.unwrap_or_default().0,
+            local: local.map(LuaMatrix::into),
+        };
+
+        Ok(
+            gradient_shader(descriptor).or_failed("GradientShader.makeTwoPointConical", || {
+                format!(
+                    "invalid gradient with radii {} and {} and {} stops",
+                    start_radius, end_radius, color_count
+                )
+            })?,
+        )
+    }
+    /// Positions a gradient created in normalized coordinates inside `rect`.
+    ///
+    /// Gradient points and radii in `0..1` span the rect, so a radial
+    /// gradient with center `(0.5, 0.5)` and radius `0.5` becomes an ellipse
+    /// touching all sides of a non-square rect.
+    pub fn in_rect(rect: LuaRect, shader: LuaShader) -> LuaShader {
+        let rect: Rect = rect.into();
+        if rect.is_empty() {
+            return Err(LuaError::RuntimeError(format!(
+                "GradientShader.inRect failed: empty rect {:?}",
+                rect
+            )));
+        }
+        let mut matrix = Matrix::translate((rect.left, rect.top));
+        matrix.pre_scale((rect.width(), rect.height()), None);
+        Ok(shader.with_local_matrix(&matrix))
+    }
+    /// Returns a dithered paint with a linear gradient from `from` to `to`,
+    /// suitable for large, subtle gradients that would otherwise band.
+    ///
+    /// With `opts.noise` set, low amplitude noise is blended over the
+    /// gradient to further break up banding.
+    pub fn smooth(
+        from: LuaPoint,
+        to: LuaPoint,
+        stops: LuaColorStops,
+        opts: LuaSmoothGradientOptions,
+    ) -> LuaPaint {
+        let mut shader = Shader::linear_gradient(
+            (from, to),
             (
                 stops.colors.as_slice(),
-                color_space.map(LuaColorSpace::unwrap),
+                opts.color_space.map(LuaColorSpace::unwrap),
             ),
             Some(stops.positions.as_slice()),
-            tile_mode,
-            interpolation,
-            local.as_ref(),
+            TileMode::Clamp,
+            None,
+            None,
         )
-        .map(LuaShader))
+        .or_failed("GradientShader.smooth", || {
+            format!(
+                "invalid gradient from {:?} to {:?} with {} stops",
+                Point::from(from),
+                Point::from(to),
+                stops.colors.len()
+            )
+        })?;
+
+        if opts.noise {
+            let noise = shaders::turbulence((0.8, 0.8), 2, opts.seed, None)
+                .or_failed("GradientShader.smooth", || {
+                    format!("unable to create noise shader with seed {}", opts.seed)
+                })?;
+            // map noise to mid gray +-2/255 which overlay blending turns into
+            // a barely visible brightness variation
+            let (multiply, add) = (Color::from_rgb(4, 4, 4), Color::from_rgb(126, 126, 126));
+            let amplitude = color_filters::lighting(multiply, add)
+                .or_failed("GradientShader.smooth", || {
+                    "unable to create noise color filter"
+                })?;
+            shader = shaders::blend(
+                BlendMode::Overlay,
+                shader,
+                noise.with_color_filter(amplitude),
+            );
+        }
+
+        let mut paint = Paint::default();
+        paint.set_shader(shader);
+        paint.set_dither(true);
+        Ok(LuaPaint::new(paint))
+    }
+}
+
+/// Options for `GradientShader.smooth`.
+#[derive(Default)]
+pub struct LuaSmoothGradientOptions {
+    noise: bool,
+    seed: f32,
+    color_space: Option<LuaColorSpace>,
+}
+
+impl<'lua> FromArgPack<'lua> for LuaSmoothGradientOptions {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match args.pop() {
+            LuaValue::Table(it) => it,
+            LuaNil => return Ok(LuaSmoothGradientOptions::default()),
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "SmoothGradientOptions",
+                    message: Some("expected a SmoothGradientOptions table or nil".to_string()),
+                });
+            }
+        };
+
+        Ok(LuaSmoothGradientOptions {
+            noise: table.try_get_or_default("noise", lua)?,
+            seed: table.try_get_or_default("seed", lua)?,
+            color_space: table.try_get("colorSpace", lua)?,
+        })
     }
 }
 
-wrap_skia_handle!(Image);
+wrap_skia_handle!(Image, pixels: |image| stats::image_pixels(image));
+
+/// Number of bytes read from image files to probe their header.
+const PROBE_HEADER_SIZE: u64 = 64 * 1024;
+
+/// Creates a codec for `source`, reading files only up to
+/// [`PROBE_HEADER_SIZE`] unless `full` is set.
+fn probe_codec(source: &PathOrData, full: bool) -> std::io::Result<Option<Codec<'static>>> {
+    let data = match source {
+        PathOrData::Path(path) if !full => {
+            use std::io::Read;
+            let mut header = Vec::new();
+            std::fs::File::open(path)?
+                .take(PROBE_HEADER_SIZE)
+                .read_to_end(&mut header)?;
+            header.into()
+        }
+        _ => source.read()?,
+    };
+    Ok(Codec::from_data(Data::new_copy(&data)))
+}
+
+/// Copies `image` pixels into a raster image described by `info`, letting
+/// Skia convert color type, alpha type and color space.
+fn convert_image(image: &Image, info: &ImageInfo) -> Result<Image, &'static str> {
+    if info.color_type() == ColorType::Unknown || info.alpha_type() == AlphaType::Unknown {
+        return Err("unknown color or alpha type");
+    }
+    let row_bytes = info.min_row_bytes();
+    let mut pixels = vec![0u8; info.compute_byte_size(row_bytes)];
+    if !image.read_pixels(
+        info,
+        &mut pixels,
+        row_bytes,
+        (0, 0),
+        image::CachingHint::Allow,
+    ) {
+        return Err("unsupported conversion");
+    }
+    images::raster_from_data(info, Data::new_copy(&pixels), row_bytes)
+        .ok_or("unable to create converted image")
+}
 
 #[lua_methods(lua_name: Image)]
 impl LuaImage {
-    pub fn load(path: String) -> LuaImage {
+    pub fn load<'lua>(lua: &'lua LuaContext, path: String) -> LuaImage {
+        let path = fs_policy::check_read(lua, "Image.load", &path)?;
         let handle: Data = Data::new_copy(
             &std::fs::read(path).map_err(|io_err| mlua::Error::RuntimeError(io_err.to_string()))?,
         );
         Image::from_encoded(handle)
-            .map(LuaImage)
+            .map(LuaImage::new)
             .ok_or(LuaError::RuntimeError(
                 "unsupported encoded image format".to_string(),
             ))
     }
+    /// Reads image properties from its header without decoding pixels.
+    ///
+    /// Files are only read up to the header, except for animated formats
+    /// whose frame count requires reading all frames.
+    pub fn probe<'lua>(lua: &'lua LuaContext, path_or_data: LuaString<'lua>) -> LuaTable<'lua> {
+        let source = PathOrData::new(path_or_data.as_bytes()).check_read(lua, "Image.probe")?;
+        let io_err =
+            |err: std::io::Error| LuaError::RuntimeError(format!("Image.probe failed: {}", err));
+        let codec = match probe_codec(&source, false).map_err(io_err)? {
+            Some(it) => Some(it),
+            // headers can extend past the probed bytes, such as JPEG
+            // metadata segments preceding the frame header
+            None if matches!(source, PathOrData::Path(_)) => {
+                probe_codec(&source, true).map_err(io_err)?
+            }
+            None => None,
+        };
+        let mut codec = codec.or_failed("Image.probe", || "unsupported or corrupt image")?;
+        let format = codec.encoded_format();
+        if let (PathOrData::Path(_), EncodedImageFormat::GIF | EncodedImageFormat::WEBP) =
+            (&source, format)
+        {
+            if let Some(full) = probe_codec(&source, true).map_err(io_err)? {
+                codec = full;
+            }
+        }
+
+        let info = codec.info();
+        let result = lua.create_table()?;
+        result.set("width", info.width())?;
+        result.set("height", info.height())?;
+        result.set("colorType", LuaColorType(info.color_type()))?;
+        result.set("alphaType", LuaAlphaType(info.alpha_type()))?;
+        result.set("format", format!("{:?}", format).to_lowercase())?;
+        result.set("frameCount", codec.get_frame_count().max(1))?;
+        Ok(result)
+    }
+    /// Loads an image no larger than `max_dimension` on its longest side.
+    ///
+    /// Formats that support it (JPEG) are decoded at a reduced size directly,
+    /// others are decoded fully and then resized.
+    pub fn load_scaled<'lua>(lua: &'lua LuaContext, path: String, max_dimension: i32) -> LuaImage {
+        if max_dimension <= 0 {
+            return Err(LuaError::RuntimeError(format!(
+                "Image.loadScaled failed: maxDimension must be positive; got {}",
+                max_dimension
+            )));
+        }
+        let resolved = fs_policy::check_read(lua, "Image.loadScaled", &path)?;
+        let data = std::fs::read(resolved).map_err(|err| {
+            LuaError::RuntimeError(format!(
+                "Image.loadScaled failed: unable to read '{}': {}",
+                path, err
+            ))
+        })?;
+        let unsupported = || format!("unsupported image file '{}'", path);
+        let mut codec =
+            Codec::from_data(Data::new_copy(&data)).or_failed("Image.loadScaled", unsupported)?;
+
+        let size = codec.dimensions();
+        let longest = size.width.max(size.height);
+        let target = if longest <= max_dimension {
+            size
+        } else {
+            let scale = max_dimension as f32 / longest as f32;
+            ISize::new(
+                ((size.width as f32 * scale).round() as i32).clamp(1, max_dimension),
+                ((size.height as f32 * scale).round() as i32).clamp(1, max_dimension),
+            )
+        };
+        // codecs without scaled decoding support return the full size
+        let native = codec.get_scaled_dimensions(target.width as f32 / size.width as f32);
+        let decode_size = if native.width >= target.width && native.height >= target.height {
+            native
+        } else {
+            size
+        };
+
+        let info = codec.info().with_dimensions(decode_size);
+        let image = codec
+            .get_image(info, None::<&codec::Options>)
+            .ok()
+            .or_failed("Image.loadScaled", unsupported)?;
+        if image.dimensions() == target {
+            return Ok(LuaImage::new(image));
+        }
+
+        let mut surface = surfaces::raster_n32_premul(target)
+            .or_failed("Image.loadScaled", || "unable to allocate resized image")?;
+        surface.canvas().draw_image_rect_with_sampling_options(
+            &image,
+            None,
+            Rect::from_isize(target),
+            SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear),
+            &Paint::default(),
+        );
+        Ok(LuaImage::new(surface.image_snapshot()))
+    }
     pub fn width(&self) -> usize {
         Ok(self.0.width() as usize)
     }
     pub fn height(&self) -> usize {
         Ok(self.0.height() as usize)
     }
+    pub fn alpha_type(&self) -> LuaAlphaType {
+        Ok(LuaAlphaType(self.0.alpha_type()))
+    }
+    /// Returns whether the image has mipmap levels, which sampling with a
+    /// mipmap mode other than `"none"` requires to have an effect.
+    pub fn has_mipmaps(&self) -> bool {
+        Ok(self.0.has_mipmaps())
+    }
+    /// Returns a copy of the image with generated mipmaps, for drawing it
+    /// downscaled with `"linear"` or `"nearest"` mipmap sampling. Returns the
+    /// image itself if it already has mipmaps.
+    pub fn with_default_mipmaps(&self) -> LuaImage {
+        Ok(self
+            .0
+            .with_default_mipmaps()
+            .map(LuaImage::new)
+            .or_failed("Image:withDefaultMipmaps", || "unable to generate mipmaps")?)
+    }
+    pub fn color_space(&self) -> Option<LuaColorSpace> {
+        Ok(self.0.image_info().color_space().map(LuaColorSpace::new))
+    }
+    /// Returns a copy of the image with pixels converted into `color_space`,
+    /// or nil and a reason if conversion isn't supported.
+    pub fn make_color_space(
+        &self,
+        color_space: LuaColorSpace,
+    ) -> (Option<LuaImage>, Option<String>) {
+        let info = self.0.image_info().with_color_space(color_space.0);
+        Ok(match convert_image(&self.0, &info) {
+            Ok(it) => (Some(LuaImage::new(it)), None),
+            Err(reason) => (None, Some(reason.to_string())),
+        })
+    }
+    /// Returns a copy of the image with pixels converted to `color_type`
+    /// and `alpha_type`; missing arguments keep the current value.
+    ///
+    /// Unlike reinterpreting, stored values change, e.g. converting from
+    /// unpremul to premul multiplies color channels by alpha.
+    pub fn make_color_type_and_alpha_type(
+        &self,
+        color_type: Option<LuaColorType>,
+        alpha_type: Option<LuaAlphaType>,
+    ) -> (Option<LuaImage>, Option<String>) {
+        let current = self.0.image_info();
+        let info = current
+            .with_color_type(color_type.unwrap_or_t(current.color_type()))
+            .with_alpha_type(alpha_type.unwrap_or_t(current.alpha_type()));
+        Ok(match convert_image(&self.0, &info) {
+            Ok(it) => (Some(LuaImage::new(it)), None),
+            Err(reason) => (None, Some(reason.to_string())),
+        })
+    }
+    /// Returns a shader drawing the image, tiled with `tile_x` and `tile_y`
+    /// modes (`tile_x` for both if only it is given).
+    ///
+    /// If `src_rect` is given, only that area of the image is drawn and it
+    /// becomes the tile, so tile modes repeat, mirror or clamp the area
+    /// instead of the whole image. The area is placed at the origin of
+    /// shader space before `local_matrix` is applied.
     pub fn new_shader(
         &self,
         tile_x: LuaFallible<LuaTileMode>,
         tile_y: LuaFallible<LuaTileMode>,
         sampling: LuaFallible<LuaSamplingOptions>,
         local_matrix: LuaFallible<LuaMatrix>,
-    ) -> Option<LuaShader> {
+        src_rect: LuaFallible<LuaRect>,
+    ) -> LuaShader {
+        let image = match src_rect.map(IRect::from) {
+            Some(subset) if subset != self.0.bounds() => {
+                let bounds = self.0.bounds();
+                let area = IRect::intersect(subset, bounds)
+                    .filter(|it| !it.is_empty())
+                    .or_failed("Image:newShader", || {
+                        format!(
+                            "srcRect {:?} doesn't overlap {}x{} image",
+                            subset,
+                            bounds.width(),
+                            bounds.height()
+                        )
+                    })?;
+                let subset_image = self
+                    .0
+                    .make_subset(None, area)
+                    .or_failed("Image:newShader", || {
+                        format!("unable to read {:?} area of the image", area)
+                    })?;
+                // keep mipmaps so mipmap sampling still applies to the subset
+                if self.0.has_mipmaps() && !subset_image.has_mipmaps() {
+                    subset_image.with_default_mipmaps().unwrap_or(subset_image)
+                } else {
+                    subset_image
+                }
+            }
+            _ => self.0.clone(),
+        };
+
         let tile_modes = if tile_x.is_none() && tile_y.is_none() {
             None
         } else {
@@ -394,15 +1113,13 @@ impl LuaImage {
             Some((n_tile_x, n_tile_y))
         };
         let local_matrix = local_matrix.map(LuaMatrix::into);
+        let sampling: SamplingOptions = sampling.unwrap_or_default().into();
+        warn_missing_mipmaps("Image:newShader", &image, &sampling);
 
-        Ok(self
-            .0
-            .to_shader(
-                tile_modes,
-                sampling.unwrap_or_default(),
-                local_matrix.as_ref(),
-            )
-            .map(LuaShader))
+        image
+            .to_shader(tile_modes, sampling, local_matrix.as_ref())
+            .map(LuaShader::new)
+            .or_failed("Image:newShader", || "unable to create image shader")
     }
 }
 
@@ -410,17 +1127,17 @@ wrap_skia_handle!(ColorSpace);
 
 impl Default for LuaColorSpace {
     fn default() -> Self {
-        LuaColorSpace(ColorSpace::new_srgb())
+        LuaColorSpace::new(ColorSpace::new_srgb())
     }
 }
 
 #[lua_methods(lua_name: ColorSpace)]
 impl LuaColorSpace {
     pub fn make_srgb() -> LuaColorSpace {
-        Ok(LuaColorSpace(ColorSpace::new_srgb()))
+        Ok(LuaColorSpace::new(ColorSpace::new_srgb()))
     }
     pub fn make_srgb_linear() -> LuaColorSpace {
-        Ok(LuaColorSpace(ColorSpace::new_srgb_linear()))
+        Ok(LuaColorSpace::new(ColorSpace::new_srgb_linear()))
     }
     pub fn is_srgb(&self) -> bool {
         Ok(self.0.is_srgb())
@@ -429,13 +1146,13 @@ impl LuaColorSpace {
         Ok(self.0.to_xyzd50_hash().0)
     }
     pub fn make_linear_gamma(&self) -> LuaColorSpace {
-        Ok(LuaColorSpace(self.0.with_linear_gamma()))
+        Ok(LuaColorSpace::new(self.0.with_linear_gamma()))
     }
     pub fn make_srgb_gamma(&self) -> LuaColorSpace {
-        Ok(LuaColorSpace(self.0.with_srgb_gamma()))
+        Ok(LuaColorSpace::new(self.0.with_srgb_gamma()))
     }
     pub fn make_color_spin(&self) -> LuaColorSpace {
-        Ok(LuaColorSpace(self.0.with_color_spin()))
+        Ok(LuaColorSpace::new(self.0.with_color_spin()))
     }
 }
 
@@ -444,6 +1161,8 @@ wrap_skia_handle!(Picture);
 #[lua_methods(lua_name: Picture)]
 impl LuaPicture {
     pub fn playback(&self, canvas: &LuaCanvas) {
+        canvas.check_valid()?;
+        canvas.damage(Some(*self.0.cull_rect()), None);
         self.0.playback(canvas.canvas());
         Ok(())
     }
@@ -477,7 +1196,7 @@ impl LuaPicture {
         let local_matrix: Option<Matrix> = local_matrix.map(LuaMatrix::into);
         let tile_rect: Option<Rect> = tile_rect.map(LuaRect::into);
 
-        Ok(LuaShader(self.0.to_shader(
+        Ok(LuaShader::new(self.0.to_shader(
             tm,
             mode,
             local_matrix.as_ref(),
@@ -486,7 +1205,116 @@ impl LuaPicture {
     }
 }
 
-wrap_skia_handle!(ImageFilter);
+/// Crop rect argument of image filters.
+///
+/// Besides a rect, a number `n` or `{inflate = n}` table is accepted. These
+/// leave the crop rect unset, so blurs and shadows aren't clipped to a rect
+/// that's too tight, and record `n` as the margin layers drawn with the
+/// filter need around their content (see `ImageFilter:margin`).
+#[derive(Clone, Copy)]
+pub enum LuaCropRect {
+    Rect(Rect),
+    Inflate(f32),
+}
+
+impl LuaCropRect {
+    pub fn to_crop_rect(self) -> CropRect {
+        match self {
+            LuaCropRect::Rect(it) => CropRect::from(it),
+            LuaCropRect::Inflate(_) => CropRect::default(),
+        }
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for LuaCropRect {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(inflate) = args.pop_typed::<f32>() {
+            return Ok(LuaCropRect::Inflate(inflate));
+        }
+        if let Some(table) = args.pop_typed::<LuaTable>() {
+            if let Some(inflate) = table.get::<_, Option<f32>>("inflate")? {
+                return Ok(LuaCropRect::Inflate(inflate));
+            }
+            args.revert(table);
+        }
+        LuaRect::convert(args, lua).map(|it| LuaCropRect::Rect(it.into()))
+    }
+}
+
+impl From<LuaCropRect> for Param {
+    fn from(value: LuaCropRect) -> Self {
+        match value {
+            LuaCropRect::Rect(it) => Param::Rect(it),
+            LuaCropRect::Inflate(it) => Param::Number(it as f64),
+        }
+    }
+}
+
+wrap_skia_handle!(ImageFilter, meta: FilterProvenance);
+
+/// Inputs of a merge filter, given either as a single table or unpacked.
+///
+/// `nil` entries are allowed and stand for the source (unfiltered) content.
+pub struct MergeInputs(Vec<Option<LuaImageFilter>>);
+
+impl MergeInputs {
+    /// Reads a table of filters with possible `nil` holes, or returns `None`
+    /// if the table contains anything else (e.g. it's a crop rect).
+    fn from_table(table: &LuaTable) -> LuaResult<Option<Vec<Option<LuaImageFilter>>>> {
+        let mut entries = Vec::new();
+        for pair in table.clone().pairs::<LuaValue, LuaValue>() {
+            let (key, value) = pair?;
+            let index = match key {
+                LuaValue::Integer(it) if it >= 1 => it as usize,
+                _ => return Ok(None),
+            };
+            match value {
+                LuaValue::UserData(ud) if ud.is::<LuaImageFilter>() => {
+                    entries.push((index, ud.borrow::<LuaImageFilter>()?.clone()));
+                }
+                _ => return Ok(None),
+            }
+        }
+
+        let count = entries.iter().map(|(index, _)| *index).max().unwrap_or(0);
+        let mut result = vec![None; count];
+        for (index, filter) in entries {
+            result[index - 1] = Some(filter);
+        }
+        Ok(Some(result))
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for MergeInputs {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        if let Some(table) = args.pop_typed::<LuaTable>() {
+            if let Some(filters) = MergeInputs::from_table(&table)? {
+                return Ok(MergeInputs(filters));
+            }
+            args.revert(table);
+        }
+
+        let mut result = Vec::new();
+        while let Some(value) = args.try_pop() {
+            match &value {
+                LuaValue::Nil => result.push(None),
+                LuaValue::UserData(ud) if ud.is::<LuaImageFilter>() => {
+                    result.push(Some(ud.borrow::<LuaImageFilter>()?.clone()));
+                }
+                _ => {
+                    args.revert(value);
+                    break;
+                }
+            }
+        }
+        // trailing nils can't be told apart from an omitted crop rect
+        while let Some(None) = result.last() {
+            result.pop();
+            args.revert(LuaNil);
+        }
+        Ok(MergeInputs(result))
+    }
+}
 
 #[lua_methods(lua_name: ImageFilter)]
 #[allow(clippy::too_many_arguments)]
@@ -496,16 +1324,17 @@ impl LuaImageFilter {
         enforce_pm_color: bool,
         background: LuaFallible<LuaImageFilter>,
         foreground: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("arithmetic")
+            .param("coefficients", coefficients.to_vec())
+            .param("enforcePMColor", enforce_pm_color)
+            .opt_param("background", (*background).clone())
+            .opt_param("foreground", (*foreground).clone())
+            .opt_param("cropRect", *crop_rect);
         let background = background.map(LuaImageFilter::unwrap);
         let foreground = foreground.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
         Ok(image_filters::arithmetic(
             coefficients[0],
@@ -517,34 +1346,39 @@ impl LuaImageFilter {
             foreground,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
 
     pub fn blend(
         mode: LuaBlendMode,
         background: LuaFallible<LuaImageFilter>,
         foreground: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("blend")
+            .opt_param("mode", mode.name())
+            .opt_param("background", (*background).clone())
+            .opt_param("foreground", (*foreground).clone())
+            .opt_param("cropRect", *crop_rect);
         let background = background.map(LuaImageFilter::unwrap);
         let foreground = foreground.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
-        Ok(image_filters::blend(*mode, background, foreground, crop_rect).map(LuaImageFilter))
+        Ok(image_filters::blend(*mode, background, foreground, crop_rect).with_provenance(meta)?)
     }
 
-    pub fn blur(
+    /// Blurs `input` with Gaussian blur.
+    ///
+    /// With reduced quality sigma is clamped, and with minimal quality the
+    /// input is passed through unblurred.
+    pub fn blur<'lua>(
+        lua: &'lua LuaContext,
         sigma_x: f32,
         sigma_y: LuaFallible<f32>,
         tile_mode: LuaFallible<LuaTileMode>,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
         if !sigma_x.is_finite() || sigma_x < 0f32 {
             return Err(LuaError::RuntimeError(
                 "x sigma must be a positive, finite scalar".to_string(),
@@ -560,56 +1394,66 @@ impl LuaImageFilter {
             None => sigma_x,
         };
 
+        let meta = FilterProvenance::new("blur")
+            .param("sigmaX", sigma_x)
+            .param("sigmaY", sigma_y)
+            .opt_param("tileMode", (*tile_mode).and_then(|it| it.name()))
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
-        Ok(
-            image_filters::blur((sigma_x, sigma_y), tile_mode.map_t(), input, crop_rect)
-                .map(LuaImageFilter),
-        )
+        let quality = quality::quality(lua)?;
+        let filter = match (quality.blur_sigma(sigma_x), quality.blur_sigma(sigma_y)) {
+            (Some(sigma_x), Some(sigma_y)) => {
+                image_filters::blur((sigma_x, sigma_y), tile_mode.map_t(), input, crop_rect)
+            }
+            _ => image_filters::offset((0.0, 0.0), input, crop_rect),
+        };
+        Ok(filter.with_provenance(meta)?)
     }
 
     pub fn color_filter(
         cf: LuaColorFilter,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("colorFilter")
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
-        Ok(image_filters::color_filter(cf.unwrap(), input, crop_rect).map(LuaImageFilter))
+        Ok(image_filters::color_filter(cf.unwrap(), input, crop_rect).with_provenance(meta)?)
     }
 
-    pub fn compose(outer: LuaImageFilter, inner: LuaImageFilter) -> Option<LuaImageFilter> {
-        Ok(image_filters::compose(outer.unwrap(), inner.unwrap()).map(LuaImageFilter))
+    pub fn compose(outer: LuaImageFilter, inner: LuaImageFilter) -> LuaImageFilter {
+        let meta = FilterProvenance::new("compose")
+            .param("outer", outer.clone())
+            .param("inner", inner.clone());
+        Ok(image_filters::compose(outer.unwrap(), inner.unwrap()).with_provenance(meta)?)
     }
 
     pub fn crop(
         rect: LuaRect,
         tile_mode: LuaFallible<LuaTileMode>,
         input: LuaFallible<LuaImageFilter>,
-    ) -> Option<LuaImageFilter> {
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("crop")
+            .param("rect", rect)
+            .opt_param("tileMode", (*tile_mode).and_then(|it| it.name()))
+            .opt_param("input", (*input).clone());
         let rect: Rect = rect.into();
         let input = input.map(LuaImageFilter::unwrap);
-        Ok(image_filters::crop(rect, tile_mode.map_t(), input).map(LuaImageFilter))
+        Ok(image_filters::crop(rect, tile_mode.map_t(), input).with_provenance(meta)?)
     }
 
     pub fn dilate(
         radius_x: f32,
         radius_y: LuaFallible<f32>,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
         if !radius_x.is_finite() || radius_x < 0f32 {
             return Err(LuaError::RuntimeError(
                 "x radius must be a positive, finite scalar".to_string(),
@@ -624,15 +1468,15 @@ impl LuaImageFilter {
             Some(it) => it,
             None => radius_x,
         };
+        let meta = FilterProvenance::new("dilate")
+            .param("radiusX", radius_x)
+            .param("radiusY", radius_y)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
-        Ok(image_filters::dilate((radius_x, radius_y), input, crop_rect).map(LuaImageFilter))
+        Ok(image_filters::dilate((radius_x, radius_y), input, crop_rect).with_provenance(meta)?)
     }
 
     pub fn displacement_map(
@@ -641,16 +1485,18 @@ impl LuaImageFilter {
         scale: f32,
         displacement: LuaFallible<LuaImageFilter>,
         color: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("displacementMap")
+            .opt_param("xChannelSelector", x_channel_selector.name())
+            .opt_param("yChannelSelector", y_channel_selector.name())
+            .param("scale", scale)
+            .opt_param("displacement", (*displacement).clone())
+            .opt_param("color", (*color).clone())
+            .opt_param("cropRect", *crop_rect);
         let displacement = displacement.map(LuaImageFilter::unwrap);
         let color = color.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
         Ok(image_filters::displacement_map(
             (x_channel_selector.unwrap(), y_channel_selector.unwrap()),
@@ -659,7 +1505,38 @@ impl LuaImageFilter {
             color,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
+    }
+    /// Displaces `input` (source graphic by default) by red and green
+    /// channels of `displacement` image placed at the origin, offsetting
+    /// pixels by up to half of `scale` along x and y.
+    pub fn displace(
+        displacement: LuaImage,
+        scale: f32,
+        input: LuaFallible<LuaImageFilter>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("displace")
+            .param("width", displacement.0.width())
+            .param("height", displacement.0.height())
+            .param("scale", scale)
+            .opt_param("input", (*input).clone());
+        let displacement = image_filters::image(
+            displacement.unwrap(),
+            None::<&Rect>,
+            None::<&Rect>,
+            SamplingOptions::default(),
+        )
+        .ok_or_else(|| {
+            LuaError::RuntimeError("unable to create displacement image filter".to_string())
+        })?;
+        Ok(image_filters::displacement_map(
+            (ColorChannel::R, ColorChannel::G),
+            scale,
+            displacement,
+            input.map(LuaImageFilter::unwrap),
+            CropRect::default(),
+        )
+        .with_provenance(meta)?)
     }
     pub fn distant_lit_diffuse(
         direction: LuaPoint<3>,
@@ -667,15 +1544,17 @@ impl LuaImageFilter {
         surface_scale: f32,
         kd: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("distantLitDiffuse")
+            .param("direction", direction)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("kd", kd)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::distant_lit_diffuse(
             direction,
             light_color,
@@ -684,7 +1563,7 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
     pub fn distant_lit_specular(
         direction: LuaPoint<3>,
@@ -693,15 +1572,18 @@ impl LuaImageFilter {
         ks: f32,
         shininess: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("distantLitSpecular")
+            .param("direction", direction)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("ks", ks)
+            .param("shininess", shininess)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::distant_lit_specular(
             direction,
             light_color,
@@ -711,82 +1593,111 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
-    pub fn drop_shadow(
+    /// Draws `input` over its shadow.
+    ///
+    /// With reduced quality sigma is clamped, and with minimal quality the
+    /// shadow is left out.
+    pub fn drop_shadow<'lua>(
+        lua: &'lua LuaContext,
         offset: LuaPoint,
         sigma_x: f32,
         sigma_y: f32,
         color: LuaColor,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("dropShadow")
+            .param("offset", offset)
+            .param("sigmaX", sigma_x)
+            .param("sigmaY", sigma_y)
+            .param("color", color)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
-        Ok(
-            image_filters::drop_shadow(offset, (sigma_x, sigma_y), color, input, crop_rect)
-                .map(LuaImageFilter),
-        )
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
+        let quality = quality::quality(lua)?;
+        let filter = match (quality.blur_sigma(sigma_x), quality.blur_sigma(sigma_y)) {
+            (Some(sigma_x), Some(sigma_y)) => {
+                image_filters::drop_shadow(offset, (sigma_x, sigma_y), color, input, crop_rect)
+            }
+            _ => image_filters::offset((0.0, 0.0), input, crop_rect),
+        };
+        Ok(filter.with_provenance(meta)?)
     }
-    pub fn drop_shadow_only(
+    /// Draws only the shadow of `input`.
+    ///
+    /// With reduced quality sigma is clamped, and with minimal quality
+    /// nothing is drawn.
+    pub fn drop_shadow_only<'lua>(
+        lua: &'lua LuaContext,
         offset: LuaPoint,
         sigma_x: f32,
         sigma_y: f32,
         color: LuaColor,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("dropShadowOnly")
+            .param("offset", offset)
+            .param("sigmaX", sigma_x)
+            .param("sigmaY", sigma_y)
+            .param("color", color)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
-        Ok(
-            image_filters::drop_shadow_only(offset, (sigma_x, sigma_y), color, input, crop_rect)
-                .map(LuaImageFilter),
-        )
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
+        let quality = quality::quality(lua)?;
+        let filter = match (quality.blur_sigma(sigma_x), quality.blur_sigma(sigma_y)) {
+            (Some(sigma_x), Some(sigma_y)) => {
+                image_filters::drop_shadow_only(offset, (sigma_x, sigma_y), color, input, crop_rect)
+            }
+            _ => Some(image_filters::empty()),
+        };
+        Ok(filter.with_provenance(meta)?)
     }
     pub fn empty() -> LuaImageFilter {
-        Ok(LuaImageFilter(image_filters::empty()))
+        let meta = FilterProvenance::new("empty");
+        Ok(LuaImageFilter::new(image_filters::empty()).with_meta(meta))
     }
     pub fn erode(
         radius_x: f32,
         radius_y: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("erode")
+            .param("radiusX", radius_x)
+            .param("radiusY", radius_y)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
-        Ok(image_filters::erode((radius_x, radius_y), input, crop_rect).map(LuaImageFilter))
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
+        Ok(image_filters::erode((radius_x, radius_y), input, crop_rect).with_provenance(meta)?)
     }
     pub fn image(
         image: LuaImage,
         src_rect: LuaFallible<LuaRect>,
         dst_rect: LuaFallible<LuaRect>,
         sampling: LuaFallible<LuaSamplingOptions>,
-    ) -> Option<LuaImageFilter> {
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("image")
+            .param("width", image.0.width())
+            .param("height", image.0.height())
+            .opt_param("srcRect", *src_rect)
+            .opt_param("dstRect", *dst_rect);
         let src_rect: Option<Rect> = src_rect.map(LuaRect::into);
         let dst_rect: Option<Rect> = dst_rect.map(LuaRect::into);
         let sampling: SamplingOptions = sampling.unwrap_or_default().into();
+        warn_missing_mipmaps("ImageFilter.image", &image.0, &sampling);
         Ok(image_filters::image(
             image.unwrap(),
             src_rect.as_ref(),
             dst_rect.as_ref(),
             sampling,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
     pub fn magnifier(
         lens_bounds: LuaRect,
@@ -794,20 +1705,21 @@ impl LuaImageFilter {
         inset: f32,
         sampling: LuaFallible<LuaSamplingOptions>,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("magnifier")
+            .param("lensBounds", lens_bounds)
+            .param("zoomAmount", zoom_amount)
+            .param("inset", inset)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let lens_bounds: Rect = lens_bounds.into();
         let sampling: SamplingOptions = sampling.unwrap_or_default().into();
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(
             image_filters::magnifier(lens_bounds, zoom_amount, inset, sampling, input, crop_rect)
-                .map(LuaImageFilter),
+                .with_provenance(meta)?,
         )
     }
     pub fn matrix_convolution(
@@ -819,15 +1731,23 @@ impl LuaImageFilter {
         tile_mode: LuaTileMode,
         convolve_alpha: bool,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("matrixConvolution")
+            .param(
+                "kernelSize",
+                vec![kernel_size.width(), kernel_size.height()],
+            )
+            .param("kernel", kernel.clone())
+            .param("gain", gain)
+            .param("bias", bias)
+            .param("kernelOffset", kernel_offset)
+            .opt_param("tileMode", tile_mode.name())
+            .param("convolveAlpha", convolve_alpha)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::matrix_convolution(
             kernel_size,
             &kernel,
@@ -839,48 +1759,49 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
     pub fn matrix_transform(
         matrix: LuaMatrix,
         sampling: LuaFallible<LuaSamplingOptions>,
         input: LuaFallible<LuaImageFilter>,
-    ) -> Option<LuaImageFilter> {
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("matrixTransform").opt_param("input", (*input).clone());
         let matrix: Matrix = matrix.into();
         let sampling = sampling.unwrap_or_default();
         let input = input.map(LuaImageFilter::unwrap);
-        Ok(image_filters::matrix_transform(&matrix, sampling, input).map(LuaImageFilter))
-    }
-    pub fn merge(
-        filters: Vec<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
-        let filters = filters.into_iter().map(|it| Some(it.unwrap()));
-        Ok(image_filters::merge(filters, crop_rect).map(LuaImageFilter))
+        Ok(image_filters::matrix_transform(&matrix, sampling, input).with_provenance(meta)?)
+    }
+    /// Merges `filters` given as a table or unpacked, followed by an
+    /// optional crop rect. `nil` filters stand for the source content.
+    pub fn merge(filters: MergeInputs, crop_rect: LuaFallible<LuaCropRect>) -> LuaImageFilter {
+        let meta = FilterProvenance::new("merge")
+            .param("filters", Param::Filters(filters.0.clone()))
+            .opt_param("cropRect", *crop_rect);
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
+        let filters = filters
+            .0
+            .into_iter()
+            .map(|it| it.map(LuaImageFilter::unwrap));
+        Ok(image_filters::merge(filters, crop_rect).with_provenance(meta)?)
     }
     pub fn offset(
         offset: LuaPoint,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("offset")
+            .param("offset", offset)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
-        Ok(image_filters::offset(offset, input, crop_rect).map(LuaImageFilter))
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
+        Ok(image_filters::offset(offset, input, crop_rect).with_provenance(meta)?)
     }
-    pub fn picture(pic: LuaPicture, target_rect: LuaFallible<LuaRect>) -> Option<LuaImageFilter> {
+    pub fn picture(pic: LuaPicture, target_rect: LuaFallible<LuaRect>) -> LuaImageFilter {
+        let meta = FilterProvenance::new("picture").opt_param("targetRect", *target_rect);
         let target_rect: Option<Rect> = target_rect.map(LuaRect::into);
-        Ok(image_filters::picture(pic.unwrap(), target_rect.as_ref()).map(LuaImageFilter))
+        Ok(image_filters::picture(pic.unwrap(), target_rect.as_ref()).with_provenance(meta)?)
     }
     pub fn point_lit_diffuse(
         location: LuaPoint<3>,
@@ -888,15 +1809,17 @@ impl LuaImageFilter {
         surface_scale: f32,
         specular_reflectance: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("pointLitDiffuse")
+            .param("location", location)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("specularReflectance", specular_reflectance)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::point_lit_diffuse(
             location,
             light_color,
@@ -905,7 +1828,7 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
     pub fn point_lit_specular(
         location: LuaPoint<3>,
@@ -914,15 +1837,18 @@ impl LuaImageFilter {
         specular_reflectance: f32,
         shininess: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("pointLitSpecular")
+            .param("location", location)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("specularReflectance", specular_reflectance)
+            .param("shininess", shininess)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::point_lit_specular(
             location,
             light_color,
@@ -932,17 +1858,13 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
-    pub fn shader(shader: LuaShader, crop_rect: LuaFallible<LuaRect>) -> Option<LuaImageFilter> {
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+    pub fn shader(shader: LuaShader, crop_rect: LuaFallible<LuaCropRect>) -> LuaImageFilter {
+        let meta = FilterProvenance::new("shader").opt_param("cropRect", *crop_rect);
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
-        Ok(image_filters::shader(shader.unwrap(), crop_rect).map(LuaImageFilter))
+        Ok(image_filters::shader(shader.unwrap(), crop_rect).with_provenance(meta)?)
     }
     pub fn spot_lit_diffuse(
         location: LuaPoint<3>,
@@ -953,15 +1875,20 @@ impl LuaImageFilter {
         surface_scale: f32,
         specular_reflectance: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("spotLitDiffuse")
+            .param("location", location)
+            .param("target", target)
+            .param("falloffExponent", falloff_exponent)
+            .param("cutoffAngle", cutoff_angle)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("specularReflectance", specular_reflectance)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
 
         Ok(image_filters::spot_lit_diffuse(
             location,
@@ -974,7 +1901,7 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
     pub fn spot_lit_specular(
         location: LuaPoint<3>,
@@ -986,15 +1913,21 @@ impl LuaImageFilter {
         specular_reflectance: f32,
         shininess: f32,
         input: LuaFallible<LuaImageFilter>,
-        crop_rect: LuaFallible<LuaRect>,
-    ) -> Option<LuaImageFilter> {
+        crop_rect: LuaFallible<LuaCropRect>,
+    ) -> LuaImageFilter {
+        let meta = FilterProvenance::new("spotLitSpecular")
+            .param("location", location)
+            .param("target", target)
+            .param("falloffExponent", falloff_exponent)
+            .param("cutoffAngle", cutoff_angle)
+            .param("lightColor", light_color)
+            .param("surfaceScale", surface_scale)
+            .param("specularReflectance", specular_reflectance)
+            .param("shininess", shininess)
+            .opt_param("input", (*input).clone())
+            .opt_param("cropRect", *crop_rect);
         let input = input.map(LuaImageFilter::unwrap);
-        let crop_rect: CropRect = crop_rect
-            .map(|it| {
-                let it: Rect = it.into();
-                CropRect::from(it)
-            })
-            .unwrap_or_default();
+        let crop_rect: CropRect = crop_rect.map(LuaCropRect::to_crop_rect).unwrap_or_default();
         Ok(image_filters::spot_lit_specular(
             location,
             target,
@@ -1007,45 +1940,61 @@ impl LuaImageFilter {
             input,
             crop_rect,
         )
-        .map(LuaImageFilter))
+        .with_provenance(meta)?)
     }
-    pub fn tile(
-        src: LuaRect,
-        dst: LuaRect,
-        input: LuaFallible<LuaImageFilter>,
-    ) -> Option<LuaImageFilter> {
+    pub fn tile(src: LuaRect, dst: LuaRect, input: LuaFallible<LuaImageFilter>) -> LuaImageFilter {
+        let meta = FilterProvenance::new("tile")
+            .param("src", src)
+            .param("dst", dst)
+            .opt_param("input", (*input).clone());
         let src: Rect = src.into();
         let dst: Rect = dst.into();
         let input = input.map(LuaImageFilter::unwrap);
-        Ok(image_filters::tile(src, dst, input).map(LuaImageFilter))
+        Ok(image_filters::tile(src, dst, input).with_provenance(meta)?)
     }
 
+    /// Maps `src` device bounds through the filter. `ctm` defaults to
+    /// identity and `mapDirection` to `"forward"`, which returns bounds the
+    /// filter output covers.
     pub fn filter_bounds(
         &self,
         src: LuaRect,
-        ctm: LuaMatrix,
-        map_direction: LuaMapDirection,
+        ctm: LuaFallible<LuaMatrix>,
+        map_direction: LuaFallible<LuaMapDirection>,
         input_rect: Option<LuaRect>,
     ) -> LuaRect {
         let src: IRect = src.into();
-        let ctm: Matrix = ctm.into();
+        let ctm: Matrix = ctm.map(LuaMatrix::into).unwrap_or_default();
+        let map_direction = map_direction.unwrap_or_t(MapDirection::Forward);
         let input_rect = input_rect.map(Into::<IRect>::into);
         let filtered = self
             .0
-            .filter_bounds(src, &ctm, *map_direction, input_rect.as_ref());
+            .filter_bounds(src, &ctm, map_direction, input_rect.as_ref());
         Ok(LuaRect::from(filtered))
     }
+    /// Returns bounds a layer drawing `content_rect` with this filter needs,
+    /// including the `cropRect` inflate margin.
+    pub fn layer_bounds_for(&self, content_rect: LuaRect) -> LuaRect {
+        let content: IRect = Rect::from(content_rect).round_out();
+        let filtered =
+            self.0
+                .filter_bounds(content, &Matrix::default(), MapDirection::Forward, None);
+        let margin = self.crop_margin();
+        Ok(LuaRect::from(
+            Rect::from(filtered).with_outset((margin, margin)),
+        ))
+    }
     pub fn is_color_filter_node(&self) -> Option<LuaColorFilter> {
-        Ok(self.0.color_filter_node().map(LuaColorFilter))
+        Ok(self.0.color_filter_node().map(LuaColorFilter::new))
     }
     pub fn as_a_color_filter(&self) -> Option<LuaColorFilter> {
-        Ok(self.0.to_a_color_filter().map(LuaColorFilter))
+        Ok(self.0.to_a_color_filter().map(LuaColorFilter::new))
     }
     pub fn count_inputs(&self) -> usize {
         Ok(self.0.count_inputs())
     }
     pub fn get_input(&self, index: usize) -> Option<LuaImageFilter> {
-        Ok(self.0.get_input(index).map(LuaImageFilter))
+        Ok(self.0.get_input(index).map(LuaImageFilter::new))
     }
     pub fn compute_fast_bounds(&self, rect: LuaRect) -> LuaRect {
         let rect: Rect = rect.into();
@@ -1055,9 +2004,27 @@ impl LuaImageFilter {
     pub fn can_compute_fast_bounds(&self) -> bool {
         Ok(self.0.can_compute_fast_bounds())
     }
+    /// Margin requested with a `cropRect` inflate shorthand by this filter
+    /// or its inputs. `Canvas:saveLayer` outsets layer bounds by it when the
+    /// filter is set on a paint table or as the backdrop.
+    pub fn margin(&self) -> f32 {
+        Ok(self.crop_margin())
+    }
+    /// Returns a human readable tree of the filter graph. Nodes not
+    /// constructed through Lua bindings are listed as `<unknown>`.
+    pub fn describe(&self) -> String {
+        let mut result = String::new();
+        self.describe_into(&mut result, 0);
+        Ok(result)
+    }
+    /// Returns a nested table with the `type` and construction parameters of
+    /// the filter graph.
+    pub fn to_table<'lua>(&self, lua: &'lua LuaContext) -> LuaTable<'lua> {
+        self.provenance_table(lua)
+    }
     pub fn make_with_local_matrix(&self, matrix: LuaMatrix) -> Option<LuaImageFilter> {
         let matrix: Matrix = matrix.into();
-        Ok(self.0.with_local_matrix(&matrix).map(LuaImageFilter))
+        Ok(self.0.with_local_matrix(&matrix).map(LuaImageFilter::new))
     }
 }
 
@@ -1069,31 +2036,52 @@ impl LuaColorFilter {
         color: LuaColor,
         _color_space: LuaFallible<LuaColorSpace>,
         mode: LuaBlendMode,
-    ) -> Option<LuaColorFilter> {
+    ) -> LuaColorFilter {
         // NYI: blend color filter color_space handling
         let mode = mode.unwrap();
-        Ok(color_filters::blend(color, mode).map(LuaColorFilter))
+        Ok(color_filters::blend(color, mode)
+            .map(LuaColorFilter::new)
+            .or_failed("ColorFilter.blend", || {
+                format!("invalid color or blend mode {:?}", mode)
+            })?)
     }
-    pub fn compose(outer: LuaColorFilter, inner: LuaColorFilter) -> Option<LuaColorFilter> {
-        Ok(color_filters::compose(outer, inner).map(LuaColorFilter))
+    pub fn compose(outer: LuaColorFilter, inner: LuaColorFilter) -> LuaColorFilter {
+        Ok(color_filters::compose(outer, inner)
+            .map(LuaColorFilter::new)
+            .or_failed("ColorFilter.compose", || "unable to compose filters")?)
     }
     // TODO: ColorFilters::HSLA_matrix(matrix: LuaColorMatrix)
-    pub fn lerp(
-        t: f32,
-        source: LuaColorFilter,
-        destination: LuaColorFilter,
-    ) -> Option<LuaColorFilter> {
-        Ok(color_filters::lerp(t, source, destination).map(LuaColorFilter))
+    pub fn lerp(t: f32, source: LuaColorFilter, destination: LuaColorFilter) -> LuaColorFilter {
+        Ok(color_filters::lerp(t, source, destination)
+            .map(LuaColorFilter::new)
+            .or_failed("ColorFilter.lerp", || format!("invalid t = {}", t))?)
     }
-    pub fn lighting(multiply: LuaColor, add: LuaColor) -> Option<LuaColorFilter> {
-        Ok(color_filters::lighting(multiply, add).map(LuaColorFilter))
+    pub fn lighting(multiply: LuaColor, add: LuaColor) -> LuaColorFilter {
+        Ok(color_filters::lighting(multiply, add)
+            .map(LuaColorFilter::new)
+            .or_failed("ColorFilter.lighting", || "invalid multiply or add color")?)
     }
     pub fn linear_to_srgb_gamma() -> LuaColorFilter {
-        Ok(LuaColorFilter(color_filters::linear_to_srgb_gamma()))
+        Ok(LuaColorFilter::new(color_filters::linear_to_srgb_gamma()))
+    }
+    /// Returns a filter transforming colors by a 4x5 row major `matrix` of
+    /// 20 numbers, such as ones returned by `ColorMatrix` functions.
+    pub fn matrix(matrix: [f32; 20]) -> LuaColorFilter {
+        Ok(LuaColorFilter::new(color_filters::matrix_row_major(
+            &matrix, None,
+        )))
+    }
+    /// Returns a filter applying `{brightness, contrast, saturation,
+    /// hueRotate, sepia, invert}` adjustments in that order, skipping
+    /// missing ones. Amounts are the same as of `ColorMatrix` functions.
+    pub fn adjust(adjustments: color_matrix::Adjustments) -> LuaColorFilter {
+        Ok(LuaColorFilter::new(color_filters::matrix_row_major(
+            &adjustments.to_matrix(),
+            None,
+        )))
     }
-    // TODO: ColorFilters::matrix(matrix: LuaColorMatrix)
     pub fn srgb_to_linear_gamma() -> LuaColorFilter {
-        Ok(LuaColorFilter(color_filters::srgb_to_linear_gamma()))
+        Ok(LuaColorFilter::new(color_filters::srgb_to_linear_gamma()))
     }
     // TODO: ColorFilters::table(table: LuaColorTable)
     // TODO: ColorFilters::table_ARGB(table: LuaColorTable)
@@ -1148,7 +2136,7 @@ impl LuaColorFilter {
             .ok_or(LuaError::RuntimeError(
                 "unable to compose filters".to_string(),
             ))
-            .map(LuaColorFilter)
+            .map(LuaColorFilter::new)
     }
 
     pub fn make_with_working_color_space(&self, color_space: LuaColorSpace) -> LuaColorFilter {
@@ -1157,20 +2145,38 @@ impl LuaColorFilter {
             .ok_or(LuaError::RuntimeError(
                 "unable to apply color space to filter".to_string(),
             ))
-            .map(LuaColorFilter)
+            .map(LuaColorFilter::new)
+    }
+}
+
+/// Converts blur `radius` of `function` into Gaussian sigma the way Skia
+/// does for shadows.
+fn blur_sigma(function: &str, radius: f32) -> LuaResult<f32> {
+    if !radius.is_finite() || radius <= 0.0 {
+        return Err(LuaError::RuntimeError(format!(
+            "{} failed: radius must be a positive, finite scalar; got {}",
+            function, radius
+        )));
     }
+    Ok(radius * 0.57735 + 0.5)
 }
 
 wrap_skia_handle!(MaskFilter);
 
 #[lua_methods(lua_name: MaskFilter)]
 impl LuaMaskFilter {
-    pub fn make_blur(
-        style: LuaBlurStyle,
-        sigma: f32,
-        ctm: LuaFallible<bool>,
-    ) -> Option<LuaMaskFilter> {
-        Ok(MaskFilter::blur(style.unwrap(), sigma, *ctm).map(LuaMaskFilter))
+    pub fn make_blur(style: LuaBlurStyle, sigma: f32, ctm: LuaFallible<bool>) -> LuaMaskFilter {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(LuaError::RuntimeError(format!(
+                "MaskFilter.makeBlur failed: sigma must be a positive, finite scalar; got {}",
+                sigma
+            )));
+        }
+        Ok(MaskFilter::blur(style.unwrap(), sigma, *ctm)
+            .map(LuaMaskFilter::new)
+            .or_failed("MaskFilter.makeBlur", || {
+                format!("invalid sigma = {}", sigma)
+            })?)
     }
     pub fn approximate_filtered_bounds(&self, src: LuaRect) -> LuaRect {
         let src: Rect = src.into();
@@ -1186,7 +2192,7 @@ impl<'lua> TryFrom<LuaTable<'lua>> for LuaDashInfo {
     fn try_from(t: LuaTable<'lua>) -> Result<Self, Self::Error> {
         let phase: f32 = t.get("phase").unwrap_or_default();
         if let Ok(intervals) = t.get("intervals") {
-            return Ok(LuaDashInfo(DashInfo { intervals, phase }));
+            return Ok(LuaDashInfo::new(DashInfo { intervals, phase }));
         } else {
             let intervals: Vec<f32> = t
                 .sequence_values::<f32>()
@@ -1195,7 +2201,7 @@ impl<'lua> TryFrom<LuaTable<'lua>> for LuaDashInfo {
                 .collect();
 
             if !intervals.is_empty() {
-                return Ok(LuaDashInfo(DashInfo { intervals, phase }));
+                return Ok(LuaDashInfo::new(DashInfo { intervals, phase }));
             }
         }
 
@@ -1229,7 +2235,10 @@ impl<'lua> FromArgPack<'lua> for LikeDashInfo {
         let intervals: Vec<f32> = FromLua::from_lua(LuaValue::Table(table), lua)?;
         let phase: f32 = args.pop_typed().unwrap_or_default();
 
-        Ok(LikeDashInfo(LuaDashInfo(DashInfo { intervals, phase })))
+        Ok(LikeDashInfo(LuaDashInfo::new(DashInfo {
+            intervals,
+            phase,
+        })))
     }
 }
 
@@ -1247,7 +2256,7 @@ wrap_skia_handle!(StrokeRec);
 
 impl Default for LuaStrokeRec {
     fn default() -> Self {
-        LuaStrokeRec(StrokeRec::new(StrokeRecInitStyle::Fill))
+        LuaStrokeRec::new(StrokeRec::new(StrokeRecInitStyle::Fill))
     }
 }
 
@@ -1267,7 +2276,7 @@ impl LuaStrokeRec {
             LuaNil => return Ok(stroke_rec),
             LuaValue::String(init_style) => {
                 let init_style = LuaStrokeRecInitStyle::try_from(init_style)?;
-                return Ok(LuaStrokeRec(StrokeRec::new(*init_style)));
+                return Ok(LuaStrokeRec::new(StrokeRec::new(*init_style)));
             }
             LuaValue::Table(paint_like) => LuaPaint::try_from((paint_like, lua))?,
             LuaValue::UserData(ud) if ud.is::<LuaPaint>() => ud.borrow::<LuaPaint>()?.to_owned(),
@@ -1388,7 +2397,7 @@ impl LuaStrokeRec {
     pub fn apply_to_path(&self, path: LuaPath) -> LuaPath {
         let mut result = Path::new();
         self.0.apply_to_path(&mut result, &path.0);
-        Ok(LuaPath(result))
+        Ok(LuaPath::new(result))
     }
     pub fn apply_to_paint(&self, mut paint: LuaPaint) -> LuaPaint {
         self.0.apply_to_paint(&mut paint.0);
@@ -1407,41 +2416,98 @@ wrap_skia_handle!(PathEffect);
 #[lua_methods(lua_name: PathEffect)]
 impl LuaPathEffect {
     pub fn make_sum(first: LuaPathEffect, second: LuaPathEffect) -> LuaPathEffect {
-        Ok(LuaPathEffect(path_effect::PathEffect::sum(
-            first.0, second.0,
+        Ok(LuaPathEffect::new(path_effect::PathEffect::sum(
+            first.unwrap(),
+            second.unwrap(),
         )))
     }
     pub fn make_compose(outer: LuaPathEffect, inner: LuaPathEffect) -> LuaPathEffect {
-        Ok(LuaPathEffect(path_effect::PathEffect::compose(
-            outer.0, inner.0,
+        Ok(LuaPathEffect::new(path_effect::PathEffect::compose(
+            outer.unwrap(),
+            inner.unwrap(),
         )))
     }
-    pub fn make_dash(like_dash: LikeDashInfo) -> Option<LuaPathEffect> {
+    pub fn make_dash(like_dash: LikeDashInfo) -> LuaPathEffect {
+        let dash = &like_dash.0 .0;
         Ok(
-            skia_safe::dash_path_effect::new(&like_dash.0 .0.intervals, like_dash.0 .0.phase)
-                .map(LuaPathEffect),
+            skia_safe::dash_path_effect::new(&dash.intervals, dash.phase)
+                .map(LuaPathEffect::new)
+                .or_failed("PathEffect.makeDash", || {
+                    format!(
+                        "intervals must be an even number of non-negative lengths with a positive sum; got {:?}",
+                        dash.intervals
+                    )
+                })?,
         )
     }
-    pub fn make_trim(
-        start: f32,
-        stop: f32,
-        mode: LuaFallible<LuaTrimMode>,
-    ) -> Option<LuaPathEffect> {
-        Ok(skia_safe::trim_path_effect::new(start, stop, mode.map_t()).map(LuaPathEffect))
-    }
-    pub fn make_radius(radius: f32) -> Option<LuaPathEffect> {
-        Ok(skia_safe::corner_path_effect::new(radius).map(LuaPathEffect))
-    }
-    pub fn make_discrete(length: f32, dev: f32, seed: LuaFallible<u32>) -> Option<LuaPathEffect> {
-        Ok(skia_safe::discrete_path_effect::new(length, dev, *seed).map(LuaPathEffect))
-    }
-    pub fn make_2d_path(width: f32, mx: LuaMatrix) -> Option<LuaPathEffect> {
+    /// Returns an `AnimatedDash` of `intervals` for animating dash phase
+    /// (e.g. marching ants) without building a new effect every frame.
+    pub fn make_dash_animated(intervals: Vec<f32>) -> LuaAnimatedDash {
+        let cache = dash::PhaseCache::new(intervals.clone()).or_failed(
+            "PathEffect.makeDashAnimated",
+            || {
+                format!(
+                    "intervals must be an even number of non-negative lengths with a positive sum; got {:?}",
+                    intervals
+                )
+            },
+        )?;
+        Ok(LuaAnimatedDash(cache))
+    }
+    /// Returns a dash effect with dashes of roughly `dash_length` and gaps of
+    /// `dash_length * gap_ratio`, stretched so a whole number of dashes fits
+    /// the first contour of `path`. Open contours start and end with a dash.
+    ///
+    /// A dash effect has a single pattern, so it can't snap to corners; use
+    /// `Canvas:strokeDashedFitted` to fit dashes to each side separately.
+    pub fn make_dash_fitted(path: LuaPath, dash_length: f32, gap_ratio: f32) -> LuaPathEffect {
+        let fitted = ContourMeasureIter::new(&path.0, false, None)
+            .next()
+            .and_then(|it| dash::fit(it.length(), dash_length, gap_ratio, it.is_closed()))
+            .or_failed("PathEffect.makeDashFitted", || {
+                format!(
+                    "path must have a non-empty contour, dash length must be positive and gap ratio non-negative; got dash length {} and gap ratio {}",
+                    dash_length, gap_ratio
+                )
+            })?;
+        Ok(skia_safe::dash_path_effect::new(&fitted.intervals(), 0.0)
+            .map(LuaPathEffect::new)
+            .or_failed("PathEffect.makeDashFitted", || {
+                format!("invalid intervals {:?}", fitted.intervals())
+            })?)
+    }
+    pub fn make_trim(start: f32, stop: f32, mode: LuaFallible<LuaTrimMode>) -> LuaPathEffect {
+        Ok(skia_safe::trim_path_effect::new(start, stop, mode.map_t())
+            .map(LuaPathEffect::new)
+            .or_failed("PathEffect.makeTrim", || {
+                format!("invalid start = {} and stop = {}", start, stop)
+            })?)
+    }
+    pub fn make_radius(radius: f32) -> LuaPathEffect {
+        Ok(skia_safe::corner_path_effect::new(radius)
+            .map(LuaPathEffect::new)
+            .or_failed("PathEffect.makeRadius", || {
+                format!("radius must be positive; got {}", radius)
+            })?)
+    }
+    pub fn make_discrete(length: f32, dev: f32, seed: LuaFallible<u32>) -> LuaPathEffect {
+        Ok(skia_safe::discrete_path_effect::new(length, dev, *seed)
+            .map(LuaPathEffect::new)
+            .or_failed("PathEffect.makeDiscrete", || {
+                format!("invalid length = {} and deviation = {}", length, dev)
+            })?)
+    }
+    pub fn make_2d_path(width: f32, mx: LuaMatrix) -> LuaPathEffect {
         let mx: Matrix = mx.into();
-        Ok(skia_safe::line_2d_path_effect::new(width, &mx).map(LuaPathEffect))
+        Ok(skia_safe::line_2d_path_effect::new(width, &mx)
+            .map(LuaPathEffect::new)
+            .or_failed("PathEffect.make2dPath", || {
+                format!("invalid width = {} or non-invertible matrix", width)
+            })?)
     }
 
     pub fn as_a_dash(&self) -> Option<LuaDashInfo> {
-        Ok(self.0.as_a_dash().map(LuaDashInfo))
+        Ok(self.0.as_a_dash().map(LuaDashInfo::new))
     }
 
     pub fn filter_path<'lua>(
@@ -1476,8 +2542,8 @@ impl LuaPathEffect {
             }
         };
         let result = lua.create_table()?;
-        result.set(0, LuaPath(dst))?;
-        result.set(1, LuaStrokeRec(stroke_rec))?;
+        result.set(0, LuaPath::new(dst))?;
+        result.set(1, LuaStrokeRec::new(stroke_rec))?;
         Ok(LuaValue::Table(result))
     }
 
@@ -1843,74 +2909,124 @@ impl LuaMatrix {
         }
         Ok(result)
     }
+    /// Returns bounds of `rect` mapped by the matrix.
+    ///
+    /// Rects lie in the z = 0 plane, so 4x4 matrices are mapped through their
+    /// 3x3 projection, which keeps perspective and bounds all four corners.
     pub fn map_rect(&self, rect: LuaRect) -> LuaRect {
         let rect: Rect = rect.into();
         let mapped = match self {
             LuaMatrix::Three(it) => it.map_rect(rect).0,
-            LuaMatrix::Four(it) => {
-                let a = it.map(rect.left, rect.top, 0.0, 1.0);
-                let b = it.map(rect.right, rect.bottom, 0.0, 1.0);
-                Rect::new(a.x, a.y, b.x, b.y)
-            }
+            LuaMatrix::Four(it) => it.to_m33().map_rect(rect).0,
         };
-        Ok(LuaRect::from(mapped))
+        Ok(LuaRect::from(mapped.sorted()))
     }
 }
 
-wrap_skia_handle!(Paint);
+/// Marks paints frozen with `Paint:freeze`.
+pub struct FrozenPaint;
 
-type_like_table!(Paint: |value: LuaTable, lua: &'lua Lua| {
-    let mut paint = Paint::default();
+wrap_skia_handle!(Paint, meta: FrozenPaint);
 
-    let color_space = value.try_get_t::<_, LuaColorSpace>("colorSpace", lua)?;
-    if let Ok(color) = LuaColor::from_lua(LuaValue::Table(value.clone()), lua) {
-        let color: Color4f = color.into();
-        paint.set_color4f(color, color_space.as_ref());
+impl LuaPaint {
+    #[inline]
+    pub fn is_frozen_paint(&self) -> bool {
+        self.meta().is_some()
     }
 
-    if let Some(aa) = value.try_get::<_, bool>("antiAlias", lua)? {
-        paint.set_anti_alias(aa);
+    fn check_mutable(&self) -> LuaResult<()> {
+        if self.is_frozen_paint() {
+            return Err(LuaError::RuntimeError(
+                "can't modify a frozen Paint; use Paint:clone() to get a mutable copy".to_string(),
+            ));
+        }
+        Ok(())
     }
+}
 
-    if let Some(dither) = value.try_get::<_, bool>("dither",lua)? {
-        paint.set_dither(dither);
-    }
+impl LuaPaint {
+    /// Applies properties from a paint constructor table to `paint`.
+    pub(crate) fn apply_table<'lua>(
+        paint: &mut Paint,
+        value: LuaTable<'lua>,
+        lua: &'lua Lua,
+    ) -> LuaResult<()> {
+        let color_space = value.try_get_t::<_, LuaColorSpace>("colorSpace", lua)?;
+        if let Ok(color) = LuaColor::from_lua(LuaValue::Table(value.clone()), lua) {
+            let color: Color4f = color.into();
+            paint.set_color4f(color, color_space.as_ref());
+        }
 
-    if let Some(image_filter) = value.try_get_t::<_, LuaImageFilter>("imageFilter",lua)? {
-        paint.set_image_filter(image_filter);
-    }
-    if let Some(mask_filter) = value.try_get_t::<_, LuaMaskFilter>("maskFilter",lua)? {
-        paint.set_mask_filter(mask_filter);
-    }
-    if let Some(color_filter) = value.try_get_t::<_, LuaColorFilter>("colorFilter", lua)? {
-        paint.set_color_filter(color_filter);
-    }
+        if let Some(aa) = value.try_get::<_, bool>("antiAlias", lua)? {
+            paint.set_anti_alias(aa);
+        }
 
-    if let Some(style) = value.try_get_t::<_, LuaPaintStyle>("style", lua)? {
-        paint.set_style(style);
-    }
-    if let Some(cap) = value.try_get_t::<_, LuaPaintCap>("strokeCap", lua)?.or(value.try_get_t::<_, LuaPaintCap>("cap", lua)?) {
-        paint.set_stroke_cap(cap);
-    }
-    if let Some(join) = value.try_get_t::<_, LuaPaintJoin>("strokeJoin", lua)?.or(value.try_get_t::<_, LuaPaintJoin>("join", lua)?) {
-        paint.set_stroke_join(join);
-    }
-    if let Some(width) = value.try_get::<_, f32>("strokeWidth", lua)?.or(value.try_get::<_, f32>("width", lua)?) {
-        paint.set_stroke_width(width);
-    }
-    if let Some(miter) = value.try_get::<_, f32>("strokeMiter", lua)?.or(value.try_get::<_, f32>("miter", lua)?) {
-        paint.set_stroke_miter(miter);
-    }
-    if let Some(path_effect) = value.try_get_t::<_, LuaPathEffect>("pathEffect", lua)? {
-        paint.set_path_effect(path_effect);
-    }
+        if let Some(dither) = value.try_get::<_, bool>("dither", lua)? {
+            paint.set_dither(dither);
+        }
+
+        if let Some(image_filter) = value.try_get_t::<_, LuaImageFilter>("imageFilter", lua)? {
+            paint.set_image_filter(image_filter);
+        }
+        if let Some(mask_filter) = value.try_get_t::<_, LuaMaskFilter>("maskFilter", lua)? {
+            paint.set_mask_filter(mask_filter);
+        }
+        if let Some(color_filter) = value.try_get_t::<_, LuaColorFilter>("colorFilter", lua)? {
+            paint.set_color_filter(color_filter);
+        }
+
+        if let Some(mode) = value.try_get_t::<_, LuaBlendMode>("blendMode", lua)? {
+            paint.set_blend_mode(mode);
+        }
+
+        if let Some(style) = value.try_get_t::<_, LuaPaintStyle>("style", lua)? {
+            paint.set_style(style);
+        }
+        if let Some(cap) = value
+            .try_get_t::<_, LuaPaintCap>("strokeCap", lua)?
+            .or(value.try_get_t::<_, LuaPaintCap>("cap", lua)?)
+        {
+            paint.set_stroke_cap(cap);
+        }
+        if let Some(join) = value
+            .try_get_t::<_, LuaPaintJoin>("strokeJoin", lua)?
+            .or(value.try_get_t::<_, LuaPaintJoin>("join", lua)?)
+        {
+            paint.set_stroke_join(join);
+        }
+        if let Some(width) = value
+            .try_get::<_, f32>("strokeWidth", lua)?
+            .or(value.try_get::<_, f32>("width", lua)?)
+        {
+            paint.set_stroke_width(width);
+        }
+        if let Some(miter) = value
+            .try_get::<_, f32>("strokeMiter", lua)?
+            .or(value.try_get::<_, f32>("miter", lua)?)
+        {
+            paint.set_stroke_miter(miter);
+        }
+        if let Some(path_effect) = value.try_get_t::<_, LuaPathEffect>("pathEffect", lua)? {
+            paint.set_path_effect(path_effect);
+        }
+
+        if let Some(shader) = value.try_get_t::<_, LuaShader>("shader", lua)? {
+            paint.set_shader(Some(shader));
+        }
 
-    if let Some(shader) = value.try_get_t::<_, LuaShader>("shader", lua)? {
-        paint.set_shader(Some(shader));
+        Ok(())
     }
+}
 
-    return Ok(LuaPaint(paint))
-});
+impl<'lua> TryFrom<(LuaTable<'lua>, &'lua Lua)> for LuaPaint {
+    type Error = LuaError;
+
+    fn try_from((value, lua): (LuaTable<'lua>, &'lua Lua)) -> LuaResult<Self> {
+        let mut paint = Paint::default();
+        LuaPaint::apply_table(&mut paint, value, lua)?;
+        Ok(LuaPaint::new(paint))
+    }
+}
 
 #[lua_methods(lua_name: Paint)]
 impl LuaPaint {
@@ -1930,43 +3046,124 @@ impl LuaPaint {
                 Paint::new(color, Some(&color_space.0))
             }
         };
-        Ok(LuaPaint(paint))
+        Ok(LuaPaint::new(paint))
+    }
+    /// Returns a paint drawing a `color` halo extending `radius` outside of
+    /// drawn shapes, without the shapes themselves.
+    ///
+    /// With minimal quality the paint is transparent.
+    pub fn glow<'lua>(lua: &'lua LuaContext, color: LuaColor, radius: f32) -> LuaPaint {
+        let sigma = blur_sigma("Paint.glow", radius)?;
+        let mut paint = Paint::new(Color4f::from(color), None);
+        paint.set_anti_alias(true);
+        match quality::quality(lua)?.blur_sigma(sigma) {
+            Some(sigma) => {
+                paint.set_mask_filter(MaskFilter::blur(BlurStyle::Outer, sigma, false));
+            }
+            // an unblurred outer mask is empty
+            None => {
+                paint.set_alpha(0);
+            }
+        }
+        Ok(LuaPaint::new(paint))
+    }
+    /// Returns a paint drawing a `color` shadow inside of drawn shapes, as
+    /// cast by their edges moved by `offset` and blurred by `radius`.
+    ///
+    /// Only the shadow is drawn, so shapes should be filled before drawing
+    /// them again with this paint. With minimal quality the shadow isn't
+    /// blurred.
+    pub fn inner_shadow<'lua>(
+        lua: &'lua LuaContext,
+        color: LuaColor,
+        radius: f32,
+        offset: LuaPoint,
+    ) -> LuaPaint {
+        let sigma = blur_sigma("Paint.innerShadow", radius)?;
+        let color: Color = Color4f::from(color).to_color();
+        let failed = || LuaError::RuntimeError("Paint.innerShadow failed".to_string());
+
+        // shape alpha moved by offset and spread by blur
+        let mut cast = image_filters::offset(offset, None, None);
+        if let Some(sigma) = quality::quality(lua)?.blur_sigma(sigma) {
+            cast = image_filters::blur((sigma, sigma), TileMode::Decal, cast, None);
+        }
+        // color where the moved shape doesn't cover, which affects transparent
+        // pixels and so extends past shape bounds
+        let uncovered = color_filters::blend(color, BlendMode::SrcOut).ok_or_else(failed)?;
+        let shadow = image_filters::color_filter(uncovered, cast, None);
+        // keep only the part inside the original shape
+        let filter =
+            image_filters::blend(BlendMode::DstIn, shadow, None, None).ok_or_else(failed)?;
+
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+        paint.set_image_filter(filter);
+        Ok(LuaPaint::new(paint))
+    }
+
+    /// Returns a frozen copy of this paint, which can be drawn with but
+    /// raises an error when modified.
+    pub fn freeze(&self) -> LuaPaint {
+        Ok(LuaPaint::new(self.0.clone()).with_meta(FrozenPaint))
+    }
+    pub fn is_frozen(&self) -> bool {
+        Ok(self.is_frozen_paint())
+    }
+    /// Returns a mutable copy of this paint.
+    #[lua(rename: "clone")]
+    pub fn duplicate(&self) -> LuaPaint {
+        Ok(LuaPaint::new(self.0.clone()))
+    }
+    /// Alias for `clone`.
+    pub fn copy(&self) -> LuaPaint {
+        Ok(LuaPaint::new(self.0.clone()))
     }
 
     pub fn is_anti_alias(&self) -> bool {
         Ok(self.0.is_anti_alias())
     }
+    #[lua(chain)]
     pub fn set_anti_alias(&mut self, anti_alias: bool) {
+        self.check_mutable()?;
         self.0.set_anti_alias(anti_alias);
         Ok(())
     }
     pub fn is_dither(&self) -> bool {
         Ok(self.0.is_dither())
     }
+    #[lua(chain)]
     pub fn set_dither(&mut self, dither: bool) {
+        self.check_mutable()?;
         self.0.set_dither(dither);
         Ok(())
     }
     pub fn get_image_filter(&self) -> Option<LuaImageFilter> {
-        Ok(self.0.image_filter().map(LuaImageFilter))
+        Ok(self.0.image_filter().map(LuaImageFilter::new))
     }
+    #[lua(chain)]
     pub fn set_image_filter(&mut self, image_filter: Option<LuaImageFilter>) {
+        self.check_mutable()?;
         self.0
             .set_image_filter(image_filter.map(LuaImageFilter::unwrap));
         Ok(())
     }
     pub fn get_mask_filter(&self) -> Option<LuaMaskFilter> {
-        Ok(self.0.mask_filter().map(LuaMaskFilter))
+        Ok(self.0.mask_filter().map(LuaMaskFilter::new))
     }
+    #[lua(chain)]
     pub fn set_mask_filter(&mut self, mask_filter: Option<LuaMaskFilter>) {
+        self.check_mutable()?;
         self.0
             .set_mask_filter(mask_filter.map(LuaMaskFilter::unwrap));
         Ok(())
     }
     pub fn get_color_filter(&self) -> Option<LuaColorFilter> {
-        Ok(self.0.color_filter().map(LuaColorFilter))
+        Ok(self.0.color_filter().map(LuaColorFilter::new))
     }
+    #[lua(chain)]
     pub fn set_color_filter(&mut self, color_filter: Option<LuaColorFilter>) {
+        self.check_mutable()?;
         self.0
             .set_color_filter(color_filter.map(LuaColorFilter::unwrap));
         Ok(())
@@ -1974,14 +3171,28 @@ impl LuaPaint {
     pub fn get_alpha(&self) -> f32 {
         Ok(self.0.alpha_f())
     }
+    /// Returns blend mode of the paint, or `nil` if it uses a custom blender.
+    pub fn get_blend_mode(&self) -> Option<LuaBlendMode> {
+        Ok(self.0.as_blend_mode().map(LuaBlendMode))
+    }
+    #[lua(chain)]
+    pub fn set_blend_mode(&mut self, mode: LuaBlendMode) {
+        self.check_mutable()?;
+        self.0.set_blend_mode(*mode);
+        Ok(())
+    }
+    #[lua(chain)]
     pub fn set_alpha(&mut self, alpha: f32) {
+        self.check_mutable()?;
         self.0.set_alpha_f(alpha);
         Ok(())
     }
     pub fn get_color(&self) -> LuaColor {
         Ok(LuaColor::from(self.0.color4f()))
     }
+    #[lua(chain)]
     pub fn set_color(&mut self, color: LuaColor, color_space: Option<LuaColorSpace>) {
+        self.check_mutable()?;
         let color: Color4f = color.into();
         self.0
             .set_color4f(color, color_space.map(LuaColorSpace::unwrap).as_ref());
@@ -2005,7 +3216,9 @@ impl LuaPaint {
         }
         Ok(result)
     }
+    #[lua(chain)]
     pub fn set_style(&mut self, style: LuaTable) {
+        self.check_mutable()?;
         let fill: bool = style.get("fill").unwrap_or_default();
         let stroke: bool = style.get("stroke").unwrap_or_default();
         self.0.set_style(match (fill, stroke) {
@@ -2023,54 +3236,136 @@ impl LuaPaint {
     pub fn get_stroke_cap(&self) -> LuaPaintCap {
         Ok(LuaPaintCap(self.0.stroke_cap()))
     }
+    #[lua(chain)]
     pub fn set_stroke_cap(&mut self, cap: LuaPaintCap) {
+        self.check_mutable()?;
         self.0.set_stroke_cap(*cap);
         Ok(())
     }
     pub fn get_stroke_join(&self) -> LuaPaintJoin {
         Ok(LuaPaintJoin(self.0.stroke_join()))
     }
+    #[lua(chain)]
     pub fn set_stroke_join(&mut self, join: LuaPaintJoin) {
+        self.check_mutable()?;
         self.0.set_stroke_join(*join);
         Ok(())
     }
     pub fn get_stroke_width(&self) -> f32 {
         Ok(self.0.stroke_width())
     }
+    #[lua(chain)]
     pub fn set_stroke_width(&mut self, width: f32) {
+        self.check_mutable()?;
         self.0.set_stroke_width(width);
         Ok(())
     }
     pub fn get_stroke_miter(&self) -> f32 {
         Ok(self.0.stroke_miter())
     }
+    #[lua(chain)]
     pub fn set_stroke_miter(&mut self, miter: f32) {
+        self.check_mutable()?;
         self.0.set_stroke_miter(miter);
         Ok(())
     }
     pub fn get_path_effect(&self) -> Option<LuaPathEffect> {
-        Ok(self.0.path_effect().map(LuaPathEffect))
+        Ok(self.0.path_effect().map(LuaPathEffect::new))
     }
+    #[lua(chain)]
     pub fn set_path_effect(&mut self, effect: Option<LuaPathEffect>) {
+        self.check_mutable()?;
         self.0.set_path_effect(effect.map(LuaPathEffect::unwrap));
         Ok(())
     }
     pub fn get_shader(&self) -> Option<LuaShader> {
-        Ok(self.0.shader().map(LuaShader))
+        Ok(self.0.shader().map(LuaShader::new))
     }
+    #[lua(chain)]
     pub fn set_shader(&mut self, shader: Option<LuaShader>) {
+        self.check_mutable()?;
         self.0.set_shader(shader.map(LuaShader::unwrap));
         Ok(())
     }
+    /// Returns the outline of `path` as it would be filled when drawn with
+    /// this paint, including stroke and path effect. Hairline paints return
+    /// the original path.
+    pub fn get_fill_path(
+        &self,
+        path: LuaPath,
+        cull_rect: LuaFallible<LuaRect>,
+        res_scale: Option<f32>,
+    ) -> LuaPath {
+        let cull_rect: Option<Rect> = cull_rect.map(LuaRect::into);
+        let matrix = res_scale.map(|scale| Matrix::scale((scale, scale)));
+        let mut result = Path::new();
+        if path_utils::fill_path_with_paint(
+            &path.0,
+            &self.0,
+            &mut result,
+            cull_rect.as_ref(),
+            matrix,
+        ) {
+            Ok(LuaPath::new(result))
+        } else {
+            Ok(path)
+        }
+    }
 }
 
 wrap_skia_handle!(Path);
 
+/// Returns `tolerance` for path flattening, or the default one if missing.
+fn valid_tolerance(tolerance: Option<f32>) -> LuaResult<f32> {
+    match tolerance {
+        None => Ok(tessellate::DEFAULT_TOLERANCE),
+        Some(it) if it.is_finite() && it > 0.0 => Ok(it),
+        Some(it) => Err(LuaError::RuntimeError(format!(
+            "tolerance must be a positive, finite number; got {}",
+            it
+        ))),
+    }
+}
+
+/// Splits `path` into paths holding one contour each, skipping contours
+/// that consist only of a move.
+fn split_contours(path: &Path) -> Vec<Path> {
+    let mut result = Vec::new();
+    let mut current: Option<Path> = None;
+    let mut iter = path::Iter::new(path, false);
+    while let Some((verb, p)) = iter.next() {
+        if verb == Verb::Move {
+            result.extend(current.take().filter(|it| it.count_verbs() > 1));
+            let mut contour = Path::new();
+            contour.set_fill_type(path.fill_type());
+            contour.move_to(p[0]);
+            current = Some(contour);
+            continue;
+        }
+        let contour = match &mut current {
+            Some(it) => it,
+            None => continue,
+        };
+        match verb {
+            // lines back to the start are implied by the close that follows
+            Verb::Line if iter.is_close_line() => contour,
+            Verb::Line => contour.line_to(p[1]),
+            Verb::Quad => contour.quad_to(p[1], p[2]),
+            Verb::Conic => contour.conic_to(p[1], p[2], iter.conic_weight().unwrap_or(1.0)),
+            Verb::Cubic => contour.cubic_to(p[1], p[2], p[3]),
+            Verb::Close => contour.close(),
+            _ => contour,
+        };
+    }
+    result.extend(current.filter(|it| it.count_verbs() > 1));
+    result
+}
+
 #[lua_methods(lua_name: Path)]
 impl LuaPath {
     #[lua(constructor)]
     pub fn empty() -> LuaPath {
-        Ok(LuaPath(Path::default()))
+        Ok(LuaPath::new(Path::default()))
     }
     pub fn make(
         points: Vec<LuaPoint>,
@@ -2081,7 +3376,7 @@ impl LuaPath {
     ) -> LuaPath {
         let points: Vec<Point> = points.into_iter().map(LuaPoint::into).collect();
         let verbs: Vec<u8> = verbs.into_iter().map(|it| it.0 as u8).collect();
-        Ok(LuaPath(Path::new_from(
+        Ok(LuaPath::new(Path::new_from(
             &points,
             &verbs,
             &conic_weights,
@@ -2090,15 +3385,18 @@ impl LuaPath {
         )))
     }
 
+    #[lua(chain)]
     pub fn add_arc(&mut self, oval: LuaRect, start_angle: f32, sweep_angle: f32) {
         let oval: Rect = oval.into();
         self.0.add_arc(oval, start_angle, sweep_angle);
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_circle(&mut self, center: LuaPoint, radius: f32, dir: Option<LuaPathDirection>) {
         self.0.add_circle(center, radius, dir.map_t());
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_oval(&mut self, oval: LuaRect, dir: Option<LuaPathDirection>, start: Option<usize>) {
         let oval: Rect = oval.into();
         let start = start.unwrap_or(1);
@@ -2106,10 +3404,12 @@ impl LuaPath {
             .add_oval(oval, Some((dir.unwrap_or_default_t(), start)));
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_path(&mut self, other: LuaPath, offset: LuaPoint, mode: Option<LuaAddPathMode>) {
         self.0.add_path(&other.0, offset, mode.map_t());
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_poly(&mut self, points: MaybeUnpacked<Vec<LuaPoint>>, close: bool) {
         if points.is_empty() {
             self.0.close();
@@ -2123,6 +3423,7 @@ impl LuaPath {
         self.0.add_poly(&points, close);
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_rect(&mut self, rect: LuaRect, dir: Option<LuaPathDirection>, start: Option<usize>) {
         let rect: Rect = rect.into();
         let start = start.unwrap_or(1);
@@ -2130,6 +3431,7 @@ impl LuaPath {
             .add_rect(rect, Some((dir.unwrap_or_default_t(), start)));
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_round_rect(
         &mut self,
         rect: LuaRect,
@@ -2144,6 +3446,7 @@ impl LuaPath {
         );
         Ok(())
     }
+    #[lua(chain)]
     pub fn add_r_rect(
         &mut self,
         rrect: LuaRRect,
@@ -2155,6 +3458,7 @@ impl LuaPath {
             .add_rrect(rrect.unwrap(), Some((dir.unwrap_or_default_t(), start)));
         Ok(())
     }
+    #[lua(chain)]
     pub fn arc_to(
         &mut self,
         oval: LuaRect,
@@ -2166,6 +3470,7 @@ impl LuaPath {
         self.0.arc_to(oval, start_angle, sweep_angle, force_move_to);
         Ok(())
     }
+    #[lua(chain)]
     pub fn close(&mut self) {
         self.0.close();
         Ok(())
@@ -2173,6 +3478,7 @@ impl LuaPath {
     pub fn compute_tight_bounds(&self) -> LuaRect {
         Ok(LuaRect::from(self.0.compute_tight_bounds()))
     }
+    #[lua(chain)]
     pub fn conic_to(&mut self, points: MaybeUnpacked<[LuaPoint; 2]>, w: f32) {
         self.0.conic_to(points[0], points[1], w);
         Ok(())
@@ -2190,6 +3496,7 @@ impl LuaPath {
     pub fn count_verbs(&self) -> usize {
         Ok(self.0.count_verbs())
     }
+    #[lua(chain)]
     pub fn cubic_to(&mut self, points: MaybeUnpacked<[LuaPoint; 3]>) {
         self.0.cubic_to(points[0], points[1], points[2]);
         Ok(())
@@ -2249,13 +3556,35 @@ impl LuaPath {
             Ok(result)
         }
     }
+    #[lua(chain)]
     pub fn inc_reserve(&mut self, extra_pt_count: usize) {
         self.0.inc_reserve(extra_pt_count);
         Ok(())
     }
-    pub fn interpolate(&self, ending: LuaPath, weight: f32) {
-        self.0.interpolate(&ending.0, weight);
-        Ok(())
+    /// Returns a path between `ending` (`weight` 0) and this path (`weight`
+    /// 1), or `nil` if the paths aren't interpolatable.
+    pub fn interpolate(&self, ending: LuaPath, weight: f32) -> Option<LuaPath> {
+        Ok(self.0.interpolate(&ending.0, weight).map(LuaPath::new))
+    }
+    /// Returns an array of paths holding one contour of this path each, in
+    /// order. Contours consisting only of a move are skipped.
+    pub fn contours(&self) -> Vec<LuaPath> {
+        Ok(split_contours(&self.0)
+            .into_iter()
+            .map(LuaPath::new)
+            .collect())
+    }
+    /// Returns the number of contours `contours` would return.
+    pub fn count_contours(&self) -> usize {
+        Ok(split_contours(&self.0).len())
+    }
+    /// Returns a copy of the path with the direction of every contour
+    /// reversed.
+    pub fn reversed(&self) -> LuaPath {
+        let mut result = Path::new();
+        result.set_fill_type(self.0.fill_type());
+        result.reverse_add_path(&self.0);
+        Ok(LuaPath::new(result))
     }
     pub fn is_convex(&self) -> bool {
         Ok(self.0.is_convex())
@@ -2284,8 +3613,38 @@ impl LuaPath {
     pub fn is_rect(&self) -> Option<LuaRect> {
         Ok(self.0.is_rect().map(|(rect, _, _)| LuaRect::from(rect)))
     }
+    /// Returns a copy of the path with curves replaced by line segments
+    /// deviating at most `tolerance` (0.25 by default) from them.
+    pub fn flatten(&self, tolerance: Option<f32>) -> LuaPath {
+        let tolerance = valid_tolerance(tolerance)?;
+        Ok(LuaPath::new(tessellate::flatten(&self.0, tolerance)))
+    }
+    /// Triangulates the path filled after flattening it with `tolerance`.
+    ///
+    /// Returns `{positions, indices}`, where `positions` holds `x, y` pairs
+    /// and `indices` holds 0-based vertex indices of triangles. Contours are
+    /// ear clipped separately, so holes are filled and self-intersecting
+    /// contours produce overlapping triangles.
+    pub fn to_triangles<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        tolerance: Option<f32>,
+    ) -> LuaTable<'lua> {
+        let tolerance = valid_tolerance(tolerance)?;
+        let (positions, indices) = tessellate::triangulate(&self.0, tolerance);
+        let result = lua.create_table()?;
+        result.set(
+            "positions",
+            positions
+                .iter()
+                .flat_map(|it| [it.x, it.y])
+                .collect::<Vec<_>>(),
+        )?;
+        result.set("indices", indices)?;
+        Ok(result)
+    }
     pub fn is_r_rect(&self) -> Option<LuaRRect> {
-        Ok(self.0.is_rrect().map(LuaRRect))
+        Ok(self.0.is_rrect().map(LuaRRect::new))
     }
     pub fn is_valid(&self) -> bool {
         Ok(self.0.is_valid())
@@ -2293,13 +3652,14 @@ impl LuaPath {
     pub fn is_volatile(&self) -> bool {
         Ok(self.0.is_volatile())
     }
+    #[lua(chain)]
     pub fn line_to(&mut self, point: LuaPoint) {
         self.0.line_to(point);
         Ok(())
     }
     pub fn make_scale(&mut self, sx: f32, sy: Option<f32>) -> LuaPath {
         let sy = sy.unwrap_or(sx);
-        Ok(LuaPath(self.0.make_scale((sx, sy))))
+        Ok(LuaPath::new(self.0.make_scale((sx, sy))))
     }
     pub fn make_transform(&mut self, matrix: LuaMatrix, pc: Option<bool>) -> LuaPath {
         let matrix = matrix.into();
@@ -2307,20 +3667,24 @@ impl LuaPath {
             true => skia_safe::matrix::ApplyPerspectiveClip::Yes,
             false => skia_safe::matrix::ApplyPerspectiveClip::No,
         };
-        Ok(LuaPath(self.0.make_transform(&matrix, pc)))
+        Ok(LuaPath::new(self.0.make_transform(&matrix, pc)))
     }
+    #[lua(chain)]
     pub fn move_to(&mut self, p: LuaPoint) {
         self.0.move_to(p);
         Ok(())
     }
+    #[lua(chain)]
     pub fn offset(&mut self, d: LuaPoint) {
         self.0.offset(d);
         Ok(())
     }
+    #[lua(chain)]
     pub fn quad_to(&mut self, points: MaybeUnpacked<[LuaPoint; 2]>) {
         self.0.quad_to(points[0], points[1]);
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_arc_to(
         &mut self,
         r: LuaPoint,
@@ -2333,54 +3697,67 @@ impl LuaPath {
             .r_arc_to_rotated(r, x_axis_rotate, *arc_size, *sweep, d);
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_conic_to(&mut self, d1: LuaPoint, d2: LuaPoint, w: f32) {
         self.0.r_conic_to(d1, d2, w);
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_cubic_to(&mut self, d1: LuaPoint, d2: LuaPoint, d3: LuaPoint) {
         self.0.r_cubic_to(d1, d2, d3);
         Ok(())
     }
+    #[lua(chain)]
     pub fn reset(&mut self) {
         self.0.reset();
         Ok(())
     }
+    #[lua(chain)]
     pub fn reverse_add_path(&mut self, path: LuaPath) {
         self.0.reverse_add_path(&path.0);
         Ok(())
     }
+    #[lua(chain)]
     pub fn rewind(&mut self) {
         self.0.rewind();
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_line_to(&mut self, point: LuaPoint) {
         self.0.r_line_to(point);
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_move_to(&mut self, point: LuaPoint) {
         self.0.r_move_to(point);
         Ok(())
     }
+    #[lua(chain)]
     pub fn r_quad_to(&mut self, dx1: LuaPoint, dx2: LuaPoint) {
         self.0.r_quad_to(dx1, dx2);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_fill_type(&mut self, fill_type: LuaPathFillType) {
         self.0.set_fill_type(*fill_type);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_is_volatile(&mut self, is_volatile: bool) {
         self.0.set_is_volatile(is_volatile);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_last_pt(&mut self, point: LuaPoint) {
         self.0.set_last_pt(point);
         Ok(())
     }
+    #[lua(chain)]
     pub fn toggle_inverse_fill_type(&mut self) {
         self.0.toggle_inverse_fill_type();
         Ok(())
     }
+    #[lua(chain)]
     pub fn transform(&mut self, matrix: LuaMatrix) {
         let matrix = matrix.into();
         self.0.transform(&matrix);
@@ -2394,9 +3771,14 @@ wrap_skia_handle!(RRect);
 impl LuaRRect {
     #[lua(constructor)]
     pub fn make() -> LuaRRect {
-        Ok(LuaRRect(RRect::new()))
+        Ok(LuaRRect::new(RRect::new()))
     }
 
+    /// Returns a copy of this RRect.
+    #[lua(rename: "clone")]
+    pub fn duplicate(&self) -> LuaRRect {
+        Ok(LuaRRect::new(self.0))
+    }
     pub fn contains(&self, rect: LuaRect) -> bool {
         let rect: Rect = rect.into();
         Ok(self.0.contains(rect))
@@ -2410,10 +3792,15 @@ impl LuaRRect {
     pub fn height(&self) -> f32 {
         Ok(self.0.height())
     }
+    #[lua(chain)]
     pub fn inset(&mut self, delta: LuaPoint) {
         self.0.inset(delta);
         Ok(())
     }
+    /// Returns bounds of this RRect inset by `delta`.
+    pub fn inset_rect(&self, delta: LuaPoint) -> LuaRect {
+        Ok(LuaRect::from(self.0.rect().with_inset(delta)))
+    }
     pub fn is_complex(&self) -> bool {
         Ok(self.0.is_complex())
     }
@@ -2436,12 +3823,14 @@ impl LuaRRect {
         Ok(self.0.is_valid())
     }
     pub fn make_offset(&self, delta: LuaPoint) -> LuaRRect {
-        Ok(LuaRRect(self.0.with_offset(delta)))
+        Ok(LuaRRect::new(self.0.with_offset(delta)))
     }
+    #[lua(chain)]
     pub fn offset(&mut self, delta: LuaPoint) {
         self.0.offset(delta);
         Ok(())
     }
+    #[lua(chain)]
     pub fn outset(&mut self, delta: LuaPoint) {
         self.0.outset(delta);
         Ok(())
@@ -2456,26 +3845,31 @@ impl LuaRRect {
     pub fn rect(&self) -> LuaRect {
         Ok(LuaRect::from(*self.0.rect()))
     }
+    #[lua(chain)]
     pub fn set_empty(&mut self) {
         self.0.set_empty();
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_nine_patch(&mut self, rect: LuaRect, sides: SidePack) {
         let rect: Rect = rect.into();
         self.0
             .set_nine_patch(rect, sides.left, sides.top, sides.right, sides.bottom);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_oval(&mut self, oval: LuaRect) {
         let oval: Rect = oval.into();
         self.0.set_oval(oval);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_rect(&mut self, rect: LuaRect) {
         let rect: Rect = rect.into();
         self.0.set_rect(rect);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_rect_radii(&mut self, rect: LuaRect, radii: MaybeUnpacked<[LuaPoint; 4]>) {
         let rect: Rect = rect.into();
         let radii: [Point; 4] = radii
@@ -2487,6 +3881,7 @@ impl LuaRRect {
         self.0.set_rect_radii(rect, &radii);
         Ok(())
     }
+    #[lua(chain)]
     pub fn set_rect_xy(&mut self, rect: LuaRect, x_rad: f32, y_rad: f32) {
         let rect: Rect = rect.into();
         self.0.set_rect_xy(rect, x_rad, y_rad);
@@ -2494,7 +3889,7 @@ impl LuaRRect {
     }
     pub fn transform(&self, matrix: LuaMatrix) -> Option<LuaRRect> {
         let matrix: Matrix = matrix.into();
-        Ok(self.0.transform(&matrix).map(LuaRRect))
+        Ok(self.0.transform(&matrix).map(LuaRRect::new))
     }
     #[lua(rename: "type")]
     pub fn get_type(&self) -> LuaRRectType {
@@ -2503,6 +3898,292 @@ impl LuaRRect {
     pub fn width(&self) -> f32 {
         Ok(self.0.width())
     }
+    /// Returns a copy of this RRect inset by `delta`, leaving this one
+    /// unchanged.
+    pub fn with_inset(&self, delta: LuaPoint) -> LuaRRect {
+        Ok(LuaRRect::new(self.0.with_inset(delta)))
+    }
+    /// Returns a copy of this RRect outset by `delta`, leaving this one
+    /// unchanged.
+    pub fn with_outset(&self, delta: LuaPoint) -> LuaRRect {
+        Ok(LuaRRect::new(self.0.with_outset(delta)))
+    }
+}
+
+wrap_skia_handle!(Region);
+
+#[lua_methods(lua_name: Region)]
+impl LuaRegion {
+    /// Creates a region covering `rect` rounded out to whole pixels, or an
+    /// empty one.
+    #[lua(constructor)]
+    pub fn make(rect: LuaFallible<LuaRect>) -> LuaRegion {
+        Ok(LuaRegion::new(match rect.into_inner() {
+            Some(rect) => Region::from_rect(irect_out(rect.into())),
+            None => Region::new(),
+        }))
+    }
+
+    /// Returns a copy of this region.
+    #[lua(rename: "clone")]
+    pub fn duplicate(&self) -> LuaRegion {
+        Ok(LuaRegion::new(self.0.clone()))
+    }
+    pub fn get_bounds(&self) -> LuaRect {
+        Ok(LuaRect::from(Rect::from_irect(*self.0.bounds())))
+    }
+    pub fn is_empty(&self) -> bool {
+        Ok(self.0.is_empty())
+    }
+    pub fn is_rect(&self) -> bool {
+        Ok(self.0.is_rect())
+    }
+    pub fn is_complex(&self) -> bool {
+        Ok(self.0.is_complex())
+    }
+    /// Returns `true` if the pixel containing `point` is in the region.
+    pub fn contains(&self, point: LuaPoint) -> bool {
+        let point: Point = point.into();
+        Ok(self
+            .0
+            .contains_point(IPoint::new(point.x.floor() as i32, point.y.floor() as i32)))
+    }
+    /// Combines this region with `rect` rounded out to whole pixels.
+    #[lua(chain)]
+    pub fn op_rect(&mut self, rect: LuaRect, op: LuaRegionOp) {
+        self.0.op_rect(irect_out(rect.into()), *op);
+        Ok(())
+    }
+    #[lua(chain)]
+    pub fn op_region(&mut self, region: &LuaRegion, op: LuaRegionOp) {
+        self.0.op_region(&region.0, *op);
+        Ok(())
+    }
+    /// Moves the region by `delta` rounded to whole pixels.
+    #[lua(chain)]
+    pub fn translate(&mut self, delta: LuaPoint) {
+        let delta: Point = delta.into();
+        self.0
+            .translate(IVector::new(delta.x.round() as i32, delta.y.round() as i32));
+        Ok(())
+    }
+    /// Returns the outline of the region, which can be stroked to show its
+    /// edges.
+    pub fn to_path(&self) -> LuaPath {
+        let mut path = Path::new();
+        self.0.get_boundary_path(&mut path);
+        Ok(LuaPath::new(path))
+    }
+}
+
+/// Returns `rect` rounded out to whole pixels.
+fn irect_out(rect: Rect) -> IRect {
+    rect.round_out()
+}
+
+/// Shape argument accepting a [`LuaRRect`], [`LuaPath`], [`LuaRect`] table
+/// or a circle table (`{x = x, y = y, r = r}` or `{center = point, radius = r}`).
+#[derive(Clone)]
+pub enum LikeShape {
+    Rect(Rect),
+    RRect(RRect),
+    Path(Path),
+    Circle(Point, f32),
+}
+
+impl LikeShape {
+    pub fn bounds(&self) -> Rect {
+        match self {
+            LikeShape::Rect(it) => *it,
+            LikeShape::RRect(it) => *it.rect(),
+            LikeShape::Path(it) => *it.bounds(),
+            LikeShape::Circle(center, r) => {
+                Rect::new(center.x - r, center.y - r, center.x + r, center.y + r)
+            }
+        }
+    }
+
+    /// Returns the shape as an RRect, or `None` for paths which aren't a
+    /// rect, oval or rounded rect.
+    pub fn to_rrect(&self) -> Option<RRect> {
+        match self {
+            LikeShape::Rect(it) => Some(RRect::new_rect(it)),
+            LikeShape::RRect(it) => Some(*it),
+            LikeShape::Path(it) => it
+                .is_rrect()
+                .or_else(|| it.is_oval().map(RRect::new_oval))
+                .or_else(|| it.is_rect().map(|(it, ..)| RRect::new_rect(it))),
+            LikeShape::Circle(..) => Some(RRect::new_oval(self.bounds())),
+        }
+    }
+
+    /// Returns `true` if drawing the shape doesn't cover its bounds, e.g.
+    /// for inverse filled paths.
+    fn is_unbounded(&self) -> bool {
+        matches!(self, LikeShape::Path(it) if it.is_inverse_fill_type())
+    }
+
+    pub fn clip(&self, canvas: &Canvas, anti_alias: bool) {
+        match self {
+            LikeShape::Rect(it) => canvas.clip_rect(it, None, anti_alias),
+            LikeShape::RRect(it) => canvas.clip_rrect(it, None, anti_alias),
+            LikeShape::Path(it) => canvas.clip_path(it, None, anti_alias),
+            LikeShape::Circle(..) => {
+                canvas.clip_rrect(RRect::new_oval(self.bounds()), None, anti_alias)
+            }
+        };
+    }
+
+    pub fn draw(&self, canvas: &Canvas, paint: &Paint) {
+        match self {
+            LikeShape::Rect(it) => {
+                canvas.draw_rect(it, paint);
+            }
+            LikeShape::RRect(it) => {
+                canvas.draw_rrect(it, paint);
+            }
+            LikeShape::Path(it) => profile::draw_path(canvas, it, paint),
+            LikeShape::Circle(center, r) => {
+                canvas.draw_circle(*center, *r, paint);
+            }
+        }
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for LikeShape {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(ud) = args.pop_typed::<LuaAnyUserData>() {
+            if let Ok(rrect) = ud.borrow::<LuaRRect>() {
+                return Ok(LikeShape::RRect(rrect.0));
+            }
+            if let Ok(path) = ud.borrow::<LuaPath>() {
+                return Ok(LikeShape::Path(path.0.clone()));
+            }
+            args.revert(ud);
+        }
+        if let Some(table) = args.pop_typed::<LuaTable>() {
+            let radius: Option<f32> = match table.get::<_, Option<f32>>("radius")? {
+                Some(it) => Some(it),
+                None => table.get("r")?,
+            };
+            if let Some(radius) = radius {
+                let center = match table.get::<_, Option<LuaTable>>("center")? {
+                    Some(center) => LuaPoint::try_from(center),
+                    None => LuaPoint::try_from(table.clone()),
+                };
+                match center {
+                    Ok(center) => return Ok(LikeShape::Circle(center.into(), radius)),
+                    Err(err) => {
+                        args.revert(table);
+                        return Err(args.bad_argument(err));
+                    }
+                }
+            }
+            args.revert(table);
+        }
+        let value = args.pop();
+        match LuaRect::from_lua(value.clone(), lua) {
+            Ok(rect) => Ok(LikeShape::Rect(rect.into())),
+            Err(err) => {
+                args.revert(value);
+                Err(args.bad_argument(err))
+            }
+        }
+    }
+}
+
+/// Rounded rect argument given as an `RRect`, or as a rect followed by an
+/// optional corner radius.
+///
+/// Radii too large for the rect are scaled down the same way `RRect`
+/// constructors do.
+pub struct RoundedRect(pub RRect);
+
+impl<'lua> FromArgPack<'lua> for RoundedRect {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(ud) = args.pop_typed::<LuaAnyUserData>() {
+            if let Ok(rrect) = ud.borrow::<LuaRRect>() {
+                return Ok(RoundedRect(rrect.0));
+            }
+            args.revert(ud);
+        }
+        let rect: Rect = LuaRect::convert(args, lua)?.into();
+        let radius = LuaFallible::<f32>::convert(args, lua)?.unwrap_or_default();
+        Ok(RoundedRect(RRect::new_rect_xy(rect, radius, radius)))
+    }
+}
+
+/// Fill and stroke paints of [`LuaCanvas::draw_styled`], each a `Paint` or
+/// a paint constructor table.
+pub struct ShapeStyle {
+    pub fill: Option<Paint>,
+    pub stroke: Option<Paint>,
+}
+
+impl<'lua> FromArgPack<'lua> for ShapeStyle {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = args.pop_typed_or::<LuaTable, _>(Some("expected {fill, stroke} table"))?;
+        let fill = table.try_get::<_, LikePaint>("fill", lua)?;
+        let stroke = table.try_get::<_, LikePaint>("stroke", lua)?;
+        Ok(ShapeStyle {
+            fill: fill.map(LikePaint::unwrap),
+            stroke: stroke.map(LikePaint::unwrap),
+        })
+    }
+}
+
+/// Width and color of a single border side.
+#[derive(Clone, Copy)]
+pub struct LuaBorderSide {
+    pub width: f32,
+    pub color: Color4f,
+}
+
+impl<'lua> FromLua<'lua> for LuaBorderSide {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match value {
+            LuaValue::Table(it) => it,
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "BorderSide",
+                    message: Some("expected a {width, color} table".to_string()),
+                })
+            }
+        };
+
+        let width: f32 = match table.get::<_, Option<f32>>("width")? {
+            Some(it) => it,
+            None => table.get(1)?,
+        };
+        let color: LuaValue = match table.get("color")? {
+            LuaNil => table.get(2)?,
+            it => it,
+        };
+        let color: Color4f = LuaColor::from_lua(color, lua)?.into();
+        Ok(LuaBorderSide { width, color })
+    }
+}
+
+/// Border sides in `top`, `right`, `bottom`, `left` order.
+pub struct LuaBorders([Option<LuaBorderSide>; 4]);
+
+impl<'lua> FromArgPack<'lua> for LuaBorders {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table: LuaTable = args.pop_typed_or(Some(
+            "expected a borders table with top, right, bottom and/or left sides",
+        ))?;
+        let mut sides = [None; 4];
+        for (side, name) in sides.iter_mut().zip(border::SIDES) {
+            *side = table
+                .get::<_, Option<LuaBorderSide>>(name)
+                .map_err(|inner| LuaError::CallbackError {
+                    traceback: format!("while reading '{}' border side", name),
+                    cause: Arc::new(inner),
+                })?;
+        }
+        Ok(LuaBorders(sides))
+    }
 }
 
 wrap_skia_handle!(ColorInfo);
@@ -2516,7 +4197,7 @@ impl LuaColorInfo {
         Ok(self.0.bytes_per_pixel())
     }
     pub fn color_space(&self) -> Option<LuaColorSpace> {
-        Ok(self.0.color_space().map(LuaColorSpace))
+        Ok(self.0.color_space().map(LuaColorSpace::new))
     }
     pub fn color_type(&self) -> LuaColorType {
         Ok(LuaColorType(self.0.color_type()))
@@ -2528,16 +4209,16 @@ impl LuaColorInfo {
         Ok(self.0.is_opaque())
     }
     pub fn make_alpha_type(&self, alpha_type: LuaAlphaType) -> LuaColorInfo {
-        Ok(LuaColorInfo(self.0.with_alpha_type(*alpha_type)))
+        Ok(LuaColorInfo::new(self.0.with_alpha_type(*alpha_type)))
     }
     pub fn make_color_space(&self, color_space: Option<LuaColorSpace>) -> LuaColorInfo {
-        Ok(LuaColorInfo(
+        Ok(LuaColorInfo::new(
             self.0
                 .with_color_space(color_space.map(LuaColorSpace::unwrap)),
         ))
     }
     pub fn make_color_type(&self, color_type: LuaColorType) -> LuaColorInfo {
-        Ok(LuaColorInfo(self.0.with_color_type(*color_type)))
+        Ok(LuaColorInfo::new(self.0.with_color_type(*color_type)))
     }
     pub fn shift_per_pixel(&self) -> usize {
         Ok(self.0.shift_per_pixel())
@@ -2560,7 +4241,7 @@ impl LuaImageInfo {
     pub fn color_info<'lua>(&self, lua: &'lua LuaContext) -> LuaTable<'lua> {
         let result = lua.create_table()?;
         let info = self.0.color_info();
-        result.set("colorSpace", info.color_space().map(LuaColorSpace))?;
+        result.set("colorSpace", info.color_space().map(LuaColorSpace::new))?;
         result.set("colorType", LuaColorType(info.color_type()))?;
         result.set("alphaType", LuaAlphaType(info.alpha_type()))?;
         result.set("isOpaque", info.is_opaque())?;
@@ -2570,7 +4251,7 @@ impl LuaImageInfo {
         Ok(result)
     }
     pub fn color_space(&self) -> Option<LuaColorSpace> {
-        Ok(self.0.color_space().map(LuaColorSpace))
+        Ok(self.0.color_space().map(LuaColorSpace::new))
     }
     pub fn color_type(&self) -> LuaColorType {
         Ok(LuaColorType(self.0.color_type()))
@@ -2603,16 +4284,18 @@ impl LuaImageInfo {
         Ok(self.0.is_opaque())
     }
     pub fn make_alpha_type(&self, alpha_type: LuaAlphaType) -> LuaImageInfo {
-        Ok(LuaImageInfo(self.0.with_alpha_type(*alpha_type)))
+        Ok(LuaImageInfo::new(self.0.with_alpha_type(*alpha_type)))
     }
     pub fn make_color_space(&self, color_space: LuaColorSpace) -> LuaImageInfo {
-        Ok(LuaImageInfo(self.0.with_color_space(color_space.unwrap())))
+        Ok(LuaImageInfo::new(
+            self.0.with_color_space(color_space.unwrap()),
+        ))
     }
     pub fn make_color_type(&self, color_type: LuaColorType) -> LuaImageInfo {
-        Ok(LuaImageInfo(self.0.with_color_type(*color_type)))
+        Ok(LuaImageInfo::new(self.0.with_color_type(*color_type)))
     }
     pub fn make_dimensions(&self, dimensions: LuaSize) -> LuaImageInfo {
-        Ok(LuaImageInfo(self.0.with_dimensions(dimensions)))
+        Ok(LuaImageInfo::new(self.0.with_dimensions(dimensions)))
     }
     pub fn min_row_bytes(&self) -> usize {
         Ok(self.0.min_row_bytes())
@@ -2640,16 +4323,29 @@ type_like_table!(ImageInfo: |value: LuaTable| {
     let alpha_type = LuaAlphaType::try_from(
         value
             .get::<_, String>("alpha_type")
-            .unwrap_or("unknown".to_string()),
+            .unwrap_or("premul".to_string()),
     )?;
+    // some color types only support some alpha types, e.g. "gray8" is always
+    // opaque and "alpha8" can't be unpremultiplied
+    let alpha_type = match *color_type {
+        ColorType::Unknown => *alpha_type,
+        color_type => color_type
+            .validate_alpha_type(*alpha_type)
+            .ok_or_else(|| {
+                LuaError::RuntimeError(format!(
+                    "alpha type {:?} isn't supported by color type {:?}",
+                    *alpha_type, color_type
+                ))
+            })?,
+    };
     let color_space = value
         .get_user_data::<_, LuaColorSpace>("color_space")
         .ok()
-        .map(|it| it.0);
+        .map(LuaColorSpace::unwrap);
 
-    let result = ImageInfo::new(dimensions, *color_type, *alpha_type, color_space);
+    let result = ImageInfo::new(dimensions, *color_type, alpha_type, color_space);
 
-    Ok(LuaImageInfo(result))
+    Ok(LuaImageInfo::new(result))
 });
 
 wrap_skia_handle!(SurfaceProps);
@@ -2670,28 +4366,72 @@ impl LuaSurfaceProps {
     }
 }
 
-type_like_table!(SurfaceProps: |value: LuaTable| {
-    let flags = match value.get::<_, LuaValue>("flags") {
-        Ok(LuaValue::Table(it)) => LuaSurfacePropsFlags::from_table(it)?.0,
-        Ok(LuaNil) => {
-            SurfacePropsFlags::empty()
-        }
-        Ok(other) => {
-            return Err(LuaError::FromLuaConversionError { from: other.type_name(), to: "SurfacePropFlags", message: None })
-        }
-        Err(other) => return Err(other)
-    };
-    let pixel_geometry = LuaPixelGeometry::try_from(value.get::<_, String>("pixel_geometry").unwrap_or("unknown".to_string()))?;
-
-    Ok(LuaSurfaceProps(SurfaceProps::new(flags, *pixel_geometry)))
-});
-
-pub struct LuaSamplingOptions {
-    pub filter_mode: FilterMode,
-    pub mipmap_mode: MipmapMode,
-}
+type_like!(SurfaceProps);
 
-impl Default for LuaSamplingOptions {
+impl<'lua> TryFrom<LuaTable<'lua>> for LuaSurfaceProps {
+    type Error = LuaError;
+    fn try_from(value: LuaTable<'lua>) -> Result<Self, Self::Error> {
+        let flags = match value.get::<_, LuaValue>("flags") {
+            Ok(LuaValue::Table(it)) => LuaSurfacePropsFlags::from_table(it)?.0,
+            Ok(LuaNil) => SurfacePropsFlags::empty(),
+            Ok(other) => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "SurfacePropFlags",
+                    message: None,
+                })
+            }
+            Err(other) => return Err(other),
+        };
+        let pixel_geometry = value
+            .get::<_, Option<String>>("pixel_geometry")?
+            .or(value.get::<_, Option<String>>("pixelGeometry")?)
+            .unwrap_or_else(|| "unknown".to_string());
+        let pixel_geometry = LuaPixelGeometry::try_from(pixel_geometry)?;
+
+        Ok(LuaSurfaceProps::new(SurfaceProps::new(
+            flags,
+            *pixel_geometry,
+        )))
+    }
+}
+
+/// ## Supported formats
+/// - [`LuaSurfaceProps`]
+/// - pixel geometry name, e.g. `"rgbh"`, for props without flags
+/// - {flags: {string...}, pixel_geometry: string}
+impl<'lua> FromLua<'lua> for LikeSurfaceProps {
+    fn from_lua(value: LuaValue<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        match value {
+            LuaValue::UserData(ud) if ud.is::<LuaSurfaceProps>() => {
+                Ok(LikeSurfaceProps(ud.borrow::<LuaSurfaceProps>()?.to_owned()))
+            }
+            LuaValue::String(name) => {
+                let pixel_geometry = LuaPixelGeometry::try_from(name)?;
+                Ok(LikeSurfaceProps(LuaSurfaceProps::new(SurfaceProps::new(
+                    SurfacePropsFlags::empty(),
+                    *pixel_geometry,
+                ))))
+            }
+            LuaValue::Table(it) => LuaSurfaceProps::try_from(it).map(LikeSurfaceProps),
+            other => Err(LuaError::FromLuaConversionError {
+                from: other.type_name(),
+                to: "SurfaceProps",
+                message: Some(
+                    "expected SurfaceProps, pixel geometry name or constructor Table".to_string(),
+                ),
+            }),
+        }
+    }
+}
+from_lua_argpack!(LikeSurfaceProps);
+
+pub struct LuaSamplingOptions {
+    pub filter_mode: FilterMode,
+    pub mipmap_mode: MipmapMode,
+}
+
+impl Default for LuaSamplingOptions {
     fn default() -> Self {
         LuaSamplingOptions {
             filter_mode: FilterMode::Nearest,
@@ -2700,6 +4440,20 @@ impl Default for LuaSamplingOptions {
     }
 }
 
+/// Logs a warning in debug builds when `sampling` requests mipmaps `image`
+/// doesn't have, as it's then drawn as if mipmapping was disabled.
+fn warn_missing_mipmaps(call: &str, image: &Image, sampling: &SamplingOptions) {
+    if cfg!(debug_assertions) && sampling.mipmap != MipmapMode::None && !image.has_mipmaps() {
+        log::warn!(
+            "{}: image has no mipmaps, so mipmap sampling has no effect; use Image:withDefaultMipmaps()",
+            call
+        );
+    }
+}
+
+/// Mipmap modes other than `"none"` only apply to images with mipmaps (see
+/// `Image:withDefaultMipmaps()`).
+///
 /// ## Supported formats
 /// - { filter: Filter, mipmap: Mipmap }
 /// - FilterMode, Mipmap
@@ -2780,29 +4534,248 @@ impl From<LuaSamplingOptions> for SamplingOptions {
     }
 }
 
-wrap_skia_handle!(Surface);
+/// Mask argument of `Canvas:withAlphaMask`.
+pub enum ShaderOrImage {
+    Shader(Shader),
+    Image(Image),
+}
+
+impl<'lua> FromArgPack<'lua> for ShaderOrImage {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let value = args.pop();
+        if let LuaValue::UserData(ud) = &value {
+            if let Ok(shader) = ud.borrow::<LuaShader>() {
+                return Ok(ShaderOrImage::Shader(shader.0.clone()));
+            }
+            if let Ok(image) = ud.borrow::<LuaImage>() {
+                return Ok(ShaderOrImage::Image(image.0.clone()));
+            }
+        }
+        let from = value.type_name();
+        args.revert(value);
+        Err(args.bad_argument(LuaError::FromLuaConversionError {
+            from,
+            to: "Shader",
+            message: Some("expected a Shader or an Image".to_string()),
+        }))
+    }
+}
+
+/// Text argument of `Canvas:drawTextBlob`.
+pub enum BlobOrString {
+    Blob(TextBlob),
+    String(String),
+}
+
+impl<'lua> FromArgPack<'lua> for BlobOrString {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let value = args.pop();
+        match &value {
+            LuaValue::UserData(ud) => {
+                if let Ok(blob) = ud.borrow::<LuaTextBlob>() {
+                    return Ok(BlobOrString::Blob(blob.0.clone()));
+                }
+            }
+            LuaValue::String(text) => {
+                return Ok(BlobOrString::String(text.to_str()?.to_string()));
+            }
+            _ => {}
+        }
+        let from = value.type_name();
+        args.revert(value);
+        Err(args.bad_argument(LuaError::FromLuaConversionError {
+            from,
+            to: "TextBlob",
+            message: Some("expected a TextBlob or a string".to_string()),
+        }))
+    }
+}
+
+/// Source of pixels for `Surface:diff` and `Canvas:drawMagnifier`.
+pub enum SurfaceOrImage {
+    Surface(Surface),
+    Image(Image),
+}
+
+impl SurfaceOrImage {
+    fn kind(&self) -> &'static str {
+        match self {
+            SurfaceOrImage::Surface(_) => "surface",
+            SurfaceOrImage::Image(_) => "image",
+        }
+    }
+
+    fn dimensions(&self) -> ISize {
+        match self {
+            SurfaceOrImage::Surface(it) => it.image_info().dimensions(),
+            SurfaceOrImage::Image(it) => it.dimensions(),
+        }
+    }
+
+    /// Returns the image, or a snapshot of current surface contents.
+    fn to_image(&mut self) -> Image {
+        match self {
+            SurfaceOrImage::Surface(it) => it.image_snapshot(),
+            SurfaceOrImage::Image(it) => it.clone(),
+        }
+    }
+
+    /// Reads all pixels into `pixels` with tightly packed rows.
+    fn read_pixels(&mut self, info: &ImageInfo, pixels: &mut [u8]) -> bool {
+        let row_bytes = info.min_row_bytes();
+        match self {
+            SurfaceOrImage::Surface(it) => it.read_pixels(info, pixels, row_bytes, (0, 0)),
+            SurfaceOrImage::Image(it) => {
+                it.read_pixels(info, pixels, row_bytes, (0, 0), image::CachingHint::Allow)
+            }
+        }
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for SurfaceOrImage {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        let value = args.pop();
+        if let LuaValue::UserData(ud) = &value {
+            if let Ok(surface) = ud.borrow::<LuaSurface>() {
+                return Ok(SurfaceOrImage::Surface(surface.0.clone()));
+            }
+            if let Ok(image) = ud.borrow::<LuaImage>() {
+                return Ok(SurfaceOrImage::Image(image.0.clone()));
+            }
+        }
+        let from = value.type_name();
+        args.revert(value);
+        Err(args.bad_argument(LuaError::FromLuaConversionError {
+            from,
+            to: "Surface",
+            message: Some("expected a Surface or an Image".to_string()),
+        }))
+    }
+}
+
+/// Dimensions or full image info of a surface created from another one.
+pub enum SurfaceSpec {
+    Size(ISize),
+    Info(ImageInfo),
+}
+
+impl SurfaceSpec {
+    /// Returns image info of the specified surface; given only dimensions,
+    /// the rest is taken from `base`.
+    fn image_info(self, base: &ImageInfo) -> ImageInfo {
+        match self {
+            SurfaceSpec::Size(size) => base.with_dimensions(size),
+            SurfaceSpec::Info(it) => it,
+        }
+    }
+}
+
+impl<'lua> FromArgPack<'lua> for SurfaceSpec {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        if let Some(width) = args.pop_typed::<i32>() {
+            let height = args.pop_typed_or::<i32, _>(Some("expected surface height"))?;
+            return Ok(SurfaceSpec::Size(ISize::new(width, height)));
+        }
+        LikeImageInfo::convert(args, lua).map(|it| SurfaceSpec::Info(it.unwrap()))
+    }
+}
+
+/// Number of times a surface replaced its backing surface, shared with
+/// canvases returned by `Surface:getCanvas`.
+#[derive(Default)]
+pub struct SurfaceGeneration(Cell<u64>);
+
+/// Generation of the surface a canvas was created for.
+#[derive(Clone)]
+pub struct SurfaceStamp {
+    generation: Rc<SurfaceGeneration>,
+    value: u64,
+}
+
+impl SurfaceStamp {
+    fn is_current(&self) -> bool {
+        self.generation.0.get() == self.value
+    }
+}
+
+wrap_skia_handle!(
+    Surface,
+    pixels: |surface| stats::surface_pixels(surface),
+    meta: SurfaceGeneration
+);
+
+impl LuaSurface {
+    /// Replaces the backing surface, e.g. after the host resized the window
+    /// it's presented in. Canvases previously returned by `getCanvas` raise
+    /// an error instead of drawing into the old surface.
+    pub fn replace(&mut self, mut surface: Surface) {
+        self.2
+            .set_pixels(<LuaSurface as stats::TrackedHandle>::pixels(&mut surface));
+        self.0 = surface;
+        if let Some(generation) = &self.1 {
+            generation.0.set(generation.0.get() + 1);
+        }
+    }
+
+    /// Returns the stamp of canvases drawing into the current backing
+    /// surface.
+    fn stamp(&mut self) -> SurfaceStamp {
+        let generation = self.1.get_or_insert_with(Default::default).clone();
+        SurfaceStamp {
+            value: generation.0.get(),
+            generation,
+        }
+    }
+}
+
+/// Returns a snapshot of `surface` with pixels converted to color type, alpha
+/// type and color space of `info`, such as the format a compositor expects
+/// regardless of the one scripts chose. Dimensions of `info` are ignored.
+pub fn snapshot_as(surface: &mut Surface, info: &ImageInfo) -> Result<Image, &'static str> {
+    let snapshot = surface.image_snapshot();
+    convert_image(&snapshot, &info.with_dimensions(snapshot.dimensions()))
+}
 
 #[lua_methods(lua_name: Surface)]
 impl LuaSurface {
-    pub fn null(size: LuaSize) -> Option<LuaSurface> {
+    pub fn null(size: LuaSize) -> LuaSurface {
         let size: ISize = size.into();
-        Ok(surfaces::null(size).map(LuaSurface))
-    }
+        Ok(surfaces::null(size)
+            .map(LuaSurface::new)
+            .or_failed("Surface.null", || {
+                format!("invalid size {}x{}", size.width, size.height)
+            })?)
+    }
+    /// Creates a raster surface. `props` can be given as a pixel geometry
+    /// name (e.g. `"rgbh"`) for LCD text rendering.
     pub fn raster(
         info: LikeImageInfo,
         row_bytes: LuaFallible<usize>,
         props: LuaFallible<LikeSurfaceProps>,
-    ) -> Option<LuaSurface> {
+    ) -> LuaSurface {
         let info: ImageInfo = info.unwrap();
         let row_bytes = row_bytes.unwrap_or_else(|| info.min_row_bytes());
         let props: Option<SurfaceProps> = props.map_t();
 
-        Ok(surfaces::raster(&info, row_bytes, props.as_ref()).map(LuaSurface))
+        Ok(surfaces::raster(&info, row_bytes, props.as_ref())
+            .map(LuaSurface::new)
+            .or_failed("Surface.raster", || {
+                format!(
+                    "unsupported {}x{} surface with color type {:?}, alpha type {:?} and {} row bytes",
+                    info.width(),
+                    info.height(),
+                    info.color_type(),
+                    info.alpha_type(),
+                    row_bytes
+                )
+            })?)
     }
     // wrap_pixels - not able to detect table value updates
 
     // capabilities - not useful from Lua?
     // characterize - no graphite bindings
+    /// Draws the whole surface into `canvas` at `offset`. Use
+    /// `Canvas:drawSurface` to draw only a part of it or scale it.
     pub fn draw(
         &mut self,
         canvas: &LuaCanvas,
@@ -2810,16 +4783,24 @@ impl LuaSurface {
         sampling: LuaFallible<LuaSamplingOptions>,
         paint: LuaFallible<LikePaint>,
     ) {
+        canvas.check_valid()?;
         let sampling: SamplingOptions = sampling.unwrap_or_default().into();
         let paint = paint.map(LikePaint::unwrap);
 
-        self.0
-            .draw(canvas.canvas(), offset, sampling, paint.as_ref());
+        canvas.draw_surface_area(
+            &mut self.0,
+            None,
+            PointOrRect::Point(offset.into()),
+            sampling,
+            paint.as_ref(),
+        );
         Ok(())
     }
     // generationID - not useful from Lua without graphite?
+    /// Returns a canvas drawing into the surface. It raises an error when
+    /// used after the surface is resized.
     pub fn get_canvas(&mut self) -> LuaCanvas {
-        Ok(LuaCanvas::Owned(self.0.clone()))
+        Ok(LuaCanvas::Owned(self.0.clone(), Some(self.stamp())))
     }
     pub fn width(&self) -> i32 {
         Ok(self.0.width())
@@ -2828,18 +4809,80 @@ impl LuaSurface {
         Ok(self.0.height())
     }
     pub fn image_info(&mut self) -> LuaImageInfo {
-        Ok(LuaImageInfo(self.0.image_info()))
+        Ok(LuaImageInfo::new(self.0.image_info()))
     }
     // isCompatible - no low-level renderer bindings in Lua
-    pub fn make_image_snapshot(&mut self) -> LuaImage {
-        Ok(LuaImage(self.0.image_snapshot()))
+    /// Returns a snapshot of surface contents within `bounds` (whole surface
+    /// by default).
+    ///
+    /// If `targetInfo` is given, pixels are converted to its color type,
+    /// alpha type and color space; on failure nil and a reason are returned.
+    pub fn make_image_snapshot(
+        &mut self,
+        // nil bounds have to be consumed so `targetInfo` can follow them,
+        // while an ImageInfo in their place means they're omitted
+        bounds: Option<LuaFallible<LuaRect>>,
+        target_info: Option<LuaImageInfo>,
+    ) -> (Option<LuaImage>, Option<String>) {
+        let snapshot = match bounds.and_then(LuaFallible::into_inner) {
+            Some(bounds) => match self.0.image_snapshot_with_bounds(IRect::from(bounds)) {
+                Some(it) => it,
+                None => {
+                    return Ok((
+                        None,
+                        Some("snapshot bounds don't intersect the surface".to_string()),
+                    ))
+                }
+            },
+            None => self.0.image_snapshot(),
+        };
+        let info = match target_info {
+            Some(it) => it.0.with_dimensions(snapshot.dimensions()),
+            None => return Ok((Some(LuaImage::new(snapshot)), None)),
+        };
+        Ok(match convert_image(&snapshot, &info) {
+            Ok(it) => (Some(LuaImage::new(it)), None),
+            Err(reason) => (None, Some(reason.to_string())),
+        })
     }
-    pub fn make_surface(&mut self, image_info: LikeImageInfo) -> Option<LuaSurface> {
-        Ok(self.0.new_surface(&image_info.unwrap()).map(LuaSurface))
+    /// Creates a compatible surface from `width, height` or an image info.
+    ///
+    /// Given only dimensions, color type, alpha type and color space are
+    /// inherited from this surface. Surface props are inherited in both
+    /// cases.
+    pub fn make_surface(&mut self, spec: SurfaceSpec) -> (Option<LuaSurface>, Option<String>) {
+        let info = spec.image_info(&self.0.image_info());
+        Ok(match self.0.new_surface(&info) {
+            Some(it) => (Some(LuaSurface::new(it)), None),
+            None => (
+                None,
+                Some(format!(
+                    "unable to create {}x{} surface",
+                    info.width(),
+                    info.height()
+                )),
+            ),
+        })
+    }
+    /// Replaces the surface with a compatible one from `width, height` or
+    /// an image info, inherited like in `makeSurface`. Contents are
+    /// discarded and canvases returned by `getCanvas` before raise an error
+    /// when used.
+    pub fn resize(&mut self, spec: SurfaceSpec) {
+        let info = spec.image_info(&self.0.image_info());
+        let surface = self.0.new_surface(&info).or_failed("Surface:resize", || {
+            format!(
+                "unable to create {}x{} surface",
+                info.width(),
+                info.height()
+            )
+        })?;
+        self.replace(surface);
+        Ok(())
     }
     // peekPixels - very complicated to handle properly
     pub fn props(&self) -> LuaSurfaceProps {
-        Ok(LuaSurfaceProps(*self.0.props()))
+        Ok(LuaSurfaceProps::new(*self.0.props()))
     }
     pub fn read_pixels<'lua>(
         &mut self,
@@ -2854,7 +4897,7 @@ impl LuaSurface {
             .map(LuaImageInfo::unwrap)
             .unwrap_or_else(|| self.0.image_info().with_dimensions(area.size()));
         let row_bytes = area.width() as usize * image_info.bytes_per_pixel();
-        let mut result = Vec::with_capacity(row_bytes * area.height() as usize);
+        let mut result = vec![0u8; row_bytes * area.height() as usize];
         let is_some = self.0.read_pixels(
             &image_info,
             result.as_mut_slice(),
@@ -2864,12 +4907,159 @@ impl LuaSurface {
         match is_some {
             true => {
                 let result = lua.create_table_from_vec(result)?;
-                result.set("info", LuaImageInfo(image_info))?;
+                result.set("info", LuaImageInfo::new(image_info))?;
                 Ok(Some(result))
             }
             false => Ok(None),
         }
     }
+    /// Copies pixels in `src_rect` (whole surface by default) of `source` to
+    /// `dst` of this surface, replacing existing pixels.
+    pub fn copy_from(&mut self, source: LuaSurface, src_rect: Option<LuaRect>, dst: LuaPoint) {
+        let mut source = source.unwrap();
+        let bounds = IRect::new(0, 0, source.width(), source.height());
+        let area = match src_rect.map(IRect::from) {
+            Some(it) => match IRect::intersect(&it, &bounds) {
+                Some(it) => it,
+                None => return Ok(()),
+            },
+            None => bounds,
+        };
+        let dst: Point = dst.into();
+        let dst = IPoint::new(dst.x.round() as i32, dst.y.round() as i32);
+
+        // raster surfaces are copied directly, others go through a snapshot
+        if let Some(pixmap) = source.peek_pixels() {
+            if let Some(subset) = pixmap.extract_subset(area) {
+                self.0.write_pixels_from_pixmap(&subset, dst);
+                return Ok(());
+            }
+        }
+        let snapshot = source.image_snapshot();
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::Src);
+        let dst_rect = Rect::from_irect(IRect::from_pt_size(dst, area.size()));
+        self.0.canvas().draw_image_rect(
+            snapshot,
+            Some((&Rect::from_irect(area), canvas::SrcRectConstraint::Strict)),
+            dst_rect,
+            &paint,
+        );
+        Ok(())
+    }
+    /// Returns a hash of pixels in `rect` (whole surface by default) as a
+    /// hex string.
+    ///
+    /// Pixels are hashed in the surface color type, without row padding, so
+    /// equal content produces equal hashes across platforms.
+    pub fn pixel_hash(&mut self, rect: Option<LuaRect>) -> String {
+        let area = rect
+            .map(Into::into)
+            .unwrap_or_else(|| IRect::new(0, 0, self.0.width(), self.0.height()));
+        let image_info = self.0.image_info().with_dimensions(area.size());
+        let row_bytes = area.width() as usize * image_info.bytes_per_pixel();
+        let mut pixels = vec![0u8; row_bytes * area.height() as usize];
+        if !self.0.read_pixels(
+            &image_info,
+            pixels.as_mut_slice(),
+            row_bytes,
+            IPoint::new(area.x(), area.y()),
+        ) {
+            return Err(LuaError::RuntimeError(
+                "unable to read surface pixels".to_string(),
+            ));
+        }
+
+        let mut hash = crate::util::FNV1A_64_OFFSET;
+        hash = crate::util::fnv1a_64(hash, &(image_info.color_type() as i32).to_le_bytes());
+        hash = crate::util::fnv1a_64(hash, &area.width().to_le_bytes());
+        hash = crate::util::fnv1a_64(hash, &area.height().to_le_bytes());
+        hash = crate::util::fnv1a_64(hash, &pixels);
+        Ok(format!("{:016x}", hash))
+    }
+    /// Compares pixels with `other` surface or image of the same size.
+    ///
+    /// Channels differing by no more than `tolerance` (0-255, default 0) are
+    /// considered equal. Returns `{differingPixels, maxDelta, bbox}`, where
+    /// `bbox` bounds the differing pixels and is `nil` if there are none.
+    pub fn diff<'lua>(
+        &mut self,
+        lua: &'lua LuaContext,
+        other: SurfaceOrImage,
+        tolerance: Option<u8>,
+    ) -> LuaTable<'lua> {
+        let size = self.0.image_info().dimensions();
+        if other.dimensions() != size {
+            return Err(LuaError::RuntimeError(format!(
+                "can't diff {}x{} surface with {}x{} {}",
+                size.width,
+                size.height,
+                other.dimensions().width,
+                other.dimensions().height,
+                other.kind()
+            )));
+        }
+        let tolerance = tolerance.unwrap_or_default();
+
+        let info = ImageInfo::new(size, ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let row_bytes = info.min_row_bytes();
+        let mut pixels = vec![0u8; info.compute_min_byte_size()];
+        let mut other_pixels = pixels.clone();
+        let read = self
+            .0
+            .read_pixels(&info, &mut pixels, row_bytes, IPoint::new(0, 0))
+            && other.read_pixels(&info, &mut other_pixels);
+        if !read {
+            return Err(LuaError::RuntimeError(
+                "unable to read pixels for diff".to_string(),
+            ));
+        }
+
+        let mut differing = 0usize;
+        let mut max_delta = 0u8;
+        let mut bbox: Option<IRect> = None;
+        for (i, (a, b)) in pixels
+            .chunks_exact(4)
+            .zip(other_pixels.chunks_exact(4))
+            .enumerate()
+        {
+            let delta = a
+                .iter()
+                .zip(b)
+                .map(|(a, b)| a.abs_diff(*b))
+                .max()
+                .unwrap_or_default();
+            if delta <= tolerance {
+                continue;
+            }
+            differing += 1;
+            max_delta = max_delta.max(delta);
+            let x = (i % size.width as usize) as i32;
+            let y = (i / size.width as usize) as i32;
+            let pixel = IRect::new(x, y, x + 1, y + 1);
+            bbox = Some(match bbox {
+                Some(it) => IRect::new(
+                    it.left.min(pixel.left),
+                    it.top.min(pixel.top),
+                    it.right.max(pixel.right),
+                    it.bottom.max(pixel.bottom),
+                ),
+                None => pixel,
+            });
+        }
+
+        let result = lua.create_table()?;
+        result.set("differingPixels", differing)?;
+        result.set("maxDelta", max_delta)?;
+        result.set("bbox", bbox.map(|it| LuaRect::from(it)))?;
+        Ok(result)
+    }
+    /// Writes pixel `data` at `dst`.
+    ///
+    /// `data` can either be a pixel record returned by `readPixels` (a byte
+    /// sequence with an `info` field), in which case `info` and `size`
+    /// arguments are ignored, or a raw byte sequence described by `info`
+    /// (defaults to surface info) and `size` (defaults to `info` dimensions).
     pub fn write_pixels(
         &mut self,
         dst: LuaPoint,
@@ -2877,24 +5067,30 @@ impl LuaSurface {
         info: LuaFallible<LikeImageInfo>,
         size: LuaFallible<LuaSize>,
     ) -> bool {
-        let info = info
-            .or_else(|| data.get("info").ok())
-            .map(LikeImageInfo::unwrap)
-            .unwrap_or_else(|| self.0.image_info());
-        let size = size
-            .map(LuaSize::into)
-            .unwrap_or_else(|| ISize::new(info.width(), info.height()));
-        let row_bytes = info.bytes_per_pixel();
+        let info = match data.get_user_data::<_, LuaImageInfo>("info").ok() {
+            Some(record_info) => record_info.unwrap(),
+            None => {
+                let info = info
+                    .map(LikeImageInfo::unwrap)
+                    .unwrap_or_else(|| self.0.image_info());
+                match size.map(Into::<ISize>::into) {
+                    Some(size) => info.with_dimensions(size),
+                    None => info,
+                }
+            }
+        };
+        let row_bytes = info.min_row_bytes();
+        let byte_count = row_bytes * info.height() as usize;
 
         // TODO: Properly handle data.width/height != size to allow
         // easy resizing from Lua
         let mut pixels: Vec<u8> = data
             .sequence_values::<u8>()
             .filter_map(Result::ok)
-            .take(row_bytes * size.height as usize)
+            .take(byte_count)
             .collect();
 
-        if pixels.len() < row_bytes * size.height as usize {
+        if pixels.len() < byte_count {
             return Ok(false);
         }
 
@@ -2917,7 +5113,7 @@ wrap_skia_handle!(FontStyleSet);
 #[lua_methods(lua_name: FontStyleSet)]
 impl LuaFontStyleSet {
     pub fn create_empty() -> LuaFontStyleSet {
-        Ok(LuaFontStyleSet(FontStyleSet::new_empty()))
+        Ok(LuaFontStyleSet::new(FontStyleSet::new_empty()))
     }
 
     pub fn count(&mut self) -> usize {
@@ -2925,13 +5121,28 @@ impl LuaFontStyleSet {
     }
     pub fn get_style(&mut self, index: usize) -> (LuaFontStyle, Option<String>) {
         let (style, name) = self.0.style(index);
-        Ok((LuaFontStyle(style), name))
+        Ok((LuaFontStyle::new(style), name))
+    }
+    /// Returns an array of `{style, name}` tables in set order.
+    pub fn styles<'lua>(&mut self, lua: &'lua LuaContext) -> Vec<LuaTable<'lua>> {
+        let mut result = Vec::with_capacity(self.0.count());
+        for index in 0..self.0.count() {
+            let (style, name) = self.0.style(index);
+            let entry = lua.create_table()?;
+            entry.set("style", LuaFontStyle::new(style))?;
+            entry.set("name", name)?;
+            result.push(entry);
+        }
+        Ok(result)
     }
     pub fn create_typeface(&mut self, index: usize) -> Option<LuaTypeface> {
-        Ok(self.0.new_typeface(index).map(LuaTypeface))
+        Ok(self.0.new_typeface(index).map(LuaTypeface::new))
     }
     pub fn match_style(&mut self, index: usize, pattern: LuaFontStyle) -> Option<LuaTypeface> {
-        Ok(self.0.match_style(index, pattern.unwrap()).map(LuaTypeface))
+        Ok(self
+            .0
+            .match_style(index, pattern.unwrap())
+            .map(LuaTypeface::new))
     }
 }
 
@@ -3070,17 +5281,40 @@ impl LuaFontMgr {
         Ok(self.unwrap().count_families())
     }
     pub fn create_style_set(&self, index: usize) -> LuaFontStyleSet {
-        Ok(LuaFontStyleSet(self.unwrap().new_style_set(index)))
+        Ok(LuaFontStyleSet::new(self.unwrap().new_style_set(index)))
     }
     pub fn get_family_name(&self, index: usize) -> String {
         Ok(self.unwrap().family_name(index))
     }
-    // NYI: legacyMakeTypeface by skia_safe
-    pub fn make_from_data(&self, bytes: Vec<u8>, ttc: Option<usize>) -> Option<LuaTypeface> {
-        Ok(self.unwrap().new_from_data(&bytes, ttc).map(LuaTypeface))
+    /// Returns sorted array of family names without duplicates.
+    pub fn families(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.unwrap().family_names().collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
     }
-    pub fn make_from_file(&self, path: String, ttc: Option<usize>) -> Option<LuaTypeface> {
-        let bytes = match std::fs::read(path.as_str()) {
+    // NYI: legacyMakeTypeface by skia_safe
+    pub fn make_from_data(&self, bytes: Vec<u8>, ttc: Option<usize>) -> LuaTypeface {
+        Ok(self
+            .unwrap()
+            .new_from_data(&bytes, ttc)
+            .map(LuaTypeface::new)
+            .or_failed("FontMgr:makeFromData", || {
+                format!(
+                    "unsupported font data ({} bytes) or missing face index {}",
+                    bytes.len(),
+                    ttc.unwrap_or_default()
+                )
+            })?)
+    }
+    pub fn make_from_file<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        path: String,
+        ttc: Option<usize>,
+    ) -> LuaTypeface {
+        let resolved = fs_policy::check_read(lua, "FontMgr:makeFromFile", &path)?;
+        let bytes = match std::fs::read(resolved) {
             Ok(it) => it,
             Err(_) => {
                 return Err(LuaError::RuntimeError(format!(
@@ -3089,11 +5323,23 @@ impl LuaFontMgr {
                 )))
             }
         };
-        Ok(self.unwrap().new_from_data(&bytes, ttc).map(LuaTypeface))
+        Ok(self
+            .unwrap()
+            .new_from_data(&bytes, ttc)
+            .map(LuaTypeface::new)
+            .or_failed("FontMgr:makeFromFile", || {
+                format!(
+                    "unsupported font file '{}' or missing face index {}",
+                    path,
+                    ttc.unwrap_or_default()
+                )
+            })?)
     }
     // makeFromStream - Lua has no streams
     pub fn match_family(&self, family_name: String) -> LuaFontStyleSet {
-        Ok(LuaFontStyleSet(self.unwrap().match_family(family_name)))
+        Ok(LuaFontStyleSet::new(
+            self.unwrap().match_family(family_name),
+        ))
     }
     pub fn match_family_style(
         &self,
@@ -3103,7 +5349,7 @@ impl LuaFontMgr {
         Ok(self
             .unwrap()
             .match_family_style(family_name, style.unwrap())
-            .map(LuaTypeface))
+            .map(LuaTypeface::new))
     }
     pub fn match_family_style_character(
         &self,
@@ -3116,7 +5362,30 @@ impl LuaFontMgr {
         Ok(self
             .unwrap()
             .match_family_style_character(family_name, style.unwrap(), &bcp_refs, character)
-            .map(LuaTypeface))
+            .map(LuaTypeface::new))
+    }
+    /// Returns a typeface with a glyph for `character`, preferring the
+    /// `family_hint` family (any by default), `style` (normal by default)
+    /// and `bcp47` languages, e.g. to find a fallback for `FontChain`.
+    pub fn match_character(
+        &self,
+        family_hint: Option<String>,
+        style: Option<LuaFontStyle>,
+        bcp47: Option<Vec<String>>,
+        character: Unichar,
+    ) -> Option<LuaTypeface> {
+        let style = style.map(LuaFontStyle::unwrap).unwrap_or_default();
+        let bcp47 = bcp47.unwrap_or_default();
+        let bcp_refs: Vec<&str> = bcp47.iter().map(|it| it.as_ref()).collect();
+        Ok(self
+            .unwrap()
+            .match_family_style_character(
+                family_hint.unwrap_or_default(),
+                style,
+                &bcp_refs,
+                character,
+            )
+            .map(LuaTypeface::new))
     }
 }
 
@@ -3125,7 +5394,7 @@ wrap_skia_handle!(Typeface);
 impl Default for LuaTypeface {
     fn default() -> Self {
         let mgr = FontMgr::default();
-        LuaTypeface(mgr.legacy_make_typeface(None, FontStyle::normal()).unwrap())
+        LuaTypeface::new(mgr.legacy_make_typeface(None, FontStyle::normal()).unwrap())
     }
 }
 
@@ -3138,19 +5407,35 @@ impl LuaTypeface {
     pub fn make_from_name(
         family_name: String,
         font_style: LuaFallible<LuaFontStyle>,
-    ) -> Option<LuaTypeface> {
+    ) -> LuaTypeface {
         let font_style = font_style.map(LuaFontStyle::unwrap).unwrap_or_default();
         Ok(FontMgr::default()
-            .match_family_style(family_name, font_style)
-            .map(LuaTypeface))
-    }
-    pub fn make_from_data(data: Vec<u8>, index: LuaFallible<usize>) -> Option<LuaTypeface> {
+            .match_family_style(&family_name, font_style)
+            .map(LuaTypeface::new)
+            .or_failed("Typeface.makeFromName", || {
+                format!("no typeface matching family '{}'", family_name)
+            })?)
+    }
+    pub fn make_from_data(data: Vec<u8>, index: LuaFallible<usize>) -> LuaTypeface {
+        let index = index.unwrap_or_default();
         Ok(FontMgr::default()
-            .new_from_data(&data, index.unwrap_or_default())
-            .map(LuaTypeface))
-    }
-    pub fn make_from_file(path: String, index: LuaFallible<usize>) -> Option<LuaTypeface> {
-        let data = match std::fs::read(path.as_str()) {
+            .new_from_data(&data, index)
+            .map(LuaTypeface::new)
+            .or_failed("Typeface.makeFromData", || {
+                format!(
+                    "unsupported font data ({} bytes) or missing face index {}",
+                    data.len(),
+                    index
+                )
+            })?)
+    }
+    pub fn make_from_file<'lua>(
+        lua: &'lua LuaContext,
+        path: String,
+        index: LuaFallible<usize>,
+    ) -> LuaTypeface {
+        let resolved = fs_policy::check_read(lua, "Typeface.makeFromFile", &path)?;
+        let data = match std::fs::read(resolved) {
             Ok(it) => it,
             Err(_) => {
                 return Err(LuaError::RuntimeError(format!(
@@ -3159,9 +5444,16 @@ impl LuaTypeface {
                 )))
             }
         };
+        let index = index.unwrap_or_default();
         Ok(FontMgr::default()
-            .new_from_data(&data, index.unwrap_or_default())
-            .map(LuaTypeface))
+            .new_from_data(&data, index)
+            .map(LuaTypeface::new)
+            .or_failed("Typeface.makeFromFile", || {
+                format!(
+                    "unsupported font file '{}' or missing face index {}",
+                    path, index
+                )
+            })?)
     }
 
     pub fn count_glyphs(&self) -> usize {
@@ -3171,7 +5463,23 @@ impl LuaTypeface {
         Ok(self.0.count_tables())
     }
     // createFamilyNameIterator -> familyNames; Lua doesn't have iterators
-    pub fn family_names(&self) -> HashMap<String, String> {
+    /// Returns an array of `{language, name}` tables in the order Skia
+    /// reports them.
+    pub fn family_names<'lua>(&self, lua: &'lua LuaContext) -> Vec<LuaTable<'lua>> {
+        let mut result = Vec::new();
+        for it in self.0.new_family_name_iterator() {
+            let entry = lua.create_table()?;
+            entry.set("language", it.language)?;
+            entry.set("name", it.string)?;
+            result.push(entry);
+        }
+        Ok(result)
+    }
+    /// Returns a table of family names keyed by language.
+    ///
+    /// Deprecated: iteration order of the table isn't stable, use
+    /// `familyNames` instead.
+    pub fn family_name_map(&self) -> HashMap<String, String> {
         let names: HashMap<_, _> = self
             .0
             .new_family_name_iterator()
@@ -3182,7 +5490,7 @@ impl LuaTypeface {
     // NYI: createScalerContext by skia_safe
     // NYI: filterRec by skia_safe
     pub fn font_style(&self) -> LuaFontStyle {
-        Ok(LuaFontStyle(self.0.font_style()))
+        Ok(LuaFontStyle::new(self.0.font_style()))
     }
     pub fn get_bounds(&self) -> LuaRect {
         Ok(LuaRect::from(self.0.bounds()))
@@ -3191,11 +5499,22 @@ impl LuaTypeface {
         Ok(self.0.family_name())
     }
     // methods.add_method_ext("getFontDescriptor" Ok(()));
-    pub fn get_kerning_pair_adjustments(&self, glyphs: Vec<GlyphId>) -> Vec<i32> {
-        let mut adjustments = Vec::with_capacity(glyphs.len());
-        self.0
+    /// Returns kerning adjustments (in font design units) between each
+    /// consecutive pair of `glyphs`, in text order, so the `i`-th value
+    /// adjusts the advance between glyphs `i` and `i + 1`.
+    ///
+    /// Returns `nil` if the typeface has no kerning data.
+    pub fn get_kerning_pair_adjustments(&self, glyphs: Vec<GlyphId>) -> Option<Vec<i32>> {
+        let mut adjustments = vec![0; glyphs.len().saturating_sub(1)];
+        let has_kerning = self
+            .0
             .get_kerning_pair_adjustments(glyphs.as_ref(), adjustments.as_mut_slice());
-        Ok(adjustments)
+        Ok(has_kerning.then_some(adjustments))
+    }
+    /// Returns whether the typeface provides kerning data.
+    pub fn has_kerning(&self) -> bool {
+        // with no glyphs, skia only reports whether kerning is supported
+        Ok(self.0.get_kerning_pair_adjustments(&[], &mut []))
     }
     pub fn get_post_script_name(&self) -> Option<String> {
         Ok(self.0.post_script_name())
@@ -3231,23 +5550,26 @@ impl LuaTypeface {
         Ok(self.0.is_italic())
     }
     pub fn make_clone(&self) -> LuaTypeface {
-        Ok(LuaTypeface(self.0.clone()))
+        Ok(LuaTypeface::new(self.0.clone()))
     }
     // NYI: openExistingStream by skia_safe
     // NYI: openStream by skia_safe
     pub fn text_to_glyphs(&self, text: LuaText) -> Vec<GlyphId> {
-        let mut result = Vec::with_capacity(text.text.len());
-        self.0.text_to_glyphs(text, result.as_mut_slice());
+        // every encoding takes at least a byte per glyph
+        let mut result = vec![0; text.text.len()];
+        let count = self.0.text_to_glyphs(text, &mut result);
+        result.truncate(count);
         Ok(result)
     }
     pub fn string_to_glyphs(&self, text: String) -> Vec<GlyphId> {
-        let mut result = Vec::with_capacity(text.len());
-        self.0.str_to_glyphs(&text, result.as_mut_slice());
+        let mut result = vec![0; text.len()];
+        let count = self.0.str_to_glyphs(&text, &mut result);
+        result.truncate(count);
         Ok(result)
     }
     pub fn unichars_to_glyphs(&self, unichars: Vec<Unichar>) -> Vec<GlyphId> {
-        let mut result = Vec::new();
-        self.0.unichars_to_glyphs(&unichars, result.as_mut_slice());
+        let mut result = vec![0; unichars.len()];
+        self.0.unichars_to_glyphs(&unichars, &mut result);
         Ok(result)
     }
     pub fn unichar_to_glyph(&self, unichar: Unichar) -> GlyphId {
@@ -3259,17 +5581,11 @@ wrap_skia_handle!(FontStyle);
 
 #[lua_methods(lua_name: FontStyle)]
 impl LuaFontStyle {
-    pub fn make(
-        weight: Option<LuaFontWeight>,
-        width: Option<LuaFontWidth>,
-        slant: Option<LuaSlant>,
-    ) -> LuaFontStyle {
-        let weight = weight
-            .map(|it| it.to_skia_weight())
-            .unwrap_or(Weight::NORMAL);
-        let width = width.map(|it| it.to_skia_width()).unwrap_or(Width::NORMAL);
-        let slant = slant.unwrap_or_t(Slant::Upright);
-        Ok(LuaFontStyle(FontStyle::new(weight, width, slant)))
+    /// Creates a font style from `weight`, `width` and `slant` arguments, or
+    /// from a single descriptor string listing them in any order, e.g.
+    /// `"semi_bold condensed italic"`.
+    pub fn make(style: FontStyleArgs) -> LuaFontStyle {
+        Ok(LuaFontStyle::new(style.0))
     }
 
     pub fn weight(&self) -> LuaFontWeight {
@@ -3281,10 +5597,45 @@ impl LuaFontStyle {
     pub fn slant(&self) -> LuaSlant {
         Ok(LuaSlant(self.0.slant()))
     }
+    /// Returns the canonical descriptor accepted by `FontStyle.make`.
+    pub fn to_string(&self) -> String {
+        Ok(describe_font_style(&self.0))
+    }
 }
 
 wrap_skia_handle!(Font);
 
+/// Transparent border around text rendered by `Font:renderToImage`, so
+/// anti-aliased edges aren't cut off.
+const TEXT_IMAGE_PADDING: f32 = 2.0;
+
+impl LuaFont {
+    /// Returns outline of `glyph`, treating empty outlines of whitespace
+    /// glyphs as missing.
+    fn glyph_path(&self, glyph: GlyphId) -> Option<Path> {
+        self.0.get_path(glyph).filter(|it| !it.is_empty())
+    }
+
+    /// Returns union of bounds of `glyphs` placed by their advances, or
+    /// `None` if none of them has ink.
+    fn glyph_ink_bounds(&self, glyphs: &[GlyphId], paint: Option<&Paint>) -> Option<Rect> {
+        let mut bounds = vec![Rect::new_empty(); glyphs.len()];
+        self.0.get_bounds(glyphs, &mut bounds, paint);
+        let mut positions = vec![Point::default(); glyphs.len()];
+        self.0.get_pos(glyphs, &mut positions, None);
+
+        bounds
+            .into_iter()
+            .zip(positions)
+            .filter(|(it, _)| !it.is_empty())
+            .map(|(it, position)| it.with_offset(position))
+            .reduce(|mut result, it| {
+                result.join(it);
+                result
+            })
+    }
+}
+
 #[lua_methods(lua_name: Font)]
 impl LuaFont {
     #[lua(constructor)]
@@ -3297,7 +5648,7 @@ impl LuaFont {
         let size = size.unwrap_or(12.0);
         let scale_x = scale_x.unwrap_or(1.0);
         let skew_x = skew_x.unwrap_or(0.0);
-        Ok(LuaFont(Font::from_typeface_with_params(
+        Ok(LuaFont::new(Font::from_typeface_with_params(
             typeface, size, scale_x, skew_x,
         )))
     }
@@ -3327,7 +5678,7 @@ impl LuaFont {
         paint: Option<LuaPaint>,
     ) -> Vec<f32> {
         let points: Vec<Point> = points.into_iter().map(|it| it.into()).collect();
-        let paint = paint.map(|it| it.0);
+        let paint = paint.map(LuaPaint::unwrap);
         let intercepts = self
             .0
             .get_intercepts(&glyphs, &points, (top, bottom), paint.as_ref());
@@ -3336,15 +5687,52 @@ impl LuaFont {
     pub fn get_metrics<'lua>(&self, lua: &'lua LuaContext) -> LuaTable<'lua> {
         self.0.metrics().1.to_table(lua)
     }
+    /// Returns outline of `glyph`, or `nil` if it has none (e.g. spaces).
     pub fn get_path(&self, glyph: GlyphId) -> Option<LuaPath> {
-        Ok(self.0.get_path(glyph).map(LuaPath))
+        Ok(self.glyph_path(glyph).map(LuaPath::new))
+    }
+    /// Returns an array of glyph outlines parallel to `glyphs`, with `nil`
+    /// entries for glyphs without one.
+    ///
+    /// As `#` is unreliable for arrays with holes, the number of entries is
+    /// stored in the `n` field, like `table.pack` does; iterate with
+    /// `for i = 1, paths.n do`.
+    pub fn get_paths<'lua>(&self, lua: &'lua LuaContext, glyphs: Vec<GlyphId>) -> LuaTable<'lua> {
+        let result = lua.create_table()?;
+        result.set("n", glyphs.len())?;
+        for (index, glyph) in glyphs.into_iter().enumerate() {
+            if let Some(path) = self.glyph_path(glyph) {
+                result.set(index + 1, LuaPath::new(path))?;
+            }
+        }
+        Ok(result)
     }
-    pub fn get_paths(&self, glyphs: Vec<GlyphId>) -> HashMap<GlyphId, LuaPath> {
+    /// Returns a table mapping each distinct glyph to its outline.
+    pub fn get_paths_unique(&self, glyphs: Vec<GlyphId>) -> HashMap<GlyphId, LuaPath> {
         Ok(glyphs
             .into_iter()
-            .filter_map(|it| self.0.get_path(it).map(LuaPath).map(|b| (it, b)))
+            .filter_map(|it| self.glyph_path(it).map(LuaPath::new).map(|b| (it, b)))
             .collect::<HashMap<GlyphId, LuaPath>>())
     }
+    /// Returns outlines of all glyphs of `text` combined into a single path,
+    /// with the baseline starting at `origin` (`0, 0` by default).
+    ///
+    /// Glyphs are placed by their advances without shaping, and glyphs
+    /// without outlines, such as spaces and bitmap emoji, are skipped.
+    pub fn get_text_path(&self, text: String, origin: LuaFallible<LuaPoint>) -> LuaPath {
+        let glyphs = self.0.str_to_glyphs_vec(&text);
+        let mut positions = vec![Point::default(); glyphs.len()];
+        self.0
+            .get_pos(&glyphs, &mut positions, origin.map(LuaPoint::into));
+
+        let mut result = Path::new();
+        for (glyph, position) in glyphs.into_iter().zip(positions) {
+            if let Some(path) = self.glyph_path(glyph) {
+                result.add_path(&path, position, None);
+            }
+        }
+        Ok(LuaPath::new(result))
+    }
     pub fn get_pos(&self, glyphs: Vec<GlyphId>, origin: LuaFallible<LuaPoint>) -> Vec<LuaPoint> {
         let mut points = [Point::new(0., 0.)].repeat(glyphs.len());
         let origin = origin.map(LuaPoint::into);
@@ -3365,10 +5753,10 @@ impl LuaFont {
         Ok(self.0.spacing())
     }
     pub fn get_typeface(&self) -> LuaTypeface {
-        Ok(LuaTypeface(self.0.typeface()))
+        Ok(LuaTypeface::new(self.0.typeface()))
     }
     pub fn get_widths(&self, glyphs: Vec<GlyphId>) -> Vec<f32> {
-        let mut widths = Vec::with_capacity(glyphs.len());
+        let mut widths = vec![0.0; glyphs.len()];
         self.0.get_widths(&glyphs, &mut widths);
         Ok(widths)
     }
@@ -3377,8 +5765,8 @@ impl LuaFont {
         glyphs: Vec<GlyphId>,
         paint: Option<LuaPaint>,
     ) -> (Vec<f32>, Vec<LuaRect>) {
-        let mut widths: Vec<f32> = Vec::with_capacity(glyphs.len());
-        let mut bounds = Vec::with_capacity(glyphs.len());
+        let mut widths = vec![0.0; glyphs.len()];
+        let mut bounds = vec![Rect::default(); glyphs.len()];
         self.0.get_widths_bounds(
             &glyphs,
             Some(&mut widths),
@@ -3391,7 +5779,7 @@ impl LuaFont {
         ))
     }
     pub fn get_x_pos(&self, glyphs: Vec<GlyphId>, origin: Option<f32>) -> Vec<f32> {
-        let mut result = Vec::with_capacity(glyphs.len());
+        let mut result = vec![0.0; glyphs.len()];
         self.0.get_x_pos(&glyphs, &mut result, origin);
         Ok(result)
     }
@@ -3414,7 +5802,19 @@ impl LuaFont {
         Ok(self.0.is_subpixel())
     }
     pub fn make_with_size(&self, size: f32) -> Option<LuaFont> {
-        Ok(self.0.with_size(size).map(LuaFont))
+        Ok(self.0.with_size(size).map(LuaFont::new))
+    }
+    /// Returns bounds of ink of `text` laid out from the origin, or `nil` if
+    /// it has none (e.g. only spaces).
+    ///
+    /// Unlike bounds returned by `measureText`, these are tight around the
+    /// glyphs which makes them suitable for visually centering text.
+    pub fn tight_bounds_of(&self, text: String, paint: Option<LuaPaint>) -> Option<LuaRect> {
+        let glyphs = self.0.str_to_glyphs_vec(&text);
+        let paint = paint.map(LuaPaint::unwrap);
+        Ok(self
+            .glyph_ink_bounds(&glyphs, paint.as_ref())
+            .map(LuaRect::from))
     }
     pub fn measure_text(&self, text: LuaText, paint: Option<LuaPaint>) -> (f32, LuaRect) {
         let measurements = self
@@ -3422,19 +5822,229 @@ impl LuaFont {
             .measure_text(text, paint.map(LuaPaint::unwrap).as_ref());
         Ok((measurements.0, LuaRect::from(measurements.1)))
     }
-    pub fn set_baseline_snap(&mut self, baseline_snap: bool) {
-        self.0.set_baseline_snap(baseline_snap);
-        Ok(())
-    }
-    pub fn set_edging(&mut self, edging: LuaFontEdging) {
-        self.0.set_edging(*edging);
-        Ok(())
+    /// Renders `text` into an image for caching.
+    ///
+    /// Subpixel (LCD) anti-aliasing needs an opaque background, so it's only
+    /// used if an opaque `background` color is given, for pixel geometry of
+    /// `props` (`"rgbh"` by default). Returns `{image, baselineOffset,
+    /// bounds}`; drawing `image` at the text origin minus `baselineOffset`
+    /// matches drawing the text directly at a whole pixel position.
+    pub fn render_to_image<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        text: String,
+        paint: LikePaint,
+        background: LuaFallible<LuaColor>,
+        props: LuaFallible<LikeSurfaceProps>,
+    ) -> LuaTable<'lua> {
+        let paint = paint.unwrap();
+        let background: Option<Color4f> = background.map(LuaColor::into);
+        let opaque = background.map_or(false, |it| it.a >= 1.0);
+        let props = match props.map_t() {
+            Some(props) if opaque => props,
+            None if opaque => SurfaceProps::new(SurfacePropsFlags::empty(), PixelGeometry::RGBH),
+            // LCD text on transparent pixels leaves colored fringes
+            _ => SurfaceProps::new(SurfacePropsFlags::empty(), PixelGeometry::Unknown),
+        };
+
+        let mut font = self.0.clone();
+        font.set_subpixel(true);
+        font.set_edging(if lcd_text_props(&props) {
+            FontEdging::SubpixelAntiAlias
+        } else {
+            FontEdging::AntiAlias
+        });
+
+        let (advance, bounds) = font.measure_str(&text, Some(&paint));
+        let (_, metrics) = font.metrics();
+        let left = bounds.left.min(0.0);
+        let right = bounds.right.max(advance);
+        let top = bounds.top.min(metrics.ascent);
+        let bottom = bounds.bottom.max(metrics.descent);
+        // whole pixel origin keeps glyph positioning identical to direct draws
+        let origin = Point::new(
+            (TEXT_IMAGE_PADDING - left).ceil(),
+            (TEXT_IMAGE_PADDING - top).ceil(),
+        );
+        let size = ISize::new(
+            (origin.x + right + TEXT_IMAGE_PADDING).ceil() as i32,
+            (origin.y + bottom + TEXT_IMAGE_PADDING).ceil() as i32,
+        );
+
+        let alpha_type = if opaque {
+            AlphaType::Opaque
+        } else {
+            AlphaType::Premul
+        };
+        let info = ImageInfo::new_n32(size, alpha_type, None);
+        let mut surface = surfaces::raster(&info, None, Some(&props))
+            .or_failed("Font:renderToImage", || {
+                format!("unable to create {}x{} surface", size.width, size.height)
+            })?;
+        let canvas = surface.canvas();
+        canvas.clear(background.unwrap_or(Color4f::new(0.0, 0.0, 0.0, 0.0)));
+        canvas.draw_str(&text, origin, &font, &paint);
+
+        let result = lua.create_table()?;
+        result.set("image", LuaImage::new(surface.image_snapshot()))?;
+        result.set("baselineOffset", LuaPoint::from(origin))?;
+        result.set("bounds", LuaRect::from(bounds))?;
+        Ok(result)
     }
-    pub fn set_embedded_bitmaps(&mut self, embedded_bitmaps: bool) {
-        self.0.set_embedded_bitmaps(embedded_bitmaps);
-        Ok(())
+    /// Measures text and returns its advance width, bounds and font
+    /// metrics relevant for vertical placement in a single table.
+    pub fn measure<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        text: LuaText,
+        paint: Option<LuaPaint>,
+    ) -> LuaTable<'lua> {
+        let (width, bounds) = self
+            .0
+            .measure_text(text, paint.map(LuaPaint::unwrap).as_ref());
+        let (_, metrics) = self.0.metrics();
+
+        let result = lua.create_table()?;
+        result.set("width", width)?;
+        result.set("bounds", LuaRect::from(bounds))?;
+        result.set("ascent", metrics.ascent)?;
+        result.set("descent", metrics.descent)?;
+        result.set("leading", metrics.leading)?;
+        result.set("capHeight", metrics.cap_height)?;
+        result.set("xHeight", metrics.x_height)?;
+        Ok(result)
     }
-    pub fn set_embolden(&mut self, embolden: bool) {
+    /// Returns the number of bytes and characters of UTF-8 `text` that fit
+    /// into `max_width`. Text is never split inside of a character.
+    pub fn break_text(&self, text: String, max_width: f32) -> (usize, usize) {
+        let boundaries: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .skip(1)
+            .chain(std::iter::once(text.len()))
+            .collect();
+
+        // number of characters that fit; advance width grows monotonically
+        // with each added character so binary search is sufficient
+        let (mut low, mut high) = (0, boundaries.len());
+        while low < high {
+            let mid = (low + high + 1) / 2;
+            let (width, _) = self.0.measure_str(&text[..boundaries[mid - 1]], None);
+            if width <= max_width {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        let bytes = if low == 0 { 0 } else { boundaries[low - 1] };
+        Ok((bytes, low))
+    }
+    /// Returns `text` truncated to fit into `max_width` with `ellipsis`
+    /// ("…" by default) appended, or unchanged `text` if it already fits.
+    ///
+    /// Text is only cut at grapheme cluster boundaries, so combining
+    /// characters and emoji sequences are kept whole. Returns an empty string
+    /// if even the ellipsis doesn't fit.
+    pub fn ellipsize(&self, text: String, max_width: f32, ellipsis: Option<String>) -> String {
+        let ellipsis = ellipsis.unwrap_or_else(|| "\u{2026}".to_string());
+        Ok(util::ellipsize(&text, &ellipsis, |it| {
+            self.0.measure_str(it, None).0 <= max_width
+        }))
+    }
+    /// Returns horizontal extents of grapheme clusters of `text` drawn at
+    /// the origin, as an array of `{glyphStartX, glyphEndX, byteOffset}`
+    /// tables in text order. `byteOffset` is the 0-based offset of the
+    /// cluster start in `text`.
+    ///
+    /// Combining marks share the entry of the character they modify. Glyphs
+    /// are looked up per character without shaping, the same way text blobs
+    /// are built from strings.
+    pub fn get_char_extents<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        text: String,
+    ) -> Vec<LuaTable<'lua>> {
+        let chars: Vec<Unichar> = text.chars().map(|it| it as Unichar).collect();
+        let mut glyphs = vec![0; chars.len()];
+        self.0.unichar_to_glyphs(&chars, &mut glyphs);
+        let mut x_pos = vec![0.0; glyphs.len()];
+        self.0.get_x_pos(&glyphs, &mut x_pos, None);
+        let mut widths = vec![0.0; glyphs.len()];
+        self.0.get_widths(&glyphs, &mut widths);
+
+        let mut result = Vec::new();
+        let mut first = 0;
+        for (offset, cluster) in text.grapheme_indices(true) {
+            let chars = first..first + cluster.chars().count();
+            first = chars.end;
+            let start = x_pos[chars.start];
+            let end = chars.map(|i| x_pos[i] + widths[i]).fold(start, f32::max);
+
+            let entry = lua.create_table()?;
+            entry.set("glyphStartX", start)?;
+            entry.set("glyphEndX", end)?;
+            entry.set("byteOffset", offset)?;
+            result.push(entry);
+        }
+        Ok(result)
+    }
+    /// Returns a copy of this font with the largest size between `min_size`
+    /// (1 by default) and `max_size` (512 by default) at which `text` fits
+    /// into `max_width` and, if provided, line height fits into `max_height`.
+    pub fn fit_size(
+        &self,
+        text: String,
+        max_width: f32,
+        max_height: Option<f32>,
+        min_size: Option<f32>,
+        max_size: Option<f32>,
+    ) -> Option<LuaFont> {
+        let min_size = min_size.unwrap_or(1.0).max(0.0);
+        let max_size = max_size.unwrap_or(512.0).max(min_size);
+
+        let fits = |size: f32| -> bool {
+            let mut font = self.0.clone();
+            font.set_size(size);
+            if let Some(max_height) = max_height {
+                let (_, metrics) = font.metrics();
+                if metrics.descent - metrics.ascent > max_height {
+                    return false;
+                }
+            }
+            font.measure_str(&text, None).0 <= max_width
+        };
+
+        let size = if fits(max_size) {
+            max_size
+        } else {
+            let (mut low, mut high) = (min_size, max_size);
+            // sizes are only meaningful to a fraction of a point
+            while high - low > 0.05 {
+                let mid = (low + high) / 2.0;
+                if fits(mid) {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            low
+        };
+        Ok(self.0.with_size(size).map(LuaFont::new))
+    }
+    pub fn set_baseline_snap(&mut self, baseline_snap: bool) {
+        self.0.set_baseline_snap(baseline_snap);
+        Ok(())
+    }
+    pub fn set_edging(&mut self, edging: LuaFontEdging) {
+        self.0.set_edging(*edging);
+        Ok(())
+    }
+    pub fn set_embedded_bitmaps(&mut self, embedded_bitmaps: bool) {
+        self.0.set_embedded_bitmaps(embedded_bitmaps);
+        Ok(())
+    }
+    pub fn set_embolden(&mut self, embolden: bool) {
         self.0.set_embolden(embolden);
         Ok(())
     }
@@ -3470,12 +6080,11 @@ impl LuaFont {
         self.0.set_typeface(typeface.unwrap());
         Ok(())
     }
-    pub fn text_to_glyphs(&self, text: LuaText) {
-        self.0.text_to_glyphs_vec(text);
-        Ok(())
+    pub fn text_to_glyphs(&self, text: LuaText) -> Vec<GlyphId> {
+        Ok(self.0.text_to_glyphs_vec(text))
     }
     pub fn unichars_to_glyphs(&self, unichars: Vec<Unichar>) -> Vec<GlyphId> {
-        let mut result = Vec::with_capacity(unichars.len());
+        let mut result = vec![0; unichars.len()];
         self.0.unichar_to_glyphs(&unichars, &mut result);
         Ok(result)
     }
@@ -3486,15 +6095,60 @@ impl LuaFont {
 
 wrap_skia_handle!(TextBlob);
 
+/// Precision of [`blob_ink_bounds`] edges found by bisection.
+const INK_BOUNDS_PRECISION: f32 = 1.0 / 64.0;
+
+/// Returns bounds of glyph outlines of `blob`, or `None` if it has none.
+///
+/// Blobs don't expose glyph positions, so edges are found through glyph
+/// intercepts: horizontal ones directly and vertical ones by bisecting
+/// bands of the conservative bounds. Glyphs without outlines, such as
+/// bitmap emoji, aren't included.
+fn blob_ink_bounds(blob: &TextBlob, paint: Option<&Paint>) -> Option<Rect> {
+    let bounds = *blob.bounds();
+    let has_ink = |top: f32, bottom: f32| !blob.get_intercepts([top, bottom], paint).is_empty();
+
+    let intercepts = blob.get_intercepts([bounds.top, bounds.bottom], paint);
+    let left = intercepts.iter().copied().reduce(f32::min)?;
+    let right = intercepts.iter().copied().reduce(f32::max)?;
+
+    // the band from bounds.top to `outside` has no ink and to `inside` has
+    let (mut outside, mut inside) = (bounds.top, bounds.bottom);
+    while inside - outside > INK_BOUNDS_PRECISION {
+        let middle = (outside + inside) * 0.5;
+        if has_ink(bounds.top, middle) {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+    let top = outside;
+
+    let (mut outside, mut inside) = (bounds.bottom, top);
+    while outside - inside > INK_BOUNDS_PRECISION {
+        let middle = (outside + inside) * 0.5;
+        if has_ink(middle, bounds.bottom) {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+    let bottom = outside;
+
+    Some(Rect::new(left, top, right, bottom))
+}
+
 #[lua_methods(lua_name: TextBlob)]
 impl LuaTextBlob {
+    /// Text blob constructors return `nil` for text without glyphs, as Skia
+    /// doesn't create empty blobs.
     pub fn make_from_pos_text(
         text: LuaText,
         pos: Vec<LuaPoint>,
         font: LuaFont,
     ) -> Option<LuaTextBlob> {
         let pos: Vec<Point> = pos.into_iter().map(LuaPoint::into).collect();
-        Ok(TextBlob::from_pos_text(text, &pos, &font.0).map(LuaTextBlob))
+        Ok(TextBlob::from_pos_text(text, &pos, &font.0).map(LuaTextBlob::new))
     }
     pub fn make_from_pos_text_h(
         text: LuaText,
@@ -3502,19 +6156,63 @@ impl LuaTextBlob {
         const_y: f32,
         font: LuaFont,
     ) -> Option<LuaTextBlob> {
-        Ok(TextBlob::from_pos_text_h(text, &x_pos, const_y, &font.0).map(LuaTextBlob))
+        Ok(TextBlob::from_pos_text_h(text, &x_pos, const_y, &font.0).map(LuaTextBlob::new))
+    }
+    #[lua(rename: "makeFromRSXform")]
+    pub fn make_from_rsxform(
+        text: LuaText,
+        xforms: Vec<LuaRSXform>,
+        font: LuaFont,
+    ) -> Option<LuaTextBlob> {
+        let xforms: Vec<RSXform> = xforms.into_iter().map(|it| it.0).collect();
+        Ok(TextBlob::from_rsxform(text, &xforms, &font.0).map(LuaTextBlob::new))
     }
-    // TODO: make_from_RSXform()
     pub fn make_from_string(string: String, font: LuaFont) -> Option<LuaTextBlob> {
-        Ok(TextBlob::new(string, &font.0).map(LuaTextBlob))
+        Ok(TextBlob::new(string, &font.0).map(LuaTextBlob::new))
     }
     pub fn make_from_text(text: LuaText, font: LuaFont) -> Option<LuaTextBlob> {
-        Ok(TextBlob::from_text(text, &font.0).map(LuaTextBlob))
+        Ok(TextBlob::from_text(text, &font.0).map(LuaTextBlob::new))
     }
 
+    /// Returns conservative bounds of the blob, which are computed from font
+    /// metrics and can be much larger than its glyphs. Use `tightBounds` for
+    /// positioning text by its ink.
     pub fn bounds(&self) -> LuaRect {
         Ok(LuaRect::from(*self.0.bounds()))
     }
+    /// Returns bounds of glyph outlines of the blob drawn with `paint`, or
+    /// `nil` if it has none (e.g. only spaces). Glyphs without outlines, such
+    /// as bitmap emoji, aren't included.
+    ///
+    /// Bounds are contained in `bounds()` and accurate to 1/64 of a unit.
+    pub fn tight_bounds(&self, paint: Option<LikePaint>) -> Option<LuaRect> {
+        let paint = paint.map(LikePaint::unwrap);
+        Ok(blob_ink_bounds(&self.0, paint.as_ref()).map(LuaRect::from))
+    }
+    /// Returns an object drawing this blob with a cached `color` glow
+    /// blurred by `sigma`.
+    ///
+    /// `font` is used to lay out text passed to `TextGlow:setText` and
+    /// defaults to the default font.
+    pub fn with_glow(
+        &self,
+        font: LuaFallible<LuaFont>,
+        color: LuaColor,
+        sigma: f32,
+    ) -> LuaTextGlow {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(LuaError::RuntimeError(
+                "glow sigma must be a positive, finite scalar".to_string(),
+            ));
+        }
+        Ok(LuaTextGlow {
+            blob: self.0.clone(),
+            font: font.map(LuaFont::unwrap).unwrap_or_default(),
+            color: color.into(),
+            sigma,
+            cached: None,
+        })
+    }
     pub fn get_intercepts(&self, bounds: LuaPoint, paint: Option<LikePaint>) -> Vec<f32> {
         Ok(self
             .0
@@ -3522,40 +6220,302 @@ impl LuaTextBlob {
     }
 }
 
+/// Dash pattern with cached effects for animated phases.
+pub struct LuaAnimatedDash(dash::PhaseCache);
+
+#[lua_methods(lua_name: AnimatedDash)]
+impl LuaAnimatedDash {
+    /// Returns the dash effect for `phase`. Phases are rounded to a quarter
+    /// unit, or to 1/256 of the period for patterns longer than 64 units, and
+    /// effects are reused for phases rounded the same.
+    pub fn with_phase(&mut self, phase: f32) -> LuaPathEffect {
+        Ok(self
+            .0
+            .effect(phase)
+            .map(LuaPathEffect::new)
+            .or_failed("AnimatedDash:withPhase", || {
+                format!("phase must be finite; got {}", phase)
+            })?)
+    }
+    pub fn get_intervals(&self) -> Vec<f32> {
+        Ok(self.0.intervals().to_vec())
+    }
+    /// Returns the sum of intervals; phases this far apart draw the same
+    /// dashes.
+    pub fn period(&self) -> f32 {
+        Ok(self.0.period())
+    }
+    /// Returns the number of cached effects.
+    pub fn cached_count(&self) -> usize {
+        Ok(self.0.len())
+    }
+    /// Frees cached effects; they're built again as needed.
+    pub fn release(&mut self) {
+        self.0.clear();
+        Ok(())
+    }
+}
+
+/// Text blob drawn with a blurred glow behind it.
+///
+/// Blurred glow is rendered once into an image and reused until the blob,
+/// font or glow parameters change, so animating glow alpha doesn't redo the
+/// blur.
+pub struct LuaTextGlow {
+    blob: TextBlob,
+    /// Font text passed to `setText` is laid out with.
+    font: Font,
+    color: Color4f,
+    sigma: f32,
+    /// Rendered glow, its offset from blob origin and parameters it was
+    /// rendered with.
+    cached: Option<(GlowKey, Image, Point)>,
+}
+
+/// Font properties affecting glyph outlines.
+#[derive(Clone, PartialEq)]
+struct FontKey {
+    typeface: Option<u32>,
+    size: f32,
+    scale_x: f32,
+    skew_x: f32,
+    embolden: bool,
+}
+
+impl From<&Font> for FontKey {
+    fn from(font: &Font) -> Self {
+        FontKey {
+            typeface: font.typeface().map(|it| it.unique_id()),
+            size: font.size(),
+            scale_x: font.scale_x(),
+            skew_x: font.skew_x(),
+            embolden: font.is_embolden(),
+        }
+    }
+}
+
+/// Parameters a cached glow was rendered with.
+#[derive(Clone, PartialEq)]
+struct GlowKey {
+    blob: u32,
+    font: FontKey,
+    color: Color4f,
+    sigma: f32,
+}
+
+impl LuaTextGlow {
+    fn key(&self) -> GlowKey {
+        GlowKey {
+            blob: self.blob.unique_id(),
+            font: FontKey::from(&self.font),
+            color: self.color,
+            sigma: self.sigma,
+        }
+    }
+
+    fn render(&self) -> LuaResult<(Image, Point)> {
+        // blur is visually negligible past 3 sigma
+        let padding = (self.sigma * 3.0).ceil();
+        let bounds = self
+            .blob
+            .bounds()
+            .with_outset((padding, padding))
+            .round_out();
+        let mut surface = surfaces::raster_n32_premul(bounds.size())
+            .ok_or_else(|| LuaError::RuntimeError("unable to allocate glow surface".to_string()))?;
+
+        let mut paint = Paint::new(self.color, None);
+        paint.set_anti_alias(true);
+        paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, self.sigma, false));
+
+        let offset = Point::new(bounds.left as f32, bounds.top as f32);
+        let canvas = surface.canvas();
+        canvas.clear(Color::TRANSPARENT);
+        canvas.draw_text_blob(&self.blob, (-offset.x, -offset.y), &paint);
+        Ok((surface.image_snapshot(), offset))
+    }
+
+    /// Returns cached glow and its offset, rendering it again if parameters
+    /// changed since it was cached.
+    fn cached_glow(&mut self) -> LuaResult<(Image, Point)> {
+        let key = self.key();
+        match &self.cached {
+            Some((cached_key, image, offset)) if *cached_key == key => Ok((image.clone(), *offset)),
+            _ => {
+                let (image, offset) = self.render()?;
+                self.cached = Some((key, image.clone(), offset));
+                Ok((image, offset))
+            }
+        }
+    }
+}
+
+#[lua_methods(lua_name: TextGlow)]
+impl LuaTextGlow {
+    /// Draws glow with `alpha` opacity and text with `paint` over it.
+    pub fn draw(
+        &mut self,
+        canvas: &LuaCanvas,
+        point: LuaPoint,
+        alpha: Option<f32>,
+        paint: LuaFallible<LikePaint>,
+    ) {
+        canvas.check_valid()?;
+        let (image, offset) = self.cached_glow()?;
+        let point: Point = point.into();
+        let paint: Paint = paint.map(LikePaint::unwrap).unwrap_or_default();
+        canvas.damage(
+            Some(Rect::from_point_and_size(
+                point + offset,
+                image.dimensions(),
+            )),
+            None,
+        );
+        canvas.damage(Some(self.blob.bounds().with_offset(point)), Some(&paint));
+        let canvas = canvas.canvas();
+
+        let mut glow_paint = Paint::default();
+        glow_paint.set_alpha_f(alpha.unwrap_or(1.0).clamp(0.0, 1.0));
+        canvas.draw_image(image, point + offset, Some(&glow_paint));
+        canvas.draw_text_blob(&self.blob, point, &paint);
+        Ok(())
+    }
+    /// Replaces drawn text with `text` laid out with `font`, or the font
+    /// previously used if it's omitted.
+    pub fn set_text(&mut self, text: String, font: LuaFallible<LuaFont>) {
+        if let Some(font) = font.into_inner() {
+            self.font = font.unwrap();
+        }
+        self.blob = TextBlob::new(&text, &self.font).ok_or_else(|| {
+            LuaError::RuntimeError(format!("unable to lay out glow text '{}'", text))
+        })?;
+        Ok(())
+    }
+    pub fn set_color(&mut self, color: LuaColor) {
+        self.color = color.into();
+        Ok(())
+    }
+    pub fn set_sigma(&mut self, sigma: f32) {
+        if !sigma.is_finite() || sigma <= 0.0 {
+            return Err(LuaError::RuntimeError(
+                "glow sigma must be a positive, finite scalar".to_string(),
+            ));
+        }
+        self.sigma = sigma;
+        Ok(())
+    }
+    /// Frees cached glow image; it's rendered again on next `draw`.
+    pub fn release(&mut self) {
+        self.cached = None;
+        Ok(())
+    }
+}
+
+/// Builds a single [`TextBlob`] out of multiple runs that can use different
+/// fonts.
+pub struct LuaTextBlobBuilder {
+    builder: TextBlobBuilder,
+    /// Pen position after the last appended run.
+    cursor: Point,
+}
+
+#[lua_methods(lua_name: TextBlobBuilder)]
+impl LuaTextBlobBuilder {
+    #[lua(constructor)]
+    pub fn new() -> LuaTextBlobBuilder {
+        Ok(LuaTextBlobBuilder {
+            builder: TextBlobBuilder::new(),
+            cursor: Point::new(0., 0.),
+        })
+    }
+
+    /// Appends `text` shaped with `font`. The run starts where the previous
+    /// one ended, moved by `offset`.
+    pub fn append_run(&mut self, font: LuaFont, text: String, offset: LuaFallible<LuaPoint>) {
+        let offset: Point = offset.map(LuaPoint::into).unwrap_or_default();
+        let origin = self.cursor + offset;
+
+        let glyphs = font.0.str_to_glyphs_vec(&text);
+        if glyphs.is_empty() {
+            self.cursor = origin;
+            return Ok(());
+        }
+
+        let mut widths = vec![0.0; glyphs.len()];
+        font.0.get_widths(&glyphs, &mut widths);
+
+        let (run_glyphs, run_pos) = self.builder.alloc_run_pos(&font.0, glyphs.len(), None);
+        run_glyphs.copy_from_slice(&glyphs);
+        font.0.get_pos(&glyphs, run_pos, Some(origin));
+
+        self.cursor = origin + Point::new(widths.iter().sum(), 0.);
+        Ok(())
+    }
+
+    /// Returns the position at which the next run will start.
+    pub fn cursor(&self) -> LuaPoint {
+        Ok(LuaPoint::from(self.cursor))
+    }
+
+    /// Builds the blob and resets the builder.
+    pub fn make(&mut self) -> Option<LuaTextBlob> {
+        self.cursor = Point::new(0., 0.);
+        Ok(self.builder.make().map(LuaTextBlob::new))
+    }
+}
+
+/// Whether text drawn with `props` can use LCD (subpixel) anti-aliasing.
+fn lcd_text_props(props: &SurfaceProps) -> bool {
+    props.pixel_geometry() != PixelGeometry::Unknown && !props.is_use_device_independent_fonts()
+}
+
 #[derive(Clone)]
 pub struct LuaSaveLayerRec {
     bounds: Option<Rect>,
     paint: Option<LikePaint>,
     backdrop: Option<LuaImageFilter>,
     flags: SaveLayerFlags,
+    /// Skia layers always inherit surface props, so these only decide
+    /// whether LCD text is preserved in the layer.
+    props: Option<SurfaceProps>,
 }
 
 impl LuaSaveLayerRec {
+    /// Returns flags of the rec, including the ones implied by `props`.
+    pub fn flags(&self) -> SaveLayerFlags {
+        match &self.props {
+            Some(props) if lcd_text_props(props) => self.flags | SaveLayerFlags::PRESERVE_LCD_TEXT,
+            _ => self.flags,
+        }
+    }
+
     pub fn to_skia_save_layer_rec(&self) -> SaveLayerRec {
         let mut result = SaveLayerRec::default();
         if let Some(bounds) = &self.bounds {
             result = result.bounds(bounds);
         }
         if let Some(paint) = &self.paint {
-            result = result.paint(&paint.0 .0);
+            result = result.paint(paint.paint());
         }
         if let Some(backdrop) = &self.backdrop {
             result = result.backdrop(&backdrop.0);
         }
-        if !self.flags.is_empty() {
-            result = result.flags(self.flags);
+        let flags = self.flags();
+        if !flags.is_empty() {
+            result = result.flags(flags);
         }
         result
     }
 }
 
 impl<'lua> FromArgPack<'lua> for LuaSaveLayerRec {
-    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         let mut result = LuaSaveLayerRec {
             bounds: None,
             paint: None,
             backdrop: None,
             flags: SaveLayerFlags::empty(),
+            props: None,
         };
         let table = match args.pop() {
             LuaValue::Table(it) => it,
@@ -3581,12 +6541,26 @@ impl<'lua> FromArgPack<'lua> for LuaSaveLayerRec {
             );
         }
 
+        // margin requested by filters with a `cropRect` inflate shorthand
+        let mut margin = 0f32;
         if table.contains_key("paint")? {
-            result.paint = Some(table.get("paint")?)
+            let paint: LuaValue = table.get("paint")?;
+            if let LuaValue::Table(paint) = &paint {
+                if let Some(filter) = paint.try_get::<_, LuaImageFilter>("imageFilter", lua)? {
+                    margin = filter.crop_margin();
+                }
+            }
+            result.paint = Some(LikePaint::from_lua(paint, lua)?)
         }
 
         if table.contains_key("backdrop")? {
-            result.backdrop = Some(table.get_user_data("backdrop")?)
+            let backdrop: LuaImageFilter = table.get_user_data("backdrop")?;
+            margin = margin.max(backdrop.crop_margin());
+            result.backdrop = Some(backdrop)
+        }
+
+        if let Some(bounds) = &mut result.bounds {
+            bounds.outset((margin, margin));
         }
 
         if table.contains_key("flags")? {
@@ -3599,32 +6573,368 @@ impl<'lua> FromArgPack<'lua> for LuaSaveLayerRec {
                     result.flags = LuaSaveLayerFlags::from_table(list)?.0;
                 }
                 LuaNil => {}
-                _ => {
+                other => {
                     return Err(LuaError::FromLuaConversionError {
-                        from: "table",
+                        from: other.type_name(),
                         to: "SaveLayerFlags",
-                        message: Some("expected SaveLayerRec.flags entry to be a SaveLayerFlags string of array of strings".to_string()),
+                        message: Some(
+                            "expected SaveLayerRec.flags entry to be a flag name, an array of names or a map of names to booleans ('preserve_lcd_text', 'init_with_previous', 'f16_color_type')".to_string(),
+                        ),
                     })
                 }
             }
         }
 
+        if let Some(props) = table.get::<_, Option<LikeSurfaceProps>>("props")? {
+            result.props = Some(props.unwrap());
+        }
+
         Ok(result)
     }
 }
 
+/// Options for plotting functions like `Canvas:drawPolyline`.
+#[derive(Default)]
+pub struct LuaPlotOptions {
+    min: Option<f32>,
+    max: Option<f32>,
+    fill: Option<LikePaint>,
+}
+
+impl<'lua> FromArgPack<'lua> for LuaPlotOptions {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match args.pop() {
+            LuaValue::Table(it) => it,
+            LuaNil => return Ok(LuaPlotOptions::default()),
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "PlotOptions",
+                    message: Some("expected a PlotOptions table or nil".to_string()),
+                });
+            }
+        };
+
+        Ok(LuaPlotOptions {
+            min: table.try_get("min", lua)?,
+            max: table.try_get("max", lua)?,
+            fill: table.try_get("fill", lua)?,
+        })
+    }
+}
+
+impl LuaPlotOptions {
+    fn plot(&self, canvas: &Canvas, values: &[f32], rect: Rect, paint: &Paint, smooth: bool) {
+        let (min, max) = match (self.min, self.max, chart::value_range(values)) {
+            (Some(min), Some(max), _) => (min, max),
+            (min, max, Some((data_min, data_max))) => {
+                (min.unwrap_or(data_min), max.unwrap_or(data_max))
+            }
+            // no finite samples to draw
+            (_, _, None) => return,
+        };
+
+        let runs = chart::map_values(values, rect, min, max);
+        let (line, area) = chart::plot_paths(&runs, rect, smooth);
+        if let Some(fill) = &self.fill {
+            profile::draw_path(canvas, &area, fill.paint());
+        }
+        profile::draw_path(canvas, &line, paint);
+    }
+}
+
+/// Options for `Canvas:drawMagnifier`.
+#[derive(Default)]
+pub struct LuaMagnifierOptions {
+    border: Option<LikePaint>,
+    sampling: Option<LuaSamplingOptions>,
+    circular: Option<bool>,
+}
+
+impl<'lua> FromArgPack<'lua> for LuaMagnifierOptions {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match args.pop() {
+            LuaValue::Table(it) => it,
+            LuaNil => return Ok(LuaMagnifierOptions::default()),
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "MagnifierOptions",
+                    message: Some("expected a MagnifierOptions table or nil".to_string()),
+                });
+            }
+        };
+
+        Ok(LuaMagnifierOptions {
+            border: table.try_get("border", lua)?,
+            sampling: table.try_get("sampling", lua)?,
+            circular: table.try_get("circular", lua)?,
+        })
+    }
+}
+
+/// Options for `Canvas:drawArrow`.
+#[derive(Default)]
+pub struct LuaArrowOptions {
+    head_length: Option<f32>,
+    head_width: Option<f32>,
+    head: Option<LuaArrowHead>,
+    both_ends: bool,
+}
+
+impl<'lua> FromArgPack<'lua> for LuaArrowOptions {
+    fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let table = match args.pop() {
+            LuaValue::Table(it) => it,
+            LuaNil => return Ok(LuaArrowOptions::default()),
+            other => {
+                return Err(LuaError::FromLuaConversionError {
+                    from: other.type_name(),
+                    to: "ArrowOptions",
+                    message: Some("expected an ArrowOptions table or nil".to_string()),
+                });
+            }
+        };
+
+        Ok(LuaArrowOptions {
+            head_length: table.try_get("headLength", lua)?,
+            head_width: table.try_get("headWidth", lua)?,
+            head: table.try_get("head", lua)?,
+            both_ends: table.try_get_or_default("bothEnds", lua)?,
+        })
+    }
+}
+
+impl LuaArrowOptions {
+    /// Returns head shape, sized relative to stroke width of `paint` unless
+    /// specified.
+    fn head_shape(&self, paint: &Paint) -> marker::HeadShape {
+        let length = self
+            .head_length
+            .unwrap_or_else(|| (paint.stroke_width() * 4.0).max(10.0))
+            .max(0.0);
+        let width = self.head_width.unwrap_or(length * 0.8).max(0.0);
+        marker::HeadShape {
+            style: self.head.map(LuaArrowHead::unwrap).unwrap_or_default(),
+            length,
+            width,
+        }
+    }
+}
+
+/// Rounds `value` to device pixels of `matrix` and maps it back.
+pub(crate) fn snap_to_pixel(matrix: &Matrix, value: PointOrRect) -> PointOrRect {
+    let inverse = match matrix.invert() {
+        Some(it) => it,
+        None => return value,
+    };
+    match value {
+        PointOrRect::Point(point) => {
+            let device = matrix.map_point(point);
+            let snapped = Point::new(device.x.round(), device.y.round());
+            PointOrRect::Point(inverse.map_point(snapped))
+        }
+        PointOrRect::Rect(rect) => {
+            let (device, _) = matrix.map_rect(rect);
+            let snapped = Rect::from(device.round());
+            let (local, _) = inverse.map_rect(snapped);
+            PointOrRect::Rect(local)
+        }
+    }
+}
+
+thread_local! {
+    static CHECKERBOARD_SHADERS: ShaderCache<(i32, u32, u32)> = ShaderCache::new();
+    static STRIPE_SHADERS: ShaderCache<(u32, u32, u32, u32)> = ShaderCache::new();
+    static CHECKER_SHADERS: ShaderCache<(u32, u32)> = ShaderCache::new();
+}
+
+/// Largest checkerboard cell size in pixels, keeping the backing raster small.
+pub(crate) const MAX_CHECKERBOARD_CELL: i32 = 256;
+
+/// Drops memoized preset shaders.
+pub(crate) fn clear_shader_caches() {
+    CHECKERBOARD_SHADERS.with(ShaderCache::clear);
+    STRIPE_SHADERS.with(ShaderCache::clear);
+    CHECKER_SHADERS.with(ShaderCache::clear);
+}
+
+/// Most pixels of a single stripe pattern image; longer patterns are
+/// stretched.
+const MAX_STRIPE_PIXELS: f32 = 1024.0;
+
+/// Returns a shader of vertical stripes, `width_a` wide `a` colored ones
+/// starting at x = 0, alternating with `width_b` wide `b` colored ones.
+pub(crate) fn stripe_shader(width_a: f32, a: Color, width_b: f32, b: Color) -> Option<Shader> {
+    let key = (width_a.to_bits(), a.into(), width_b.to_bits(), b.into());
+    STRIPE_SHADERS.with(|cache| {
+        cache.get_or_build(key, || {
+            let period = width_a + width_b;
+            // a single row with a pixel per unit, stretched to exact widths
+            let pixels = period.ceil().clamp(1.0, MAX_STRIPE_PIXELS);
+            let mut surface = surfaces::raster_n32_premul((pixels as i32, 1))?;
+            let canvas = surface.canvas();
+            canvas.clear(b);
+            canvas.scale((pixels / period, 1.0));
+            let mut paint = Paint::new(Color4f::from(a), None);
+            paint.set_anti_alias(true);
+            canvas.draw_rect(Rect::from_xywh(0.0, 0.0, width_a, 1.0), &paint);
+            surface.image_snapshot().to_shader(
+                (TileMode::Repeat, TileMode::Repeat),
+                SamplingOptions::new(FilterMode::Linear, MipmapMode::None),
+                &Matrix::scale((period / pixels, 1.0)),
+            )
+        })
+    })
+}
+
+/// Returns a repeating checkerboard shader with `cell` sized squares,
+/// starting with `a` colored cell in the top-left corner.
+///
+/// `cell` is clamped to `1..=MAX_CHECKERBOARD_CELL`.
+pub(crate) fn checkerboard_shader(cell: i32, a: Color, b: Color) -> Option<Shader> {
+    let cell = cell.clamp(1, MAX_CHECKERBOARD_CELL);
+    CHECKERBOARD_SHADERS.with(|cache| {
+        cache.get_or_build((cell, a.into(), b.into()), || {
+            let mut surface = surfaces::raster_n32_premul((cell * 2, cell * 2))?;
+            let canvas = surface.canvas();
+            canvas.clear(b);
+            let paint = Paint::new(Color4f::from(a), None);
+            let size = cell as f32;
+            canvas.draw_rect(Rect::from_xywh(0.0, 0.0, size, size), &paint);
+            canvas.draw_rect(Rect::from_xywh(size, size, size, size), &paint);
+            surface.image_snapshot().to_shader(
+                (TileMode::Repeat, TileMode::Repeat),
+                SamplingOptions::default(),
+                None,
+            )
+        })
+    })
+}
+
+/// Returns a repeating checkerboard shader with `cell` sized squares,
+/// starting with `a` colored cell at the origin.
+///
+/// A single 2×2 image is cached per pair of colors and scaled to `cell`, so
+/// cell sizes don't grow the image or the cache.
+pub(crate) fn checker_shader(cell: f32, a: Color, b: Color) -> Option<Shader> {
+    let shader = CHECKER_SHADERS.with(|cache| {
+        cache.get_or_build((a.into(), b.into()), || {
+            let mut surface = surfaces::raster_n32_premul((2, 2))?;
+            let canvas = surface.canvas();
+            canvas.clear(b);
+            let paint = Paint::new(Color4f::from(a), None);
+            canvas.draw_rect(Rect::from_xywh(0.0, 0.0, 1.0, 1.0), &paint);
+            canvas.draw_rect(Rect::from_xywh(1.0, 1.0, 1.0, 1.0), &paint);
+            surface.image_snapshot().to_shader(
+                (TileMode::Repeat, TileMode::Repeat),
+                SamplingOptions::new(FilterMode::Nearest, MipmapMode::None),
+                None,
+            )
+        })
+    })?;
+    Some(shader.with_local_matrix(&Matrix::scale((cell, cell))))
+}
+
+/// Returns stroke width of `paint` in device pixels along x and y axes.
+fn device_stroke_width(matrix: &Matrix, paint: &Paint) -> (f32, f32) {
+    match paint.stroke_width() {
+        // hairlines are always a single device pixel wide
+        it if it <= 0.0 => (1.0, 1.0),
+        it => (it * matrix.scale_x().abs(), it * matrix.scale_y().abs()),
+    }
+}
+
+/// Moves stroke center `coord` so that stroke edges of `width` fall on
+/// device pixel boundaries.
+#[inline]
+fn crisp_coord(coord: f32, width: f32) -> f32 {
+    (coord - width / 2.0).round() + width / 2.0
+}
+
+/// Adjusts line from `a` to `b` so that it's stroked crisply by `paint`.
+///
+/// Only horizontal and vertical lines drawn with a scale/translate matrix
+/// are adjusted, others are returned unchanged.
+pub(crate) fn crisp_line(matrix: &Matrix, paint: &Paint, a: Point, b: Point) -> (Point, Point) {
+    let inverse = match matrix.invert() {
+        Some(it) if matrix.is_scale_translate() => it,
+        _ => return (a, b),
+    };
+    let (width_x, width_y) = device_stroke_width(matrix, paint);
+    let mut da = matrix.map_point(a);
+    let mut db = matrix.map_point(b);
+
+    if da.y == db.y {
+        da.y = crisp_coord(da.y, width_y);
+        db.y = da.y;
+    } else if da.x == db.x {
+        da.x = crisp_coord(da.x, width_x);
+        db.x = da.x;
+    } else {
+        return (a, b);
+    }
+    (inverse.map_point(da), inverse.map_point(db))
+}
+
+/// Adjusts `rect` so that its outline is stroked crisply by `paint`.
+///
+/// Rects are only adjusted when drawn with a scale/translate matrix.
+pub(crate) fn crisp_rect(matrix: &Matrix, paint: &Paint, rect: Rect) -> Rect {
+    let inverse = match matrix.invert() {
+        Some(it) if matrix.is_scale_translate() => it,
+        _ => return rect,
+    };
+    let (width_x, width_y) = device_stroke_width(matrix, paint);
+    let (device, _) = matrix.map_rect(rect);
+    let snapped = Rect::new(
+        crisp_coord(device.left, width_x),
+        crisp_coord(device.top, width_y),
+        crisp_coord(device.right, width_x),
+        crisp_coord(device.bottom, width_y),
+    );
+    inverse.map_rect(snapped).0
+}
+
 #[derive(Clone)]
 pub enum LuaCanvas<'a> {
-    Owned(Surface),
+    /// Draws into a surface, stamped with its generation if it was returned
+    /// by `Surface:getCanvas`.
+    Owned(Surface, Option<SurfaceStamp>),
     Borrowed(&'a Canvas),
+    /// Draws into a null surface and records issued commands.
+    Debug(Surface, Rc<debug::CommandLog>),
+    /// Records draw calls for a tiled surface.
+    Tiled(Rc<tiled::TiledSurface>),
 }
 
 unsafe impl<'a> Send for LuaCanvas<'a> {}
 
+/// Returns area of a source with `bounds` shown magnified by `zoom` in
+/// `lens`, centered on the lens center.
+///
+/// Near source edges the area is moved rather than cut off, so magnification
+/// stays uniform and the lens never samples outside the source. Areas larger
+/// than the source are centered on it.
+pub(crate) fn magnifier_src_rect(lens: Rect, zoom: f32, bounds: Rect) -> Rect {
+    let (width, height) = (lens.width() / zoom, lens.height() / zoom);
+    let center = lens.center();
+    let clamp_axis = |center: f32, size: f32, min: f32, max: f32| {
+        if size >= max - min {
+            (min + max - size) / 2.0
+        } else {
+            (center - size / 2.0).clamp(min, max - size)
+        }
+    };
+    let left = clamp_axis(center.x, width, bounds.left, bounds.right);
+    let top = clamp_axis(center.y, height, bounds.top, bounds.bottom);
+    Rect::from_xywh(left, top, width, height)
+}
+
 impl<'a> LuaCanvas<'a> {
     pub fn canvas(&self) -> &Canvas {
         match self {
-            LuaCanvas::Owned(surface) => {
+            LuaCanvas::Owned(surface, _) | LuaCanvas::Debug(surface, _) => {
                 let surface = unsafe {
                     // SAFETY: This isn't safe. BUT, owning a RCHandle<SkSurface>
                     // doesn't guarantee unique mutable access to surface
@@ -3638,44 +6948,464 @@ impl<'a> LuaCanvas<'a> {
                 surface.canvas()
             }
             LuaCanvas::Borrowed(it) => it,
+            LuaCanvas::Tiled(it) => it.canvas(),
         }
     }
-}
 
-#[lua_methods(lua_name: Canvas)]
-impl<'a> LuaCanvas<'a> {
-    pub fn clear(&self, color: LuaFallible<LuaColor>) {
-        let color = color
-            .map(LuaColor::into)
+    /// Returns another handle drawing into the same canvas.
+    ///
+    /// # Safety
+    ///
+    /// Borrowed canvases are only valid while this handle is, so the returned
+    /// one must not outlive it.
+    unsafe fn share(&self) -> LuaCanvas<'static> {
+        match self {
+            LuaCanvas::Owned(surface, stamp) => LuaCanvas::Owned(surface.clone(), stamp.clone()),
+            LuaCanvas::Borrowed(it) => LuaCanvas::Borrowed(&*(*it as *const Canvas)),
+            LuaCanvas::Debug(surface, log) => LuaCanvas::Debug(surface.clone(), log.clone()),
+            LuaCanvas::Tiled(it) => LuaCanvas::Tiled(it.clone()),
+        }
+    }
+
+    /// Fails if this canvas draws into a surface that was resized since it
+    /// was created. Checked before every `Canvas` method.
+    pub(crate) fn check_valid(&self) -> LuaResult<()> {
+        match self {
+            LuaCanvas::Owned(_, Some(stamp)) if !stamp.is_current() => Err(LuaError::RuntimeError(
+                "canvas invalidated by surface resize".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports drawing local `bounds` (whole clip if `None`) with `paint`,
+    /// so tiled surfaces redraw tiles it hits and damage is tracked if
+    /// enabled.
+    #[inline]
+    fn damage(&self, bounds: Option<Rect>, paint: Option<&Paint>) {
+        if let LuaCanvas::Tiled(it) = self {
+            it.mark_drawn(bounds, paint);
+        }
+        if damage::is_active() {
+            if let Some(target) = self.damage_target() {
+                damage::add(&target, self.canvas(), bounds, paint);
+            }
+        }
+    }
+
+    /// Returns the surface this canvas draws into, or `None` if it isn't
+    /// backed by one.
+    fn damage_target(&self) -> Option<damage::Target> {
+        match self {
+            LuaCanvas::Owned(surface, _) | LuaCanvas::Debug(surface, _) => {
+                Some(damage::Target::Surface(surface.clone()))
+            }
+            LuaCanvas::Borrowed(canvas) => {
+                // SAFETY: Returned surface is reference counted and only
+                // used as tracking identity, canvas isn't mutated.
+                let canvas = unsafe { addr_of!(**canvas).cast_mut().as_mut() }?;
+                unsafe { canvas.surface() }.map(damage::Target::Surface)
+            }
+            LuaCanvas::Tiled(it) => Some(damage::Target::Tiled(it.clone())),
+        }
+    }
+
+    /// Counts the call when profiling and records a command if this is a
+    /// debug canvas. `params` are only evaluated when recording.
+    fn record(
+        &self,
+        op: &'static str,
+        params: impl FnOnce() -> Vec<(&'static str, Param)>,
+        paint: Option<&Paint>,
+    ) {
+        profile::count_call(self.canvas(), op);
+        if let LuaCanvas::Debug(_, log) = self {
+            log.push(debug::DebugCommand {
+                op,
+                params: params(),
+                paint: paint.cloned(),
+            });
+        }
+    }
+
+    /// Draws `src` area of `surface` (all of it by default) into `dst`, given
+    /// as a rect or a point to draw it at without scaling. Parts of `src`
+    /// outside the surface are skipped without moving the rest.
+    fn draw_surface_area(
+        &self,
+        surface: &mut Surface,
+        src: Option<Rect>,
+        dst: PointOrRect,
+        sampling: SamplingOptions,
+        paint: Option<&Paint>,
+    ) {
+        let bounds = Rect::from_isize(surface.image_info().dimensions());
+        let src = src.unwrap_or(bounds);
+        let dst = match dst {
+            PointOrRect::Point(it) => Rect::from_point_and_size(it, src.size()),
+            PointOrRect::Rect(it) => it,
+        };
+        let mut area = src;
+        if src.is_empty() || !area.intersect(bounds) {
+            return;
+        }
+        let (sx, sy) = (dst.width() / src.width(), dst.height() / src.height());
+        let dst = Rect::new(
+            dst.left + (area.left - src.left) * sx,
+            dst.top + (area.top - src.top) * sy,
+            dst.left + (area.right - src.left) * sx,
+            dst.top + (area.bottom - src.top) * sy,
+        );
+
+        self.record(
+            "drawSurface",
+            || vec![("srcRect", area.into()), ("dstRect", dst.into())],
+            paint,
+        );
+        self.damage(Some(dst), paint);
+        let canvas = self.canvas();
+        profile::count_surface(canvas, &surface.image_info(), area);
+
+        // Skia draws whole surfaces without a snapshot where the backend
+        // allows it, but not into their own canvas
+        let unscaled = dst.size() == area.size();
+        if area == bounds && unscaled && !std::ptr::eq(surface.canvas(), canvas) {
+            surface.draw(canvas, (dst.left, dst.top), sampling, paint);
+            return;
+        }
+        let paint = paint.cloned().unwrap_or_default();
+        canvas.draw_image_rect_with_sampling_options(
+            surface.image_snapshot(),
+            Some((&area, canvas::SrcRectConstraint::Strict)),
+            dst,
+            sampling,
+            &paint,
+        );
+    }
+}
+
+/// Places glyphs with advance `widths` along `path`, starting `h_offset`
+/// along it and shifted `v_offset` perpendicular to it.
+///
+/// Returns indices of glyphs that fit on the path along with their
+/// transforms; glyphs past the end of the path are dropped.
+pub(crate) fn path_glyph_xforms(
+    path: &Path,
+    widths: &[f32],
+    h_offset: f32,
+    v_offset: f32,
+) -> Vec<(usize, RSXform)> {
+    let contours: Vec<ContourMeasure> = ContourMeasureIter::new(path, false, None).collect();
+    let mut result = Vec::with_capacity(widths.len());
+    let mut advance = h_offset;
+    'glyphs: for (i, width) in widths.iter().copied().enumerate() {
+        // glyphs are placed by their horizontal center
+        let mut distance = advance + width / 2.0;
+        advance += width;
+        if distance < 0.0 {
+            continue;
+        }
+        for contour in &contours {
+            if distance > contour.length() {
+                distance -= contour.length();
+                continue;
+            }
+            let (position, tangent) = match contour.pos_tan(distance) {
+                Some(it) => it,
+                None => continue 'glyphs,
+            };
+            let normal = Vector::new(-tangent.y, tangent.x);
+            let origin = position - tangent * (width / 2.0) + normal * v_offset;
+            result.push((i, RSXform::new(tangent.x, tangent.y, origin)));
+            continue 'glyphs;
+        }
+        // past the end of the path
+        break;
+    }
+    result
+}
+
+#[lua_methods(lua_name: Canvas)]
+impl<'a> LuaCanvas<'a> {
+    /// Replaces pixels within the current clip with `color` (transparent by
+    /// default). Use `clearRect` to clear only a part of the clip.
+    pub fn clear(&self, color: LuaFallible<LuaColor>) {
+        let color = color
+            .map(LuaColor::into)
             .unwrap_or(skia_safe::colors::TRANSPARENT);
+        self.record(
+            "clear",
+            || vec![("color", Param::Color(color.into()))],
+            None,
+        );
+        self.damage(None, None);
         self.canvas().clear(color);
         Ok(())
     }
+    /// Replaces pixels of `rect` within the current clip with `color`
+    /// (transparent by default), unlike `drawRect` which blends over them.
+    pub fn clear_rect(&self, rect: LuaRect, color: LuaFallible<LuaColor>) {
+        let rect: Rect = rect.into();
+        let color: Color4f = color
+            .map(LuaColor::into)
+            .unwrap_or(skia_safe::colors::TRANSPARENT);
+        self.record(
+            "clearRect",
+            || vec![("rect", rect.into()), ("color", Param::Color(color.into()))],
+            None,
+        );
+        self.damage(Some(rect), None);
+        let mut paint = Paint::new(color, None);
+        paint.set_blend_mode(BlendMode::Src);
+        self.canvas().draw_rect(rect, &paint);
+        Ok(())
+    }
+    /// Hints that current contents of the surface won't be used again, which
+    /// lets some backends skip preserving them. Contents become undefined.
+    pub fn discard(&self) {
+        self.record("discard", Vec::new, None);
+        self.damage(None, None);
+        self.canvas().discard();
+        Ok(())
+    }
     pub fn draw_color(&self, color: LuaColor, blend_mode: LuaFallible<LuaBlendMode>) {
+        self.record(
+            "drawColor",
+            || {
+                let mut params = vec![("color", color.into())];
+                if let Some(name) = blend_mode.as_ref().and_then(LuaBlendMode::name) {
+                    params.push(("blendMode", name.into()));
+                }
+                params
+            },
+            None,
+        );
+        self.damage(None, None);
         self.canvas().draw_color(color, blend_mode.map_t());
         Ok(())
     }
+    /// Darkens existing pixels towards black, keeping `1 - alpha` of their
+    /// current value.
+    pub fn fade(&self, alpha: f32) {
+        self.record("fade", || vec![("alpha", alpha.into())], None);
+        self.damage(None, None);
+        let color = Color4f::new(0.0, 0.0, 0.0, alpha.clamp(0.0, 1.0));
+        self.canvas().draw_color(color, BlendMode::SrcOver);
+        Ok(())
+    }
+    /// Moves existing pixels towards `color` by `amount` (0 keeps them, 1
+    /// replaces them). Color alpha scales the amount.
+    pub fn tint(&self, color: LuaColor, amount: f32) {
+        self.record(
+            "tint",
+            || vec![("color", color.into()), ("amount", amount.into())],
+            None,
+        );
+        let mut color: Color4f = color.into();
+        // src-over with a non-premultiplied source computes
+        // `dst * (1 - a) + color * a`, which is exactly a lerp by `a`
+        color.a *= amount.clamp(0.0, 1.0);
+        self.damage(None, None);
+        self.canvas().draw_color(color, BlendMode::SrcOver);
+        Ok(())
+    }
     pub fn draw_paint(&self, paint: LikePaint) {
-        self.canvas().draw_paint(&paint.0 .0);
+        self.record("drawPaint", Vec::new, Some(paint.paint()));
+        self.damage(None, Some(paint.paint()));
+        self.canvas().draw_paint(paint.paint());
         Ok(())
     }
     pub fn draw_rect(&self, rect: LuaRect, paint: LikePaint) {
         let rect: Rect = rect.into();
-        self.canvas().draw_rect(rect, &paint.0 .0);
+        self.record(
+            "drawRect",
+            || vec![("rect", rect.into())],
+            Some(paint.paint()),
+        );
+        self.damage(Some(rect), Some(paint.paint()));
+        self.canvas().draw_rect(rect, paint.paint());
+        Ok(())
+    }
+    /// Draws a line from `p1` to `p2`, offset so that horizontal and vertical
+    /// lines cover whole device pixels instead of blending across two.
+    pub fn draw_hairline(&self, p1: LuaPoint, p2: LuaPoint, paint: LikePaint) {
+        let mut paint = paint.unwrap();
+        paint.set_style(PaintStyle::Stroke);
+        self.record(
+            "drawHairline",
+            || vec![("p1", p1.into()), ("p2", p2.into())],
+            Some(&paint),
+        );
+        let canvas = self.canvas();
+        let matrix = canvas.local_to_device_as_3x3();
+        let (p1, p2) = crisp_line(&matrix, &paint, p1.into(), p2.into());
+        let mut bounds = Rect::new(p1.x, p1.y, p2.x, p2.y);
+        bounds.sort();
+        self.damage(Some(bounds), Some(&paint));
+        canvas.draw_line(p1, p2, &paint);
+        Ok(())
+    }
+    /// Strokes outline of `rect`, offset so that its edges cover whole device
+    /// pixels.
+    pub fn stroke_rect_crisp(&self, rect: LuaRect, paint: LikePaint) {
+        let mut paint = paint.unwrap();
+        paint.set_style(PaintStyle::Stroke);
+        self.record(
+            "strokeRectCrisp",
+            || vec![("rect", rect.into())],
+            Some(&paint),
+        );
+        let canvas = self.canvas();
+        let matrix = canvas.local_to_device_as_3x3();
+        let rect = crisp_rect(&matrix, &paint, rect.into());
+        self.damage(Some(rect), Some(&paint));
+        canvas.draw_rect(rect, &paint);
+        Ok(())
+    }
+    /// Fills border inside `shape` with per side widths and colors. Sides meet
+    /// at mitered joins and follow rounded corners of RRect shapes.
+    ///
+    /// Borders aren't anti-aliased with minimal quality.
+    pub fn draw_border<'lua>(&self, lua: &'lua LuaContext, shape: LikeShape, borders: LuaBorders) {
+        let outer = shape.to_rrect().ok_or_else(|| {
+            LuaError::RuntimeError(
+                "drawBorder requires a rect, rrect, circle or oval path shape".to_string(),
+            )
+        })?;
+        self.record("drawBorder", || vec![("rect", shape.bounds().into())], None);
+        let widths = borders
+            .0
+            .map(|it| it.map(|side| side.width.max(0.0)).unwrap_or_default());
+        let paths = border::side_paths(&outer, widths);
+        self.damage(Some(*outer.rect()), None);
+        let anti_alias = quality::quality(lua)?.anti_alias();
+
+        let canvas = self.canvas();
+        for (side, path) in borders.0.iter().zip(paths) {
+            if let (Some(side), Some(path)) = (side, path) {
+                let mut paint = Paint::new(side.color, None);
+                paint.set_anti_alias(anti_alias);
+                profile::draw_path(canvas, &path, &paint);
+            }
+        }
+        Ok(())
+    }
+    /// Fills `shape` with `style.fill` and then outlines it with
+    /// `style.stroke`. Fill and stroke styles are forced regardless of the
+    /// style set on the provided paints.
+    pub fn draw_styled(&self, shape: LikeShape, style: ShapeStyle) {
+        let ShapeStyle { fill, stroke } = style;
+        let fill = fill.map(|mut it| {
+            it.set_style(PaintStyle::Fill);
+            it
+        });
+        let stroke = stroke.map(|mut it| {
+            it.set_style(PaintStyle::Stroke);
+            it
+        });
+
+        let canvas = self.canvas();
+        let bounds = Some(shape.bounds()).filter(|_| !shape.is_unbounded());
+        for paint in [&fill, &stroke].into_iter().flatten() {
+            self.record(
+                "drawStyled",
+                || vec![("bounds", shape.bounds().into())],
+                Some(paint),
+            );
+            self.damage(bounds, Some(paint));
+            shape.draw(canvas, paint);
+        }
+        Ok(())
+    }
+    /// Fills `rect` with a checkerboard pattern commonly used to show
+    /// transparency. Defaults to 8px light gray and white cells.
+    ///
+    /// Cell size is clamped to 256px.
+    pub fn draw_checkerboard(
+        &self,
+        rect: LuaRect,
+        cell_size: Option<f32>,
+        color_a: LuaFallible<LuaColor>,
+        color_b: LuaFallible<LuaColor>,
+    ) {
+        let rect: Rect = rect.into();
+        // NaN fails both comparisons and ends up as the smallest cell
+        let cell = cell_size
+            .unwrap_or(8.0)
+            .round()
+            .max(1.0)
+            .min(MAX_CHECKERBOARD_CELL as f32) as i32;
+        let a: Color = color_a
+            .map(LuaColor::into)
+            .unwrap_or(Color::from_rgb(204, 204, 204));
+        let b: Color = color_b.map(LuaColor::into).unwrap_or(Color::WHITE);
+        self.record(
+            "drawCheckerboard",
+            || vec![("rect", rect.into()), ("cellSize", cell.into())],
+            None,
+        );
+
+        let shader = checkerboard_shader(cell, a, b).ok_or_else(|| {
+            LuaError::RuntimeError("unable to create checkerboard shader".to_string())
+        })?;
+        let mut paint = Paint::default();
+        // align pattern with rect instead of canvas origin
+        paint.set_shader(shader.with_local_matrix(&Matrix::translate((rect.left, rect.top))));
+        self.damage(Some(rect), None);
+        self.canvas().draw_rect(rect, &paint);
         Ok(())
     }
     pub fn draw_oval(&self, oval: LuaRect, paint: LikePaint) {
         let oval: Rect = oval.into();
-        self.canvas().draw_oval(oval, &paint.0 .0);
+        self.record(
+            "drawOval",
+            || vec![("oval", oval.into())],
+            Some(paint.paint()),
+        );
+        self.damage(Some(oval), Some(paint.paint()));
+        self.canvas().draw_oval(oval, paint.paint());
         Ok(())
     }
     pub fn draw_circle(&self, point: LuaPoint, r: f32, paint: LikePaint) {
-        self.canvas().draw_circle(point, r, &paint.0 .0);
+        self.record(
+            "drawCircle",
+            || vec![("point", point.into()), ("r", r.into())],
+            Some(paint.paint()),
+        );
+        let center: Point = point.into();
+        self.damage(
+            Some(Rect::new(
+                center.x - r,
+                center.y - r,
+                center.x + r,
+                center.y + r,
+            )),
+            Some(paint.paint()),
+        );
+        self.canvas().draw_circle(point, r, paint.paint());
         Ok(())
     }
     pub fn draw_image(&self, image: LuaImage, point: LuaPoint, paint: LuaFallible<LikePaint>) {
+        let paint = paint.map(LikePaint::unwrap);
+        self.record(
+            "drawImage",
+            || {
+                vec![
+                    ("point", point.into()),
+                    ("width", image.0.width().into()),
+                    ("height", image.0.height().into()),
+                ]
+            },
+            paint.as_ref(),
+        );
+        let origin: Point = point.into();
+        self.damage(
+            Some(Rect::from_point_and_size(origin, image.0.dimensions())),
+            paint.as_ref(),
+        );
+        profile::count_image(self.canvas(), &image.0, None);
         self.canvas()
-            .draw_image(image.unwrap(), point, paint.map(LikePaint::unwrap).as_ref());
+            .draw_image(image.unwrap(), point, paint.as_ref());
         Ok(())
     }
     pub fn draw_image_rect(
@@ -3683,14 +7413,27 @@ impl<'a> LuaCanvas<'a> {
         image: LuaImage,
         src_rect: Option<LuaRect>,
         dst_rect: LuaRect,
-        paint: Option<LikePaint>,
+        paint: LuaFallible<LikePaint>,
     ) {
-        let paint: Paint = match paint {
-            Some(it) => it.unwrap(),
-            None => Paint::default(),
-        };
+        // skia_safe requires a paint here; SkCanvas treats a missing paint
+        // the same as a default constructed one, so drawImage and
+        // drawImageRect produce identical output when paint is nil.
+        let paint: Paint = paint.map(LikePaint::unwrap).unwrap_or_default();
         let src_rect = src_rect.map(|it| it.into());
         let dst_rect: Rect = dst_rect.into();
+        self.record(
+            "drawImageRect",
+            || {
+                let mut params = vec![("dstRect", dst_rect.into())];
+                if let Some(src_rect) = src_rect {
+                    params.push(("srcRect", Param::Rect(src_rect)));
+                }
+                params
+            },
+            Some(&paint),
+        );
+        self.damage(Some(dst_rect), Some(&paint));
+        profile::count_image(self.canvas(), &image.0, src_rect);
         self.canvas().draw_image_rect(
             image.unwrap(),
             src_rect
@@ -3701,29 +7444,351 @@ impl<'a> LuaCanvas<'a> {
         );
         Ok(())
     }
+    /// Draws `srcRect` area of `surface` (whole surface if `nil`) into `dst`,
+    /// given as a rect to scale it into or a point to draw it at.
+    ///
+    /// Pixels outside of `srcRect` aren't sampled, so a scrolled part of a
+    /// larger surface doesn't bleed in its neighbors when filtered. The
+    /// alpha, blend mode and filters of `paint` apply like for images.
+    pub fn draw_surface(
+        &self,
+        surface: LuaSurface,
+        src_rect: Option<LuaRect>,
+        dst: PointOrRect,
+        sampling: LuaFallible<LuaSamplingOptions>,
+        paint: LuaFallible<LikePaint>,
+    ) {
+        let sampling: SamplingOptions = sampling.unwrap_or_default().into();
+        let paint = paint.map(LikePaint::unwrap);
+        self.draw_surface_area(
+            &mut surface.unwrap(),
+            src_rect.map(LuaRect::into),
+            dst,
+            sampling,
+            paint.as_ref(),
+        );
+        Ok(())
+    }
+    /// Draws part of `source` around the center of `lens_rect` magnified by
+    /// `zoom` into the lens, assuming the source is drawn at the origin.
+    ///
+    /// The lens is clipped to an oval unless `opts.circular` is `false` and
+    /// outlined with `opts.border` paint if provided. `opts.sampling`
+    /// defaults to linear filtering. Near source edges the magnified area
+    /// is moved to stay within the source instead of being stretched.
+    pub fn draw_magnifier<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        source: SurfaceOrImage,
+        lens_rect: LuaRect,
+        zoom: f32,
+        opts: LuaMagnifierOptions,
+    ) {
+        if !zoom.is_finite() || zoom <= 0.0 {
+            return Err(LuaError::RuntimeError(format!(
+                "drawMagnifier: zoom must be a positive, finite scalar; got {}",
+                zoom
+            )));
+        }
+        let mut source = source;
+        let lens: Rect = lens_rect.into();
+        let border = opts.border.map(|it| {
+            let mut paint = it.unwrap();
+            paint.set_style(PaintStyle::Stroke);
+            paint
+        });
+        self.record(
+            "drawMagnifier",
+            || vec![("lensRect", lens.into()), ("zoom", zoom.into())],
+            border.as_ref(),
+        );
+        if lens.is_empty() {
+            return Ok(());
+        }
+
+        let image = source.to_image();
+        let src = magnifier_src_rect(lens, zoom, Rect::from_irect(image.bounds()));
+        let sampling: SamplingOptions = opts
+            .sampling
+            .map(Into::into)
+            .unwrap_or_else(|| SamplingOptions::new(FilterMode::Linear, MipmapMode::None));
+        warn_missing_mipmaps("Canvas:drawMagnifier", &image, &sampling);
+        let anti_alias = quality::quality(lua)?.anti_alias();
+
+        let canvas = self.canvas();
+        self.damage(Some(lens), border.as_ref());
+        canvas.save();
+        if opts.circular.unwrap_or(true) {
+            canvas.clip_rrect(RRect::new_oval(lens), None, anti_alias);
+        } else {
+            canvas.clip_rect(lens, None, anti_alias);
+        }
+        profile::count_image(canvas, &image, Some(src));
+        canvas.draw_image_rect_with_sampling_options(
+            &image,
+            Some((&src, canvas::SrcRectConstraint::Strict)),
+            lens,
+            sampling,
+            &Paint::default(),
+        );
+        canvas.restore();
+
+        if let Some(border) = &border {
+            if opts.circular.unwrap_or(true) {
+                canvas.draw_oval(lens, border);
+            } else {
+                canvas.draw_rect(lens, border);
+            }
+        }
+        Ok(())
+    }
+    /// Draws `image` scaled into `rrect`, given as an `RRect` or a rect and
+    /// corner radius, with anti-aliased corners.
+    ///
+    /// The image is drawn as a shader filling the rounded rect, so it's a
+    /// single draw without a clip; a shader set on `paint` is replaced.
+    /// Corners aren't anti-aliased with minimal quality.
+    pub fn draw_image_rounded<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        image: LuaImage,
+        rrect: RoundedRect,
+        sampling: LuaFallible<LuaSamplingOptions>,
+        paint: LuaFallible<LikePaint>,
+    ) {
+        let rrect = rrect.0;
+        let dst = *rrect.rect();
+        let sampling: SamplingOptions = sampling.unwrap_or_default().into();
+        warn_missing_mipmaps("Canvas:drawImageRounded", &image.0, &sampling);
+        let mut paint: Paint = paint.map(LikePaint::unwrap).unwrap_or_default();
+        self.record(
+            "drawImageRounded",
+            || {
+                vec![
+                    ("rect", dst.into()),
+                    ("width", image.0.width().into()),
+                    ("height", image.0.height().into()),
+                ]
+            },
+            Some(&paint),
+        );
+        if dst.is_empty() || image.0.width() == 0 || image.0.height() == 0 {
+            return Ok(());
+        }
+
+        let mut local = Matrix::translate((dst.left, dst.top));
+        local.pre_scale(
+            (
+                dst.width() / image.0.width() as f32,
+                dst.height() / image.0.height() as f32,
+            ),
+            None,
+        );
+        let shader = image
+            .0
+            .to_shader((TileMode::Clamp, TileMode::Clamp), sampling, &local)
+            .ok_or_else(|| {
+                LuaError::RuntimeError(
+                    "drawImageRounded: unable to create image shader".to_string(),
+                )
+            })?;
+        paint.set_shader(shader);
+        paint.set_anti_alias(quality::quality(lua)?.anti_alias());
+
+        self.damage(Some(dst), Some(&paint));
+        profile::count_image(self.canvas(), &image.0, None);
+        self.canvas().draw_rrect(rrect, &paint);
+        Ok(())
+    }
     pub fn draw_patch(
         &self,
         cubics: [LuaPoint; 12],
         colors: LuaFallible<[LuaColor; 4]>,
         tex_coords: LuaFallible<[LuaPoint; 4]>,
-        blend_mode: LuaBlendMode,
+        blend_mode: LuaFallible<LuaBlendMode>,
         paint: LikePaint,
     ) {
-        let cubics = cubics.map(Into::into);
+        let cubics: [Point; 12] = cubics.map(Into::into);
         let colors = colors.into_inner().map(|it| it.map(Into::into));
         let tex_coords = tex_coords.into_inner().map(|it| it.map(Into::into));
+        let paint = paint.unwrap();
+        // blend mode only combines corner colors with the paint shader, so
+        // modulate is only a sensible default when both are present
+        let blend_mode = blend_mode.unwrap_or_else_t(|| {
+            if colors.is_some() && paint.shader().is_some() {
+                BlendMode::Modulate
+            } else {
+                BlendMode::SrcOver
+            }
+        });
 
+        self.record(
+            "drawPatch",
+            || {
+                let points: Vec<f32> = cubics.iter().flat_map(|it| [it.x, it.y]).collect();
+                vec![("cubics", points.into())]
+            },
+            Some(&paint),
+        );
+        let mut bounds = Rect::default();
+        bounds.set_bounds(&cubics);
+        self.damage(Some(bounds), Some(&paint));
         self.canvas().draw_patch(
             &cubics,
             colors.as_ref(),
             tex_coords.as_ref(),
-            *blend_mode,
-            &paint.0 .0,
+            blend_mode,
+            &paint,
         );
         Ok(())
     }
     pub fn draw_path(&self, path: LuaPath, paint: LikePaint) {
-        self.canvas().draw_path(&path.0, &paint.0 .0);
+        self.record(
+            "drawPath",
+            || vec![("bounds", (*path.0.bounds()).into())],
+            Some(paint.paint()),
+        );
+        let bounds = match path.0.is_inverse_fill_type() {
+            true => None,
+            false => Some(*path.0.bounds()),
+        };
+        self.damage(bounds, Some(paint.paint()));
+        profile::draw_path(self.canvas(), &path.0, paint.paint());
+        Ok(())
+    }
+    /// Strokes `path` with dashes of roughly `dash_length` and gaps of
+    /// `dash_length * gap_ratio`. Path is split at sharp corners and dashes
+    /// are fitted to each part, so every side starts and ends with a dash.
+    pub fn stroke_dashed_fitted(
+        &self,
+        path: LuaPath,
+        paint: LikePaint,
+        dash_length: f32,
+        gap_ratio: f32,
+    ) {
+        if !dash_length.is_finite()
+            || dash_length <= 0.0
+            || !gap_ratio.is_finite()
+            || gap_ratio < 0.0
+        {
+            return Err(LuaError::RuntimeError(format!(
+                "dash length must be positive and gap ratio non-negative; got {} and {}",
+                dash_length, gap_ratio
+            )));
+        }
+        let mut paint = paint.unwrap();
+        paint.set_style(PaintStyle::Stroke);
+        self.record(
+            "strokeDashedFitted",
+            || {
+                vec![
+                    ("bounds", (*path.0.bounds()).into()),
+                    ("dashLength", dash_length.into()),
+                    ("gapRatio", gap_ratio.into()),
+                ]
+            },
+            Some(&paint),
+        );
+        let dashes = dash::fitted_dashes(&path.0, dash_length, gap_ratio);
+        self.damage(Some(*path.0.bounds()), Some(&paint));
+        profile::draw_path(self.canvas(), &dashes, &paint);
+        Ok(())
+    }
+    /// Plots `values` evenly spaced across `rect`, mapping the value range
+    /// (`opts.min`/`opts.max`, or data range by default) to its height.
+    /// Non-finite values (NaN) leave gaps in the line. If `opts.fill` paint
+    /// is provided, the area under the line is filled with it.
+    pub fn draw_polyline(
+        &self,
+        values: Vec<f32>,
+        rect: LuaRect,
+        paint: LikePaint,
+        opts: LuaPlotOptions,
+    ) {
+        self.record(
+            "drawPolyline",
+            || vec![("rect", rect.into()), ("count", values.len().into())],
+            Some(paint.paint()),
+        );
+        self.damage(Some(rect.into()), Some(paint.paint()));
+        opts.plot(self.canvas(), &values, rect.into(), paint.paint(), false);
+        Ok(())
+    }
+    /// Same as `drawPolyline`, but smooths the line with a Catmull-Rom
+    /// spline passing through every sample.
+    pub fn draw_spline(
+        &self,
+        values: Vec<f32>,
+        rect: LuaRect,
+        paint: LikePaint,
+        opts: LuaPlotOptions,
+    ) {
+        self.record(
+            "drawSpline",
+            || vec![("rect", rect.into()), ("count", values.len().into())],
+            Some(paint.paint()),
+        );
+        self.damage(Some(rect.into()), Some(paint.paint()));
+        opts.plot(self.canvas(), &values, rect.into(), paint.paint(), true);
+        Ok(())
+    }
+    /// Draws a line from `from` to `to` with a head pointing at `to`.
+    ///
+    /// `opts.head` is `"filled"` (default), `"open"` or `"dot"`, sized by
+    /// `opts.headLength` and `opts.headWidth` (by default relative to the
+    /// stroke width). Set `opts.bothEnds` to add a head at `from` as well.
+    pub fn draw_arrow(
+        &self,
+        from: LuaPoint,
+        to: LuaPoint,
+        paint: LikePaint,
+        opts: LuaArrowOptions,
+    ) {
+        let mut stroke = paint.unwrap();
+        stroke.set_style(PaintStyle::Stroke);
+        // keep the apex of open heads at `to`
+        stroke.set_stroke_join(PaintJoin::Miter);
+        let mut fill = stroke.clone();
+        fill.set_style(PaintStyle::Fill);
+        self.record(
+            "drawArrow",
+            || vec![("from", from.into()), ("to", to.into())],
+            Some(&stroke),
+        );
+
+        let head = opts.head_shape(&stroke);
+        let paths = marker::arrow_paths(from.into(), to.into(), head, opts.both_ends);
+        let canvas = self.canvas();
+        for (path, paint) in [(&paths.stroke, &stroke), (&paths.fill, &fill)] {
+            if !path.is_empty() {
+                self.damage(Some(*path.bounds()), Some(paint));
+                profile::draw_path(canvas, path, paint);
+            }
+        }
+        Ok(())
+    }
+    /// Draws a `"cross"`, `"circle"`, `"diamond"` or `"square"` marker of
+    /// `size` centered on `point`. Crosses are always stroked, other markers
+    /// use the style of `paint`.
+    pub fn draw_marker(&self, point: LuaPoint, style: LuaMarkerStyle, size: f32, paint: LikePaint) {
+        let mut paint = paint.unwrap();
+        if *style == marker::MarkerStyle::Cross {
+            paint.set_style(PaintStyle::Stroke);
+        }
+        self.record(
+            "drawMarker",
+            || {
+                vec![
+                    ("point", point.into()),
+                    ("style", style.name().unwrap_or_default().into()),
+                    ("size", size.into()),
+                ]
+            },
+            Some(&paint),
+        );
+        let path = marker::marker_path(style.unwrap(), point.into(), size.max(0.0));
+        self.damage(Some(*path.bounds()), Some(&paint));
+        profile::draw_path(self.canvas(), &path, &paint);
         Ok(())
     }
     pub fn draw_picture(
@@ -3734,13 +7799,232 @@ impl<'a> LuaCanvas<'a> {
     ) {
         let matrix: Option<Matrix> = matrix.map(LuaMatrix::into);
         let paint: Option<Paint> = paint.map(LikePaint::unwrap);
+        self.record(
+            "drawPicture",
+            || vec![("cullRect", picture.0.cull_rect().into())],
+            paint.as_ref(),
+        );
+        let mut bounds = *picture.0.cull_rect();
+        if let Some(matrix) = &matrix {
+            bounds = matrix.map_rect(bounds).0;
+        }
+        self.damage(Some(bounds), paint.as_ref());
         self.canvas()
             .draw_picture(picture, matrix.as_ref(), paint.as_ref());
         Ok(())
     }
-    pub fn draw_text_blob(&self, blob: LuaTextBlob, point: LuaPoint, paint: LikePaint) {
-        self.canvas()
-            .draw_text_blob(blob.unwrap(), point, &paint.0 .0);
+    /// Renders `dom` scaled from its container size to fill `dst_rect`.
+    ///
+    /// With `paint`, the document is drawn into a layer composed with it,
+    /// e.g. to apply opacity or a color filter to the whole icon.
+    #[cfg(feature = "svg")]
+    pub fn draw_svg(&self, dom: &svg::LuaSvgDom, dst_rect: LuaRect, paint: LuaFallible<LikePaint>) {
+        let dst_rect: Rect = dst_rect.into();
+        let size = dom.container_size();
+        if size.is_empty() || dst_rect.is_empty() {
+            return Ok(());
+        }
+        self.record(
+            "drawSvg",
+            || vec![("dstRect", dst_rect.into())],
+            paint.as_ref().map(LikePaint::paint),
+        );
+        self.damage(Some(dst_rect), paint.as_ref().map(LikePaint::paint));
+
+        let canvas = self.canvas();
+        let restore_count = match paint.as_ref() {
+            Some(paint) => {
+                profile::count_save_layer(canvas);
+                canvas.save_layer(
+                    &SaveLayerRec::default()
+                        .bounds(&dst_rect)
+                        .paint(paint.paint()),
+                )
+            }
+            None => canvas.save(),
+        };
+        canvas.clip_rect(dst_rect, None, true);
+        canvas.translate((dst_rect.left, dst_rect.top));
+        canvas.scale((
+            dst_rect.width() / size.width,
+            dst_rect.height() / size.height,
+        ));
+        dom.dom.render(canvas);
+        canvas.restore_to_count(restore_count);
+        Ok(())
+    }
+    /// Calls `draw(canvas)` and masks what it draws with `mask`: a `Shader`
+    /// whose alpha is used directly (e.g. a gradient fading to transparent),
+    /// or an `Image` stretched over the clip bounds. Alpha of `"alpha8"`
+    /// images is used directly as well, while other images use their
+    /// luminance as alpha.
+    ///
+    /// Content is drawn into a layer, which is restored even if `draw`
+    /// raises an error; content drawn before the error is discarded then.
+    /// The canvas passed to `draw` can't be used after it returns.
+    pub fn with_alpha_mask<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        mask: ShaderOrImage,
+        draw: LuaFunction<'lua>,
+    ) {
+        let canvas = self.canvas();
+        let mut mask_paint = Paint::default();
+        mask_paint.set_blend_mode(BlendMode::DstIn);
+        match mask {
+            ShaderOrImage::Shader(shader) => {
+                mask_paint.set_shader(shader);
+            }
+            ShaderOrImage::Image(image) => {
+                let bounds = canvas.local_clip_bounds().unwrap_or_default();
+                let mut local = Matrix::translate((bounds.left, bounds.top));
+                local.pre_scale(
+                    (
+                        bounds.width() / image.width().max(1) as f32,
+                        bounds.height() / image.height().max(1) as f32,
+                    ),
+                    None,
+                );
+                let sampling = SamplingOptions::new(FilterMode::Linear, MipmapMode::None);
+                let shader = image
+                    .to_shader((TileMode::Decal, TileMode::Decal), sampling, &local)
+                    .ok_or_else(|| {
+                        LuaError::RuntimeError(
+                            "withAlphaMask: unable to create image shader".to_string(),
+                        )
+                    })?;
+                mask_paint.set_shader(shader);
+                // alpha only images have black color, so their luminance is 0
+                if !image.is_alpha_only() {
+                    mask_paint.set_color_filter(luma_color_filter::new());
+                }
+            }
+        }
+        self.record("withAlphaMask", Vec::new, Some(&mask_paint));
+
+        profile::count_save_layer(canvas);
+        let count = canvas.save_layer(&SaveLayerRec::default());
+        // SAFETY: the handle is destroyed before this call returns, or an
+        // error is raised if that fails
+        let result = lua
+            .create_userdata(unsafe { self.share() })
+            .and_then(|handle| {
+                let result = draw.call::<_, ()>(handle.clone());
+                let taken = handle.take::<LuaCanvas<'static>>().map(|_| ());
+                result.and(taken)
+            });
+        if result.is_ok() {
+            canvas.draw_paint(&mask_paint);
+        } else {
+            // drop partial content instead of compositing it unmasked
+            canvas.restore_to_count(count + 1);
+            canvas.clear(Color::TRANSPARENT);
+        }
+        canvas.restore_to_count(count);
+        result
+    }
+    /// Fills `region` with `paint`. Useful with a translucent paint to show
+    /// input or damage areas while debugging.
+    pub fn draw_region(&self, region: &LuaRegion, paint: LikePaint) {
+        let bounds = Rect::from_irect(*region.0.bounds());
+        self.record(
+            "drawRegion",
+            || vec![("bounds", bounds.into())],
+            Some(paint.paint()),
+        );
+        self.damage(Some(bounds), Some(paint.paint()));
+        self.canvas().draw_region(&region.0, paint.paint());
+        Ok(())
+    }
+    /// Intersects the clip with `region`, which is in device pixels and not
+    /// affected by the canvas matrix.
+    pub fn clip_region(&self, region: &LuaRegion) {
+        self.record(
+            "clipRegion",
+            || vec![("bounds", Rect::from_irect(*region.0.bounds()).into())],
+            None,
+        );
+        self.canvas().clip_region(&region.0, ClipOp::Intersect);
+        Ok(())
+    }
+    /// Draws a `TextBlob`, or a string laid out with `font` which is
+    /// required in that case.
+    pub fn draw_text_blob(
+        &self,
+        blob: BlobOrString,
+        point: LuaPoint,
+        paint: LikePaint,
+        font: LuaFallible<LuaFont>,
+    ) {
+        let blob = match blob {
+            BlobOrString::Blob(it) => it,
+            BlobOrString::String(text) => {
+                let font = font.into_inner().ok_or_else(|| {
+                    LuaError::RuntimeError(
+                        "drawTextBlob: a font argument is required when drawing a string"
+                            .to_string(),
+                    )
+                })?;
+                match TextBlob::new(text, &font.0) {
+                    Some(it) => it,
+                    // nothing to draw
+                    None => return Ok(()),
+                }
+            }
+        };
+        self.record(
+            "drawTextBlob",
+            || vec![("point", point.into()), ("bounds", (*blob.bounds()).into())],
+            Some(paint.paint()),
+        );
+        let origin: Point = point.into();
+        self.damage(Some(blob.bounds().with_offset(origin)), Some(paint.paint()));
+        self.canvas().draw_text_blob(blob, point, paint.paint());
+        Ok(())
+    }
+    /// Draws `text` along `path`, starting `h_offset` along it and shifted
+    /// `v_offset` perpendicular to it (positive values move glyphs below the
+    /// path). Glyphs which don't fit on the path are dropped.
+    pub fn draw_text_on_path(
+        &self,
+        text: LuaText,
+        path: LuaPath,
+        font: LuaFont,
+        paint: LikePaint,
+        h_offset: Option<f32>,
+        v_offset: Option<f32>,
+    ) {
+        let h_offset = h_offset.unwrap_or_default();
+        let v_offset = v_offset.unwrap_or_default();
+        self.record(
+            "drawTextOnPath",
+            || {
+                vec![
+                    ("bounds", (*path.0.bounds()).into()),
+                    ("hOffset", h_offset.into()),
+                    ("vOffset", v_offset.into()),
+                ]
+            },
+            Some(paint.paint()),
+        );
+
+        let glyphs = font.0.text_to_glyphs_vec(text);
+        let mut widths = vec![0.0; glyphs.len()];
+        font.0.get_widths(&glyphs, &mut widths);
+
+        let (kept_glyphs, xforms): (Vec<GlyphId>, Vec<RSXform>) =
+            path_glyph_xforms(&path.0, &widths, h_offset, v_offset)
+                .into_iter()
+                .map(|(i, xform)| (glyphs[i], xform))
+                .unzip();
+        if kept_glyphs.is_empty() {
+            return Ok(());
+        }
+        if let Some(blob) = TextBlob::from_rsxform(kept_glyphs.as_slice(), &xforms, &font.0) {
+            self.damage(Some(*blob.bounds()), Some(paint.paint()));
+            self.canvas()
+                .draw_text_blob(blob, (0.0, 0.0), paint.paint());
+        }
         Ok(())
     }
     pub fn get_save_count(&self) -> usize {
@@ -3752,36 +8036,263 @@ impl<'a> LuaCanvas<'a> {
     pub fn get_local_to_device3x3(&self) -> LuaMatrix {
         Ok(LuaMatrix::Three(self.canvas().local_to_device_as_3x3()))
     }
-    pub fn save(&self) -> usize {
+    /// Returns `true` if `shape` drawn with the current matrix is entirely
+    /// outside of the clip, so drawing it can be skipped.
+    ///
+    /// Paths are checked by their bounds and other shapes by their bounding
+    /// rect, so shapes close to the clip may not be rejected even if they
+    /// wouldn't draw anything. Inverse filled paths are never rejected.
+    pub fn quick_reject(&self, shape: LikeShape) -> bool {
+        if shape.is_unbounded() {
+            return Ok(false);
+        }
+        let canvas = self.canvas();
+        Ok(match &shape {
+            LikeShape::Path(path) => canvas.quick_reject(path),
+            _ => canvas.quick_reject(&shape.bounds()),
+        })
+    }
+    /// Returns bounds of the clip in local coordinates, or `nil` if the clip
+    /// is empty. Bounds are outset by a pixel to account for anti-aliasing.
+    pub fn get_local_clip_bounds(&self) -> Option<LuaRect> {
+        Ok(self.canvas().local_clip_bounds().map(LuaRect::from))
+    }
+    /// Returns bounds of the clip in device pixels, or `nil` if the clip is
+    /// empty.
+    pub fn get_device_clip_bounds(&self) -> Option<LuaRect> {
+        Ok(self.canvas().device_clip_bounds().map(LuaRect::from))
+    }
+    /// Returns font edging text drawn into this canvas should use.
+    ///
+    /// Subpixel anti-aliasing is only recommended if the current layer has
+    /// a known pixel geometry, doesn't use device independent fonts and text
+    /// isn't rotated, skewed or in perspective; otherwise it's
+    /// `"anti_alias"`. Layers lose the pixel geometry of their surface unless
+    /// they were saved with LCD `props` or the `preserve_lcd_text` flag.
+    pub fn recommended_font_edging(&self) -> LuaFontEdging {
+        let canvas = self.canvas();
+        let axis_aligned = canvas.local_to_device_as_3x3().is_scale_translate();
+        let edging = if axis_aligned && lcd_text_props(&canvas.top_props()) {
+            FontEdging::SubpixelAntiAlias
+        } else {
+            FontEdging::AntiAlias
+        };
+        Ok(LuaFontEdging(edging))
+    }
+    /// Blurs content behind `shape` (frosted glass effect) and optionally
+    /// tints it with `tint` using `blend` mode (src-over by default).
+    ///
+    /// Below full quality, the shape is only filled with `tint`.
+    pub fn draw_backdrop_blur<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        shape: LikeShape,
+        sigma: f32,
+        tint: LuaFallible<LuaColor>,
+        blend: LuaFallible<LuaBlendMode>,
+    ) {
+        if !sigma.is_finite() || sigma < 0f32 {
+            return Err(LuaError::RuntimeError(
+                "sigma must be a positive, finite scalar".to_string(),
+            ));
+        }
+        let tint: Option<Color4f> = tint.map(LuaColor::into);
+        let blend = blend.unwrap_or_t(BlendMode::SrcOver);
+        self.record(
+            "drawBackdropBlur",
+            || {
+                let mut params = vec![("rect", shape.bounds().into()), ("sigma", sigma.into())];
+                if let Some(tint) = tint {
+                    params.push(("tint", Param::Color(tint)));
+                }
+                params
+            },
+            None,
+        );
+
+        self.damage(Some(shape.bounds()), None);
+        let quality = quality::quality(lua)?;
+        let canvas = self.canvas();
+        let restore_count = canvas.save();
+        shape.clip(canvas, quality.anti_alias());
+
+        if quality != Quality::Full {
+            let tint = tint.unwrap_or(Color4f::new(0.5, 0.5, 0.5, 0.5));
+            canvas.draw_color(tint, blend);
+        } else {
+            let backdrop = image_filters::blur((sigma, sigma), TileMode::Clamp, None, None)
+                .ok_or_else(|| {
+                    LuaError::RuntimeError("unable to create backdrop blur filter".to_string())
+                })?;
+            let bounds = shape.bounds();
+            profile::count_save_layer(canvas);
+            canvas.save_layer(&SaveLayerRec::default().bounds(&bounds).backdrop(&backdrop));
+            if let Some(tint) = tint {
+                canvas.draw_color(tint, blend);
+            }
+        }
+
+        canvas.restore_to_count(restore_count);
+        Ok(())
+    }
+    /// Returns number of device pixels per logical pixel.
+    pub fn scale_factor(&self) -> f32 {
+        Ok(session::session().scale_factor)
+    }
+    /// Saves canvas state and applies device scale so that following draw
+    /// calls use logical pixels. Must be paired with `endFrame`.
+    ///
+    /// Returns `logical_size` if provided, or canvas size in logical pixels.
+    pub fn begin_frame<'lua>(
+        &self,
+        lua: &'lua LuaContext,
+        logical_size: LuaFallible<LuaSize>,
+    ) -> LuaSize {
+        let scale = session::session().scale_factor;
+        let canvas = self.canvas();
+        session::push_frame(lua, canvas);
+        canvas.scale((scale, scale));
+
+        Ok(logical_size.into_inner().unwrap_or_else(|| {
+            let size = canvas.base_layer_size();
+            LuaSize::from(Size::new(
+                size.width as f32 / scale,
+                size.height as f32 / scale,
+            ))
+        }))
+    }
+    /// Restores canvas state saved by the matching `beginFrame`.
+    pub fn end_frame<'lua>(&self, lua: &'lua LuaContext) {
+        let canvas = self.canvas();
+        let count = session::pop_frame(lua, canvas).ok_or_else(|| {
+            LuaError::RuntimeError("endFrame called without matching beginFrame".to_string())
+        })?;
+        canvas.restore_to_count(count);
+        Ok(())
+    }
+    /// Rounds a point or rect to device pixel boundaries through the current
+    /// total matrix, returning it in local coordinates.
+    ///
+    /// Rects are snapped through their device space bounds, so rotated
+    /// rects will grow to contain the rotated shape.
+    pub fn snap_to_pixel(&self, value: PointOrRect) -> PointOrRect {
+        let matrix = self.canvas().local_to_device_as_3x3();
+        Ok(snap_to_pixel(&matrix, value))
+    }
+    pub fn save(&self) -> usize {
+        self.record("save", Vec::new, None);
         Ok(self.canvas().save())
     }
+    /// Saves state and starts a layer described by a `{bounds, paint,
+    /// backdrop, flags, props}` table (all optional).
+    ///
+    /// `flags` is a flag name, an array of names or a map of names to
+    /// booleans, e.g. `{initWithPrevious = true}` to start the layer with a
+    /// copy of what's below it, as backdrop effects need. Names are
+    /// `preserve_lcd_text`, `init_with_previous` and `f16_color_type`, in
+    /// snake_case or camelCase.
     pub fn save_layer(&self, save_layer_rec: LuaSaveLayerRec) -> usize {
         let rec = save_layer_rec.to_skia_save_layer_rec();
+        self.record("saveLayer", Vec::new, None);
+        // filters of the layer can spread content past what's drawn into it
+        let paint = save_layer_rec.paint.as_ref().map(|it| &it.0 .0);
+        let filtered = paint.map_or(false, |it| it.image_filter().is_some());
+        if filtered || save_layer_rec.backdrop.is_some() {
+            self.damage(save_layer_rec.bounds, paint);
+        }
+        profile::count_save_layer(self.canvas());
         Ok(self.canvas().save_layer(&rec))
     }
     pub fn restore(&self) {
+        self.record("restore", Vec::new, None);
         self.canvas().restore();
         Ok(())
     }
     pub fn restore_to_count(&self, count: usize) {
+        self.record("restoreToCount", || vec![("count", count.into())], None);
         self.canvas().restore_to_count(count);
         Ok(())
     }
-    pub fn scale(&self, sx: f32, sy: LuaFallible<f32>) {
-        let sy = sy.unwrap_or(sx);
-        self.canvas().scale((sx, sy));
+    /// Scales by `sx` and `sy` (defaults to `sx`), or by a `{x, y}` table,
+    /// about an optional `pivot` point.
+    pub fn scale(&self, scale: ScalePack) {
+        let ScalePack { sx, sy, pivot } = scale;
+        self.record(
+            "scale",
+            || {
+                let mut params = vec![("sx", sx.into()), ("sy", sy.into())];
+                if let Some(pivot) = pivot {
+                    params.push(("pivot", Param::Numbers(vec![pivot.x, pivot.y])));
+                }
+                params
+            },
+            None,
+        );
+        let canvas = self.canvas();
+        match pivot {
+            Some(pivot) => {
+                canvas.translate(pivot);
+                canvas.scale((sx, sy));
+                canvas.translate(-pivot);
+            }
+            None => {
+                canvas.scale((sx, sy));
+            }
+        }
         Ok(())
     }
     pub fn translate(&self, point: LuaPoint) {
+        self.record("translate", || vec![("point", point.into())], None);
         self.canvas().translate(point);
         Ok(())
     }
     pub fn rotate(&self, degrees: f32, point: LuaFallible<LuaPoint>) {
-        let point = point.map(LuaPoint::into);
+        let point: Option<Point> = point.map(LuaPoint::into);
+        self.record(
+            "rotate",
+            || {
+                let mut params = vec![("degrees", degrees.into())];
+                if let Some(point) = point {
+                    params.push(("point", Param::Numbers(vec![point.x, point.y])));
+                }
+                params
+            },
+            None,
+        );
         self.canvas().rotate(degrees, point);
         Ok(())
     }
+    /// Rotates by `degrees` about a point, or about the center of a rect.
+    pub fn rotate_around(&self, pivot: PointOrRect, degrees: f32) {
+        let pivot = match pivot {
+            PointOrRect::Point(it) => it,
+            PointOrRect::Rect(it) => it.center(),
+        };
+        self.record(
+            "rotate",
+            || {
+                vec![
+                    ("degrees", degrees.into()),
+                    ("point", Param::Numbers(vec![pivot.x, pivot.y])),
+                ]
+            },
+            None,
+        );
+        self.canvas().rotate(degrees, Some(pivot));
+        Ok(())
+    }
     pub fn concat(&self, matrix: LuaMatrix) {
+        self.record(
+            "concat",
+            || {
+                let values = match &matrix {
+                    LuaMatrix::Three(matrix) => (0..9).map(|i| matrix[i]).collect(),
+                    LuaMatrix::Four(matrix) => (0..4).flat_map(|i| matrix.row(i)).collect(),
+                };
+                vec![("matrix", values.into())]
+            },
+            None,
+        );
         match matrix {
             LuaMatrix::Three(matrix) => self.canvas().concat(&matrix),
             LuaMatrix::Four(matrix) => self.canvas().concat_44(&matrix),
@@ -3790,7 +8301,7 @@ impl<'a> LuaCanvas<'a> {
     }
     pub fn new_surface(&self, info: LikeImageInfo, props: LuaFallible<LikeSurfaceProps>) {
         self.canvas()
-            .new_surface(&info.0 .0, props.map(|it| it.0 .0).as_ref());
+            .new_surface(&info.0 .0, props.map(LikeSurfaceProps::unwrap).as_ref());
         Ok(())
     }
     pub fn width(&self) -> i32 {
@@ -3799,37 +8310,4124 @@ impl<'a> LuaCanvas<'a> {
     pub fn height(&self) -> i32 {
         Ok(self.canvas().base_layer_size().height)
     }
+    /// Starts accumulating device space bounds of following draw calls into
+    /// the surface of this canvas, discarding previously accumulated damage.
+    pub fn begin_damage_tracking<'lua>(&self, lua: &'lua LuaContext) {
+        let target = self.damage_target().ok_or_else(|| {
+            LuaError::RuntimeError("canvas isn't backed by a surface".to_string())
+        })?;
+        damage::begin_context_tracking(lua, &target);
+        Ok(())
+    }
+    /// Intersects the clip with the union of device space `rects`, such as
+    /// those returned by `takeDamage`, so only damaged areas get redrawn.
+    pub fn clip_to_damage(&self, rects: Vec<LuaRect>) {
+        let mut clip = Region::new();
+        for rect in rects {
+            let rect: Rect = rect.into();
+            clip.op_rect(rect.round_out(), region::RegionOp::Union);
+        }
+        self.record(
+            "clipToDamage",
+            || vec![("bounds", Param::Rect(Rect::from_irect(*clip.bounds())))],
+            None,
+        );
+        self.canvas().clip_region(&clip, ClipOp::Intersect);
+        Ok(())
+    }
+    /// Stops accumulating damage.
+    pub fn end_damage_tracking<'lua>(&self, lua: &'lua LuaContext) {
+        if let Some(target) = self.damage_target() {
+            damage::end_context_tracking(lua, &target);
+        }
+        Ok(())
+    }
+    /// Returns an array of device space rects changed since tracking
+    /// started or the previous call, and resets it. Returns `nil` if damage
+    /// isn't tracked.
+    pub fn take_damage(&self) -> Option<Vec<LuaRect>> {
+        Ok(self
+            .damage_target()
+            .and_then(|target| damage::take_damage(&target))
+            .map(|rects| rects.into_iter().map(LuaRect::from).collect()))
+    }
+    /// Returns commands recorded by a debug canvas as an array of
+    /// `{op = "drawRect", rect = ..., paintSummary = {...}}` tables.
+    pub fn get_commands<'lua>(&self, lua: &'lua LuaContext) -> LuaTable<'lua> {
+        match self {
+            LuaCanvas::Debug(_, log) => log.to_table(lua),
+            _ => Err(LuaError::RuntimeError(
+                "only debug canvases record commands".to_string(),
+            )),
+        }
+    }
+    /// Discards commands recorded by a debug canvas.
+    pub fn clear_commands(&self) {
+        match self {
+            LuaCanvas::Debug(_, log) => {
+                log.clear();
+                Ok(())
+            }
+            _ => Err(LuaError::RuntimeError(
+                "only debug canvases record commands".to_string(),
+            )),
+        }
+    }
 }
 
-macro_rules! global_constructors {
-    ($ctx: ident: $($t: ty),* $(,)?) => {paste::paste!{
+/// Surface split into tiles that are drawn through a single canvas.
+pub struct LuaTiledSurface(Rc<tiled::TiledSurface>);
+
+#[lua_methods(lua_name: TiledSurface)]
+impl LuaTiledSurface {
+    /// Returns a canvas recording draw calls, which are rendered into tiles
+    /// they hit on `flush`, `snapshotTile` or `drawTo`.
+    pub fn get_canvas(&self) -> LuaCanvas<'static> {
+        Ok(LuaCanvas::Tiled(self.0.clone()))
+    }
+    pub fn width(&self) -> i32 {
+        Ok(self.0.size().width)
+    }
+    pub fn height(&self) -> i32 {
+        Ok(self.0.size().height)
+    }
+    pub fn tile_size(&self) -> i32 {
+        Ok(self.0.tile_size())
+    }
+    /// Returns number of tile columns and rows.
+    pub fn tile_count(&self) -> (i32, i32) {
+        Ok(self.0.tile_count())
+    }
+    /// Returns number of tiles holding pixel memory.
+    pub fn allocated_tiles(&self) -> usize {
+        Ok(self.0.allocated_tiles())
+    }
+    /// Marks tiles intersecting `rect` (all tiles if `nil`) to be cleared on
+    /// next flush. Other tiles keep their content and only have draw calls
+    /// that hit them added on top.
+    pub fn mark_dirty(&self, rect: Option<LuaRect>) {
+        self.0.mark_dirty(rect.map(Into::into));
+        Ok(())
+    }
+    /// Renders recorded draw calls into dirty tiles, returning the number of
+    /// redrawn tiles.
+    pub fn flush(&self) -> usize {
+        self.0
+            .flush()
+            .map_err(|err| LuaError::RuntimeError(err.to_string()))
+    }
+    /// Returns an image of tile at 0-based column `ix` and row `iy`, or
+    /// `nil` if there's no such tile or it was never drawn.
+    pub fn snapshot_tile(&self, ix: i32, iy: i32) -> Option<LuaImage> {
+        Ok(self
+            .0
+            .snapshot_tile(ix, iy)
+            .map_err(|err| LuaError::RuntimeError(err.to_string()))?
+            .map(LuaImage::new))
+    }
+    pub fn draw_to(
+        &self,
+        canvas: &LuaCanvas,
+        offset: LuaFallible<LuaPoint>,
+        paint: LuaFallible<LikePaint>,
+    ) {
+        canvas.check_valid()?;
+        let offset: Point = offset.map(LuaPoint::into).unwrap_or_default();
+        let offset = IPoint::new(offset.x.round() as i32, offset.y.round() as i32);
+        let paint = paint.map(LikePaint::unwrap);
+        self.0
+            .draw_to(canvas.canvas(), offset, paint.as_ref())
+            .map_err(|err| LuaError::RuntimeError(err.to_string()))?;
+        canvas.damage(
+            Some(Rect::from(IRect::from_pt_size(offset, self.0.size()))),
+            paint.as_ref(),
+        );
+        Ok(())
+    }
+    /// Resizes the surface, keeping tiles that remain in bounds.
+    pub fn resize(&self, size: LuaSize) {
+        self.0
+            .resize(size.into())
+            .map_err(|err| LuaError::RuntimeError(err.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Pair of surfaces drawn into alternately, so images handed to the host
+/// are never snapshots of a partially drawn frame.
+pub struct LuaDoubleBuffer {
+    info: ImageInfo,
+    /// Surfaces are allocated when first drawn into after creation or
+    /// resize.
+    surfaces: [Option<Surface>; 2],
+    back: usize,
+    /// Canvas userdata of the back buffer handed out since the last swap.
+    canvas: Option<LuaRegistryKey>,
+}
+
+impl LuaDoubleBuffer {
+    fn back_surface(&mut self) -> LuaResult<Surface> {
+        if let Some(surface) = &self.surfaces[self.back] {
+            return Ok(surface.clone());
+        }
+        let info = &self.info;
+        let surface = surfaces::raster(info, None, None).or_failed("DoubleBuffer", || {
+            format!(
+                "unsupported {}x{} surface with color type {:?} and alpha type {:?}",
+                info.width(),
+                info.height(),
+                info.color_type(),
+                info.alpha_type()
+            )
+        })?;
+        self.surfaces[self.back] = Some(surface.clone());
+        Ok(surface)
+    }
+
+    /// Destroys canvas returned by `drawCanvas`, so that drawing with it
+    /// raises an error instead of going into the wrong buffer.
+    fn invalidate_canvas(&mut self, lua: &LuaContext) -> LuaResult<()> {
+        if let Some(key) = self.canvas.take() {
+            let canvas: LuaAnyUserData = lua.registry_value(&key)?;
+            lua.remove_registry_value(key)?;
+            // already destroyed if the canvas was garbage collected
+            let _ = canvas.take::<LuaCanvas<'static>>();
+        }
+        Ok(())
+    }
+}
+
+#[lua_methods(lua_name: DoubleBuffer)]
+impl LuaDoubleBuffer {
+    pub fn new(info: LikeImageInfo) -> LuaDoubleBuffer {
+        let mut result = LuaDoubleBuffer {
+            info: info.unwrap(),
+            surfaces: [None, None],
+            back: 0,
+            canvas: None,
+        };
+        // report unsupported image info when it's provided
+        result.back_surface()?;
+        Ok(result)
+    }
+    /// Returns canvas drawing into the back buffer. It stays valid until
+    /// the next `present` or `resize`, and raises an error when used after.
+    ///
+    /// Back buffer keeps the frame presented before the last one, so it
+    /// should be cleared unless everything is redrawn.
+    pub fn draw_canvas<'lua>(&mut self, lua: &'lua LuaContext) -> LuaAnyUserData<'lua> {
+        if let Some(key) = &self.canvas {
+            return lua.registry_value(key);
+        }
+        let canvas = lua.create_userdata(LuaCanvas::Owned(self.back_surface()?, None))?;
+        self.canvas = Some(lua.create_registry_value(canvas.clone())?);
+        Ok(canvas)
+    }
+    /// Swaps buffers and returns an image of the frame drawn into the back
+    /// buffer since the last swap.
+    pub fn present<'lua>(&mut self, lua: &'lua LuaContext) -> LuaImage {
+        self.invalidate_canvas(lua)?;
+        let image = self.back_surface()?.image_snapshot();
+        self.back = 1 - self.back;
+        Ok(LuaImage::new(image))
+    }
+    /// Changes image info of both buffers, which are reallocated when next
+    /// drawn into. Invalidates canvas returned by `drawCanvas`.
+    pub fn resize<'lua>(&mut self, lua: &'lua LuaContext, info: LikeImageInfo) {
+        self.invalidate_canvas(lua)?;
+        let info = info.unwrap();
+        if info != self.info {
+            self.info = info;
+            self.surfaces = [None, None];
+        }
+        Ok(())
+    }
+    pub fn width(&self) -> i32 {
+        Ok(self.info.width())
+    }
+    pub fn height(&self) -> i32 {
+        Ok(self.info.height())
+    }
+}
+
+pub struct Surfaces;
+
+#[lua_methods(lua_name: Surfaces)]
+impl Surfaces {
+    /// Creates a canvas which doesn't draw anything, but records issued
+    /// commands retrievable with `Canvas:getCommands`.
+    pub fn debug(size: LuaSize) -> LuaCanvas<'static> {
+        let size: ISize = size.into();
+        Ok(surfaces::null(size)
+            .map(|surface| LuaCanvas::Debug(surface, Default::default()))
+            .or_failed("Surfaces.debug", || {
+                format!("invalid size {}x{}", size.width, size.height)
+            })?)
+    }
+    /// Creates a surface split into `tile_size` (512 by default) square
+    /// tiles, which are only allocated once drawn into.
+    pub fn tiled(size: LuaSize, tile_size: Option<i32>) -> LuaTiledSurface {
+        let size: ISize = size.into();
+        Ok(tiled::TiledSurface::new(size, tile_size)
+            .map(|it| LuaTiledSurface(Rc::new(it)))
+            .or_failed("Surfaces.tiled", || {
+                format!(
+                    "invalid size {}x{} or tile size {}",
+                    size.width,
+                    size.height,
+                    tile_size.unwrap_or(tiled::DEFAULT_TILE_SIZE)
+                )
+            })?)
+    }
+}
+
+macro_rules! handle_stats {
+    ($ctx: ident, $stats: ident, $result: ident: $($t: ty),* $(,)?) => {paste::paste!{
         $(
-            [<Lua $t>]::register_globals($ctx)?;
+            let name = <[<Lua $t>] as stats::TrackedHandle>::NAME;
+            let entry = $ctx.create_table()?;
+            entry.set("live", $stats.live(name))?;
+            entry.set("bytes", $stats.bytes(name))?;
+            $result.set(stringify!($t), entry)?;
         )*
     }};
 }
 
+pub struct Skia;
+
+#[lua_methods(lua_name: Skia)]
+impl Skia {
+    /// Returns live handle counts and approximate pixel memory use per
+    /// wrapped type, as well as `totalBytes` across all of them.
+    ///
+    /// Pixels shared by several handles, such as a surface and images
+    /// snapshotted from it, count once towards the type that created them.
+    pub fn stats<'lua>(lua: &'lua LuaContext) -> LuaTable<'lua> {
+        let result = lua.create_table()?;
+        let stats = stats::context_stats(lua).unwrap_or_default();
+        handle_stats!(lua, stats, result:
+            ColorFilter,
+            ColorInfo,
+            ColorSpace,
+            DashInfo,
+            Font,
+            FontStyle,
+            FontStyleSet,
+            Image,
+            ImageFilter,
+            ImageInfo,
+            MaskFilter,
+            Paint,
+            Path,
+            PathEffect,
+            Picture,
+            Region,
+            RRect,
+            Shader,
+            StrokeRec,
+            Surface,
+            SurfaceProps,
+            TextBlob,
+            Typeface,
+        );
+        result.set("totalBytes", stats.total_bytes())?;
+        Ok(result)
+    }
+
+    /// Returns render quality set by the host: `"full"`, `"reduced"` or
+    /// `"minimal"`.
+    pub fn quality<'lua>(lua: &'lua LuaContext) -> Quality {
+        Ok(quality::quality(lua)?)
+    }
+
+    /// Calls `callback(totalBytes, budget)` once memory held by images and
+    /// surfaces exceeds `bytes`. Passing `nil` as callback disables the
+    /// budget.
+    pub fn on_budget_exceeded<'lua>(
+        lua: &'lua LuaContext,
+        bytes: usize,
+        callback: Option<LuaFunction<'lua>>,
+    ) {
+        let bytes = if callback.is_some() { bytes } else { 0 };
+        stats::set_budget(lua, bytes, callback)
+    }
+
+    /// Starts counting draw calls, path verbs, sampled image bytes and
+    /// layers per canvas, as well as time spent in bound functions. Restarts
+    /// profiling if it's already active.
+    pub fn begin_profiling() {
+        profile::begin();
+        Ok(())
+    }
+
+    /// Stops profiling and returns a report with `canvases` array of
+    /// `{calls, totalCalls, pathVerbs, imageBytes, saveLayers}` tables in
+    /// order of their first draw call, and `bindingCalls` and `bindingTime`
+    /// in seconds. Returns `nil` if profiling wasn't started.
+    pub fn end_profiling<'lua>(lua: &'lua LuaContext) -> Option<LuaTable<'lua>> {
+        profile::end(lua)
+    }
+
+    /// Returns a table describing every registered type for tooling, e.g.
+    /// `describeApi().Canvas.drawRect` is `{kind = "method", params =
+    /// {{name = "rect", type = "Rect"}, ...}, returns, signature, doc}`.
+    pub fn describe_api<'lua>(lua: &'lua LuaContext) -> LuaTable<'lua> {
+        help::describe(lua, API_HELP)
+    }
+}
+
+/// Lists every bound type once, generating [`API_HELP`] and
+/// `register_globals`, which registers global tables of `globals` types, so
+/// help can't drift from what [`setup`] registers. `userdata` types only have
+/// methods and `tables` document globals not bound with `lua_methods`.
+macro_rules! bound_types {
+    (
+        globals: [$($(#[$g_meta: meta])* $global: ty),* $(,)?],
+        userdata: [$($(#[$u_meta: meta])* $userdata: ty),* $(,)?],
+        tables: [$($table: expr),* $(,)?] $(,)?
+    ) => {
+        /// Documentation of every global and type registered by [`setup`]
+        /// and [`theme::setup_with_theme`], used for the `Help` table and
+        /// `Skia.describeApi()`.
+        pub const API_HELP: &[&help::TypeHelp] = &[
+            $($(#[$g_meta])* &<$global>::LUA_HELP,)*
+            $($(#[$u_meta])* &<$userdata>::LUA_HELP,)*
+            $(&$table,)*
+        ];
+
+        fn register_globals(lua: &LuaContext) -> LuaResult<()> {
+            $($(#[$g_meta])* <$global>::register_globals(lua)?;)*
+            Ok(())
+        }
+    };
+}
+
+bound_types! {
+    globals: [
+        anim::Anim,
+        animated::LuaAnimatedImage,
+        BlendModeFns,
+        color_matrix::ColorMatrixFns,
+        font_chain::LuaFontChain,
+        frame::Frame,
+        GradientShader,
+        input_region::InputRegion,
+        layout::Layout,
+        LuaColorFilter,
+        LuaColorSpace,
+        LuaColorStops,
+        LuaDoubleBuffer,
+        LuaFont,
+        LuaFontMgr,
+        LuaFontStyle,
+        LuaFontStyleSet,
+        LuaImage,
+        LuaImageFilter,
+        LuaMatrix,
+        LuaPaint,
+        LuaPath,
+        LuaPathEffect,
+        LuaRegion,
+        LuaRRect,
+        LuaShader,
+        LuaStrokeRec,
+        LuaSurface,
+        LuaTextBlob,
+        LuaTextBlobBuilder,
+        LuaTypeface,
+        PointFns,
+        RectFns,
+        Skia,
+        Surfaces,
+        #[cfg(feature = "svg")]
+        svg::Svg,
+    ],
+    userdata: [
+        LuaAnimatedDash,
+        LuaCanvas,
+        LuaColorInfo,
+        LuaDashInfo,
+        LuaImageInfo,
+        LuaMaskFilter,
+        LuaPicture,
+        LuaSurfaceProps,
+        LuaTextGlow,
+        LuaTiledSurface,
+        #[cfg(feature = "svg")]
+        svg::LuaSvgDom,
+    ],
+    tables: [enums::LUA_HELP, help::LUA_HELP, theme::LUA_HELP],
+}
+
 // TODO: filter conversion isn't automatic
 #[allow(non_snake_case)]
 pub fn setup(lua: &LuaContext) -> Result<(), mlua::Error> {
-    global_constructors!(lua:
-        ColorFilter,
-        ColorSpace,
-        Font,
-        FontMgr,
-        FontStyle,
-        FontStyleSet,
-        Image,
-        ImageFilter,
-        Matrix,
-        Paint,
-        Path,
-        PathEffect,
-        RRect,
-        StrokeRec,
-        Surface,
-        TextBlob,
-        Typeface,
-    );
+    stats::install(lua);
+    register_globals(lua)?;
+    enums::register_enums(lua)?;
+    help::register(lua, API_HELP)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns unpremultiplied RGBA bytes of the pixel at `x, y`.
+    fn read_pixel(surface: &mut Surface, x: i32, y: i32) -> [u8; 4] {
+        let info = ImageInfo::new((1, 1), ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let mut result = [0; 4];
+        assert!(
+            surface.read_pixels(&info, &mut result, 4, (x, y)),
+            "pixel {}, {} is outside of the surface",
+            x,
+            y
+        );
+        result
+    }
+
+    /// Lua helpers of test scripts, on top of the ones registered by [`lua`].
+    const PRELUDE: &str = r#"
+        -- fails unless each RGBA byte of a pixel is within `tolerance` of
+        -- `expected`
+        function expectPixel(surface, x, y, expected, tolerance)
+            local actual = {pixel(surface, x, y)}
+            for i = 1, 4 do
+                if math.abs(actual[i] - expected[i]) > (tolerance or 0) then
+                    error(("pixel %d, %d is {%s}, expected {%s}"):format(
+                        x, y, table.concat(actual, ", "), table.concat(expected, ", ")
+                    ), 2)
+                end
+            end
+        end
+    "#;
+
+    /// Returns a context with bindings registered, along with test helpers:
+    /// `raster(width, height)` creating a transparent N32 surface,
+    /// `pixel(surface, x, y)` returning unpremultiplied RGBA bytes of a
+    /// pixel, and the ones in [`PRELUDE`].
+    fn lua() -> LuaContext {
+        let lua = LuaContext::new();
+        setup(&lua).expect("bindings should register");
+        {
+            let globals = lua.globals();
+            let raster = lua
+                .create_function(|lua, (width, height): (i32, i32)| {
+                    let _stats = stats::enter(lua);
+                    surfaces::raster_n32_premul((width, height))
+                        .map(LuaSurface::from)
+                        .ok_or_else(|| LuaError::RuntimeError("invalid surface size".to_string()))
+                })
+                .unwrap();
+            globals.set("raster", raster).unwrap();
+            let pixel = lua
+                .create_function(|_, (surface, x, y): (LuaAnyUserData, i32, i32)| {
+                    let mut surface = surface.borrow_mut::<LuaSurface>()?;
+                    let [r, g, b, a] = read_pixel(&mut surface.0, x, y);
+                    Ok((r, g, b, a))
+                })
+                .unwrap();
+            globals.set("pixel", pixel).unwrap();
+        }
+        lua.load(PRELUDE).exec().unwrap();
+        lua
+    }
+
+    /// Runs `script` in a new context, failing with the Lua error if it
+    /// raises one.
+    fn run(script: &str) {
+        let lua = lua();
+        if let Err(err) = lua.load(script).exec() {
+            panic!("{}", err);
+        }
+    }
+
+    /// DejaVu fonts bundled in `tests/data`, so text tests don't depend on
+    /// fonts installed on the machine running them.
+    const DEJAVU_SANS: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/DejaVuSans.ttf"
+    ));
+    const DEJAVU_SANS_MONO: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/data/DejaVuSansMono.ttf"
+    ));
+
+    /// Returns bundled `"DejaVu Sans"` or `"DejaVu Sans Mono"` typeface.
+    fn test_typeface(family: &str) -> Typeface {
+        let data = match family {
+            "DejaVu Sans" => DEJAVU_SANS,
+            "DejaVu Sans Mono" => DEJAVU_SANS_MONO,
+            other => panic!("{} font isn't bundled", other),
+        };
+        FontMgr::default()
+            .new_from_data(data, None)
+            .expect("bundled font should load")
+    }
+
+    /// Returns a bundled DejaVu Sans font of `size`.
+    fn test_font(size: f32) -> LuaFont {
+        LuaFont::new(Font::from_typeface(test_typeface("DejaVu Sans"), size))
+    }
+
+    #[test]
+    fn draw_image_and_draw_image_rect_match_without_paint() {
+        run(r#"
+            local source = raster(8, 8)
+            local canvas = source:getCanvas()
+            canvas:clear({0, 0, 1, 1})
+            canvas:drawCircle({4, 4}, 3, {r = 1, a = 0.5, antiAlias = true})
+            local image = source:makeImageSnapshot()
+
+            local direct, rect, explicit = raster(16, 16), raster(16, 16), raster(16, 16)
+            direct:getCanvas():drawImage(image, {3, 2})
+            rect:getCanvas():drawImageRect(image, nil, {x = 3, y = 2, width = 8, height = 8})
+            explicit:getCanvas():drawImageRect(image, nil, {x = 3, y = 2, width = 8, height = 8}, nil)
+
+            for y = 0, 15 do
+                for x = 0, 15 do
+                    local expected = {pixel(direct, x, y)}
+                    for _, other in ipairs({rect, explicit}) do
+                        local actual = {pixel(other, x, y)}
+                        for i = 1, 4 do
+                            assert(actual[i] == expected[i], ("pixel %d, %d differs"):format(x, y))
+                        end
+                    end
+                end
+            end
+        "#);
+    }
+
+    #[test]
+    fn fade_and_tint_blend_towards_color() {
+        run(r#"
+            local surface = raster(2, 1)
+            local canvas = surface:getCanvas()
+            canvas:clear({0.4, 0.6, 0.8, 1})
+            canvas:fade(0.5)
+            expectPixel(surface, 0, 0, {51, 77, 102, 255}, 1)
+
+            canvas:clear({1, 1, 1, 1})
+            canvas:tint({1, 0, 0, 1}, 0.25)
+            expectPixel(surface, 1, 0, {255, 191, 191, 255}, 1)
+
+            -- color alpha scales the amount
+            canvas:clear({1, 1, 1, 1})
+            canvas:tint({0, 0, 0, 0.5}, 1)
+            expectPixel(surface, 0, 0, {128, 128, 128, 255}, 1)
+
+            canvas:clear({1, 1, 1, 1})
+            canvas:tint({0, 0, 0, 1}, 0)
+            expectPixel(surface, 0, 0, {255, 255, 255, 255})
+        "#);
+    }
+
+    #[test]
+    fn fill_path_contains_stroke_outline() {
+        run(r#"
+            local path = Path()
+            path:moveTo({0, 50})
+            path:lineTo({100, 50})
+
+            local paint = Paint.make({0, 0, 0, 1})
+            paint:setStyle({stroke = true})
+            paint:setStrokeWidth(10)
+            local outline = paint:getFillPath(path)
+            assert(outline:contains({50, 46}), "point 4px above the line should be inside")
+            assert(not outline:contains({50, 44}), "point 6px above the line should be outside")
+            assert(outline:contains({50, 54}), "point 4px below the line should be inside")
+        "#);
+    }
+
+    #[test]
+    fn image_shader_tiles_src_rect() {
+        run(r#"
+            -- red top-left quarter, blue elsewhere
+            local source = raster(4, 4)
+            local canvas = source:getCanvas()
+            canvas:clear({0, 0, 1, 1})
+            canvas:drawRect({x = 0, y = 0, width = 2, height = 2}, {r = 1, a = 1})
+            local image = source:makeImageSnapshot()
+
+            local paint = Paint.make({0, 0, 0, 1})
+            paint:setShader(image:newShader("repeat", "repeat", nil, nil, {x = 0, y = 0, width = 2, height = 2}))
+            local target = raster(9, 9)
+            target:getCanvas():drawPaint(paint)
+            for y = 0, 8 do
+                for x = 0, 8 do
+                    expectPixel(target, x, y, {255, 0, 0, 255})
+                end
+            end
+
+            local ok, err = pcall(image.newShader, image, "repeat", "repeat", nil, nil, {x = 8, y = 8, width = 2, height = 2})
+            assert(not ok and tostring(err):find("Image:newShader failed", 1, true), tostring(err))
+        "#);
+    }
+
+    #[test]
+    fn break_text_stops_at_character_boundaries() {
+        let font = LuaFont::new(Font::default());
+        let text = "aé€😀b".to_string();
+        assert_eq!(font.break_text(text.clone(), -1.0).unwrap(), (0, 0));
+
+        let chars = text.chars().count();
+        for (count, (end, _)) in text
+            .char_indices()
+            .skip(1)
+            .chain(std::iter::once((text.len(), ' ')))
+            .enumerate()
+        {
+            let (width, _) = font.0.measure_str(&text[..end], None);
+            let (bytes, fit) = font.break_text(text.clone(), width).unwrap();
+            assert!(
+                text.is_char_boundary(bytes),
+                "{} isn't a character boundary",
+                bytes
+            );
+            assert_eq!(text[..bytes].chars().count(), fit);
+            // glyphs may have zero advance without a system font, so more
+            // characters than measured can fit but never fewer
+            assert!(
+                fit > count && fit <= chars,
+                "{} characters fit into {}",
+                fit,
+                width
+            );
+        }
+    }
+
+    #[test]
+    fn stats_live_counts_drop_after_collection() {
+        run(r#"
+            local before = Skia.stats().Path.live
+            local paths = {}
+            for i = 1, 1000 do
+                paths[i] = Path()
+            end
+            local held = Skia.stats().Path.live
+            assert(held == before + 1000, ("%d paths live after creating 1000, %d before"):format(held, before))
+
+            local surface = raster(64, 64)
+            local stats = Skia.stats()
+            assert(stats.Surface.live == 1, "surface isn't tracked")
+            assert(stats.Surface.bytes == 64 * 64 * 4, "surface bytes aren't tracked")
+            assert(stats.totalBytes == stats.Surface.bytes, "total doesn't match surface bytes")
+
+            paths, surface = nil, nil
+            collectgarbage()
+            collectgarbage()
+            local after = Skia.stats()
+            assert(after.Path.live == before, ("%d paths live after collection, %d before"):format(after.Path.live, before))
+            assert(after.Surface.live == 0 and after.totalBytes == 0, "surface wasn't released")
+        "#);
+    }
+
+    #[test]
+    fn stats_count_snapshot_pixels_once() {
+        run(r#"
+            local surface = raster(32, 32)
+            local image = surface:makeImageSnapshot()
+            local stats = Skia.stats()
+            assert(stats.Image.live == 1, "snapshot isn't tracked")
+            assert(stats.Surface.bytes == 32 * 32 * 4, "surface bytes aren't tracked")
+            assert(stats.Image.bytes == 0, "snapshot pixels are counted twice")
+            assert(stats.totalBytes == 32 * 32 * 4, ("total is %d"):format(stats.totalBytes))
+        "#);
+    }
+
+    #[test]
+    fn stats_are_kept_per_context() {
+        let first = lua();
+        first
+            .load("held = {} for i = 1, 10 do held[i] = Path() end")
+            .exec()
+            .unwrap();
+        let second = lua();
+        let live: usize = second.load("return Skia.stats().Path.live").eval().unwrap();
+        assert_eq!(live, 0, "paths of another context are counted");
+    }
+
+    #[test]
+    fn write_pixels_accepts_read_pixels_record() {
+        run(r#"
+            local surface = raster(8, 8)
+            local canvas = surface:getCanvas()
+            canvas:clear({0, 0, 0, 1})
+            canvas:drawRect({x = 0, y = 0, width = 1, height = 2}, {r = 1, a = 1})
+            canvas:drawRect({x = 1, y = 0, width = 1, height = 2}, {g = 1, a = 1})
+
+            local record = surface:readPixels({x = 0, y = 0, width = 2, height = 2})
+            assert(record ~= nil, "reading pixels failed")
+            assert(surface:writePixels({5, 4}, record), "writing pixels failed")
+
+            local copy = surface:readPixels({x = 5, y = 4, width = 2, height = 2})
+            assert(#copy == #record, ("%d bytes written, %d read"):format(#record, #copy))
+            for i = 1, #record do
+                assert(copy[i] == record[i], ("byte %d differs"):format(i))
+            end
+            expectPixel(surface, 5, 5, {255, 0, 0, 255})
+            expectPixel(surface, 6, 4, {0, 255, 0, 255})
+        "#);
+    }
+
+    #[test]
+    fn draw_patch_defaults_to_modulate_with_colors_and_shader() {
+        run(r#"
+            assert(BlendMode.forPatch() == "modulate", "forPatch should recommend modulate")
+
+            local source = raster(8, 8)
+            source:getCanvas():clear({0.5, 0.75, 1, 1})
+            local paint = Paint.make({0, 0, 0, 1})
+            paint:setShader(source:makeImageSnapshot():newShader("repeat"))
+
+            local cubics = {
+                {0, 0}, {5, 0}, {11, 0}, {16, 0}, {16, 5}, {16, 11},
+                {16, 16}, {11, 16}, {5, 16}, {0, 16}, {0, 11}, {0, 5},
+            }
+            local colors = {{1, 0, 0, 1}, {0, 1, 0, 1}, {0, 0, 1, 1}, {1, 1, 1, 1}}
+            local texCoords = {{0, 0}, {8, 0}, {8, 8}, {0, 8}}
+            local default, modulate = raster(16, 16), raster(16, 16)
+            default:getCanvas():drawPatch(cubics, colors, texCoords, nil, paint)
+            modulate:getCanvas():drawPatch(cubics, colors, texCoords, "modulate", paint)
+
+            for y = 0, 15 do
+                for x = 0, 15 do
+                    expectPixel(default, x, y, {pixel(modulate, x, y)})
+                end
+            end
+        "#);
+    }
+
+    #[test]
+    fn text_blob_builder_accumulates_run_advances() {
+        let lua = lua();
+        lua.globals()
+            .set("font", LuaFont::new(Font::default()))
+            .unwrap();
+        let script = r#"
+            local bigger = font:makeWithSize(24)
+            local builder = TextBlobBuilder()
+            builder:appendRun(font, "Gear")
+            builder:appendRun(bigger, " Settings")
+
+            local first, firstBounds = font:measureText("Gear")
+            local second, secondBounds = bigger:measureText(" Settings")
+            local cursor = builder:cursor()
+            assert(math.abs(cursor.x - (first + second)) < 0.01,
+                ("cursor at %f, expected %f"):format(cursor.x, first + second))
+
+            builder:appendRun(font, "", {4, 0})
+            assert(math.abs(builder:cursor().x - (first + second + 4)) < 0.01, "offset isn't applied")
+
+            local blob = builder:make()
+            if blob ~= nil and secondBounds.right > 0 then
+                assert(blob:bounds().right >= first + secondBounds.right - 0.01,
+                    "blob bounds don't contain the second run")
+            end
+            assert(builder:cursor().x == 0, "make should reset the cursor")
+        "#;
+        if let Err(err) = lua.load(script).exec() {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn image_filter_describes_construction() {
+        run(r#"
+            local graph = ImageFilter.compose(ImageFilter.blur(2, 3), ImageFilter.blur(1))
+
+            local expected = table.concat({
+                "compose()",
+                "  outer:",
+                "    blur(sigmaX=2, sigmaY=3)",
+                "  inner:",
+                "    blur(sigmaX=1, sigmaY=1)",
+                "",
+            }, "\n")
+            assert(graph:describe() == expected, graph:describe())
+
+            local description = graph:toTable()
+            assert(description.type == "compose", description.type)
+            assert(description.outer.type == "blur" and description.outer.sigmaY == 3)
+            assert(description.inner.sigmaX == 1)
+        "#);
+    }
+
+    #[test]
+    fn backdrop_blur_blurs_only_inside_shape() {
+        run(r#"
+            local surface = raster(20, 10)
+            local canvas = surface:getCanvas()
+            canvas:clear({1, 1, 1, 1})
+            canvas:drawRect({x = 0, y = 0, width = 10, height = 10}, {r = 0, a = 1})
+
+            canvas:drawBackdropBlur({x = 5, y = 0, width = 10, height = 10}, 2)
+
+            local r = pixel(surface, 9, 5)
+            assert(r > 20 and r < 235, ("edge inside of the shape isn't blurred: %d"):format(r))
+            expectPixel(surface, 2, 5, {0, 0, 0, 255})
+            expectPixel(surface, 17, 5, {255, 255, 255, 255})
+
+            local ok = pcall(canvas.drawBackdropBlur, canvas, {x = 0, y = 0, width = 4, height = 4}, 0 / 0)
+            assert(not ok, "NaN sigma should raise an error")
+        "#);
+    }
+
+    #[test]
+    fn rrect_inset_helpers_leave_original_unchanged() {
+        run(r#"
+            local base = RRect()
+            base:setRectXY({x = 0, y = 0, width = 20, height = 10}, 2, 2)
+
+            local inner = base:withInset({2, 2})
+            assert(inner:width() == 16 and inner:height() == 6, "withInset result has wrong size")
+            local outer = base:withOutset({1, 1})
+            assert(outer:width() == 22 and outer:height() == 12, "withOutset result has wrong size")
+            local copy = base:clone()
+            copy:inset({1, 1})
+            assert(base:width() == 20 and base:height() == 10, "original RRect was modified")
+
+            local bounds = base:insetRect({2, 3})
+            assert(bounds.right == 18 and bounds.bottom == 7, "insetRect returned wrong bounds")
+            local rect = Rect.inset({x = 0, y = 0, width = 10, height = 10}, 2)
+            assert(rect.right == 8 and rect.bottom == 8, "Rect.inset should default dy to dx")
+        "#);
+    }
+
+    #[test]
+    fn rect_utilities_accept_both_conventions() {
+        run(r#"
+            local function expectRect(rect, l, t, r, b)
+                assert(rect.left == l and rect.top == t and rect.right == r and rect.bottom == b,
+                    ("got {%s, %s, %s, %s}"):format(rect.left, rect.top, rect.right, rect.bottom))
+            end
+
+            expectRect(Rect.make(1, 2, 3, 4), 1, 2, 4, 6)
+            expectRect(Rect.makeLTRB(1, 2, 3, 4), 1, 2, 3, 4)
+
+            local xywh = {x = 0, y = 0, width = 10, height = 10}
+            local ltrb = {left = 5, top = 5, right = 20, bottom = 15}
+            expectRect(Rect.intersect(xywh, ltrb), 5, 5, 10, 10)
+            expectRect(Rect.intersect(ltrb, xywh), 5, 5, 10, 10)
+            assert(Rect.intersect(xywh, {left = 11, top = 0, right = 12, bottom = 1}) == nil,
+                "disjoint rects shouldn't intersect")
+            expectRect(Rect.union(xywh, ltrb), 0, 0, 20, 15)
+
+            assert(Rect.contains(xywh, {5, 5}), "point inside")
+            assert(not Rect.contains(ltrb, {x = 1, y = 1}), "point outside")
+            assert(Rect.contains(ltrb, {x = 6, y = 6, width = 2, height = 2}), "rect inside")
+            assert(not Rect.contains(xywh, ltrb), "overlapping rect")
+
+            expectRect(Rect.inflate(ltrb, 1, 2), 4, 3, 21, 17)
+            local center = Rect.center(xywh)
+            assert(center.x == 5 and center.y == 5, "wrong center")
+        "#);
+    }
+
+    #[test]
+    fn point_math_accepts_both_forms() {
+        run(r#"
+            local function expectPoint(p, x, y)
+                assert(math.abs(p.x - x) < 1e-5 and math.abs(p.y - y) < 1e-5,
+                    ("got {%f, %f}, expected {%f, %f}"):format(p.x, p.y, x, y))
+            end
+
+            expectPoint(Point.add({1, 2}, {x = 3, y = 4}), 4, 6)
+            expectPoint(Point.sub({x = 3, y = 4}, {1, 2}), 2, 2)
+            expectPoint(Point.scale({1, -2}, 3), 3, -6)
+            assert(Point.dot({1, 2}, {x = 3, y = 4}) == 11)
+            assert(Point.length({x = 3, y = 4}) == 5)
+            assert(Point.distance({1, 1}, {x = 4, y = 5}) == 5)
+            expectPoint(Point.normalize({0, 5}), 0, 1)
+            expectPoint(Point.normalize({0, 0}), 0, 0)
+            expectPoint(Point.lerp({0, 0}, {x = 10, y = 20}, 0.25), 2.5, 5)
+            expectPoint(Point.rotate({1, 0}, 90), 0, 1)
+            expectPoint(Point.rotate({x = 2, y = 1}, 180, {1, 1}), 0, 1)
+
+            -- results are accepted wherever points are
+            local path = Path()
+            path:moveTo(Point.add({0, 0}, {1, 1}))
+            path:lineTo(Point.scale({5, 5}, 2))
+            assert(path:contains(Point.lerp({1, 1}, {10, 10}, 0.5)) == false)
+            local surface = raster(8, 8)
+            surface:getCanvas():drawCircle(Point.rotate({4, 4}, 0), 2, {r = 1, a = 1})
+            expectPixel(surface, 4, 4, {255, 0, 0, 255})
+        "#);
+    }
+
+    #[test]
+    fn snap_to_pixel_lands_on_device_pixels() {
+        for scale in [1.0, 1.5, 2.0] {
+            let matrices = [
+                Matrix::scale((scale, scale)),
+                *Matrix::scale((scale, scale)).post_translate((0.25, 0.75)),
+                *Matrix::rotate_deg(90.).post_scale((scale, scale), None),
+            ];
+            for matrix in matrices {
+                let point = Point::new(10.3, 4.6);
+                let snapped = match snap_to_pixel(&matrix, PointOrRect::Point(point)) {
+                    PointOrRect::Point(it) => it,
+                    PointOrRect::Rect(_) => panic!("point snapped to a rect"),
+                };
+                let device = matrix.map_point(snapped);
+                assert!(
+                    (device.x - device.x.round()).abs() < 1e-3
+                        && (device.y - device.y.round()).abs() < 1e-3,
+                    "{:?} isn't on a device pixel at scale {}",
+                    device,
+                    scale
+                );
+                // rounding moves by at most half a device pixel on each axis
+                assert!(
+                    (snapped - point).length() <= 0.75 / scale,
+                    "{:?} moved too far",
+                    snapped
+                );
+
+                let rect = Rect::new(1.2, 2.7, 20.1, 9.4);
+                let snapped = match snap_to_pixel(&matrix, PointOrRect::Rect(rect)) {
+                    PointOrRect::Rect(it) => it,
+                    PointOrRect::Point(_) => panic!("rect snapped to a point"),
+                };
+                let (device, _) = matrix.map_rect(snapped);
+                for edge in [device.left, device.top, device.right, device.bottom] {
+                    assert!(
+                        (edge - edge.round()).abs() < 1e-3,
+                        "{:?} isn't aligned at scale {}",
+                        device,
+                        scale
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frames_are_tracked_per_canvas() {
+        run(r#"
+            local a, b = raster(4, 4):getCanvas(), raster(4, 4):getCanvas()
+            a:beginFrame()
+            b:beginFrame()
+            a:save()
+            a:endFrame()
+            assert(a:getSaveCount() == 1, "endFrame should restore its own canvas")
+            assert(b:getSaveCount() == 2, "endFrame restored another canvas")
+            b:endFrame()
+            assert(b:getSaveCount() == 1)
+
+            -- frames restored past by hand are dropped
+            a:beginFrame()
+            a:restore()
+            assert(not pcall(a.endFrame, a), "endFrame of a restored frame should fail")
+            assert(not pcall(b.endFrame, b), "unmatched endFrame should fail")
+        "#);
+    }
+
+    #[test]
+    fn crisp_coord_aligns_stroke_edges() {
+        // odd widths are centered on pixel centers, even ones on boundaries
+        assert_eq!(crisp_coord(5.0, 1.0), 5.5);
+        assert_eq!(crisp_coord(5.2, 1.0), 5.5);
+        assert_eq!(crisp_coord(5.0, 2.0), 5.0);
+        assert_eq!(crisp_coord(5.4, 3.0), 5.5);
+    }
+
+    #[test]
+    fn hairline_and_crisp_rect_cover_single_pixel_row() {
+        run(r#"
+            local surface = raster(10, 10)
+            local canvas = surface:getCanvas()
+            local paint = {r = 0, a = 1, antiAlias = true, strokeWidth = 1}
+
+            canvas:drawHairline({1, 5}, {9, 5}, paint)
+            for x = 2, 8 do
+                local _, _, _, alpha = pixel(surface, x, 5)
+                assert(alpha == 255, ("hairline pixel %d isn't opaque: %d"):format(x, alpha))
+                for _, y in ipairs({4, 6}) do
+                    local _, _, _, alpha = pixel(surface, x, y)
+                    assert(alpha == 0, ("hairline bleeds into row %d: %d"):format(y, alpha))
+                end
+            end
+
+            canvas:clear()
+            canvas:strokeRectCrisp({x = 2, y = 2, width = 6, height = 6}, paint)
+            local _, _, _, alpha = pixel(surface, 2, 5)
+            assert(alpha == 255, ("left edge isn't opaque: %d"):format(alpha))
+            for _, x in ipairs({1, 3}) do
+                local _, _, _, alpha = pixel(surface, x, 5)
+                assert(alpha == 0, ("left edge bleeds into column %d: %d"):format(x, alpha))
+            end
+        "#);
+    }
+
+    #[test]
+    fn smooth_gradient_rows_change_gradually() {
+        run(r#"
+            local width, height = 32, 256
+            local surface = raster(width, height)
+            local stops = {{0.1, 0.12, 0.15, 1}, {0.16, 0.18, 0.22, 1}}
+            local paint = GradientShader.smooth({0, 0}, {0, height}, stops, {0, 1})
+            assert(paint:isDither(), "smooth gradient paint should dither")
+            surface:getCanvas():drawPaint(paint)
+
+            local function rowAverage(y)
+                local sum = 0
+                for x = 0, width - 1 do
+                    sum = sum + pixel(surface, x, y)
+                end
+                return sum / width
+            end
+            local previous = rowAverage(0)
+            for y = 1, height - 1 do
+                local current = rowAverage(y)
+                assert(math.abs(current - previous) <= 1,
+                    ("rows %d and %d differ by %f"):format(y - 1, y, current - previous))
+                previous = current
+            end
+
+            local noisy = GradientShader.smooth({0, 0}, {0, height}, stops, {0, 1}, {noise = true, seed = 3})
+            surface:getCanvas():drawPaint(noisy)
+        "#);
+    }
+
+    #[test]
+    fn border_corners_split_colors_along_miter() {
+        run(r#"
+            local surface = raster(20, 20)
+            surface:getCanvas():drawBorder({x = 0, y = 0, width = 20, height = 20}, {
+                top = {4, {1, 0, 0, 1}},
+                left = {width = 4, color = {0, 0, 1, 1}},
+                right = {2, {0, 1, 0, 1}},
+            })
+
+            -- upper-left corner: top above the diagonal, left below it
+            expectPixel(surface, 3, 0, {255, 0, 0, 255})
+            expectPixel(surface, 0, 3, {0, 0, 255, 255})
+            -- upper-right corner between top and right sides
+            expectPixel(surface, 17, 1, {255, 0, 0, 255})
+            expectPixel(surface, 19, 3, {0, 255, 0, 255})
+            -- missing bottom side and the inside are left untouched
+            expectPixel(surface, 10, 19, {0, 0, 0, 0})
+            expectPixel(surface, 10, 10, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn path_glyphs_follow_semicircle() {
+        let mut path = Path::new();
+        path.add_arc(Rect::new(0., 0., 100., 100.), 180., 180.);
+        let center = Point::new(50., 50.);
+        let widths = [10.; 20];
+
+        for v_offset in [0., 3.] {
+            let placed = path_glyph_xforms(&path, &widths, 0., v_offset);
+            // semicircle is ~157 long, fitting centers of 16 glyphs
+            assert_eq!(placed.len(), 16);
+            assert_eq!(
+                placed.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+                (0..16).collect::<Vec<_>>()
+            );
+
+            for (_, xform) in [placed[0], placed[15]] {
+                let tangent = Vector::new(xform.scos, xform.ssin);
+                let glyph_center = Point::new(xform.tx, xform.ty) + tangent * 5.;
+                let radius = (glyph_center - center).length();
+                // positive offsets move glyphs below the path, inside the arc
+                assert!(
+                    (radius - (50. - v_offset)).abs() < 0.1,
+                    "glyph center at radius {}",
+                    radius
+                );
+                assert!((tangent.length() - 1.).abs() < 1e-3);
+            }
+        }
+
+        // glyphs before the start are skipped
+        let placed = path_glyph_xforms(&path, &widths, -15., 0.);
+        assert_eq!(placed[0].0, 1);
+    }
+
+    #[test]
+    fn checkerboard_draws_cells_from_cached_shader() {
+        run(r#"
+            local surface = raster(20, 20)
+            local canvas = surface:getCanvas()
+            canvas:drawCheckerboard({x = 2, y = 2, width = 16, height = 16}, 4, {1, 0, 0, 1}, {0, 0, 1, 1})
+            canvas:drawCheckerboard({x = 2, y = 2, width = 16, height = 16}, 4, {1, 0, 0, 1}, {0, 0, 1, 1})
+
+            -- pattern starts at the rect corner
+            expectPixel(surface, 2, 2, {255, 0, 0, 255})
+            expectPixel(surface, 6, 2, {0, 0, 255, 255})
+            expectPixel(surface, 6, 6, {255, 0, 0, 255})
+            expectPixel(surface, 1, 1, {0, 0, 0, 0})
+        "#);
+        // repeated draws with the same parameters reuse the shader
+        assert_eq!(CHECKERBOARD_SHADERS.with(ShaderCache::len), 1);
+    }
+
+    #[test]
+    fn checkerboard_clamps_cell_size_and_bounds_cache() {
+        run(r#"
+            local surface = raster(20, 20)
+            local canvas = surface:getCanvas()
+            canvas:drawCheckerboard({x = 0, y = 0, width = 20, height = 20}, 1e12, {1, 0, 0, 1}, {0, 0, 1, 1})
+            expectPixel(surface, 19, 19, {255, 0, 0, 255})
+            canvas:drawCheckerboard({x = 0, y = 0, width = 20, height = 20}, 0 / 0, {1, 0, 0, 1}, {0, 0, 1, 1})
+            expectPixel(surface, 0, 0, {255, 0, 0, 255})
+            expectPixel(surface, 1, 0, {0, 0, 255, 255})
+
+            for i = 1, 100 do
+                canvas:drawCheckerboard({x = 0, y = 0, width = 4, height = 4}, i, {1, 0, 0, 1}, {0, 0, 1, 1})
+            end
+        "#);
+        assert!(CHECKERBOARD_SHADERS.with(ShaderCache::len) <= cache::DEFAULT_CAPACITY);
+    }
+
+    #[test]
+    fn color_stops_sample_matches_rendered_gradient() {
+        run(r#"
+            local stops = ColorStops.new({{1, 0, 0, 1}, {0, 0, 1, 1}})
+            assert(stops:add(0.3, {0, 1, 0, 1}) == 2)
+            assert(stops:add(0.8, {1, 1, 1, 1}) == 3)
+            assert(stops:remove(3) == nil and stops:count() == 3)
+            assert(stops:set(2, 0.25) == 2)
+
+            local width = 100
+            local surface = raster(width, 1)
+            local shader = GradientShader.makeLinear({0, 0}, {width, 0}, stops)
+            surface:getCanvas():drawPaint({shader = shader})
+
+            for _, x in ipairs({0, 10, 24, 25, 40, 73, 99}) do
+                local color = stops:sample((x + 0.5) / width)
+                expectPixel(surface, x, 0, {
+                    color.r * 255, color.g * 255, color.b * 255, color.a * 255,
+                }, 1.5)
+            end
+
+            local colors, positions = stops:toShaderArgs()
+            assert(#colors == 3 and positions[2] == 0.25)
+        "#);
+    }
+
+    #[test]
+    fn cached_glow_matches_naive_blur() {
+        let mut font = Font::default();
+        font.set_size(24.);
+        let blob = TextBlob::new("Glow", &font).expect("default font should shape text");
+        let glow = LuaTextGlow {
+            blob: blob.clone(),
+            font: font.clone(),
+            color: Color4f::new(1., 0.5, 0., 1.),
+            sigma: 3.,
+            cached: None,
+        };
+        let origin = Point::new(20., 40.);
+
+        let mut naive = surfaces::raster_n32_premul((120, 70)).unwrap();
+        let mut paint = Paint::new(glow.color, None);
+        paint.set_anti_alias(true);
+        paint.set_mask_filter(MaskFilter::blur(BlurStyle::Normal, glow.sigma, false));
+        naive.canvas().draw_text_blob(&blob, origin, &paint);
+
+        let mut cached = surfaces::raster_n32_premul((120, 70)).unwrap();
+        let (image, offset) = glow.render().unwrap();
+        // padding must fit the whole blur
+        assert!(image.width() as f32 >= blob.bounds().width() + 6. * glow.sigma);
+        cached.canvas().draw_image(&image, origin + offset, None);
+
+        for y in 0..70 {
+            for x in 0..120 {
+                let (a, b) = (read_pixel(&mut naive, x, y), read_pixel(&mut cached, x, y));
+                for channel in 0..4 {
+                    assert!(
+                        a[channel].abs_diff(b[channel]) <= 2,
+                        "pixel {}, {} differs: {:?} vs {:?}",
+                        x,
+                        y,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn glow_draw_renders_again_when_font_changes() {
+        run(r#"
+            local font = Font(Typeface.makeDefault(), 12)
+            local glow = TextBlob.makeFromString("Glow", font):withGlow(font, {1, 1, 1, 1}, 2)
+
+            local small = raster(160, 80)
+            glow:draw(small:getCanvas(), {10, 40}, 1, {r = 0, a = 0})
+
+            local large = raster(160, 80)
+            glow:setText("Glow", Font(Typeface.makeDefault(), 36))
+            glow:draw(large:getCanvas(), {10, 40}, 1, {r = 0, a = 0})
+
+            -- glyphs of the larger font reach further right than the cached
+            -- glow of the smaller one
+            local function covered(surface)
+                local right = 0
+                for x = 0, 159 do
+                    local _, _, _, a = pixel(surface, x, 36)
+                    if a > 0 then right = x end
+                end
+                return right
+            end
+            local before, after = covered(small), covered(large)
+            assert(after > before + 20, ("glow ends at %d with small font, %d with large"):format(before, after))
+        "#);
+    }
+
+    #[test]
+    fn debug_canvas_records_commands() {
+        let lua = lua();
+        lua.globals()
+            .set("font", LuaFont::new(Font::default()))
+            .unwrap();
+        let script = r#"
+            local canvas = Surfaces.debug({100, 100})
+            canvas:drawRect({x = 0, y = 0, width = 10, height = 10}, {1, 0, 0, 1})
+            canvas:drawRect({x = 10, y = 0, width = 10, height = 10}, {g = 1, style = "stroke", strokeWidth = 2})
+            canvas:drawRect({x = 20, y = 0, width = 10, height = 10}, {0, 0, 1, 1})
+            canvas:drawTextBlob(TextBlob.makeFromString("hi", font), {0, 50}, {0, 0, 0, 1})
+            raster(6, 4):draw(canvas, {30, 40})
+
+            local commands = canvas:getCommands()
+            assert(#commands == 5, ("recorded %d commands"):format(#commands))
+            for i = 1, 3 do
+                assert(commands[i].op == "drawRect", commands[i].op)
+            end
+            assert(commands[4].op == "drawTextBlob", commands[4].op)
+            assert(commands[5].op == "drawSurface", commands[5].op)
+            local dst = commands[5].dstRect
+            assert(dst.left == 30 and dst.top == 40 and dst.right == 36 and dst.bottom == 44)
+
+            assert(commands[2].rect.left == 10 and commands[2].rect.right == 20)
+            local summary = commands[2].paintSummary
+            assert(summary.style == "stroke" and summary.strokeWidth == 2)
+            assert(summary.color.g == 1 and summary.color.r == 0)
+            assert(not summary.hasShader)
+
+            canvas:clearCommands()
+            assert(#canvas:getCommands() == 0)
+
+            -- regular canvases don't record
+            assert(not pcall(function() raster(1, 1):getCanvas():getCommands() end))
+        "#;
+        if let Err(err) = lua.load(script).exec() {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn golden_gradient_matches_by_hash_and_diff() {
+        run(r#"
+            local function render()
+                local surface = raster(64, 16)
+                local shader = GradientShader.makeLinear({0, 0}, {64, 0}, {{1, 0, 0, 1}, {0, 0, 1, 1}})
+                surface:getCanvas():drawPaint({shader = shader})
+                return surface
+            end
+            local golden, actual = render(), render()
+
+            assert(golden:pixelHash() == actual:pixelHash())
+            local diff = actual:diff(golden:makeImageSnapshot())
+            assert(diff.differingPixels == 0 and diff.maxDelta == 0 and diff.bbox == nil)
+
+            actual:getCanvas():drawRect({x = 10, y = 4, width = 2, height = 2}, {0, 1, 0, 1})
+            assert(golden:pixelHash() ~= actual:pixelHash())
+            -- hashed area excluding the change is unaffected
+            local left = {x = 0, y = 0, width = 8, height = 16}
+            assert(golden:pixelHash(left) == actual:pixelHash(left))
+
+            diff = actual:diff(golden)
+            assert(diff.differingPixels == 4, diff.differingPixels)
+            assert(diff.maxDelta == 255)
+            assert(diff.bbox.left == 10 and diff.bbox.top == 4)
+            assert(diff.bbox.right == 12 and diff.bbox.bottom == 6)
+        "#);
+    }
+
+    #[test]
+    fn golden_stroked_path_tolerates_small_deltas() {
+        run(r#"
+            local function render(red)
+                local surface = raster(32, 32)
+                -- opaque background keeps channel deltas proportional to
+                -- color changes
+                surface:getCanvas():clear({1, 1, 1, 1})
+                local path = Path()
+                path:moveTo({4, 4})
+                path:lineTo({28, 16})
+                path:lineTo({4, 28})
+                surface:getCanvas():drawPath(path, {
+                    r = red, style = "stroke", strokeWidth = 3, antiAlias = true,
+                })
+                return surface
+            end
+            local golden = render(1)
+
+            -- hash covers content only, not the size of the whole surface
+            local wide = raster(64, 32)
+            wide:getCanvas():drawImage(golden:makeImageSnapshot(), {0, 0})
+            local area = {x = 0, y = 0, width = 32, height = 32}
+            assert(wide:pixelHash(area) == golden:pixelHash())
+
+            local darker = render(0.99)
+            local diff = darker:diff(golden, 3)
+            assert(diff.differingPixels == 0, diff.differingPixels)
+            diff = darker:diff(golden)
+            assert(diff.differingPixels > 0 and diff.maxDelta <= 3)
+
+            assert(not pcall(golden.diff, golden, raster(16, 16)))
+        "#);
+    }
+
+    #[test]
+    fn tiled_flush_redraws_only_tiles_hit_by_draws() {
+        run(r#"
+            local tiled = Surfaces.tiled({48, 16}, 16)
+            local canvas = tiled:getCanvas()
+            canvas:drawRect({x = 0, y = 0, width = 32, height = 16}, {r = 1, a = 1})
+            assert(tiled:flush() == 2)
+            -- tiles nothing was drawn into aren't allocated
+            assert(tiled:allocatedTiles() == 2)
+
+            tiled:markDirty({x = 0, y = 0, width = 16, height = 16})
+            canvas:drawRect({x = 4, y = 4, width = 4, height = 4}, {b = 1, a = 1})
+            assert(tiled:flush() == 1)
+
+            local surface = raster(48, 16)
+            tiled:drawTo(surface:getCanvas())
+            expectPixel(surface, 0, 0, {0, 0, 0, 0})
+            expectPixel(surface, 5, 5, {0, 0, 255, 255})
+            expectPixel(surface, 20, 5, {255, 0, 0, 255})
+
+            -- draws into tiles that weren't marked dirty are added on top
+            canvas:drawRect({x = 20, y = 0, width = 4, height = 4}, {g = 1, a = 1})
+            assert(tiled:flush() == 1)
+            tiled:drawTo(surface:getCanvas())
+            expectPixel(surface, 21, 1, {0, 255, 0, 255})
+            expectPixel(surface, 28, 8, {255, 0, 0, 255})
+
+            -- dirty tiles nothing is drawn into are released
+            tiled:markDirty({x = 16, y = 0, width = 16, height = 16})
+            assert(tiled:flush() == 0)
+            assert(tiled:allocatedTiles() == 1)
+        "#);
+    }
+
+    #[test]
+    fn tiled_surface_refuses_drawing_into_own_canvas() {
+        run(r#"
+            local tiled = Surfaces.tiled({32, 32}, 16)
+            local canvas = tiled:getCanvas()
+            canvas:drawRect({x = 0, y = 0, width = 8, height = 8}, {r = 1, a = 1})
+            local ok, err = pcall(tiled.drawTo, tiled, canvas)
+            assert(not ok and tostring(err):find("own canvas"), err)
+
+            -- drawing another tiled surface marks tiles it covers
+            local other = Surfaces.tiled({32, 32}, 16)
+            other:drawTo(canvas, {20, 20})
+            assert(tiled:flush() == 2)
+    }
+
+    #[test]
+    fn damage_tracks_disjoint_draws_separately() {
+        run(r#"
+            local canvas = raster(100, 100):getCanvas()
+            assert(canvas:takeDamage() == nil, "damage is tracked before it's enabled")
+            canvas:beginDamageTracking()
+            canvas:drawRect({x = 10, y = 10, width = 20, height = 20}, {1, 0, 0, 1})
+            canvas:drawRect({x = 60, y = 50, width = 10, height = 30}, {0, 0, 1, 1})
+
+            local damage = canvas:takeDamage()
+            assert(#damage == 2, ("expected 2 damage rects, got %d"):format(#damage))
+            local union = {left = math.huge, top = math.huge, right = -math.huge, bottom = -math.huge}
+            for _, rect in ipairs(damage) do
+                union.left = math.min(union.left, rect.left)
+                union.top = math.min(union.top, rect.top)
+                union.right = math.max(union.right, rect.right)
+                union.bottom = math.max(union.bottom, rect.bottom)
+            end
+            assert(union.left == 10 and union.top == 10, ("%d, %d"):format(union.left, union.top))
+            assert(union.right == 70 and union.bottom == 80, ("%d, %d"):format(union.right, union.bottom))
+            assert(#canvas:takeDamage() == 0, "damage isn't reset after it's taken")
+
+            -- stroke width spreads damage past the geometry
+            canvas:drawRect({x = 10, y = 10, width = 20, height = 20}, {a = 1, style = "stroke", strokeWidth = 4})
+            local stroked = canvas:takeDamage()[1]
+            assert(stroked.left <= 8 and stroked.bottom >= 32)
+
+            canvas:endDamageTracking()
+            assert(canvas:takeDamage() == nil)
+        "#);
+    }
+
+    #[test]
+    fn damage_is_shared_by_canvases_of_a_surface() {
+        run(r#"
+            local surface = raster(20, 20)
+            surface:getCanvas():beginDamageTracking()
+            surface:getCanvas():drawRect({x = 2, y = 2, width = 4, height = 4}, {1, 0, 0, 1})
+            local damage = surface:getCanvas():takeDamage()
+            assert(#damage == 1 and damage[1].left == 2 and damage[1].right == 6)
+
+            -- other surfaces don't inherit tracking
+            local other = raster(20, 20)
+            other:getCanvas():drawRect({x = 2, y = 2, width = 4, height = 4}, {1, 0, 0, 1})
+            assert(other:getCanvas():takeDamage() == nil)
+            surface:getCanvas():endDamageTracking()
+    }
+
+    #[test]
+    fn copy_from_carries_pixels_between_surfaces() {
+        run(r#"
+            local source = raster(20, 20)
+            source:getCanvas():drawRect({x = 0, y = 0, width = 10, height = 10}, {1, 0, 0, 1})
+            source:getCanvas():drawRect({x = 10, y = 10, width = 10, height = 10}, {0, 0, 1, 1})
+
+            local target = raster(20, 20)
+            target:getCanvas():clear({0, 1, 0, 1})
+            target:copyFrom(source, {x = 5, y = 5, width = 10, height = 10}, {0, 0})
+            expectPixel(target, 0, 0, {255, 0, 0, 255})
+            expectPixel(target, 4, 4, {255, 0, 0, 255})
+            expectPixel(target, 9, 9, {0, 0, 255, 255})
+            -- transparent source pixels replace destination ones
+            expectPixel(target, 9, 0, {0, 0, 0, 0})
+            expectPixel(target, 10, 10, {0, 255, 0, 255})
+
+            -- whole surface by default
+            target:copyFrom(source, nil, {0, 0})
+            expectPixel(target, 15, 15, {0, 0, 255, 255})
+            expectPixel(target, 15, 5, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn clip_to_damage_leaves_other_pixels_untouched() {
+        run(r#"
+            local surface = raster(40, 40)
+            local canvas = surface:getCanvas()
+            canvas:clear({0, 1, 0, 1})
+
+            canvas:save()
+            canvas:clipToDamage({
+                {left = 0, top = 0, right = 10, bottom = 10},
+                {left = 30, top = 30, right = 40, bottom = 40},
+            })
+            canvas:drawPaint({1, 0, 0, 1})
+            canvas:restore()
+
+            expectPixel(surface, 5, 5, {255, 0, 0, 255})
+            expectPixel(surface, 35, 35, {255, 0, 0, 255})
+            for _, point in ipairs({{10, 5}, {20, 20}, {5, 30}, {29, 35}}) do
+                expectPixel(surface, point[1], point[2], {0, 255, 0, 255})
+            end
+        "#);
+    }
+
+    #[test]
+    fn frozen_paint_rejects_changes_but_draws() {
+        run(r#"
+            local paint = Paint.make({0, 0, 1, 1})
+            local frozen = paint:freeze()
+            assert(frozen:isFrozen() and not paint:isFrozen())
+
+            local ok, err = pcall(frozen.setColor, frozen, {1, 0, 0, 1})
+            assert(not ok and tostring(err):find("frozen"), tostring(err))
+            assert(not pcall(frozen.setStrokeWidth, frozen, 2))
+            -- getters still work
+            assert(frozen:getColor().b == 1)
+
+            -- original and clones stay mutable
+            paint:setStrokeWidth(3)
+            assert(frozen:getStrokeWidth() == 0)
+            local copy = frozen:clone()
+            assert(not copy:isFrozen())
+            copy:setColor({1, 0, 0, 1})
+            assert(not frozen:copy():isFrozen())
+
+            local surface = raster(4, 4)
+            surface:getCanvas():drawRect({x = 0, y = 0, width = 4, height = 4}, frozen)
+            expectPixel(surface, 1, 1, {0, 0, 255, 255})
+        "#);
+    }
+
+    #[test]
+    fn failing_constructors_raise_descriptive_errors() {
+        run(r#"
+            local function expectError(pattern, f, ...)
+                local ok, err = pcall(f, ...)
+                assert(not ok, ("expected an error matching '%s'"):format(pattern))
+                assert(tostring(err):find(pattern, 1, true), tostring(err))
+            end
+
+            expectError("PathEffect.makeDash failed: intervals must be", PathEffect.makeDash, {1, 2, 3})
+            expectError("Surface.null failed", Surface.null, {0, 0})
+            expectError("unable to read font file: /nonexistent/font.ttf", Typeface.makeFromFile, "/nonexistent/font.ttf")
+
+            local image = raster(4, 4):makeImageSnapshot()
+            expectError("Image:newShader failed: srcRect", image.newShader, image, nil, nil, nil, nil,
+                {x = 10, y = 10, width = 2, height = 2})
+            expectError("doesn't overlap 4x4 image", image.newShader, image, nil, nil, nil, nil,
+                {x = 10, y = 10, width = 2, height = 2})
+        "#);
+    }
+
+    #[test]
+    fn failing_effect_constructors_raise_descriptive_errors() {
+        run(r#"
+            local function expectError(pattern, f, ...)
+                local ok, err = pcall(f, ...)
+                assert(not ok, ("expected an error matching '%s'"):format(pattern))
+                assert(tostring(err):find(pattern, 1, true), tostring(err))
+            end
+            local nan = 0 / 0
+            local stops = {{1, 0, 0, 1}, {0, 0, 1, 1}}
+
+            expectError("GradientShader.makeLinear failed", GradientShader.makeLinear, {0, 0}, {nan, 0}, stops)
+            expectError("GradientShader.makeRadial failed: invalid gradient", GradientShader.makeRadial, {0, 0}, -1, stops)
+            expectError("GradientShader.makeTwoPointConical failed: invalid gradient with radii -1 and 5",
+                GradientShader.makeTwoPointConical, {0, 0}, -1, {10, 0}, 5, stops)
+            expectError("GradientShader.smooth failed: invalid gradient", GradientShader.smooth, {0, 0}, {nan, 0}, stops)
+
+            expectError("ImageFilter.offset failed: invalid arguments", ImageFilter.offset, {nan, 0})
+            expectError("ImageFilter.arithmetic failed: invalid arguments", ImageFilter.arithmetic, {nan, 0, 0, 0}, true)
+            expectError("x sigma must be a positive, finite scalar", ImageFilter.blur, -1)
+
+            expectError("MaskFilter.makeBlur failed: sigma must be a positive", MaskFilter.makeBlur, "normal", 0)
+            expectError("MaskFilter.makeBlur failed: sigma must be a positive", MaskFilter.makeBlur, "normal", nan)
+
+            expectError("ColorFilter.blend failed: invalid color or blend mode", ColorFilter.blend, {1, 0, 0, 1}, "dst")
+        "#);
+    }
+
+    #[test]
+    fn kerning_adjustments_distinguish_missing_kerning() {
+        let typeface = |family: &str| {
+            let typeface = test_typeface(family);
+            let glyphs = Font::from_typeface(typeface.clone(), 12.).str_to_glyphs_vec("AV");
+            (LuaTypeface::new(typeface), glyphs)
+        };
+
+        let (kerned, glyphs) = typeface("DejaVu Sans");
+        assert!(kerned.has_kerning().unwrap());
+        let adjustments = kerned
+            .get_kerning_pair_adjustments(glyphs)
+            .unwrap()
+            .expect("kerned font should return adjustments");
+        assert_eq!(adjustments.len(), 1);
+        assert!(adjustments[0] < 0, "AV adjustment is {}", adjustments[0]);
+
+        let (monospace, glyphs) = typeface("DejaVu Sans Mono");
+        assert!(!monospace.has_kerning().unwrap());
+        assert_eq!(
+            monospace.get_kerning_pair_adjustments(glyphs).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn unpremul_image_converts_to_premul_pixels() {
+        let info = ImageInfo::new((1, 1), ColorType::RGBA8888, AlphaType::Unpremul, None);
+        let unpremul = images::raster_from_data(&info, Data::new_copy(&[200, 100, 50, 128]), 4)
+            .map(LuaImage::new)
+            .unwrap();
+        // untagged images have no color space
+        assert!(unpremul.color_space().unwrap().is_none());
+
+        let (premul, reason) = unpremul
+            .make_color_type_and_alpha_type(None, Some(LuaAlphaType(AlphaType::Premul)))
+            .unwrap();
+        assert_eq!(reason, None);
+        let premul = premul.unwrap();
+        assert_eq!(premul.alpha_type().unwrap().0, AlphaType::Premul);
+
+        // stored values get multiplied by alpha
+        let mut stored = [0u8; 4];
+        let premul_info = info.with_alpha_type(AlphaType::Premul);
+        assert!(premul.0.read_pixels(
+            &premul_info,
+            &mut stored,
+            4,
+            (0, 0),
+            image::CachingHint::Allow
+        ));
+        for (actual, expected) in stored.iter().zip([100u8, 50, 25, 128]) {
+            assert!(actual.abs_diff(expected) <= 1, "stored {:?}", stored);
+        }
+
+        let (_, reason) = unpremul
+            .make_color_type_and_alpha_type(Some(LuaColorType(ColorType::Unknown)), None)
+            .unwrap();
+        assert_eq!(reason.as_deref(), Some("unknown color or alpha type"));
+    }
+
+    #[test]
+    fn chained_and_unchained_mutators_match() {
+        run(r#"
+            local chained = Path():moveTo({0, 0}):lineTo({10, 0}):quadTo({15, 5}, {10, 10})
+                :addRect({x = 20, y = 20, width = 5, height = 5}):close()
+
+            local unchained = Path()
+            unchained:moveTo({0, 0})
+            unchained:lineTo({10, 0})
+            unchained:quadTo({15, 5}, {10, 10})
+            unchained:addRect({x = 20, y = 20, width = 5, height = 5})
+            unchained:close()
+
+            assert(chained:countVerbs() == unchained:countVerbs())
+            assert(chained:countPoints() == unchained:countPoints())
+            for i = 0, chained:countPoints() - 1 do
+                local a, b = chained:getPoint(i), unchained:getPoint(i)
+                assert(a.x == b.x and a.y == b.y, ("point %d differs"):format(i))
+            end
+
+            -- chained calls return the same userdata
+            local path = Path()
+            assert(path:moveTo({1, 1}) == path)
+
+            local rrect = RRect():setRect({x = 0, y = 0, width = 20, height = 20}):inset({1, 1})
+            local bounds = rrect:getBounds()
+            assert(bounds.left == 1 and bounds.right == 19)
+
+            local paint = Paint.make():setColor({1, 0, 0, 1}):setStrokeWidth(3):setAntiAlias(true)
+            assert(paint:getStrokeWidth() == 3 and paint:isAntiAlias() and paint:getColor().r == 1)
+
+            -- errors in chained methods are still raised
+            assert(not pcall(function() paint:freeze():setAlpha(0.5) end))
+        "#);
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_load_reads_sources_and_files() {
+        const SOURCE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="8" height="8"><rect width="4" height="8" fill="#ff0000"/></svg>"##;
+        let path = std::env::temp_dir().join(format!("mlua-skia-{}-icon.svg", std::process::id()));
+        std::fs::write(&path, SOURCE).unwrap();
+
+        let lua = lua();
+        let globals = lua.globals();
+        globals.set("source", SOURCE).unwrap();
+        globals.set("iconPath", path.to_str().unwrap()).unwrap();
+        let result = lua
+            .load(
+                r#"
+                for _, dom in ipairs({Svg.load(source), Svg.load(iconPath)}) do
+                    local surface = raster(8, 8)
+                    dom:render(surface:getCanvas())
+                    expectPixel(surface, 1, 1, {255, 0, 0, 255})
+                    expectPixel(surface, 6, 1, {0, 0, 0, 0})
+                end
+
+                assert(not pcall(Svg.load, "<svg"))
+                local ok, err = pcall(Svg.load, "/nonexistent/icon.svg")
+                assert(not ok and tostring(err):find("unable to read", 1, true), tostring(err))
+            "#,
+            )
+            .exec();
+        let _ = std::fs::remove_file(&path);
+        if let Err(err) = result {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn probe_and_load_scaled_read_encoded_files() {
+        let mut surface = surfaces::raster_n32_premul((300, 200)).unwrap();
+        surface.canvas().clear(Color::RED);
+        let image = surface.image_snapshot();
+
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let png = dir.join(format!("mlua-skia-probe-{}.png", pid));
+        let jpeg = dir.join(format!("mlua-skia-probe-{}.jpg", pid));
+        let encoded = |format| image.encode_to_data_with_quality(format, 90).unwrap();
+        std::fs::write(&png, encoded(EncodedImageFormat::PNG).as_bytes()).unwrap();
+        std::fs::write(&jpeg, encoded(EncodedImageFormat::JPEG).as_bytes()).unwrap();
+
+        let result = lua()
+            .load(format!(
+                r#"
+                for path, format in pairs({{[ [[{}]] ] = "png", [ [[{}]] ] = "jpeg"}}) do
+                    local info = Image.probe(path)
+                    assert(info.width == 300 and info.height == 200, path)
+                    assert(info.format == format, info.format)
+                    assert(info.frameCount == 1)
+
+                    local scaled = Image.loadScaled(path, 100)
+                    assert(scaled:width() == 100 and scaled:height() == 67, path)
+
+                    -- images smaller than the limit keep their size
+                    local full = Image.loadScaled(path, 500)
+                    assert(full:width() == 300 and full:height() == 200)
+
+                    assert(not pcall(Image.loadScaled, path, 0))
+                end
+                assert(not pcall(Image.probe, "does-not-exist.png"))
+                "#,
+                png.display(),
+                jpeg.display()
+            ))
+            .exec();
+        let _ = std::fs::remove_file(&png);
+        let _ = std::fs::remove_file(&jpeg);
+        result.unwrap();
+    }
+
+    #[test]
+    fn probe_reads_whole_file_when_header_exceeds_probed_bytes() {
+        let mut surface = surfaces::raster_n32_premul((30, 20)).unwrap();
+        surface.canvas().clear(Color::BLUE);
+        let encoded = surface
+            .image_snapshot()
+            .encode_to_data_with_quality(EncodedImageFormat::JPEG, 90)
+            .unwrap();
+
+        // comment segments push the frame header past the probed bytes
+        let mut data = encoded.as_bytes()[..2].to_vec();
+        for _ in 0..2 {
+            let length: u16 = 40_000;
+            data.extend_from_slice(&[0xFF, 0xFE]);
+            data.extend_from_slice(&length.to_be_bytes());
+            data.resize(data.len() + length as usize - 2, b' ');
+        }
+        data.extend_from_slice(&encoded.as_bytes()[2..]);
+        assert!(data.len() as u64 > PROBE_HEADER_SIZE);
+
+        let path = std::env::temp_dir().join(format!(
+            "mlua-skia-probe-long-header-{}.jpg",
+            std::process::id()
+        ));
+        std::fs::write(&path, &data).unwrap();
+        let lua = lua();
+        lua.globals().set("path", path.to_str().unwrap()).unwrap();
+        let result = lua
+            .load(
+                r#"
+                local info = Image.probe(path)
+                assert(info.format == "jpeg", info.format)
+                assert(info.width == 30 and info.height == 20)
+                "#,
+            )
+            .exec();
+        let _ = std::fs::remove_file(&path);
+        if let Err(err) = result {
+            panic!("{}", err);
+        }
+    }
+
+    #[test]
+    fn gradients_in_rect_match_absolute_gradients() {
+        run(r#"
+            local function expectSame(a, b)
+                for y = 0, 79 do
+                    for x = 0, 99 do
+                        expectPixel(a, x, y, {pixel(b, x, y)}, 1)
+                    end
+                end
+            end
+            local function fill(shader)
+                local surface = raster(100, 80)
+                surface:getCanvas():drawPaint({shader = shader})
+                return surface
+            end
+            local stops = {{1, 0, 0, 1}, {0, 0, 1, 1}}
+
+            local linear = fill(GradientShader.inRect(
+                {x = 10, y = 20, width = 80, height = 40},
+                GradientShader.makeLinear({0, 0.5}, {1, 0.5}, stops)
+            ))
+            expectSame(linear, fill(GradientShader.makeLinear({10, 40}, {90, 40}, stops)))
+
+            local radial = fill(GradientShader.inRect(
+                {x = 10, y = 20, width = 40, height = 40},
+                GradientShader.makeRadial({0.5, 0.5}, 0.5, stops)
+            ))
+            expectSame(radial, fill(GradientShader.makeRadial({30, 40}, 20, stops)))
+
+            -- radial gradients stretch into an ellipse touching all sides
+            local ellipse = fill(GradientShader.inRect(
+                {x = 0, y = 0, width = 100, height = 40},
+                GradientShader.makeRadial({0.5, 0.5}, 0.5, stops)
+            ))
+            expectPixel(ellipse, 50, 20, {255, 0, 0, 255}, 8)
+            expectPixel(ellipse, 99, 20, {0, 0, 255, 255}, 8)
+            expectPixel(ellipse, 50, 39, {0, 0, 255, 255}, 8)
+            expectPixel(ellipse, 50, 79, {0, 0, 255, 255})
+
+            assert(not pcall(GradientShader.inRect, {x = 0, y = 0, width = 0, height = 10},
+                GradientShader.makeLinear({0, 0}, {1, 0}, stops)))
+        "#);
+    }
+
+    #[test]
+    fn argument_errors_name_call_and_position() {
+        run(r#"
+            local function expectError(expected, f, ...)
+                local ok, err = pcall(f, ...)
+                assert(not ok, "call should fail")
+                assert(tostring(err):find(expected, 1, true), tostring(err))
+            end
+
+            local canvas = raster(4, 4):getCanvas()
+            expectError(
+                "Canvas:drawCircle: argument #2 (r): expected f32, got boolean",
+                canvas.drawCircle, canvas, {1, 1}, true, {}
+            )
+            expectError("Canvas:drawRect: argument #1 (rect): ", canvas.drawRect, canvas, "oops", {})
+            expectError(
+                "GradientShader.makeRadial: argument #2 (radius): expected f32, got table",
+                GradientShader.makeRadial, {0, 0}, {}, {{1, 0, 0, 1}, {0, 0, 1, 1}}
+            )
+        "#);
+    }
+
+    #[test]
+    fn help_describes_known_methods() {
+        let mark_dirty = LuaTiledSurface::LUA_HELP.method("markDirty").unwrap();
+        assert_eq!(mark_dirty.name, "markDirty");
+        assert_eq!(mark_dirty.signature, "markDirty(rect: Rect?)");
+        assert_eq!(mark_dirty.kind, help::MethodKind::Method);
+        assert!(mark_dirty.doc.starts_with(
+            "Marks tiles intersecting `rect` (all tiles if `nil`) to be cleared on\nnext flush."
+        ));
+
+        let load_scaled = LuaImage::LUA_HELP.method("loadScaled").unwrap();
+        assert_eq!(
+            load_scaled.signature,
+            "loadScaled(path: string, maxDimension: integer) -> Image"
+        );
+        assert_eq!(load_scaled.kind, help::MethodKind::Function);
+
+        let font = LuaFont::LUA_HELP.method("make").unwrap();
+        assert_eq!(
+            font.signature,
+            "Font(typeface: Typeface, size: number?, scaleX: number?, skewX: number?) -> Font"
+        );
+        assert_eq!(font.kind, help::MethodKind::Constructor);
+        assert_eq!(font.doc, "");
+
+        run(r#"
+            local doc = Help.TiledSurface.markDirty
+            assert(doc:find("to be cleared on\nnext flush", 1, true), doc)
+            assert(Help.signature("TiledSurface:markDirty") == "markDirty(rect: Rect?)")
+            assert(Help.kind("TiledSurface:markDirty") == "method")
+            assert(Help.kind("Image.loadScaled") == "function")
+            assert(Help.kind("Font.make") == "constructor")
+            assert(Help.signature("Canvas.doesNotExist") == nil)
+    }
+
+    #[test]
+    fn paint_path_effect_round_trips() {
+        run(r#"
+            local paint = Paint.make()
+            assert(paint:getPathEffect() == nil)
+
+            paint:setPathEffect(PathEffect.makeDash({4, 2}, 1))
+            local dash = paint:getPathEffect():asADash()
+            local intervals = dash:getIntervals()
+            assert(#intervals == 2 and intervals[1] == 4 and intervals[2] == 2)
+            assert(dash:getPhase() == 1)
+
+            paint:setPathEffect(nil)
+            assert(paint:getPathEffect() == nil)
+        "#);
+    }
+
+    #[test]
+    fn scale_and_rotate_about_pivot() {
+        run(r#"
+            local red = {r = 1, a = 1}
+
+            local surface = raster(32, 32)
+            local canvas = surface:getCanvas()
+            canvas:scale(2, {10, 10})
+            canvas:drawRect({x = 10, y = 10, width = 2, height = 2}, red)
+            expectPixel(surface, 10, 10, {255, 0, 0, 255})
+            expectPixel(surface, 13, 13, {255, 0, 0, 255})
+            expectPixel(surface, 14, 14, {0, 0, 0, 0})
+            expectPixel(surface, 9, 9, {0, 0, 0, 0})
+
+            surface = raster(32, 32)
+            canvas = surface:getCanvas()
+            canvas:scale({x = 2, y = 1}, {x = 16, y = 0})
+            canvas:drawRect({x = 14, y = 0, width = 4, height = 4}, red)
+            expectPixel(surface, 12, 3, {255, 0, 0, 255})
+            expectPixel(surface, 19, 3, {255, 0, 0, 255})
+            expectPixel(surface, 20, 3, {0, 0, 0, 0})
+            expectPixel(surface, 12, 4, {0, 0, 0, 0})
+
+            -- a half turn about the rect center maps it onto itself
+            surface = raster(32, 32)
+            canvas = surface:getCanvas()
+            local rect = {x = 4, y = 8, width = 10, height = 6}
+            canvas:rotateAround(rect, 180)
+            canvas:drawRect(rect, red)
+            expectPixel(surface, 4, 8, {255, 0, 0, 255})
+            expectPixel(surface, 13, 13, {255, 0, 0, 255})
+            expectPixel(surface, 14, 13, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn draw_styled_fills_and_strokes_each_shape() {
+        run(r#"
+            local rect = {x = 8, y = 8, width = 16, height = 16}
+            local shapes = {
+                rect = rect,
+                rrect = RRect():setRectXY(rect, 4, 4),
+                path = Path():addRect(rect),
+                circle = {x = 16, y = 16, r = 8},
+                centered = {center = {16, 16}, radius = 8},
+            }
+            -- styles of the supplied paints are swapped to check they're forced
+            local style = {
+                fill = {r = 1, a = 1, style = "stroke"},
+                stroke = Paint.make({0, 0, 1, 1}):setStyle({fill = true}):setStrokeWidth(2),
+            }
+
+            for name, shape in pairs(shapes) do
+                local surface = raster(32, 32)
+                surface:getCanvas():drawStyled(shape, style)
+                local ok, err = pcall(function()
+                    expectPixel(surface, 16, 16, {255, 0, 0, 255})
+                    expectPixel(surface, 10, 16, {255, 0, 0, 255})
+                    expectPixel(surface, 8, 16, {0, 0, 255, 255})
+                    expectPixel(surface, 5, 16, {0, 0, 0, 0})
+                end)
+                assert(ok, name .. ": " .. tostring(err))
+            end
+
+            local surface = raster(32, 32)
+            surface:getCanvas():drawStyled(rect, {stroke = {b = 1, a = 1}})
+            expectPixel(surface, 16, 16, {0, 0, 0, 0})
+            expectPixel(surface, 8, 16, {0, 0, 255, 255})
+
+            assert(not pcall(function() surface:getCanvas():drawStyled(rect, "red") end))
+        "#);
+    }
+
+    #[test]
+    fn crop_rect_inflate_shorthand_keeps_shadows() {
+        run(r#"
+            local rect = {x = 4, y = 4, width = 12, height = 12}
+            local function shadowed(cropRect)
+                local surface = raster(40, 40)
+                local filter = ImageFilter.dropShadow({8, 8}, 1, 1, {0, 0, 0, 1}, cropRect)
+                surface:getCanvas():drawRect(rect, {r = 1, a = 1, imageFilter = filter})
+                return surface
+            end
+
+            -- a crop rect matching the drawn rect clips the shadow
+            local clipped = shadowed(rect)
+            expectPixel(clipped, 10, 10, {255, 0, 0, 255})
+            expectPixel(clipped, 22, 22, {0, 0, 0, 0})
+
+            for _, shorthand in ipairs({12, {inflate = 12}}) do
+                local surface = shadowed(shorthand)
+                expectPixel(surface, 10, 10, {255, 0, 0, 255})
+                expectPixel(surface, 22, 22, {0, 0, 0, 255}, 2)
+            end
+
+            -- layers outset by the margin keep the shadow as well
+            local filter = ImageFilter.dropShadow({8, 8}, 1, 1, {0, 0, 0, 1}, {inflate = 12})
+            assert(filter:margin() == 12)
+            assert(ImageFilter.colorFilter(ColorFilter.blend({1, 1, 1, 1}, "modulate"), filter):margin() == 12)
+            assert(ImageFilter.dropShadow({8, 8}, 1, 1, {0, 0, 0, 1}, rect):margin() == 0)
+
+            local surface = raster(40, 40)
+            local canvas = surface:getCanvas()
+            canvas:saveLayer({
+                bounds = Rect.inset(rect, -filter:margin()),
+                paint = {imageFilter = filter},
+            })
+            canvas:drawRect(rect, {r = 1, a = 1})
+            canvas:restore()
+            expectPixel(surface, 22, 22, {0, 0, 0, 255}, 2)
+
+            -- layer bounds are outset by the margin of their filter, so
+            -- content drawn past the requested bounds survives the restore
+            local surface = raster(40, 40)
+            local canvas = surface:getCanvas()
+            canvas:saveLayer({bounds = rect, paint = {imageFilter = filter}})
+            canvas:drawRect({x = 18, y = 4, width = 4, height = 4}, {g = 1, a = 1})
+            canvas:restore()
+            expectPixel(surface, 20, 6, {0, 255, 0, 255})
+            expectPixel(surface, 28, 14, {0, 0, 0, 255}, 2)
+
+            local surface = raster(40, 40)
+            local canvas = surface:getCanvas()
+            canvas:saveLayer({bounds = rect})
+            canvas:drawRect({x = 18, y = 4, width = 4, height = 4}, {g = 1, a = 1})
+            canvas:restore()
+            expectPixel(surface, 20, 6, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn font_enumeration_order_is_stable() {
+        run(r#"
+            local function expectSameList(a, b, describe)
+                assert(#a == #b and #a > 0)
+                for i = 1, #a do
+                    assert(describe(a[i]) == describe(b[i]), ("entry %d differs"):format(i))
+                end
+            end
+            local function name(it) return it end
+
+            local mgr = FontMgr()
+            local families = mgr:families()
+            expectSameList(families, mgr:families(), name)
+            for i = 2, #families do
+                assert(families[i - 1] < families[i], "families should be sorted and unique")
+            end
+
+            local set = mgr:matchFamily("DejaVu Sans")
+            local styles = set:styles()
+            assert(#styles == set:count())
+            expectSameList(styles, set:styles(), function(it)
+                return tostring(it.name) .. ":" .. tostring(it.style:weight())
+            end)
+
+            local typeface = set:createTypeface(0)
+            local names = typeface:familyNames()
+            expectSameList(names, typeface:familyNames(), function(it)
+                return it.language .. ":" .. it.name
+            end)
+            local found = false
+            for _, it in ipairs(names) do
+                found = found or it.name == "DejaVu Sans"
+            end
+            assert(found, "family names should include DejaVu Sans")
+            assert(typeface:familyNameMap()[names[1].language] ~= nil)
+        "#);
+    }
+
+    #[test]
+    fn make_surface_inherits_parent_properties() {
+        let info = ImageInfo::new(
+            (64, 48),
+            ColorType::RGBAF16,
+            AlphaType::Premul,
+            ColorSpace::new_srgb_linear(),
+        );
+        let props = SurfaceProps::new(
+            SurfacePropsFlags::USE_DEVICE_INDEPENDENT_FONTS,
+            PixelGeometry::BGRH,
+        );
+        let mut parent = LuaSurface::new(surfaces::raster(&info, None, Some(&props)).unwrap());
+
+        let (child, err) = parent
+            .make_surface(SurfaceSpec::Size(ISize::new(32, 24)))
+            .unwrap();
+        assert!(err.is_none());
+        let child = child.expect("child surface should be created");
+        let child_info = child.0.image_info();
+        assert_eq!(child_info.dimensions(), ISize::new(32, 24));
+        assert_eq!(child_info.color_type(), info.color_type());
+        assert_eq!(child_info.alpha_type(), info.alpha_type());
+        assert!(child_info.color_space() == info.color_space());
+        assert_eq!(*child.0.props(), props);
+
+        // explicit image info keeps its format but still inherits props
+        let n32 = ImageInfo::new_n32_premul((16, 16), None);
+        let (child, _) = parent.make_surface(SurfaceSpec::Info(n32.clone())).unwrap();
+        let child = child.expect("child surface should be created");
+        assert_eq!(child.0.image_info().color_type(), n32.color_type());
+        assert_eq!(*child.0.props(), props);
+
+        let (child, err) = parent
+            .make_surface(SurfaceSpec::Size(ISize::new(0, 24)))
+            .unwrap();
+        assert!(child.is_none());
+        assert_eq!(err.as_deref(), Some("unable to create 0x24 surface"));
+    }
+
+    #[test]
+    fn effects_follow_render_quality() {
+        let lua = lua();
+        lua.load(
+            r#"
+            -- alpha of a blurred rect edge at x = 20, sampled 12px outside
+            -- of it and just inside it
+            function blurredEdge(sigma)
+                local surface = raster(40, 10)
+                local filter = ImageFilter.blur(sigma, sigma, "decal")
+                surface:getCanvas():drawRect(
+                    {x = 0, y = -20, width = 20, height = 50},
+                    {a = 1, imageFilter = filter}
+                )
+                return select(4, pixel(surface, 32, 5)), select(4, pixel(surface, 19, 5))
+            end
+
+            -- backdrop blur across a sharp black and white edge
+            function backdropEdge()
+                local surface = raster(20, 10)
+                local canvas = surface:getCanvas()
+                canvas:clear({1, 1, 1, 1})
+                canvas:drawRect({x = 0, y = 0, width = 10, height = 10}, {a = 1})
+                canvas:drawBackdropBlur({x = 0, y = 0, width = 20, height = 10}, 3, {0, 0, 0, 0})
+                return pixel(surface, 9, 5)
+            end
+
+            -- alpha of a border edge crossing the middle of pixels
+            function borderEdge()
+                local surface = raster(20, 20)
+                surface:getCanvas():drawBorder({x = 2.5, y = 2.5, width = 15, height = 15}, {
+                    top = {4, {1, 0, 0, 1}},
+                })
+                return select(4, pixel(surface, 10, 2))
+            end
+        "#,
+        )
+        .exec()
+        .unwrap();
+        let eval = |script: &str| -> Vec<f64> {
+            let values: LuaMultiValue = lua.load(script).eval().unwrap();
+            values
+                .into_iter()
+                .map(|it| lua.unpack(it).unwrap())
+                .collect()
+        };
+        let quality = || -> String { lua.load("Skia.quality()").eval().unwrap() };
+
+        assert_eq!(quality(), "full");
+        let full = eval("blurredEdge(8)");
+        assert!(full[0] > 8., "wide blur should reach 12px: {:?}", full);
+        assert!(eval("backdropEdge()")[0] > 20.);
+        let aa_edge = eval("borderEdge()")[0];
+        assert!(aa_edge > 64. && aa_edge < 192., "edge alpha {}", aa_edge);
+
+        quality::set_quality(&lua, Quality::Reduced).unwrap();
+        assert_eq!(quality(), "reduced");
+        let reduced = eval("blurredEdge(8)");
+        assert!(reduced[0] < 2., "sigma should be clamped: {:?}", reduced);
+        assert!(reduced[1] > 64. && reduced[1] < 192.);
+        // transparent tint over unblurred backdrop
+        assert_eq!(eval("backdropEdge()")[0], 0.);
+        assert_eq!(eval("borderEdge()")[0], aa_edge);
+
+        quality::set_quality(&lua, Quality::Minimal).unwrap();
+        assert_eq!(quality(), "minimal");
+        let minimal = eval("blurredEdge(8)");
+        assert_eq!(minimal, vec![0., 255.]);
+        assert_eq!(eval("backdropEdge()")[0], 0.);
+        let edge = eval("borderEdge()")[0];
+        assert!(edge == 0. || edge == 255., "edge alpha {}", edge);
+
+        assert!(lua
+            .load("Skia.setQuality")
+            .eval::<LuaValue>()
+            .unwrap()
+            .is_nil());
+    }
+
+    #[test]
+    fn layer_bounds_inflate_by_three_sigma() {
+        run(r#"
+            local function expectRect(rect, left, top, right, bottom)
+                assert(
+                    rect.left == left and rect.top == top
+                        and rect.right == right and rect.bottom == bottom,
+                    ("got {%g, %g, %g, %g}"):format(rect.left, rect.top, rect.right, rect.bottom)
+                )
+            end
+            local content = {x = 10, y = 10, width = 20, height = 20}
+
+            local blur = ImageFilter.blur(2, 3)
+            expectRect(blur:layerBoundsFor(content), 4, 1, 36, 39)
+            expectRect(blur:filterBounds(content), 4, 1, 36, 39)
+            -- sigma is mapped by the ctm, bounds are already in device space
+            local ctm = Matrix.new({2, 0, 0, 0, 2, 0, 0, 0, 1})
+            expectRect(blur:filterBounds(content, ctm), -2, -8, 42, 48)
+            expectRect(blur:filterBounds(content, "reverse"), 4, 1, 36, 39)
+
+            -- the inflate shorthand adds its margin on top
+            local inflated = ImageFilter.blur(2, 2, {inflate = 5})
+            expectRect(inflated:layerBoundsFor(content), -1, -1, 41, 41)
+
+            local offset = ImageFilter.offset({5, 0})
+            expectRect(offset:layerBoundsFor(content), 15, 10, 35, 30)
+            expectRect(offset:filterBounds(content, nil, "reverse"), 5, 10, 25, 30)
+        "#);
+    }
+
+    #[test]
+    fn blend_mode_names_round_trip_through_paint() {
+        run(r#"
+            local modes = BlendMode.all()
+            assert(#modes == 29 and modes[1] == "clear" and modes[#modes] == "luminosity")
+
+            local paint = Paint.make()
+            local porterDuff = 0
+            for _, name in ipairs(modes) do
+                assert(paint:setBlendMode(name):getBlendMode() == name, name)
+                if BlendMode.isPorterDuff(name) then
+                    porterDuff = porterDuff + 1
+                end
+            end
+            assert(porterDuff == 13)
+            assert(Paint.make():getBlendMode() == "src_over")
+            assert(not pcall(paint.setBlendMode, paint, "overlayy"))
+
+            local coeffs = BlendMode.coeffs("src_over")
+            assert(coeffs.srcCoeff == "one" and coeffs.dstCoeff == "isa")
+            assert(BlendMode.coeffs("screen").dstCoeff == "isc")
+            assert(BlendMode.coeffs("multiply") == nil)
+            assert(not BlendMode.isPorterDuff("multiply"))
+        "#);
+    }
+
+    #[test]
+    fn glyph_paths_follow_glyph_order() {
+        let font = test_font(24.);
+        let lua = LuaContext::new();
+
+        let glyphs = font.0.str_to_glyphs_vec("loading...");
+        let paths = font.get_paths(&lua, glyphs.clone()).unwrap();
+        assert_eq!(paths.get::<_, usize>("n").unwrap(), glyphs.len());
+        // each of the repeated '.' glyphs gets an outline
+        let points = |index: usize| {
+            let path: LuaAnyUserData = paths.raw_get(index).unwrap();
+            let count = path.borrow::<LuaPath>().unwrap().0.count_points();
+            count
+        };
+        assert!(points(8) > 0);
+        assert_eq!(points(8), points(9));
+        assert_eq!(points(8), points(10));
+
+        // trailing glyphs without outlines are counted as well
+        let glyphs = font.0.str_to_glyphs_vec("a b ");
+        let paths = font.get_paths(&lua, glyphs.clone()).unwrap();
+        assert_eq!(paths.get::<_, usize>("n").unwrap(), 4);
+        assert!(paths.raw_get::<_, LuaValue>(1).unwrap().is_userdata());
+        assert!(paths.raw_get::<_, LuaValue>(2).unwrap().is_nil());
+        assert!(paths.raw_get::<_, LuaValue>(3).unwrap().is_userdata());
+        assert!(paths.raw_get::<_, LuaValue>(4).unwrap().is_nil());
+        assert!(font.get_path(glyphs[1]).unwrap().is_none());
+        assert!(font.get_path(glyphs[0]).unwrap().is_some());
+
+        let unique = font
+            .get_paths_unique(font.0.str_to_glyphs_vec("loading..."))
+            .unwrap();
+        assert_eq!(unique.len(), 8);
+    }
+
+    #[test]
+    fn font_edging_recommendation_follows_pixel_geometry() {
+        run(r#"
+            local info = {
+                dimensions = {width = 8, height = 8},
+                color_type = "rgba8888",
+                alpha_type = "premul",
+            }
+
+            local lcd = Surface.raster(info, "rgbh")
+            assert(lcd:props():pixelGeometry() == "rgbh")
+            local canvas = lcd:getCanvas()
+            assert(canvas:recommendedFontEdging() == "subpixel_anti_alias")
+            canvas:save()
+            canvas:rotate(30)
+            assert(canvas:recommendedFontEdging() == "anti_alias")
+            canvas:restore()
+            -- plain layers drop LCD text
+            canvas:saveLayer()
+            assert(canvas:recommendedFontEdging() == "anti_alias")
+            canvas:restore()
+            assert(canvas:recommendedFontEdging() == "subpixel_anti_alias")
+            -- layers with props or the preserving flag keep it
+            canvas:saveLayer({props = "rgbh"})
+            assert(canvas:recommendedFontEdging() == "subpixel_anti_alias")
+            canvas:saveLayer({flags = "preserve_lcd_text"})
+            assert(canvas:recommendedFontEdging() == "subpixel_anti_alias")
+            canvas:saveLayer({})
+            assert(canvas:recommendedFontEdging() == "anti_alias")
+            canvas:restore()
+            assert(canvas:recommendedFontEdging() == "subpixel_anti_alias")
+            canvas:restore()
+            canvas:restore()
+
+            local unknown = Surface.raster(info)
+            local canvas = unknown:getCanvas()
+            assert(canvas:recommendedFontEdging() == "anti_alias")
+            -- layer props can't add a geometry the surface doesn't have
+            canvas:saveLayer({props = "rgbh"})
+            assert(canvas:recommendedFontEdging() == "anti_alias")
+            canvas:restore()
+
+            local independent = Surface.raster(info, {
+                flags = {"use_device_independent_fonts"},
+                pixelGeometry = "rgbh",
+            })
+            assert(independent:getCanvas():recommendedFontEdging() == "anti_alias")
+
+            assert(not pcall(Surface.raster, info, "diagonal"))
+        "#);
+    }
+
+    #[test]
+    fn map_rect_bounds_rotated_rects_for_both_sizes() {
+        let unit = LuaRect::from(Rect::new(0., 0., 1., 1.));
+        let map = |matrix: LuaMatrix| Rect::from(matrix.map_rect(unit).unwrap());
+        let close = |a: Rect, b: Rect| {
+            let d = [
+                a.left - b.left,
+                a.top - b.top,
+                a.right - b.right,
+                a.bottom - b.bottom,
+            ];
+            d.iter().all(|it| it.abs() < 1e-5)
+        };
+
+        let three = map(LuaMatrix::Three(Matrix::rotate_deg(45.)));
+        let four = map(LuaMatrix::Four(M44::rotate(
+            V3::new(0., 0., 1.),
+            std::f32::consts::FRAC_PI_4,
+        )));
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(
+            close(three, Rect::new(-half, 0., half, 2. * half)),
+            "{:?}",
+            three
+        );
+        assert!(close(three, four), "{:?} != {:?}", three, four);
+
+        // a flip must not produce inverted edges
+        let flipped = map(LuaMatrix::Four(M44::scale(-2., -1., 1.)));
+        assert!(close(flipped, Rect::new(-2., -1., 0., 0.)), "{:?}", flipped);
+
+        // perspective bounds contain every projected corner
+        let perspective = Matrix::new_all(1., 0., 0., 0., 1., 0., 0.5, 0.25, 1.);
+        let bounds = map(LuaMatrix::Four(M44::from(perspective)));
+        for corner in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)] {
+            let point = perspective.map_point(corner);
+            assert!(
+                point.x >= bounds.left - 1e-5
+                    && point.x <= bounds.right + 1e-5
+                    && point.y >= bounds.top - 1e-5
+                    && point.y <= bounds.bottom + 1e-5,
+                "{:?} outside of {:?}",
+                point,
+                bounds
+            );
+        }
+        assert!(close(bounds, map(LuaMatrix::Three(perspective))));
+    }
+
+    #[test]
+    fn font_style_accepts_descriptor_strings() {
+        run(r#"
+            local style = FontStyle.make("semi_bold condensed italic")
+            assert(style:weight() == 600 and style:slant() == "italic")
+            assert(style:toString() == "semi_bold condensed italic", style:toString())
+            assert(FontStyle.make(style:toString()):toString() == style:toString())
+
+            assert(FontStyle.make("bold", "normal", "italic"):toString() == "bold italic")
+            assert(FontStyle.make():toString() == "normal")
+
+            local ok, err = pcall(FontStyle.make, "bold bold")
+            assert(not ok and tostring(err):find("repeated token 'bold'", 1, true), tostring(err))
+        "#);
+    }
+
+    #[test]
+    fn rounded_images_have_anti_aliased_corners() {
+        run(r#"
+            local source = raster(16, 16)
+            source:getCanvas():clear({1, 0, 0, 1})
+            local image = source:makeImageSnapshot()
+
+            local surface = raster(32, 32)
+            surface:getCanvas():drawImageRounded(image, {x = 0, y = 0, width = 32, height = 32}, 8)
+            expectPixel(surface, 0, 0, {0, 0, 0, 0})
+            expectPixel(surface, 16, 16, {255, 0, 0, 255})
+            expectPixel(surface, 16, 0, {255, 0, 0, 255})
+
+            local partial = 0
+            for y = 0, 7 do
+                for x = 0, 7 do
+                    local a = select(4, pixel(surface, x, y))
+                    if a > 0 and a < 255 then
+                        partial = partial + 1
+                    end
+                end
+            end
+            assert(partial > 0, "corner arc should be anti-aliased")
+
+            -- oversized radii clamp like RRect constructors
+            local rect = {x = 0, y = 0, width = 32, height = 20}
+            local clamped = raster(32, 20)
+            clamped:getCanvas():drawImageRounded(image, rect, 100)
+            local expected = raster(32, 20)
+            expected:getCanvas():drawImageRounded(image, RRect():setRectXY(rect, 10, 10))
+            for y = 0, 19 do
+                for x = 0, 31 do
+                    expectPixel(clamped, x, y, {pixel(expected, x, y)})
+                end
+            end
+        "#);
+    }
+
+    #[test]
+    fn rendered_text_images_match_direct_draws() {
+        let lua = lua();
+        lua.globals().set("font", test_font(20.)).unwrap();
+        lua.load(
+            r#"
+            local black = {0, 0, 0, 1}
+            local direct = raster(120, 40)
+            direct:getCanvas():clear({1, 1, 1, 1})
+            font:setSubpixel(true)
+            font:setEdging("anti_alias")
+            direct:getCanvas():drawTextBlob(TextBlob.makeFromString("Hello", font), {10, 28}, black)
+
+            local cached = raster(120, 40)
+            cached:getCanvas():clear({1, 1, 1, 1})
+            local text = font:renderToImage("Hello", black)
+            assert(text.bounds.right > text.bounds.left)
+            local offset = text.baselineOffset
+            cached:getCanvas():drawImage(text.image, {10 - offset.x, 28 - offset.y})
+
+            local inked = 0
+            for y = 0, 39 do
+                for x = 0, 119 do
+                    local r = pixel(direct, x, y)
+                    if r < 128 then
+                        inked = inked + 1
+                    end
+                    expectPixel(cached, x, y, {pixel(direct, x, y)}, 2)
+                end
+            end
+            assert(inked > 0, "text wasn't drawn")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn rendered_text_images_use_lcd_edging_on_opaque_backgrounds() {
+        let lua = lua();
+        lua.globals().set("font", test_font(20.)).unwrap();
+        lua.load(
+            r#"
+            local black, white = {0, 0, 0, 1}, {1, 1, 1, 1}
+            -- returns the number of pixels with differing color channels,
+            -- and the number of inked pixels
+            local function fringes(text)
+                local image = text.image
+                local surface = raster(image:width(), image:height())
+                surface:getCanvas():drawImage(image, {0, 0})
+                local colored, inked = 0, 0
+                for y = 0, image:height() - 1 do
+                    for x = 0, image:width() - 1 do
+                        local r, g, b = pixel(surface, x, y)
+                        if r < 255 or g < 255 or b < 255 then
+                            inked = inked + 1
+                        end
+                        if math.abs(r - g) > 8 or math.abs(g - b) > 8 then
+                            colored = colored + 1
+                        end
+                    end
+                end
+                return colored, inked
+            end
+
+            local colored, inked = fringes(font:renderToImage("Hello", black, white))
+            assert(inked > 0, "LCD text wasn't drawn")
+            assert(colored > 0, "LCD text should have colored fringes")
+
+            local colored, inked = fringes(font:renderToImage("Hello", black, white, "unknown"))
+            assert(inked > 0, "grayscale text wasn't drawn")
+            assert(colored == 0, "grayscale text shouldn't have colored fringes")
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn input_regions_map_shapes_through_total_matrix() {
+        use input_region::take_input_region;
+
+        let lua = lua();
+        let collect = |script: &str| {
+            lua.load(script).exec().unwrap();
+            take_input_region(&lua).map(|it| it.bounds())
+        };
+        lua.load("canvas = raster(100, 100):getCanvas()")
+            .exec()
+            .unwrap();
+
+        let bounds = collect(
+            r#"
+            InputRegion.begin(canvas)
+            canvas:save()
+            canvas:translate({10, 20})
+            canvas:scale(2)
+            InputRegion.add({x = 0, y = 0, width = 5, height = 5})
+            canvas:restore()
+            InputRegion.finish()
+            "#,
+        );
+        assert_eq!(bounds, Some(IRect::new(10, 20, 20, 30)));
+        // taking the region clears it
+        assert_eq!(take_input_region(&lua).map(|it| it.bounds()), None);
+
+        let bounds = collect(
+            r#"
+            InputRegion.begin(canvas)
+            canvas:save()
+            canvas:translate({60, 60})
+            canvas:rotate(90)
+            InputRegion.add({x = 0, y = 0, width = 10, height = 20})
+            canvas:restore()
+            InputRegion.finish()
+            "#,
+        );
+        assert_eq!(bounds, Some(IRect::new(40, 60, 60, 70)));
+
+        let bounds = collect(
+            r#"
+            InputRegion.begin(canvas)
+            canvas:save()
+            canvas:translate({10, 20})
+            canvas:scale(2)
+            InputRegion.add({x = 20, y = 20, r = 5})
+            InputRegion.add({x = 0, y = 0, width = 5, height = 5})
+            canvas:restore()
+            InputRegion.finish()
+            "#,
+        );
+        assert_eq!(bounds, Some(IRect::new(10, 20, 60, 70)));
+
+        // unfinished collections are discarded
+        let bounds = collect(
+            r#"
+            InputRegion.begin(canvas)
+            InputRegion.add({x = 0, y = 0, width = 5, height = 5})
+            "#,
+        );
+        assert_eq!(bounds, None);
+        let err = lua
+            .load("InputRegion.add({x = 0, y = 0, width = 5, height = 5})")
+            .exec()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("without matching InputRegion.begin"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn glow_and_inner_shadow_presets() {
+        run(r#"
+            local surface = raster(100, 100)
+            local canvas = surface:getCanvas()
+            canvas:drawCircle({50, 50}, 20, Paint.glow({1, 1, 0, 1}, 10))
+            -- halo outside of the shape, which itself isn't drawn
+            local r, g, b, a = pixel(surface, 50, 73)
+            assert(a > 64 and r > 250 and g > 250, ("halo is %d, %d, %d, %d"):format(r, g, b, a))
+            expectPixel(surface, 50, 50, {0, 0, 0, 0})
+            expectPixel(surface, 50, 99, {0, 0, 0, 0}, 4)
+
+            surface = raster(100, 100)
+            canvas = surface:getCanvas()
+            canvas:drawCircle({50, 50}, 20, {1, 1, 1, 1})
+            canvas:drawCircle({50, 50}, 20, Paint.innerShadow({0, 0, 0, 1}, 4, {4, 4}))
+            -- edge facing away from the offset is darkened
+            local r, g, b, a = pixel(surface, 50, 32)
+            assert(a == 255 and r < 200, ("shadow is %d, %d, %d, %d"):format(r, g, b, a))
+            expectPixel(surface, 50, 50, {255, 255, 255, 255}, 2)
+            expectPixel(surface, 50, 64, {255, 255, 255, 255}, 2)
+            expectPixel(surface, 10, 10, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn magnifier_src_rect_stays_within_source() {
+        let bounds = Rect::from_wh(100., 100.);
+        let src = |lens: Rect, zoom: f32| magnifier_src_rect(lens, zoom, bounds);
+        assert_eq!(
+            src(Rect::new(40., 40., 60., 60.), 2.),
+            Rect::new(45., 45., 55., 55.)
+        );
+        // moved, not cut off, at edges
+        assert_eq!(
+            src(Rect::new(-10., -10., 10., 10.), 2.),
+            Rect::new(0., 0., 10., 10.)
+        );
+        assert_eq!(
+            src(Rect::new(90., 40., 110., 60.), 2.),
+            Rect::new(90., 45., 100., 55.)
+        );
+        // larger than source
+        assert_eq!(
+            src(Rect::new(0., 0., 100., 100.), 0.5),
+            Rect::new(-50., -50., 150., 150.)
+        );
+    }
+
+    #[test]
+    fn magnifier_near_edges_keeps_zoom_uniform() {
+        run(r#"
+            local source = raster(40, 40)
+            local canvas = source:getCanvas()
+            canvas:clear({1, 1, 1, 1})
+            canvas:drawRect({x = 30, y = 0, width = 1, height = 40}, {0, 0, 1, 1})
+            canvas:drawRect({x = 35, y = 0, width = 1, height = 40}, {0, 1, 0, 1})
+            canvas:drawRect({x = 39, y = 0, width = 1, height = 40}, {1, 0, 0, 1})
+
+            -- lens centered on the right source edge shows its last 10 columns
+            local target = raster(64, 40)
+            target:getCanvas():drawMagnifier(
+                source, {x = 20, y = 0, width = 40, height = 40}, 4, {circular = false}
+            )
+            expectPixel(target, 21, 20, {0, 0, 255, 255})
+            expectPixel(target, 59, 20, {255, 0, 0, 255})
+            local r, g, b = pixel(target, 41, 20)
+            assert(g == 255 and r < 64 and b < 64, ("column 35 is %d, %d, %d"):format(r, g, b))
+            expectPixel(target, 10, 20, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn char_extents_follow_grapheme_clusters() {
+        let font = test_font(16.);
+        let lua = LuaContext::new();
+        let extents = |text: &str| -> Vec<(f32, f32, usize)> {
+            font.get_char_extents(&lua, text.to_string())
+                .unwrap()
+                .into_iter()
+                .map(|it| {
+                    (
+                        it.get("glyphStartX").unwrap(),
+                        it.get("glyphEndX").unwrap(),
+                        it.get("byteOffset").unwrap(),
+                    )
+                })
+                .collect()
+        };
+
+        let ascii = extents("ab c");
+        assert_eq!(
+            ascii.iter().map(|it| it.2).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert_eq!(ascii[0].0, 0.);
+        for pair in ascii.windows(2) {
+            assert!(pair[0].0 < pair[0].1);
+            assert!(pair[0].1 <= pair[1].0 + 1e-3);
+        }
+
+        // combining acute accent joins the preceding 'e'
+        let combined = extents("e\u{301}x");
+        assert_eq!(combined.len(), 2);
+        assert_eq!((combined[0].2, combined[1].2), (0, 3));
+        assert!(combined[0].1 <= combined[1].0 + 1e-3);
+
+        let multibyte = extents("ñéa");
+        assert_eq!(
+            multibyte.iter().map(|it| it.2).collect::<Vec<_>>(),
+            vec![0, 2, 4]
+        );
+        assert!(multibyte.windows(2).all(|it| it[0].0 < it[1].0));
+    }
+
+    #[test]
+    fn double_buffer_presents_finished_frames() {
+        run(r#"
+            local function info(size)
+                return {
+                    dimensions = {width = size, height = size},
+                    color_type = "rgba8888",
+                    alpha_type = "premul",
+                }
+            end
+            local function color(image, size)
+                local target = raster(size or 4, size or 4)
+                target:getCanvas():drawImage(image, {0, 0})
+                return pixel(target, 1, 1)
+            end
+            local red, green, blue = {1, 0, 0, 1}, {0, 1, 0, 1}, {0, 0, 1, 1}
+
+            local buffer = DoubleBuffer.new(info(4))
+            local first = buffer:drawCanvas()
+            assert(rawequal(first, buffer:drawCanvas()), "canvas changed before present")
+            first:clear(red)
+            local frame = buffer:present()
+            -- canvas of the presented buffer can't be drawn with anymore
+            assert(not pcall(function() first:clear(blue) end))
+
+            local second = buffer:drawCanvas()
+            second:clear(green)
+            -- presented frame isn't affected by drawing the next one
+            local r, g, b, a = color(frame)
+            assert(r == 255 and g == 0 and a == 255, "frame 1 changed")
+            local r, g, b, a = color(buffer:present())
+            assert(r == 0 and g == 255 and a == 255, "frame 2 wasn't presented")
+
+            -- back buffer holds the frame before the last one
+            buffer:drawCanvas()
+            local r, g, b, a = color(buffer:present())
+            assert(r == 255 and g == 0, "back buffer isn't frame 1")
+
+            local canvas = buffer:drawCanvas()
+            buffer:resize(info(8))
+            assert(buffer:width() == 8 and buffer:height() == 8)
+            assert(not pcall(function() canvas:clear(blue) end))
+            buffer:drawCanvas():clear(blue)
+            local r, g, b, a = color(buffer:present(), 8)
+            assert(b == 255 and a == 255, "resized frame wasn't presented")
+        "#);
+    }
+
+    #[test]
+    fn draw_text_blob_accepts_strings() {
+        let lua = lua();
+        lua.globals().set("font", test_font(18.)).unwrap();
+        lua.load(
+            r#"
+            local black = {0, 0, 0, 1}
+            local explicit = raster(80, 30)
+            explicit:getCanvas():drawTextBlob(TextBlob.makeFromString("Text", font), {4, 22}, black)
+            local fromString = raster(80, 30)
+            fromString:getCanvas():drawTextBlob("Text", {4, 22}, black, font)
+
+            local inked = false
+            for y = 0, 29 do
+                for x = 0, 79 do
+                    local expected = {pixel(explicit, x, y)}
+                    inked = inked or expected[4] > 0
+                    expectPixel(fromString, x, y, expected)
+                end
+            end
+            assert(inked, "text wasn't drawn")
+
+            -- empty strings draw nothing
+            fromString:getCanvas():drawTextBlob("", {4, 22}, black, font)
+
+            local ok, err = pcall(function()
+                fromString:getCanvas():drawTextBlob("Text", {4, 22}, black)
+            end)
+            assert(not ok and tostring(err):find("font argument is required"), tostring(err))
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn regions_draw_and_clip_disjoint_rects() {
+        run(r#"
+            local region = Region({x = 2, y = 2, width = 6, height = 6})
+                :opRect({x = 12, y = 2, width = 6, height = 6}, "union")
+            assert(region:isComplex() and not region:isRect())
+            assert(region:contains({4.5, 4.5}) and not region:contains({10, 4}))
+            local bounds = region:getBounds()
+            assert(bounds.left == 2 and bounds.right == 18 and bounds.bottom == 8)
+
+            local surface = raster(20, 10)
+            surface:getCanvas():drawRegion(region, {1, 0, 0, 0.5})
+            expectPixel(surface, 4, 4, {255, 0, 0, 128}, 1)
+            expectPixel(surface, 14, 4, {255, 0, 0, 128}, 1)
+            expectPixel(surface, 10, 4, {0, 0, 0, 0})
+            expectPixel(surface, 4, 9, {0, 0, 0, 0})
+
+            surface = raster(20, 10)
+            local canvas = surface:getCanvas()
+            canvas:clipRegion(region)
+            canvas:clear({0, 0, 1, 1})
+            expectPixel(surface, 2, 2, {0, 0, 255, 255})
+            expectPixel(surface, 17, 7, {0, 0, 255, 255})
+            expectPixel(surface, 10, 4, {0, 0, 0, 0})
+
+            local outline = region:toPath():getBounds()
+            assert(outline.left == 2 and outline.right == 18 and outline.top == 2)
+        "#);
+    }
+
+    #[test]
+    fn alpha_masks_ramp_content_and_restore_on_errors() {
+        run(r#"
+            local function alpha(surface, x)
+                local _, _, _, a = pixel(surface, x, 2)
+                return a
+            end
+            local function fillRed(canvas)
+                canvas:drawPaint({1, 0, 0, 1})
+            end
+
+            local surface = raster(100, 4)
+            local canvas = surface:getCanvas()
+            local fade = GradientShader.makeLinear({0, 0}, {100, 0}, {{0, 0, 0, 1}, {0, 0, 0, 0}})
+            canvas:withAlphaMask(fade, fillRed)
+            assert(alpha(surface, 5) > alpha(surface, 50) and alpha(surface, 50) > alpha(surface, 95))
+            assert(math.abs(alpha(surface, 50) - 128) < 8, alpha(surface, 50))
+            local r, g = pixel(surface, 50, 2)
+            assert(r > 250 and g == 0)
+
+            -- image masks use luminance, stretched over the clip
+            local source = raster(50, 2)
+            source:getCanvas():clear({0, 0, 0, 1})
+            source:getCanvas():drawRect({x = 0, y = 0, width = 25, height = 2}, {1, 1, 1, 1})
+            surface = raster(100, 4)
+            canvas = surface:getCanvas()
+            canvas:withAlphaMask(source:makeImageSnapshot(), fillRed)
+            assert(alpha(surface, 5) == 255 and alpha(surface, 95) == 0)
+
+            local saved = canvas:getSaveCount()
+            local inner
+            local ok, err = pcall(function()
+                canvas:withAlphaMask(fade, function(it)
+                    inner = it
+                    it:save()
+                    it:translate(50, 0)
+                    it:drawPaint({0, 0, 1, 1})
+                    error("boom")
+                end)
+            end)
+            assert(not ok and tostring(err):find("boom"), tostring(err))
+            assert(canvas:getSaveCount() == saved, "layer wasn't restored")
+            assert(not pcall(function() inner:clear({0, 0, 1, 1}) end))
+            -- content drawn before the error isn't composited
+            expectPixel(surface, 5, 2, {255, 0, 0, 255})
+            expectPixel(surface, 95, 2, {0, 0, 0, 0})
+        "#);
+    }
+
+    #[test]
+    fn merge_accepts_tables_unpacked_filters_and_nil_holes() {
+        run(r#"
+            local function draw(filter)
+                local surface = raster(30, 10)
+                surface:getCanvas():drawRect(
+                    {x = 0, y = 0, width = 4, height = 4},
+                    {r = 1, a = 1, imageFilter = filter}
+                )
+                local covered = {}
+                for i, x in ipairs({2, 12, 22}) do
+                    local _, _, _, a = pixel(surface, x, 2)
+                    covered[i] = a == 255 and "x" or "."
+                end
+                return table.concat(covered)
+            end
+            local right10 = ImageFilter.offset({10, 0})
+            local right20 = ImageFilter.offset({20, 0})
+
+            assert(draw(ImageFilter.merge({right10, right20})) == ".xx")
+            assert(draw(ImageFilter.merge(right10, right20)) == ".xx")
+            local crop = {x = 0, y = 0, width = 15, height = 10}
+            assert(draw(ImageFilter.merge(right10, right20, crop)) == ".x.")
+            assert(draw(ImageFilter.merge({right10, right20}, crop)) == ".x.")
+
+            -- nil stands for the unfiltered source
+            assert(draw(ImageFilter.merge({nil, right10})) == "xx.")
+            assert(draw(ImageFilter.merge(nil, right20)) == "x.x")
+        "#);
+    }
+
+    #[test]
+    fn gradient_interpolation_accepts_css_color_spaces() {
+        run(r#"
+            local stops = {{1, 0, 0, 1}, {0, 0, 1, 1}}
+            local function midpoint(interpolation)
+                local surface = raster(101, 1)
+                local shader = GradientShader.makeLinear({0, 0}, {101, 0}, stops, nil, nil, interpolation)
+                surface:getCanvas():drawPaint({shader = shader})
+                return pixel(surface, 50, 0)
+            end
+
+            local r, g, b = midpoint("srgb")
+            assert(math.abs(r - 128) < 4 and g < 4 and math.abs(b - 128) < 4,
+                ("srgb midpoint is %d, %d, %d"):format(r, g, b))
+            -- perceptual spaces don't pass through dark purple
+            local r, g, b = midpoint("oklab")
+            assert(g > 40 and r > 128 and b > 128, ("oklab midpoint is %d, %d, %d"):format(r, g, b))
+            local r, g, b = midpoint("srgb-linear")
+            assert(r > 170 and g < 4 and b > 170, ("linear midpoint is %d, %d, %d"):format(r, g, b))
+
+            local short = {midpoint("oklch")}
+            local full = {midpoint({color_space = "oklch", hue_method = "shorter"})}
+            for i = 1, 4 do
+                assert(short[i] == full[i], "short form differs from table")
+            end
+        "#);
+
+        let lua = LuaContext::new();
+        let name = |name: &str| LuaValue::String(lua.create_string(name).unwrap());
+        let err = LuaInterpolation::from_lua(name("display-p4"), &lua)
+            .err()
+            .expect("unknown color space should be rejected")
+            .to_string();
+        assert!(
+            err.contains("'oklab'") && err.contains("'srgb_linear'"),
+            "{}",
+            err
+        );
+        let parsed = LuaInterpolation::from_lua(name("SRGB-Linear"), &lua).unwrap();
+        assert!(parsed.0.color_space == InColorSpace::SRGBLinear);
+    }
+
+    #[test]
+    fn cloned_handles_share_identity() {
+        let lua = lua();
+        let mut surface = surfaces::raster_n32_premul((4, 4)).unwrap();
+        let image = LuaImage::new(surface.image_snapshot());
+        surface.canvas().clear(Color::RED);
+        let other = LuaImage::new(surface.image_snapshot());
+        assert_eq!(image.native_id(), image.clone().native_id());
+        assert_ne!(image.native_id(), other.native_id());
+
+        let globals = lua.globals();
+        globals.set("image", image.clone()).unwrap();
+        globals.set("imageClone", image).unwrap();
+        globals.set("otherImage", other).unwrap();
+        let paint = LuaPaint::new(Paint::default());
+        globals.set("paint", paint.clone()).unwrap();
+        globals.set("paintClone", paint).unwrap();
+        lua.load(
+            r#"
+            assert(rawequal(image, imageClone) == false)
+            assert(image == imageClone and image:nativeId() == imageClone:nativeId())
+            assert(image ~= otherImage and image:nativeId() ~= otherImage:nativeId())
+            assert(image ~= paint)
+
+            local shader = GradientShader.makeLinear({0, 0}, {1, 0}, {{1, 0, 0, 1}, {0, 0, 1, 1}})
+            local cache = {[shader:nativeId()] = true}
+            assert(cache[shader:nativeId()])
+            assert(shader ~= shader:makeWithLocalMatrix(Matrix.new({1, 0, 1, 0, 1, 0, 0, 0, 1})))
+
+            -- copied values are only identical to themselves
+            assert(paint == paint and paint ~= paintClone)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn snapshots_convert_to_target_format() {
+        let info = ImageInfo::new((8, 6), ColorType::RGBAF16, AlphaType::Premul, None);
+        let mut f16 = surfaces::raster(&info, None, None).unwrap();
+        f16.canvas().clear(Color4f::new(0.25, 0.5, 0.75, 1.0));
+        let n32 = ImageInfo::new_n32_premul((1, 1), None);
+        let image = snapshot_as(&mut f16, &n32).unwrap();
+        assert_eq!(image.dimensions(), ISize::new(8, 6));
+        assert_eq!(image.color_type(), n32.color_type());
+
+        let lua = lua();
+        lua.globals().set("f16", LuaSurface::new(f16)).unwrap();
+        lua.load(
+            r#"
+            local n32 = raster(1, 1):imageInfo()
+            local function draw(image)
+                local target = raster(8, 6)
+                target:getCanvas():drawImage(image, {0, 0})
+                return target
+            end
+
+            local image, err = f16:makeImageSnapshot(nil, n32)
+            assert(image, err)
+            local converted = draw(image)
+            expectPixel(converted, 7, 5, {64, 128, 191, 255}, 2)
+            assert(f16:makeImageSnapshot(n32), "bounds can be omitted")
+
+            image = f16:makeImageSnapshot({x = 2, y = 2, width = 4, height = 3}, n32)
+            local cropped = draw(image)
+            expectPixel(cropped, 3, 2, {64, 128, 191, 255}, 2)
+            expectPixel(cropped, 4, 3, {0, 0, 0, 0})
+
+            image, err = f16:makeImageSnapshot({x = 20, y = 20, width = 4, height = 4})
+            assert(image == nil and err:find("don't intersect"), err)
+            image, err = f16:makeImageSnapshot(nil, n32:makeColorType("unknown"))
+            assert(image == nil and err:find("unknown color"), err)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn text_paths_match_measured_text() {
+        let lua = lua();
+        lua.globals().set("font", test_font(40.)).unwrap();
+        lua.load(
+            r#"
+            local advance, measured = font:measureText("Hi")
+            local path = font:getTextPath("Hi", {10, 50})
+            local bounds = path:getBounds()
+            assert(math.abs(bounds.left - (10 + measured.left)) < 0.5, bounds.left)
+            assert(math.abs(bounds.right - (10 + measured.right)) < 0.5, bounds.right)
+            assert(math.abs(bounds.top - (50 + measured.top)) < 0.5, bounds.top)
+            -- no descenders
+            assert(math.abs(bounds.bottom - 50) < 0.5, bounds.bottom)
+            assert(bounds.right <= 10 + advance)
+
+            -- left stem of 'H'
+            assert(path:contains({bounds.left + 2, 40}))
+            assert(not path:contains({bounds.left - 1, 40}))
+
+            -- spaces have no outline, but still advance
+            assert(font:getTextPath(" "):isEmpty())
+            local spaced = font:getTextPath(" Hi", {10, 50}):getBounds()
+            assert(spaced.left > bounds.left + 5)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn clear_rect_replaces_pixels_within_clip() {
+        run(r#"
+            local blue = {0, 0, 255, 255}
+            local surface = raster(20, 20)
+            local canvas = surface:getCanvas()
+            canvas:clear({0, 0, 1, 1})
+
+            canvas:save()
+            canvas:clipRegion(Region({x = 0, y = 0, width = 10, height = 20}))
+            canvas:clearRect({x = 5, y = 5, width = 10, height = 10})
+            canvas:restore()
+            expectPixel(surface, 7, 7, {0, 0, 0, 0})
+            -- outside of the clip
+            expectPixel(surface, 12, 7, blue)
+            -- outside of the rect
+            expectPixel(surface, 2, 2, blue)
+            expectPixel(surface, 7, 16, blue)
+
+            canvas:clearRect({x = 0, y = 0, width = 4, height = 4}, {1, 0, 0, 0.5})
+            expectPixel(surface, 1, 1, {255, 0, 0, 128}, 1)
+
+            -- clear respects the clip as well
+            canvas:save()
+            canvas:clipRegion(Region({x = 10, y = 10, width = 10, height = 10}))
+            canvas:clear({0, 1, 0, 1})
+            canvas:restore()
+            expectPixel(surface, 15, 15, {0, 255, 0, 255})
+            expectPixel(surface, 2, 16, blue)
+        "#);
+    }
+
+    #[test]
+    fn contours_split_on_moves_and_keep_verbs() {
+        run(r#"
+            local path = Path()
+            path:addRect({x = 0, y = 0, width = 10, height = 10})
+            path:addRect({x = 20, y = 5, width = 5, height = 15})
+            path:addCircle({40, 40}, 5)
+            assert(path:countContours() == 3)
+
+            local contours = path:contours()
+            assert(#contours == 3)
+            local bounds = contours[1]:getBounds()
+            assert(bounds.left == 0 and bounds.top == 0 and bounds.right == 10 and bounds.bottom == 10)
+            bounds = contours[2]:getBounds()
+            assert(bounds.left == 20 and bounds.top == 5 and bounds.right == 25 and bounds.bottom == 20)
+            -- circles are made of conics, which keep their weights
+            bounds = contours[3]:getBounds()
+            assert(bounds.left == 35 and bounds.right == 45)
+
+            local joined = Path()
+            for _, contour in ipairs(contours) do
+                joined:addPath(contour, {0, 0})
+            end
+            assert(joined:countVerbs() == path:countVerbs())
+            assert(joined:countPoints() == path:countPoints())
+
+            local reversed = path:reversed()
+            assert(reversed:countVerbs() == path:countVerbs())
+            bounds = reversed:getBounds()
+            assert(bounds.left == 0 and bounds.top == 0 and bounds.right == 45 and bounds.bottom == 45)
+
+            -- lone moves don't form contours
+            local moved = Path()
+            moved:addRect({x = 0, y = 0, width = 10, height = 10})
+            moved:moveTo({50, 50})
+            assert(moved:countContours() == 1)
+            contours = moved:contours()
+            assert(#contours == 1)
+            bounds = contours[1]:getBounds()
+            assert(bounds.right == 10 and bounds.bottom == 10)
+        "#);
+    }
+
+    #[test]
+    fn teardown_poisons_surviving_userdata() {
+        let lua = lua();
+        lua.load(
+            r#"
+            surface = raster(8, 8)
+            canvas = surface:getCanvas()
+            paint = Paint.make({1, 0, 0, 1})
+            copy = paint:clone()
+            canvas:drawRect({x = 0, y = 0, width = 4, height = 4}, paint)
+            canvas:beginFrame()
+            canvas:beginDamageTracking()
+            "#,
+        )
+        .exec()
+        .unwrap();
+        let surface: LuaAnyUserData = lua.globals().get("surface").unwrap();
+        let target = damage::Target::Surface(surface.borrow::<LuaSurface>().unwrap().0.clone());
+        assert!(damage::is_tracking(&target));
+
+        // state of other contexts on the thread is left alone
+        let other = self::lua();
+        other
+            .load("canvas = raster(8, 8):getCanvas(); canvas:beginFrame()")
+            .exec()
+            .unwrap();
+
+        teardown(&lua).unwrap();
+        assert!(!damage::is_tracking(&target));
+        assert!(teardown::is_torn_down(&lua));
+        // tearing down twice is harmless
+        teardown::teardown(&lua).unwrap();
+
+        for script in [
+            "canvas:drawRect({x = 0, y = 0, width = 4, height = 4}, paint)",
+            "surface:getCanvas()",
+            "paint:getColor()",
+            "Paint.make({0, 1, 0, 1})",
+            "paint:nativeId()",
+            "return paint == copy",
+            "Help.signature('Paint.make')",
+            "Help.kind('Paint.make')",
+        ] {
+            let err = lua.load(script).exec().unwrap_err().to_string();
+            assert!(err.contains("context torn down"), "{}: {}", script, err);
+        }
+        other.load("canvas:endFrame()").exec().unwrap();
+        // surviving handles can still be dropped
+        drop(surface);
+        lua.globals().set("surface", LuaNil).unwrap();
+        lua.gc_collect().unwrap();
+    }
+
+    #[test]
+    fn rect_centered_and_relative_helpers() {
+        run(r#"
+            local function same(rect, left, top, right, bottom)
+                return math.abs(rect.left - left) < 1e-4 and math.abs(rect.top - top) < 1e-4
+                    and math.abs(rect.right - right) < 1e-4 and math.abs(rect.bottom - bottom) < 1e-4
+            end
+
+            assert(same(Rect.centered({30, 50}, {40, 60}), 10, 20, 50, 80))
+            local parent = {x = 100, y = 50, width = 200, height = 100}
+            assert(same(Rect.relative(parent, {x = 0.1, y = 0.1, w = 0.8, h = 0.3}), 120, 60, 280, 90))
+            assert(same(Rect.relative(parent, {left = 0, top = 0, right = 1, bottom = 1}), 100, 50, 300, 150))
+        "#);
+    }
+
+    #[test]
+    fn layout_pieces_cover_parent_rects() {
+        run(r#"
+            local columns = Layout.columns({x = 10, y = 20, width = 100, height = 40}, {1, 2, 1}, 4)
+            assert(#columns == 3)
+            assert(columns[1].left == 10 and columns[3].right == 110)
+            assert(columns[2].left - columns[1].right == 4)
+            assert(columns[3].left - columns[2].right == 4)
+            assert(columns[2].top == 20 and columns[2].bottom == 60)
+
+            local rows = Layout.rows({left = 0, top = 0, right = 10, bottom = 100}, 3)
+            assert(rows[1].bottom == 33 and rows[2].bottom == 67 and rows[3].bottom == 100)
+
+            local cells = Layout.grid({x = 0, y = 0, width = 90, height = 60}, 3, {1, 1}, 6)
+            assert(#cells == 6)
+            assert(cells[4].top == 33 and cells[4].left == 0)
+            assert(cells[3].right == 90 and cells[6].bottom == 60)
+
+            cells = Layout.grid({x = 0, y = 0, width = 90, height = 60}, 3, 2, {x = 6, y = 10})
+            assert(cells[2].left - cells[1].right == 6)
+            assert(cells[4].top - cells[1].bottom == 10)
+
+            for _, piece in ipairs(Layout.columns({x = 0, y = 0, width = 10, height = 10}, 3, 8)) do
+                assert(piece.left >= 0 and piece.right <= 10)
+            end
+
+            local inner = Layout.inset({x = 0, y = 0, width = 100, height = 50}, {left = 10, bottom = 5})
+            assert(inner.left == 10 and inner.top == 0 and inner.right == 100 and inner.bottom == 45)
+
+            assert(not pcall(Layout.rows, {x = 0, y = 0, width = 10, height = 10}, 0))
+            assert(not pcall(Layout.rows, {x = 0, y = 0, width = 10, height = 10}, {0, 0}))
+            assert(not pcall(Layout.columns, {x = 0, y = 0, width = 10, height = 10}, 2, -1))
+            assert(not pcall(Layout.grid, {x = 0, y = 0, width = 10, height = 10}, 2, 2, {x = 1, y = -1}))
+            assert(not pcall(Layout.rows, {x = 0, y = 0, width = 10, height = 10}, 1e9))
+        "#);
+    }
+
+    #[test]
+    fn profiling_counts_scripted_draws() {
+        run(r#"
+            assert(Skia.endProfiling() == nil)
+
+            local source = raster(8, 8)
+            local image = source:makeImageSnapshot()
+            local path = Path():addRect({x = 0, y = 0, width = 5, height = 5})
+            local surface = raster(32, 32)
+            local canvas = surface:getCanvas()
+            local debug = Surfaces.debug({32, 32})
+
+            canvas:drawRect({x = 0, y = 0, width = 4, height = 4}, {1, 0, 0, 1})
+            Skia.beginProfiling()
+            for i = 1, 3 do
+                canvas:drawRect({x = i, y = 0, width = 4, height = 4}, {1, 0, 0, 1})
+            end
+            canvas:drawPath(path, {0, 1, 0, 1})
+            canvas:drawImage(image, {0, 0})
+            canvas:drawImageRect(image, {x = 0, y = 0, width = 4, height = 2}, {x = 0, y = 0, width = 8, height = 4})
+            canvas:saveLayer({})
+            canvas:restore()
+            debug:drawRect({x = 0, y = 0, width = 4, height = 4}, {1, 0, 0, 1})
+            local report = Skia.endProfiling()
+            canvas:drawRect({x = 0, y = 0, width = 4, height = 4}, {1, 0, 0, 1})
+
+            assert(#report.canvases == 2, #report.canvases)
+            local counters = report.canvases[1]
+            assert(counters.calls.drawRect == 3, counters.calls.drawRect)
+            assert(counters.calls.drawPath == 1)
+            assert(counters.calls.drawImage == 1 and counters.calls.drawImageRect == 1)
+            assert(counters.calls.saveLayer == 1 and counters.calls.restore == 1)
+            assert(counters.totalCalls == 8, counters.totalCalls)
+            assert(counters.pathVerbs == path:countVerbs(), counters.pathVerbs)
+            -- whole 8x8 image and a 4x2 subset, at 4 bytes per pixel
+            assert(counters.imageBytes == (64 + 8) * 4, counters.imageBytes)
+            assert(counters.saveLayers == 1)
+
+            local recorded = report.canvases[2]
+            assert(recorded.calls.drawRect == 1 and recorded.totalCalls == 1)
+
+            assert(report.bindingCalls >= 10, report.bindingCalls)
+            assert(report.bindingTime >= 0)
+            assert(Skia.endProfiling() == nil)
+
+            -- verbs of paths drawn by any binding are counted, once per draw
+            local shape = Path():addRect({x = 0, y = 0, width = 5, height = 5})
+            Skia.beginProfiling()
+            canvas:drawStyled(shape, {fill = {1, 0, 0, 1}, stroke = {0, 0, 1, 1}})
+            local styled = Skia.endProfiling().canvases[1]
+            assert(styled.pathVerbs == 2 * shape:countVerbs(), styled.pathVerbs)
+
+            local drawn = {
+                function() canvas:strokeDashedFitted(shape, {0, 0, 0, 1}, 2, 1) end,
+                function() canvas:drawPolyline({1, 3, 2}, {x = 0, y = 0, width = 8, height = 8}, {0, 0, 0, 1}) end,
+                function() canvas:drawArrow({0, 0}, {10, 10}, {0, 0, 0, 1}) end,
+                function() canvas:drawMarker({8, 8}, "diamond", 6, {0, 0, 0, 1}) end,
+            }
+            for i, draw in ipairs(drawn) do
+                Skia.beginProfiling()
+                draw()
+                local counters = Skia.endProfiling().canvases[1]
+                assert(counters and counters.pathVerbs > 0, i)
+            end
+        "#);
+    }
+
+    #[test]
+    fn alpha8_and_gray8_surfaces_store_single_bytes() {
+        run(r#"
+            local mask = Surfaces.raster({dimensions = {20, 20}, color_type = "alpha8"})
+            mask:getCanvas():drawCircle({10, 10}, 6, {1, 0, 0, 0.5})
+            local coverage = mask:readPixels()
+            assert(#coverage == 20 * 20, #coverage)
+            local function at(bytes, x, y)
+                return bytes[y * 20 + x + 1]
+            end
+            assert(math.abs(at(coverage, 10, 10) - 128) <= 1, at(coverage, 10, 10))
+            assert(at(coverage, 1, 1) == 0 and at(coverage, 18, 10) == 0)
+
+            local gray = Surfaces.raster({dimensions = {20, 20}, color_type = "gray8"})
+            gray:getCanvas():clear({0, 0, 0, 1})
+            gray:getCanvas():drawRect({x = 0, y = 0, width = 10, height = 20}, {1, 1, 1, 1})
+            local luminance = gray:readPixels()
+            assert(#luminance == 20 * 20, #luminance)
+            assert(at(luminance, 5, 5) == 255 and at(luminance, 15, 5) == 0)
+
+            -- alpha8 snapshots mask by their alpha
+            local solid = Surfaces.raster({dimensions = {20, 20}, color_type = "alpha8"})
+            solid:getCanvas():drawCircle({10, 10}, 6, {0, 0, 0, 1})
+            local surface = raster(20, 20)
+            surface:getCanvas():withAlphaMask(solid:makeImageSnapshot(), function(canvas)
+                canvas:drawPaint({1, 0, 0, 1})
+            end)
+            local r, g, _, a = pixel(surface, 10, 10)
+            assert(r == 255 and g == 0 and a == 255, a)
+            assert(select(4, pixel(surface, 1, 1)) == 0)
+        "#);
+    }
+
+    #[test]
+    fn described_api_lists_registered_methods() {
+        run(r#"
+            local api = Skia.describeApi()
+
+            local drawRect = api.Canvas.drawRect
+            assert(drawRect.kind == "method" and #drawRect.params == 2)
+            assert(drawRect.params[1].name == "rect" and drawRect.params[1].type == "Rect")
+            assert(drawRect.params[2].name == "paint" and drawRect.params[2].type == "Paint")
+            assert(drawRect.returns == nil)
+
+            local rows = api.Layout.rows
+            assert(rows.kind == "function" and #rows.params == 3)
+            assert(rows.params[3].name == "gap" and rows.params[3].type == "Gap?")
+            assert(rows.returns == "Rect[]", rows.returns)
+
+            assert(api.Rect.centered.kind == "function" and #api.Rect.centered.params == 2)
+            assert(#api.Skia.describeApi.params == 0)
+
+            -- every global constructor table is described
+            for _, name in ipairs({"Path", "Paint", "Image", "Surfaces", "Skia"}) do
+                assert(api[name], name .. " isn't described")
+                assert(_G[name], name .. " isn't registered")
+            end
+        "#);
+    }
+
+    #[test]
+    fn every_registered_global_is_described() {
+        let builtins: std::collections::HashSet<String> = LuaContext::new()
+            .globals()
+            .pairs::<String, LuaValue>()
+            .map(|it| it.unwrap().0)
+            .collect();
+        let lua = LuaContext::new();
+        theme::setup_with_theme(&lua, theme::ThemeSpec::default()).unwrap();
+
+        for pair in lua.globals().pairs::<String, LuaValue>() {
+            let (name, value) = pair.unwrap();
+            if builtins.contains(&name) {
+                continue;
+            }
+            let ty = API_HELP
+                .iter()
+                .find(|it| it.name == name)
+                .unwrap_or_else(|| panic!("global {} isn't described", name));
+
+            let table = match value {
+                LuaValue::Table(it) => it,
+                other => panic!("global {} is a {}", name, other.type_name()),
+            };
+            for pair in table.pairs::<String, LuaValue>() {
+                let (key, value) = pair.unwrap();
+                match (key.as_str(), &value) {
+                    // constructors are called through the type table
+                    ("__call", LuaValue::Function(_)) => assert!(
+                        ty.all_methods()
+                            .any(|it| it.kind == help::MethodKind::Constructor),
+                        "constructor of {} isn't described",
+                        name
+                    ),
+                    // Help maps type names to their docs
+                    (_, LuaValue::Table(_)) if name == "Help" => {
+                        assert!(API_HELP.iter().any(|it| it.name == key), "{}", key)
+                    }
+                    _ => assert!(
+                        ty.method(&key).is_some(),
+                        "{}.{} isn't described",
+                        name,
+                        key
+                    ),
+                }
+            }
+        }
+        // Theme is a proxy without fields of its own, so check it the other way
+        lua.load(
+            r#"
+            for name in pairs(Skia.describeApi().Theme) do
+                assert(Theme[name] ~= nil, "Theme." .. name .. " isn't registered")
+            end
+            assert(Help.kind("Paint.nativeId") == "method")
+            assert(Help.kind("Paint.__eq") == "metamethod")
+            assert(Help.signature("Image.__eq") == "Image:__eq(other: Image) -> boolean")
+            assert(Help.kind("Enums.BlendMode") == "value")
+            assert(Help.kind("Help.signature") == "function")
+            assert(Help.Canvas.nativeId == nil, "canvases aren't handles")
+        "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[cfg(feature = "describe")]
+    #[test]
+    fn api_description_matches_described_api() {
+        let description = help::api_description();
+        let draw_rect = &description["Canvas"]["drawRect"];
+        assert_eq!(draw_rect["kind"], "method");
+        assert_eq!(draw_rect["params"].as_array().map(Vec::len), Some(2));
+        assert_eq!(draw_rect["params"][0]["name"], "rect");
+        assert_eq!(
+            description["Layout"]["grid"]["params"]
+                .as_array()
+                .map(Vec::len),
+            Some(4)
+        );
+        assert_eq!(
+            description.as_object().map(|it| it.len()),
+            Some(API_HELP.len())
+        );
+    }
+
+    #[test]
+    fn displace_matches_explicit_displacement_map() {
+        run(r#"
+            local content = raster(32, 32)
+            for i = 0, 7 do
+                local color = i % 2 == 0 and {1, 0, 0, 1} or {0, 0, 1, 1}
+                content:getCanvas():drawRect({x = i * 4, y = 0, width = 4, height = 32}, color)
+            end
+            content = content:makeImageSnapshot()
+
+            local map = raster(32, 32)
+            map:getCanvas():clear({1, 0.5, 0, 1})
+            map = map:makeImageSnapshot()
+
+            local function render(filter)
+                local surface = raster(32, 32)
+                surface:getCanvas():drawImage(content, {0, 0}, {imageFilter = filter})
+                return surface
+            end
+            local displaced = render(ImageFilter.displace(map, 4))
+            local explicit = render(ImageFilter.displacementMap("R", "green", 4, ImageFilter.image(map)))
+            local plain = render(nil)
+
+            local moved = false
+            for y = 0, 31 do
+                for x = 0, 31 do
+                    local expected = {pixel(explicit, x, y)}
+                    local actual = {pixel(displaced, x, y)}
+                    for i = 1, 4 do
+                        assert(actual[i] == expected[i], ("pixel %d, %d differs"):format(x, y))
+                    end
+                    moved = moved or select(1, pixel(plain, x, y)) ~= expected[1]
+                end
+            end
+            assert(moved, "content wasn't displaced")
+
+            local ok, err = pcall(ImageFilter.displacementMap, "x", "g", 4)
+            assert(not ok and tostring(err):find("'red'"), tostring(err))
+        "#);
+    }
+
+    #[test]
+    fn animated_dashes_repeat_every_period() {
+        run(r#"
+            local path = Path()
+            path:moveTo({2, 4})
+            path:lineTo({62, 4})
+            local function render(effect)
+                local surface = raster(64, 8)
+                surface:getCanvas():drawPath(path, {
+                    a = 1, style = "stroke", strokeWidth = 2, pathEffect = effect,
+                })
+                return surface
+            end
+            local function same(a, b)
+                for x = 0, 63 do
+                    if select(4, pixel(a, x, 4)) ~= select(4, pixel(b, x, 4)) then
+                        return false
+                    end
+                end
+                return true
+            end
+
+            local dashes = PathEffect.makeDashAnimated({4, 2})
+            assert(dashes:period() == 6)
+            local intervals = dashes:getIntervals()
+            assert(#intervals == 2 and intervals[1] == 4 and intervals[2] == 2)
+
+            local base = render(dashes:withPhase(1.5))
+            assert(same(base, render(dashes:withPhase(7.5))))
+            assert(same(base, render(dashes:withPhase(-4.5))))
+            assert(same(base, render(PathEffect.makeDash({4, 2}, 1.5))))
+            assert(not same(base, render(dashes:withPhase(3))))
+
+            -- 600 frames of marching ants only build an effect per step
+            dashes:release()
+            assert(dashes:cachedCount() == 0)
+            for frame = 1, 600 do
+                dashes:withPhase(frame * 0.5)
+            end
+            assert(dashes:cachedCount() == 12, dashes:cachedCount())
+
+            assert(not pcall(PathEffect.makeDashAnimated, {4}))
+            assert(not pcall(function() dashes:withPhase(0 / 0) end))
+        "#);
+    }
+
+    #[test]
+    fn color_adjustments_filter_drawn_colors() {
+        run(r#"
+            local sepia = ColorMatrix.sepia()
+            assert(#sepia == 20 and math.abs(sepia[1] - 0.393) < 1e-6)
+            local identity = ColorMatrix.concat(ColorMatrix.identity(), ColorMatrix.identity())
+            for i = 1, 20 do
+                assert(identity[i] == ((i - 1) % 6 == 0 and 1 or 0))
+            end
+
+            local function filtered(filter)
+                local surface = raster(4, 4)
+                surface:getCanvas():drawPaint({r = 1, a = 1, colorFilter = filter})
+                return surface
+            end
+            -- Rec. 709 luma of red is 0.2126
+            expectPixel(filtered(ColorFilter.adjust({saturation = 0})), 1, 1, {54, 54, 54, 255}, 1)
+            expectPixel(filtered(ColorFilter.matrix(ColorMatrix.saturation(0))), 1, 1, {54, 54, 54, 255}, 1)
+            expectPixel(filtered(ColorFilter.adjust({brightness = 0.5, invert = 1})), 1, 1, {128, 255, 255, 255}, 1)
+            expectPixel(filtered(ColorFilter.adjust({})), 1, 1, {255, 0, 0, 255})
+
+            assert(not pcall(ColorFilter.matrix, {1, 0, 0}))
+        "#);
+    }
+
+    #[test]
+    fn stripes_and_checkers_tile_exact_widths() {
+        run(r#"
+            local red, blue = {1, 0, 0, 1}, {0, 0, 1, 1}
+            local function fill(shader, size)
+                local surface = raster(size, size)
+                surface:getCanvas():drawPaint({a = 1, shader = shader})
+                return surface
+            end
+            local function isRed(surface, x, y)
+                local r, _, b = pixel(surface, x, y)
+                if r > 250 and b < 5 then
+                    return true
+                elseif b > 250 and r < 5 then
+                    return false
+                end
+                error(("pixel %d, %d is blended: %d, %d"):format(x, y, r, b))
+            end
+
+            -- 4 red and 6 blue columns, repeating every 10
+            local vertical = fill(Shader.stripes(4, red, 6, blue, 0), 32)
+            for x = 0, 31 do
+                assert(isRed(vertical, x, 3) == (x % 10 < 4), x)
+                assert(isRed(vertical, x, 20) == isRed(vertical, x, 3), x)
+            end
+
+            -- along the perpendicular of 45 degree stripes, pixel centers
+            -- are (k + 0.5) * sqrt(2) into the pattern
+            local diagonal = fill(Shader.stripes(4, red, 6, blue), 48)
+            local checked = 0
+            for k = 0, 47 do
+                local offset = ((k + 0.5) * math.sqrt(2)) % 10
+                local nearEdge = math.min(math.abs(offset - 4), offset, 10 - offset) < 1
+                if not nearEdge then
+                    assert(isRed(diagonal, k, k) == (offset < 4), k)
+                    checked = checked + 1
+                end
+            end
+            assert(checked > 20, checked)
+            -- stripes run along the other diagonal
+            assert(isRed(diagonal, 20, 10) == isRed(diagonal, 15, 15))
+
+            local checker = fill(Shader.checker(4, red, blue), 16)
+            for y = 0, 15 do
+                for x = 0, 15 do
+                    local a = (math.floor(x / 4) + math.floor(y / 4)) % 2 == 0
+                    assert(isRed(checker, x, y) == a, ("%d, %d"):format(x, y))
+                end
+            end
+
+            assert(not pcall(Shader.stripes, 0, red, 6, blue))
+            assert(not pcall(Shader.stripes, 4, red, -1, blue))
+            assert(not pcall(Shader.checker, 0, red, blue))
+        "#);
+    }
+
+    #[test]
+    fn checker_shaders_scale_one_cached_image() {
+        run(r#"
+            local red, blue = {1, 0, 0, 1}, {0, 0, 1, 1}
+            local surface = raster(16, 16)
+            local canvas = surface:getCanvas()
+
+            -- fractional cells keep their size across tiles
+            canvas:drawPaint({a = 1, shader = Shader.checker(2.5, red, blue)})
+            expectPixel(surface, 1, 1, {255, 0, 0, 255})
+            expectPixel(surface, 3, 1, {0, 0, 255, 255})
+            expectPixel(surface, 13, 1, {0, 0, 255, 255})
+            expectPixel(surface, 11, 1, {255, 0, 0, 255})
+
+            -- huge cells don't need huge images
+            canvas:drawPaint({a = 1, shader = Shader.checker(1e6, red, blue)})
+            expectPixel(surface, 15, 15, {255, 0, 0, 255})
+
+            for size = 1, 100 do
+                Shader.checker(size, red, blue)
+            end
+
+            for _, size in ipairs({0, -4, 0 / 0, math.huge, -math.huge}) do
+                assert(not pcall(Shader.checker, size, red, blue), tostring(size))
+            end
+        "#);
+        assert_eq!(CHECKER_SHADERS.with(ShaderCache::len), 1);
+    }
+
+    #[test]
+    fn save_layer_flags_accept_names_arrays_and_maps() {
+        let lua = lua();
+        let flags = |source: &str| -> LuaResult<SaveLayerFlags> {
+            let rec: LuaValue = lua.load(source).eval()?;
+            LuaSaveLayerRec::convert_value(rec, &lua).map(|it| it.flags())
+        };
+        let init = SaveLayerFlags::INIT_WITH_PREVIOUS;
+        let lcd = SaveLayerFlags::PRESERVE_LCD_TEXT;
+
+        assert_eq!(flags("{}").unwrap(), SaveLayerFlags::empty());
+        assert_eq!(flags(r#"{flags = "init_with_previous"}"#).unwrap(), init);
+        assert_eq!(flags(r#"{flags = "initWithPrevious"}"#).unwrap(), init);
+        assert_eq!(
+            flags(r#"{flags = {"init_with_previous", "preserveLCDText"}}"#).unwrap(),
+            init | lcd
+        );
+        assert_eq!(
+            flags("{flags = {initWithPrevious = true, preserveLCDText = false}}").unwrap(),
+            init
+        );
+        assert_eq!(
+            flags(r#"{flags = {preserve_lcd_text = true, "f16_color_type"}}"#).unwrap(),
+            lcd | SaveLayerFlags::F16_COLOR_TYPE
+        );
+
+        let error = |source: &str| flags(source).unwrap_err().to_string();
+        let unknown = error("{flags = {bogus = false}}");
+        assert!(
+            unknown.contains("'preserve_lcd_text', 'init_with_previous', 'f16_color_type'"),
+            "{}",
+            unknown
+        );
+        assert!(error(r#"{flags = "bogus"}"#).contains("'init_with_previous'"));
+        let not_boolean = error("{flags = {initWithPrevious = 1}}");
+        assert!(
+            not_boolean.contains("map of names to booleans"),
+            "{}",
+            not_boolean
+        );
+        assert!(error("{flags = 2}").contains("'init_with_previous'"));
+    }
+
+    #[test]
+    fn default_mipmaps_apply_to_downscaled_draws() {
+        run(r#"
+            -- alternating black and white columns
+            local source = raster(64, 64)
+            source:getCanvas():clear({0, 0, 0, 1})
+            for x = 1, 63, 2 do
+                source:getCanvas():drawRect({x = x, y = 0, width = 1, height = 64}, {1, 1, 1, 1})
+            end
+            local image = source:makeImageSnapshot()
+            assert(not image:hasMipmaps())
+            local mipmapped = image:withDefaultMipmaps()
+            assert(mipmapped:hasMipmaps() and not image:hasMipmaps())
+            assert(mipmapped:withDefaultMipmaps():hasMipmaps())
+            assert(mipmapped:width() == 64 and mipmapped:height() == 64)
+
+            local function quarter(image)
+                local surface = raster(16, 16)
+                local canvas = surface:getCanvas()
+                canvas:scale(0.25)
+                local sampling = {filter = "nearest", mipmap = "linear"}
+                canvas:drawPaint({a = 1, shader = image:newShader("repeat", "repeat", sampling)})
+                return surface
+            end
+            -- without mipmaps every sample hits a single column, while mipmap
+            -- levels average them
+            local aliased, filtered = quarter(image), quarter(mipmapped)
+            for x = 0, 15 do
+                local plain = pixel(aliased, x, 8)
+                assert(plain == 0 or plain == 255, plain)
+                local averaged = pixel(filtered, x, 8)
+                assert(math.abs(averaged - 128) < 16, averaged)
+            end
+        "#);
+    }
+
+    #[test]
+    fn quick_reject_accounts_for_clip_and_matrix() {
+        run(r#"
+            local surface = raster(100, 100)
+            local canvas = surface:getCanvas()
+            canvas:clipRegion(Region({x = 20, y = 20, width = 20, height = 20}))
+            canvas:translate({10, 10})
+
+            local device = canvas:getDeviceClipBounds()
+            assert(device.left == 20 and device.top == 20 and device.right == 40 and device.bottom == 40)
+            -- local bounds are outset by a pixel for anti-aliasing
+            local bounds = canvas:getLocalClipBounds()
+            assert(bounds.left == 9 and bounds.top == 9 and bounds.right == 31 and bounds.bottom == 31)
+
+            assert(not canvas:quickReject({x = 15, y = 15, width = 5, height = 5}))
+            assert(not canvas:quickReject({x = 25, y = 25, width = 20, height = 20}))
+            assert(not canvas:quickReject({x = 0, y = 0, width = 100, height = 100}))
+            assert(canvas:quickReject({x = 40, y = 40, width = 5, height = 5}))
+            assert(canvas:quickReject({x = -20, y = 15, width = 5, height = 5}))
+            -- the same rect is inside of the clip before translation
+            assert(canvas:quickReject({x = 33, y = 33, width = 5, height = 5}))
+
+            assert(canvas:quickReject(Path():addCircle({60, 60}, 5)))
+            assert(not canvas:quickReject(Path():addCircle({20, 20}, 5)))
+
+            canvas:save()
+            canvas:scale(2)
+            assert(canvas:quickReject({x = 16, y = 16, width = 2, height = 2}))
+            assert(not canvas:quickReject({x = 6, y = 6, width = 2, height = 2}))
+            canvas:restore()
+
+            canvas:clipRegion(Region({x = 60, y = 60, width = 10, height = 10}))
+            assert(canvas:getLocalClipBounds() == nil and canvas:getDeviceClipBounds() == nil)
+            assert(canvas:quickReject({x = 15, y = 15, width = 5, height = 5}))
+        "#);
+    }
+
+    #[test]
+    fn pie_gradients_switch_colors_at_boundaries() {
+        run(r#"
+            local shader, angles = GradientShader.makePie({50, 50}, {
+                {value = 1, color = {1, 0, 0, 1}},
+                {value = 1, color = {0, 1, 0, 1}},
+                {value = 2, color = {0, 0, 1, 1}},
+            })
+            assert(#angles == 4)
+            local expected = {-90, 30, 150, 270}
+            for i = 1, 4 do
+                assert(math.abs(angles[i] - expected[i]) < 1e-3, angles[i])
+            end
+
+            local surface = raster(100, 100)
+            surface:getCanvas():drawPaint({a = 1, shader = shader})
+            local function at(degrees)
+                local radians = math.rad(degrees)
+                local x = math.floor(50 + 30 * math.cos(radians))
+                local y = math.floor(50 + 30 * math.sin(radians))
+                return {pixel(surface, x, y)}
+            end
+            local function is(color, r, g, b)
+                return color[1] == r and color[2] == g and color[3] == b and color[4] == 255
+            end
+
+            -- 5 degrees is over two pixels away from boundaries at radius 30
+            for _, case in ipairs({
+                {angle = 30, before = {255, 0, 0}, after = {0, 255, 0}},
+                {angle = 150, before = {0, 255, 0}, after = {0, 0, 255}},
+                {angle = 270, before = {0, 0, 255}, after = {255, 0, 0}},
+            }) do
+                local before, after = at(case.angle - 5), at(case.angle + 5)
+                assert(is(before, case.before[1], case.before[2], case.before[3]), case.angle)
+                assert(is(after, case.after[1], case.after[2], case.after[3]), case.angle)
+            end
+
+            -- starting angle rotates segments
+            local rotated = GradientShader.makePie({50, 50}, {
+                {value = 1, color = {1, 0, 0, 1}},
+                {value = 1, color = {0, 1, 0, 1}},
+            }, 0)
+            surface:getCanvas():drawPaint({a = 1, shader = rotated})
+            assert(is(at(90), 255, 0, 0) and is(at(-90), 0, 255, 0))
+
+            assert(not pcall(GradientShader.makePie, {0, 0}, {{value = -1, color = {1, 0, 0, 1}}}))
+            assert(not pcall(GradientShader.makePie, {0, 0}, {}))
+        "#);
+    }
+
+    #[test]
+    fn tight_text_bounds_fit_within_conservative_bounds() {
+        let lua = lua();
+        lua.globals().set("font", test_font(20.)).unwrap();
+        lua.load(
+            r#"
+            local blob = TextBlob.makeFromString("Hello", font)
+            local bounds = blob:bounds()
+            local tight = blob:tightBounds()
+            assert(tight.left > bounds.left and tight.right < bounds.right)
+            assert(tight.top > bounds.top and tight.bottom < bounds.bottom)
+
+            -- both ways of finding ink agree within bisection precision
+            local direct = font:tightBoundsOf("Hello")
+            for _, side in ipairs({"left", "top", "right", "bottom"}) do
+                assert(math.abs(direct[side] - tight[side]) < 0.05, side)
+            end
+            local painted = font:tightBoundsOf("Hello", Paint.make())
+            assert(painted.left == direct.left and painted.bottom == direct.bottom)
+
+            -- "Hello" has no descenders and starts with a cap height stem
+            assert(math.abs(direct.bottom) < 0.5, direct.bottom)
+            local width = font:measureText("Hello")
+            local function spread(rect)
+                return math.abs((rect.right - rect.left) - width)
+            end
+            assert(spread(tight) < spread(bounds))
+            assert(spread(tight) < 3, spread(tight))
+
+            assert(font:tightBoundsOf("   ") == nil)
+            local spaces = TextBlob.makeFromString("   ", font)
+            assert(spaces == nil or spaces:tightBounds() == nil)
+        "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn load_bindings_follow_file_access_policy() {
+        let fixture = fs_policy::tests::Fixture::new("bindings");
+        let mut surface = surfaces::raster_n32_premul((4, 4)).unwrap();
+        surface.canvas().clear(Color::RED);
+        let png = surface
+            .image_snapshot()
+            .encode_to_data(EncodedImageFormat::PNG)
+            .unwrap();
+        let allowed = fixture.path("allowed/icon.png");
+        let outside = fixture.path("icon.png");
+        std::fs::write(&allowed, png.as_bytes()).unwrap();
+        std::fs::write(&outside, png.as_bytes()).unwrap();
+
+        let lua = lua();
+        fs_policy::set_fs_policy(
+            &lua,
+            fs_policy::FsPolicy::AllowList(vec![fixture.path("allowed")]),
+        );
+        lua.load(format!(
+            r#"
+            assert(Image.load([[{}]]):width() == 4)
+            assert(Image.probe([[{}]]).width == 4)
+            for _, load in ipairs({{Image.load, Image.probe, Typeface.makeFromFile}}) do
+                local ok, err = pcall(load, [[{}]])
+                assert(not ok and tostring(err):find("not allowed by file access policy"), tostring(err))
+            end
+            "#,
+            allowed.display(),
+            allowed.display(),
+            outside.display()
+        ))
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn described_gradients_rebuild_identically() {
+        run(r#"
+            local function fill(shader)
+                local surface = raster(100, 80)
+                surface:getCanvas():drawPaint({shader = shader})
+                return surface
+            end
+            local function expectRoundTrip(shader)
+                local descriptor = shader:describe()
+                assert(descriptor, "gradient isn't described")
+                local rebuilt = Shader.fromDescriptor(descriptor)
+                local a, b = fill(shader), fill(rebuilt)
+                for y = 0, 79 do
+                    for x = 0, 99 do
+                        expectPixel(b, x, y, {pixel(a, x, y)}, 0)
+                    end
+                end
+                return descriptor, rebuilt
+            end
+            local colors = {{1, 0, 0, 1}, {0, 1, 0, 0.5}, {0, 0, 1, 1}}
+            local positions = {0, 0.3, 1}
+            local scale = Matrix.new({0.5, 0, 10, 0, 0.5, 5, 0, 0, 1})
+
+            local linear = expectRoundTrip(GradientShader.makeLinear(
+                {10, 0}, {60, 30}, colors, positions, nil, "mirror", "oklab"))
+            assert(linear.type == "linear")
+            assert(linear.from.x == 10 and linear.to.y == 30)
+            assert(#linear.colors == 3 and math.abs(linear.positions[2] - 0.3) < 1e-6)
+            assert(linear.tileMode == "mirror")
+            assert(linear.interpolation.color_space == "oklab")
+            assert(linear.localMatrix == nil)
+
+            local radial = expectRoundTrip(GradientShader.makeRadial(
+                {50, 40}, 20, colors, positions, nil, "repeat", nil, scale))
+            assert(radial.type == "radial" and radial.radius == 20)
+            assert(#radial.localMatrix == 9)
+
+            local sweep = expectRoundTrip(GradientShader.makeSweep(
+                {50, 40}, colors, positions, nil, nil, {45, 270}))
+            assert(sweep.type == "sweep" and sweep.startAngle == 45 and sweep.endAngle == 270)
+
+            local conical = expectRoundTrip(GradientShader.makeTwoPointConical(
+                {30, 40}, 5, {70, 40}, 30, colors, positions))
+            assert(conical.type == "twoPointConical" and conical.endRadius == 30)
+
+            -- local matrices applied later are part of the descriptor
+            local _, rebuilt = expectRoundTrip(
+                GradientShader.makeLinear({0, 0}, {50, 0}, colors):makeWithLocalMatrix(scale))
+            expectRoundTrip(rebuilt:makeWithLocalMatrix(scale))
+
+            -- descriptors only contain plain values
+            local function expectPlain(value)
+                local kind = type(value)
+                assert(kind == "table" or kind == "string" or kind == "number", kind)
+                if kind == "table" then
+                    for _, it in pairs(value) do
+                        expectPlain(it)
+                    end
+                end
+            end
+            expectPlain(linear)
+
+            local image = raster(4, 4):makeImageSnapshot()
+            assert(image:newShader("repeat"):describe() == nil)
+
+            linear.type = "diamond"
+            local ok, err = pcall(Shader.fromDescriptor, linear)
+            assert(not ok and tostring(err):find("unknown gradient type 'diamond'"), tostring(err))
+            linear.type = "linear"
+            linear.positions = {0, 1}
+            assert(not pcall(Shader.fromDescriptor, linear))
+        "#);
+    }
+
+    #[test]
+    fn surface_draws_apply_source_rects_and_paint_alpha() {
+        run(r#"
+            local source = raster(20, 20)
+            local canvas = source:getCanvas()
+            canvas:drawRect({x = 0, y = 0, width = 10, height = 20}, {r = 1, a = 1})
+            canvas:drawRect({x = 10, y = 0, width = 10, height = 20}, {g = 1, a = 1})
+            local function background()
+                local surface = raster(40, 20)
+                surface:getCanvas():clear({0, 0, 1, 1})
+                return surface
+            end
+            local nearest = {filter = "nearest"}
+            local half = {a = 0.5}
+
+            -- half-transparent raster-to-raster draws blend with the background
+            local target = background()
+            source:draw(target:getCanvas(), {10, 0}, nearest, half)
+            expectPixel(target, 5, 10, {0, 0, 255, 255}, 0)
+            expectPixel(target, 15, 10, {128, 0, 128, 255}, 2)
+            expectPixel(target, 25, 10, {0, 128, 128, 255}, 2)
+            expectPixel(target, 35, 10, {0, 0, 255, 255}, 0)
+
+            local target = background()
+            target:getCanvas():drawSurface(source, nil, {0, 0}, nearest, half)
+            expectPixel(target, 5, 10, {128, 0, 128, 255}, 2)
+
+            -- only the source rect is drawn at the point
+            local target = background()
+            target:getCanvas():drawSurface(source, {x = 10, y = 0, width = 10, height = 20}, {30, 0})
+            expectPixel(target, 25, 10, {0, 0, 255, 255}, 0)
+            expectPixel(target, 30, 10, {0, 255, 0, 255}, 0)
+            expectPixel(target, 39, 10, {0, 255, 0, 255}, 0)
+
+            -- or scaled into the rect, without sampling the red half
+            local target = background()
+            target:getCanvas():drawSurface(
+                source,
+                {x = 10, y = 0, width = 10, height = 20},
+                {x = 0, y = 0, width = 40, height = 20},
+                {filter = "linear"}
+            )
+            for x = 0, 39 do
+                expectPixel(target, x, 10, {0, 255, 0, 255}, 0)
+            end
+
+            -- parts of the source rect outside the surface are skipped
+            local target = background()
+            target:getCanvas():drawSurface(source, {x = -10, y = 0, width = 20, height = 20}, {0, 0})
+            expectPixel(target, 5, 10, {0, 0, 255, 255}, 0)
+            expectPixel(target, 15, 10, {255, 0, 0, 255}, 0)
+        "#);
+    }
+
+    #[test]
+    fn resized_surfaces_invalidate_their_canvases() {
+        let lua = lua();
+        lua.load(
+            r#"
+            surface = raster(10, 10)
+            stale = surface:getCanvas()
+            stale:drawRect({x = 0, y = 0, width = 5, height = 5}, {r = 1, a = 1})
+            local other = raster(10, 10):getCanvas()
+
+            surface:resize(20, 30)
+            assert(surface:width() == 20 and surface:height() == 30)
+            local function expectInvalidated(ok, err)
+                assert(not ok, "stale canvas was drawn into")
+                assert(tostring(err):find("canvas invalidated by surface resize"), tostring(err))
+            end
+            expectInvalidated(pcall(stale.drawRect, stale, {x = 0, y = 0, width = 5, height = 5}, {r = 1, a = 1}))
+            expectInvalidated(pcall(stale.save, stale))
+            expectInvalidated(pcall(raster(2, 2).draw, raster(2, 2), stale, {0, 0}))
+
+            -- new canvases and canvases of other surfaces keep working
+            local canvas = surface:getCanvas()
+            canvas:drawRect({x = 0, y = 0, width = 20, height = 30}, {g = 1, a = 1})
+            expectPixel(surface, 19, 29, {0, 255, 0, 255}, 0)
+            other:drawRect({x = 0, y = 0, width = 5, height = 5}, {r = 1, a = 1})
+            fresh = canvas
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        // hosts recreating the backing surface invalidate canvases as well
+        {
+            let surface: LuaAnyUserData = lua.globals().get("surface").unwrap();
+            let mut surface = surface.borrow_mut::<LuaSurface>().unwrap();
+            surface.replace(surfaces::raster_n32_premul((8, 8)).unwrap());
+        }
+        let err = lua
+            .load("fresh:clear({1, 1, 1, 1})")
+            .exec()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("canvas invalidated by surface resize"),
+            "{}",
+            err
+        );
+        lua.load("surface:getCanvas():clear({1, 1, 1, 1}); assert(surface:width() == 8)")
+            .exec()
+            .unwrap();
+    }
+
+    #[test]
+    fn font_chains_shape_runs_drawn_in_sequence() {
+        let lua = lua();
+        for (name, family) in [("mono", "DejaVu Sans Mono"), ("sans", "DejaVu Sans")] {
+            let font = Font::from_typeface(test_typeface(family), 20.);
+            lua.globals().set(name, LuaFont::new(font)).unwrap();
+        }
+        lua.load(
+            r#"
+            local chain = FontChain.new({mono, sans})
+            assert(#chain:fonts() == 2)
+            local text = "Status: доступно 😀 ok"
+            local runs = chain:shape(text)
+            local expected = {"Status: доступно ", "😀 ", "ok"}
+            assert(#runs == #expected, #runs)
+            local joined = ""
+            for i, run in ipairs(runs) do
+                assert(run.text == expected[i], run.text)
+                assert(run.width > 0)
+                joined = joined .. run.text
+            end
+            assert(joined == text)
+            assert(runs[1].font:getTypeface():getFamilyName() == "DejaVu Sans Mono")
+            assert(runs[2].font:getTypeface():getFamilyName() == "DejaVu Sans")
+
+            assert(not pcall(FontChain.new, {}))
+
+            -- which system font matches varies, and machines without one
+            -- covering the character have no match
+            local fallback = FontMgr():matchCharacter(nil, nil, nil, 0x1F600)
+            assert(fallback == nil or fallback:unicharToGlyph(0x1F600) ~= 0)
+            "#,
+        )
+        .exec()
+        .unwrap();
+    }
+}
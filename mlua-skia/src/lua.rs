@@ -4,11 +4,11 @@
 //! easier to handle conversion from Lua types.
 #![allow(dead_code)]
 
-use std::{fmt::Display, mem::MaybeUninit, ops::Deref, sync::Arc};
+use std::{fmt::Display, ops::Deref, sync::Arc};
 
 use mlua::{
     AnyUserData, Error, FromLua, Integer, IntoLua, LightUserData, Lua, MultiValue,
-    Result as LuaResult, Table, UserData,
+    Result as LuaResult, Table, UserData, UserDataMethods,
     Value::{self, Nil},
 };
 
@@ -41,7 +41,7 @@ impl<T> LuaFallible<T> {
 
 impl<'lua, T: FromArgPack<'lua>> FromArgPack<'lua> for LuaFallible<T> {
     fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
-        match T::convert(args, lua) {
+        match args.attempt(|args| T::convert(args, lua)) {
             Ok(it) => Ok(LuaFallible(Some(it))),
             Err(_) => Ok(LuaFallible(None)),
         }
@@ -113,11 +113,22 @@ impl Display for LuaType {
     }
 }
 
+/// Names of arguments of a call, used in error messages.
+///
+/// A leading `self` name marks the receiver table of static functions and
+/// constructors, which isn't counted in argument positions.
 pub type ArgumentNames = Option<&'static [&'static str]>;
 
 #[derive(Debug, Clone)]
 pub(crate) struct ArgumentContext<'lua> {
     value: Vec<Value<'lua>>,
+    /// Lowest length of `value` reached during the innermost
+    /// [`attempt`](ArgumentContext::attempt), `None` outside of attempts.
+    /// Values below it haven't been touched by the attempt.
+    low_water: Option<usize>,
+    /// Original values popped below `low_water` by pending attempts, in
+    /// order they were popped.
+    originals: Vec<Value<'lua>>,
     argument_names: ArgumentNames,
     initial_count: usize,
     logical_argument: usize,
@@ -137,6 +148,8 @@ impl<'lua> ArgumentContext<'lua> {
             initial_count: value.len(),
             argument_names,
             value,
+            low_water: None,
+            originals: Vec::new(),
             logical_argument: 0,
             call_name,
         }
@@ -148,11 +161,18 @@ impl<'lua> ArgumentContext<'lua> {
 
     #[inline]
     pub fn try_pop(&mut self) -> Option<Value<'lua>> {
-        self.value.pop()
+        let value = self.value.pop()?;
+        if let Some(low_water) = self.low_water.as_mut() {
+            if self.value.len() < *low_water {
+                *low_water = self.value.len();
+                self.originals.push(value.clone());
+            }
+        }
+        Some(value)
     }
 
     pub fn pop(&mut self) -> Value<'lua> {
-        self.value.pop().unwrap_or(Value::Nil)
+        self.try_pop().unwrap_or(Value::Nil)
     }
 
     pub fn peek(&self) -> &Value<'lua> {
@@ -193,11 +213,60 @@ impl<'lua> ArgumentContext<'lua> {
         ))))
     }
 
+    /// Puts `value` back in front of remaining arguments, so it's the next
+    /// one popped.
+    ///
+    /// Arguments are a stack, so restoring several popped values requires
+    /// reverting them in reverse order: after popping `a` and then `b`,
+    /// `revert(b)` has to be followed by `revert(a)`. Conversions that can
+    /// fail after popping multiple values should rather be wrapped in
+    /// [`attempt`](Self::attempt).
     #[inline]
     pub fn revert(&mut self, value: impl IsValue<'lua>) {
         self.value.push(value.into_value())
     }
 
+    /// Runs `convert` and restores remaining arguments to their exact
+    /// original order if it fails, regardless of what it popped and
+    /// reverted before failing.
+    ///
+    /// Used wherever a failed conversion isn't an error, so that following
+    /// conversions see the same arguments as the failed one did.
+    ///
+    /// Only values `convert` pops are kept for restoring, so attempting
+    /// conversion of every remaining argument in turn takes linear time.
+    pub fn attempt<T>(&mut self, convert: impl FnOnce(&mut Self) -> LuaResult<T>) -> LuaResult<T> {
+        let start = self.value.len();
+        let outer = self.low_water.replace(start);
+        let recorded = self.originals.len();
+
+        let result = convert(self);
+
+        let low_water = self.low_water.unwrap_or(start);
+        if result.is_err() {
+            self.value.truncate(low_water);
+            self.value.extend(self.originals.drain(recorded..).rev());
+            self.low_water = outer;
+            return result;
+        }
+        match outer {
+            Some(outer) => {
+                // values between outer and start were already popped by the
+                // outer attempt, so they aren't its originals
+                let foreign = start
+                    .saturating_sub(outer)
+                    .min(self.originals.len() - recorded);
+                self.originals.drain(recorded..recorded + foreign);
+                self.low_water = Some(outer.min(low_water));
+            }
+            None => {
+                self.originals.truncate(recorded);
+                self.low_water = None;
+            }
+        }
+        result
+    }
+
     #[inline]
     pub fn len(&self) -> usize {
         self.value.len()
@@ -218,6 +287,12 @@ impl<'lua> ArgumentContext<'lua> {
             .and_then(|it| it.get(self.logical_argument).copied())
     }
 
+    /// 1-based position of the next argument as seen by the caller.
+    pub fn position(&self) -> usize {
+        let has_receiver = matches!(self.argument_names, Some(["self", ..]));
+        self.at() + 1 - (has_receiver && self.at() > 0) as usize
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
@@ -231,7 +306,7 @@ impl<'lua> ArgumentContext<'lua> {
     pub fn bad_argument(&self, inner: mlua::Error) -> mlua::Error {
         mlua::Error::BadArgument {
             to: self.call_name.cloned(),
-            pos: self.at(),
+            pos: self.position(),
             name: self.at_name().cloned(),
             cause: Arc::new(inner),
         }
@@ -273,10 +348,46 @@ impl<'lua> ArgumentContext<'lua> {
         }
     }
 
+    /// Wraps an error raised while converting the current argument into
+    /// one naming the call and the argument, e.g.
+    /// `Canvas:drawRect: argument #1 (rect): expected Rect, got string`.
+    pub fn call_error(&self, err: mlua::Error) -> mlua::Error {
+        let mut cause = &err;
+        while let Error::BadArgument { cause: inner, .. } = cause {
+            cause = inner.as_ref();
+        }
+        let cause = match cause {
+            Error::FromLuaConversionError { from, to, message } => {
+                let mut result = format!("expected {}, got {}", short_type_name(to), from);
+                if let Some(message) = message {
+                    result.push_str(" (");
+                    result.push_str(message);
+                    result.push(')');
+                }
+                result
+            }
+            other => other.to_string(),
+        };
+
+        let mut result = String::with_capacity(cause.len() + 48);
+        if let Some(call_name) = self.call_name {
+            result.push_str(call_name);
+            result.push_str(": ");
+        }
+        result.push_str(&format!("argument #{}", self.position()));
+        if let Some(name) = self.at_name() {
+            result.push_str(&format!(" ({})", name));
+        }
+        result.push_str(": ");
+        result.push_str(&cause);
+        Error::RuntimeError(result)
+    }
+
     pub fn pop_all(&mut self) -> Vec<Value<'lua>> {
-        let mut result = Vec::new();
-        std::mem::swap(&mut self.value, &mut result);
-        result.reverse();
+        let mut result = Vec::with_capacity(self.value.len());
+        while let Some(value) = self.try_pop() {
+            result.push(value);
+        }
         result
     }
 }
@@ -288,6 +399,19 @@ impl<'lua> From<ArgumentContext<'lua>> for MultiValue<'lua> {
     }
 }
 
+/// Strips module path and `Lua` prefix from Rust type names, so
+/// `mlua_skia::LuaRect` reads as `Rect`.
+fn short_type_name(name: &str) -> &str {
+    if name.contains(&['<', '[', '('][..]) {
+        return name;
+    }
+    let name = name.rsplit("::").next().unwrap_or(name);
+    match name.strip_prefix("Lua") {
+        Some(rest) if rest.starts_with(char::is_uppercase) => rest,
+        _ => name,
+    }
+}
+
 pub(crate) struct ConversionError {
     from: &'static str,
     to: &'static str,
@@ -604,6 +728,16 @@ impl<'lua> FromArgPack<'lua> for Table<'lua> {
         args.pop_typed_or::<_, String>(None)
     }
 }
+impl<'lua> FromArgPack<'lua> for mlua::String<'lua> {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        args.pop_typed_or::<_, String>(None)
+    }
+}
+impl<'lua> FromArgPack<'lua> for Function<'lua> {
+    fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+        args.pop_typed_or::<_, String>(None)
+    }
+}
 impl<'lua, T: FromArgPack<'lua>> FromArgPack<'lua> for Vec<T> {
     fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
         let table = args.pop_typed_or::<Table<'lua>, String>(None)?;
@@ -620,7 +754,13 @@ impl<'lua, T: FromArgPack<'lua>> FromArgPack<'lua> for Vec<T> {
 impl<'lua, T: FromArgPack<'lua>, const N: usize> FromArgPack<'lua> for [T; N] {
     fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         let table = args.pop();
-        let result = Vec::<T>::convert_value(table.clone(), lua)?;
+        let result = match Vec::<T>::convert_value(table.clone(), lua) {
+            Ok(it) => it,
+            Err(err) => {
+                args.revert(table);
+                return Err(err);
+            }
+        };
         match result.try_into() {
             Ok(it) => Ok(it),
             Err(it) => {
@@ -676,7 +816,7 @@ impl<T> std::ops::DerefMut for MaybeUnpacked<T> {
 impl<'lua, T: FromArgPack<'lua>> FromArgPack<'lua> for Unpacked<Vec<T>> {
     fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
         let mut result: Vec<T> = Vec::new();
-        while let Ok(value) = T::convert(args, lua) {
+        while let Ok(value) = args.attempt(|args| T::convert(args, lua)) {
             result.push(value);
         }
         Ok(Unpacked(result))
@@ -697,23 +837,16 @@ impl<'lua, T: FromArgPack<'lua>> FromArgPack<'lua> for MaybeUnpacked<Vec<T>> {
 
 impl<'lua, T: FromArgPack<'lua>, const N: usize> FromArgPack<'lua> for Unpacked<[T; N]> {
     fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
-        let mut result: MaybeUninit<[T; N]> = MaybeUninit::uninit();
-        unsafe {
-            let initial = args.clone();
-            for i in 0..N {
-                match T::convert(args, lua) {
-                    Ok(value) => {
-                        (result.as_mut_ptr() as *mut T).add(i).write(value);
-                    }
-                    Err(err) => {
-                        *args = initial;
-                        return Err(err);
-                    }
-                }
+        args.attempt(|args| {
+            let mut result: Vec<T> = Vec::with_capacity(N);
+            for _ in 0..N {
+                result.push(T::convert(args, lua)?);
             }
-
-            Ok(Unpacked(result.assume_init()))
-        }
+            match result.try_into() {
+                Ok(it) => Ok(Unpacked(it)),
+                Err(_) => unreachable!("converted exactly N values"),
+            }
+        })
     }
 }
 impl<'lua, T: FromArgPack<'lua>, const N: usize> FromArgPack<'lua> for MaybeUnpacked<[T; N]> {
@@ -727,7 +860,8 @@ impl<'lua, T: FromArgPack<'lua>, const N: usize> FromArgPack<'lua> for MaybeUnpa
     }
 }
 
-// FIXME: Reverse tuples on error
+/// Tuples convert all of their elements or restore arguments consumed by
+/// ones converted before a failure.
 macro_rules! from_arg_pack_tuple {
     ($($A:ident),*) => {
         impl<'lua$(,$A)*> FromArgPack<'lua> for ($($A,)*)
@@ -736,10 +870,12 @@ macro_rules! from_arg_pack_tuple {
         {
             #[allow(non_snake_case, unused_variables)]
             fn convert(args: &mut ArgumentContext<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
-                $(
-                    let $A = $A::convert(args, lua)?;
-                )*
-                return Ok(($($A,)*));
+                args.attempt(|args| {
+                    $(
+                        let $A = $A::convert(args, lua)?;
+                    )*
+                    Ok(($($A,)*))
+                })
             }
         }
     };
@@ -828,7 +964,7 @@ macro_rules! from_args_impl {
             ) -> LuaResult<Self> {
                 let mut args = ArgumentContext::new(args, argument_names, call_name);
                 $(
-                    let $A = $A::convert(&mut args, lua)?;
+                    let $A = $A::convert(&mut args, lua).map_err(|err| args.call_error(err))?;
                     args.advance_name();
                 )*
                 return Ok(($($A,)*));
@@ -978,6 +1114,22 @@ impl<'lua> TableExt<'lua> for Table<'lua> {
     }
 }
 
+/// Reports constructor failures.
+///
+/// Constructors raise an error with the reason when Skia rejects their
+/// arguments instead of returning `nil`, so failures surface where they
+/// happen rather than at a later use of the value.
+pub trait ConstructorResult<T> {
+    fn or_failed<R: Display>(self, constructor: &str, reason: impl FnOnce() -> R) -> LuaResult<T>;
+}
+
+impl<T> ConstructorResult<T> for Option<T> {
+    #[inline]
+    fn or_failed<R: Display>(self, constructor: &str, reason: impl FnOnce() -> R) -> LuaResult<T> {
+        self.ok_or_else(|| Error::RuntimeError(format!("{} failed: {}", constructor, reason())))
+    }
+}
+
 /// Mapping and unwrapping utilities for [`Option`]al values.
 pub trait FromLuaOption<T>: Sized {
     fn map_t(self) -> Option<T>;
@@ -1116,16 +1268,161 @@ pub trait TableWrapperExt<'lua>: TableExt<'lua> {
 
 impl<'lua> TableWrapperExt<'lua> for Table<'lua> {}
 
+/// Identity of the native object behind a wrapped skia handle.
+///
+/// Clones of ref counted handles share their native object and have the same
+/// id. Other handles are copied when cloned, so they're only identical to
+/// themselves.
+pub trait NativeId {
+    fn native_id(&self) -> usize;
+}
+
+macro_rules! ref_counted_native_id {
+    ($($handle: ty),+ $(,)?) => {$(
+        // RCHandle is a transparent non-null pointer to the native object
+        const _: () = assert!(std::mem::size_of::<$handle>() == std::mem::size_of::<usize>());
+
+        impl NativeId for $handle {
+            fn native_id(&self) -> usize {
+                unsafe { std::mem::transmute_copy::<$handle, usize>(self) }
+            }
+        }
+    )+};
+}
+
+macro_rules! value_native_id {
+    ($($handle: ty),+ $(,)?) => {$(
+        impl NativeId for $handle {
+            fn native_id(&self) -> usize {
+                self as *const $handle as usize
+            }
+        }
+    )+};
+}
+
+ref_counted_native_id![
+    skia_safe::ColorFilter,
+    skia_safe::ColorSpace,
+    skia_safe::FontStyleSet,
+    skia_safe::Image,
+    skia_safe::ImageFilter,
+    skia_safe::MaskFilter,
+    skia_safe::PathEffect,
+    skia_safe::Picture,
+    skia_safe::Shader,
+    skia_safe::Surface,
+    skia_safe::TextBlob,
+    skia_safe::Typeface,
+];
+
+value_native_id![
+    skia_safe::ColorInfo,
+    skia_safe::Font,
+    skia_safe::FontStyle,
+    skia_safe::ImageInfo,
+    skia_safe::Paint,
+    skia_safe::Path,
+    skia_safe::path_effect::DashInfo,
+    skia_safe::RRect,
+    skia_safe::Region,
+    skia_safe::StrokeRec,
+    skia_safe::SurfaceProps,
+];
+
+/// Registers methods shared by all userdata types generated with
+/// `lua_methods`.
+///
+/// The default does nothing; wrapped skia handles shadow it with an inherent
+/// function generated by [`wrap_skia_handle`] that adds `nativeId` and `__eq`,
+/// and document them with an inherent `HANDLE_HELP` constant.
+pub trait HandleMethods: Sized {
+    const HANDLE_HELP: &'static [crate::help::MethodHelp] = &[];
+
+    fn add_handle_methods<'lua, M: UserDataMethods<'lua, Self>>(_methods: &mut M) {}
+}
+impl<T> HandleMethods for T {}
+
+/// Checks a userdata value is still usable before any of its methods
+/// generated with `lua_methods` runs.
+///
+/// The default accepts every value; types that can become stale shadow it
+/// with an inherent function, e.g. canvases of resized surfaces.
+pub trait CheckValid {
+    fn check_valid(&self) -> LuaResult<()> {
+        Ok(())
+    }
+}
+impl<T> CheckValid for T {}
+
 #[macro_export]
 macro_rules! wrap_skia_handle {
-    ($handle: ty) => {
+    (@common $handle: ty, pixels: |$pixels_handle: ident| $pixels: expr) => {
         paste::paste! {
-            #[derive(Clone)]
-            pub struct [<Lua $handle>](pub $handle);
+            impl [<Lua $handle>] {
+                /// Returns a stable id of the wrapped native object, shared
+                /// by clones of ref counted handles.
+                pub fn native_id(&self) -> usize {
+                    $crate::lua::NativeId::native_id(&self.0)
+                }
+
+                pub(crate) const HANDLE_HELP: &'static [$crate::help::MethodHelp] = &[
+                    $crate::help::MethodHelp {
+                        name: "nativeId",
+                        doc: "Returns a stable id of the wrapped native object, shared by \
+                              clones of ref counted handles.",
+                        signature: concat!(stringify!($handle), ":nativeId() -> integer"),
+                        kind: $crate::help::MethodKind::Method,
+                        params: &[],
+                        returns: Some("integer"),
+                    },
+                    $crate::help::MethodHelp {
+                        name: "__eq",
+                        doc: "Returns `true` if both values wrap the same native object.",
+                        signature: concat!(
+                            stringify!($handle),
+                            ":__eq(other: ",
+                            stringify!($handle),
+                            ") -> boolean"
+                        ),
+                        kind: $crate::help::MethodKind::MetaMethod,
+                        params: &[$crate::help::ParamHelp {
+                            name: "other",
+                            ty: stringify!($handle),
+                        }],
+                        returns: Some("boolean"),
+                    },
+                ];
+
+                pub(crate) fn add_handle_methods<'lua, M: mlua::UserDataMethods<'lua, Self>>(
+                    methods: &mut M,
+                ) {
+                    methods.add_method("nativeId", |lua, this, ()| {
+                        $crate::teardown::ensure_alive(lua)?;
+                        Ok(this.native_id())
+                    });
+                    methods.add_meta_method("__eq", |lua, this, other: mlua::AnyUserData| {
+                        $crate::teardown::ensure_alive(lua)?;
+                        Ok(other
+                            .borrow::<Self>()
+                            .map(|other| other.native_id() == this.native_id())
+                            .unwrap_or(false))
+                    });
+                }
+            }
+            impl $crate::stats::TrackedHandle for [<Lua $handle>] {
+                type Handle = $handle;
+
+                const NAME: &'static str = stringify!($handle);
+
+                #[allow(unused_variables)]
+                fn pixels($pixels_handle: &mut $handle) -> Option<$crate::stats::Pixels> {
+                    $pixels
+                }
+            }
 
             impl From<$handle> for [<Lua $handle>] {
                 fn from(value: $handle) -> [<Lua $handle>] {
-                    [<Lua $handle>](value)
+                    [<Lua $handle>]::new(value)
                 }
             }
             impl From<[<Lua $handle>]> for $handle {
@@ -1149,6 +1446,65 @@ macro_rules! wrap_skia_handle {
             impl<'lua> FromClonedUD<'lua> for [<Lua $handle>] {}
         }
     };
+    ($handle: ty) => {
+        wrap_skia_handle!($handle, pixels: |_handle| None);
+    };
+    ($handle: ty, pixels: |$pixels_handle: ident| $pixels: expr) => {
+        paste::paste! {
+            #[derive(Clone)]
+            pub struct [<Lua $handle>](pub $handle, $crate::stats::Tracked);
+
+            impl [<Lua $handle>] {
+                /// Wraps the handle and records it in live handle statistics.
+                pub fn new(mut value: $handle) -> [<Lua $handle>] {
+                    let pixels = <[<Lua $handle>] as $crate::stats::TrackedHandle>::pixels(&mut value);
+                    let tracked = $crate::stats::Tracked::new(
+                        <[<Lua $handle>] as $crate::stats::TrackedHandle>::NAME,
+                        pixels,
+                    );
+                    [<Lua $handle>](value, tracked)
+                }
+            }
+        }
+        wrap_skia_handle!(@common $handle, pixels: |$pixels_handle| $pixels);
+    };
+    ($handle: ty, meta: $meta: ty) => {
+        wrap_skia_handle!($handle, pixels: |_handle| None, meta: $meta);
+    };
+    ($handle: ty, pixels: |$pixels_handle: ident| $pixels: expr, meta: $meta: ty) => {
+        paste::paste! {
+            /// Second field holds optional metadata recorded when the value is
+            /// constructed through Lua bindings.
+            #[derive(Clone)]
+            pub struct [<Lua $handle>](
+                pub $handle,
+                Option<std::rc::Rc<$meta>>,
+                $crate::stats::Tracked,
+            );
+
+            impl [<Lua $handle>] {
+                /// Wraps the handle and records it in live handle statistics.
+                pub fn new(mut value: $handle) -> [<Lua $handle>] {
+                    let pixels = <[<Lua $handle>] as $crate::stats::TrackedHandle>::pixels(&mut value);
+                    let tracked = $crate::stats::Tracked::new(
+                        <[<Lua $handle>] as $crate::stats::TrackedHandle>::NAME,
+                        pixels,
+                    );
+                    [<Lua $handle>](value, None, tracked)
+                }
+
+                pub fn with_meta(mut self, meta: $meta) -> [<Lua $handle>] {
+                    self.1 = Some(std::rc::Rc::new(meta));
+                    self
+                }
+
+                pub fn meta(&self) -> Option<&$meta> {
+                    self.1.as_deref()
+                }
+            }
+        }
+        wrap_skia_handle!(@common $handle, pixels: |$pixels_handle| $pixels);
+    };
 }
 
 #[macro_export]
@@ -1215,3 +1571,226 @@ macro_rules! type_like_table {
         type_like_table!($handle: |$ident: LuaTable, _unused_lua_ctx: &'lua Lua| $body);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_type_name_strips_paths_and_prefix() {
+        assert_eq!(short_type_name("mlua_skia::LuaRect"), "Rect");
+        assert_eq!(short_type_name("LuaPaint"), "Paint");
+        assert_eq!(short_type_name("f32"), "f32");
+        assert_eq!(short_type_name("Luau"), "Luau");
+        assert_eq!(short_type_name("[f32; 4]"), "[f32; 4]");
+        assert_eq!(
+            short_type_name("mlua_skia::LuaPoint<2>"),
+            "mlua_skia::LuaPoint<2>"
+        );
+    }
+
+    #[test]
+    fn conversion_errors_name_call_and_argument() {
+        let lua = Lua::new();
+        let err = <(f32, String)>::from_arguments(
+            MultiValue::from_vec(vec![Value::Number(1.), Value::Boolean(true)]),
+            &lua,
+            Some("Font:measureText"),
+            Some(&["size", "text"]),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Font:measureText: argument #2 (text): expected String, got boolean \
+             (expected string or number)"
+        );
+
+        // receiver tables of constructors aren't counted
+        let err = <(Table, f32)>::from_arguments(
+            MultiValue::from_vec(vec![Value::Table(lua.create_table().unwrap()), Value::Nil]),
+            &lua,
+            Some("Path"),
+            Some(&["self", "width"]),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Path: argument #1 (width): expected f32, got nil \
+             (expected number or string coercible to number)"
+        );
+
+        let err = <(f32,)>::from_arguments(
+            MultiValue::from_vec(vec![Value::Boolean(false)]),
+            &lua,
+            None,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().starts_with("argument #1: expected f32"));
+    }
+
+    fn context(values: Vec<Value>) -> ArgumentContext {
+        ArgumentContext::new(MultiValue::from_vec(values), None, None)
+    }
+
+    /// Returns remaining arguments in order, as readable labels.
+    fn remaining(args: &mut ArgumentContext) -> Vec<String> {
+        args.clone()
+            .pop_all()
+            .into_iter()
+            .map(|it| match it {
+                Value::Number(it) => it.to_string(),
+                Value::Boolean(it) => it.to_string(),
+                other => other.type_name().to_string(),
+            })
+            .collect()
+    }
+
+    /// Pops two arguments and fails without reverting them.
+    struct PopsTwo;
+
+    impl<'lua> FromArgPack<'lua> for PopsTwo {
+        fn convert(args: &mut ArgumentContext<'lua>, _: &'lua Lua) -> LuaResult<Self> {
+            args.pop();
+            args.pop();
+            Err(Error::RuntimeError("failed after popping".to_string()))
+        }
+    }
+
+    #[test]
+    fn reverting_in_reverse_order_restores_arguments() {
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Number(2.),
+            Value::Number(3.),
+        ]);
+        let first = args.pop();
+        let second = args.pop();
+        assert_eq!(remaining(&mut args), ["3"]);
+        args.revert(second);
+        assert_eq!(remaining(&mut args), ["2", "3"]);
+        args.revert(first);
+        assert_eq!(remaining(&mut args), ["1", "2", "3"]);
+    }
+
+    #[test]
+    fn failed_tuple_conversions_restore_arguments() {
+        let lua = Lua::new();
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Boolean(true),
+            Value::Number(3.),
+        ]);
+        assert!(<(f32, f32)>::convert(&mut args, &lua).is_err());
+        assert_eq!(remaining(&mut args), ["1", "true", "3"]);
+
+        // nested tuples restore values consumed by the outer one as well
+        assert!(<(f32, (bool, f32, f32))>::convert(&mut args, &lua).is_err());
+        assert_eq!(remaining(&mut args), ["1", "true", "3"]);
+
+        let (a, b, c) = <(f32, bool, f32)>::convert(&mut args, &lua).unwrap();
+        assert_eq!((a, b, c), (1., true, 3.));
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn fallible_arguments_restore_partially_popped_values() {
+        let lua = Lua::new();
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Number(2.),
+            Value::Number(3.),
+        ]);
+        let skipped = LuaFallible::<PopsTwo>::convert(&mut args, &lua).unwrap();
+        assert!(skipped.is_none());
+        assert_eq!(remaining(&mut args), ["1", "2", "3"]);
+
+        let mut args = context(vec![Value::Number(1.), Value::Boolean(false)]);
+        let skipped = LuaFallible::<(f32, f32)>::convert(&mut args, &lua).unwrap();
+        assert!(skipped.is_none());
+        let next = LuaFallible::<f32>::convert(&mut args, &lua).unwrap();
+        assert_eq!(*next, Some(1.));
+        assert_eq!(remaining(&mut args), ["false"]);
+    }
+
+    #[test]
+    fn unpacked_arrays_convert_all_or_nothing() {
+        let lua = Lua::new();
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Boolean(false),
+            Value::Number(3.),
+        ]);
+        assert!(Unpacked::<[f32; 2]>::convert(&mut args, &lua).is_err());
+        assert_eq!(remaining(&mut args), ["1", "false", "3"]);
+
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Number(2.),
+            Value::Number(3.),
+        ]);
+        let values = Unpacked::<[f32; 2]>::convert(&mut args, &lua).unwrap();
+        assert_eq!(values.into_inner(), [1., 2.]);
+        assert_eq!(remaining(&mut args), ["3"]);
+
+        // running out of arguments fails as well
+        assert!(Unpacked::<[f32; 2]>::convert(&mut args, &lua).is_err());
+        assert_eq!(remaining(&mut args), ["3"]);
+    }
+
+    #[test]
+    fn unpacked_vecs_stop_at_first_mismatch() {
+        let lua = Lua::new();
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Number(2.),
+            Value::Boolean(true),
+            Value::Number(4.),
+        ]);
+        let values = Unpacked::<Vec<f32>>::convert(&mut args, &lua).unwrap();
+        assert_eq!(values.into_inner(), vec![1., 2.]);
+        assert_eq!(remaining(&mut args), ["true", "4"]);
+
+        // values of partially converted elements are restored
+        let mut args = context(vec![
+            Value::Number(1.),
+            Value::Number(2.),
+            Value::Number(3.),
+        ]);
+        let pairs = Unpacked::<Vec<(f32, f32)>>::convert(&mut args, &lua).unwrap();
+        assert_eq!(pairs.into_inner(), vec![(1., 2.)]);
+        assert_eq!(remaining(&mut args), ["3"]);
+
+        let rest = Unpacked::<Vec<f32>>::convert(&mut args, &lua).unwrap();
+        assert_eq!(rest.into_inner(), vec![3.]);
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn attempts_only_keep_values_they_popped() {
+        let lua = Lua::new();
+        let count = 10_000;
+        let mut args = context((0..count).map(|it| Value::Number(it as f64)).collect());
+
+        let result = args.attempt(|args| {
+            let first = args.pop();
+            args.revert(first);
+            let values = Unpacked::<Vec<f32>>::convert(args, &lua)?;
+            assert_eq!(values.into_inner().len(), count);
+            // every value is kept once, including the reverted one
+            assert_eq!(args.originals.len(), count);
+            Err::<(), _>(Error::RuntimeError("failed after converting".to_string()))
+        });
+        assert!(result.is_err());
+        assert!(args.originals.is_empty());
+
+        let restored = Unpacked::<Vec<f32>>::convert(&mut args, &lua).unwrap();
+        assert!(restored
+            .into_inner()
+            .into_iter()
+            .eq((0..count).map(|it| it as f32)));
+    }
+}
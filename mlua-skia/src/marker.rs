@@ -0,0 +1,255 @@
+//! Geometry of arrows and point markers used by annotation helpers.
+//!
+//! Arrow heads are built in a frame aligned with the segment, so their apex
+//! lies exactly on the segment end and they rotate with it.
+
+use skia_safe::{Path, PathDirection, Point, Rect, Vector};
+
+/// Segments shorter than this have no direction and get no pointed heads.
+const MIN_ARROW_LENGTH: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrowHead {
+    /// Filled triangle.
+    #[default]
+    Filled,
+    /// Two stroked barbs meeting at the tip.
+    Open,
+    /// Filled circle centered on the end point.
+    Dot,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerStyle {
+    Cross,
+    Circle,
+    Diamond,
+    Square,
+}
+
+/// Size and style of arrow heads.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadShape {
+    pub style: ArrowHead,
+    /// Distance from the tip to the base of the head along the segment.
+    pub length: f32,
+    /// Extent of the head across the segment.
+    pub width: f32,
+}
+
+/// Parts of an arrow, which have to be drawn with different paint styles.
+#[derive(Default)]
+pub struct ArrowPaths {
+    /// Shaft and open heads, to be stroked.
+    pub stroke: Path,
+    /// Filled and dot heads.
+    pub fill: Path,
+}
+
+/// Adds a head with its apex at `tip`, pointing in unit `direction`.
+fn add_head(paths: &mut ArrowPaths, tip: Point, direction: Vector, head: &HeadShape) {
+    let base = tip - direction * head.length;
+    let normal = Vector::new(-direction.y, direction.x) * (head.width / 2.0);
+    match head.style {
+        ArrowHead::Filled => {
+            paths.fill.move_to(base + normal);
+            paths.fill.line_to(tip);
+            paths.fill.line_to(base - normal);
+            paths.fill.close();
+        }
+        ArrowHead::Open => {
+            paths.stroke.move_to(base + normal);
+            paths.stroke.line_to(tip);
+            paths.stroke.line_to(base - normal);
+        }
+        ArrowHead::Dot => {
+            paths
+                .fill
+                .add_circle(tip, head.width / 2.0, PathDirection::CW);
+        }
+    }
+}
+
+/// Returns paths of an arrow from `from` to `to` with a head at `to`, and at
+/// `from` as well if `both_ends` is set.
+///
+/// Heads are shortened to fit the segment. Zero length arrows have no
+/// direction, so only their dot heads are drawn.
+pub fn arrow_paths(from: Point, to: Point, head: HeadShape, both_ends: bool) -> ArrowPaths {
+    let mut result = ArrowPaths::default();
+    let heads = if both_ends { 2.0 } else { 1.0 };
+    let length = (to - from).length();
+
+    if !length.is_finite() || length < MIN_ARROW_LENGTH {
+        if head.style == ArrowHead::Dot {
+            add_head(&mut result, to, Vector::default(), &head);
+        }
+        return result;
+    }
+
+    let direction = (to - from) * (1.0 / length);
+    let head = HeadShape {
+        length: head.length.min(length / heads),
+        ..head
+    };
+    // filled heads cover the shaft end, which would otherwise poke through
+    // their tip when it's drawn with round or square caps
+    let inset = match head.style {
+        ArrowHead::Filled => head.length / 2.0,
+        ArrowHead::Open | ArrowHead::Dot => 0.0,
+    };
+    let start = if both_ends {
+        from + direction * inset
+    } else {
+        from
+    };
+    result.stroke.move_to(start);
+    result.stroke.line_to(to - direction * inset);
+
+    add_head(&mut result, to, direction, &head);
+    if both_ends {
+        add_head(&mut result, from, -direction, &head);
+    }
+    result
+}
+
+/// Returns outline of a marker of `style` centered on `center`, spanning
+/// `size` in both directions.
+///
+/// Cross markers are two lines and only make sense stroked.
+pub fn marker_path(style: MarkerStyle, center: Point, size: f32) -> Path {
+    let r = size / 2.0;
+    let mut result = Path::new();
+    match style {
+        MarkerStyle::Cross => {
+            result.move_to((center.x - r, center.y));
+            result.line_to((center.x + r, center.y));
+            result.move_to((center.x, center.y - r));
+            result.line_to((center.x, center.y + r));
+        }
+        MarkerStyle::Circle => {
+            result.add_circle(center, r, PathDirection::CW);
+        }
+        MarkerStyle::Diamond => {
+            result.move_to((center.x, center.y - r));
+            result.line_to((center.x + r, center.y));
+            result.line_to((center.x, center.y + r));
+            result.line_to((center.x - r, center.y));
+            result.close();
+        }
+        MarkerStyle::Square => {
+            result.add_rect(
+                Rect::new(center.x - r, center.y - r, center.x + r, center.y + r),
+                None,
+            );
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: HeadShape = HeadShape {
+        style: ArrowHead::Filled,
+        length: 10.0,
+        width: 8.0,
+    };
+
+    fn points(path: &Path) -> Vec<Point> {
+        let mut result = vec![Point::default(); path.count_points()];
+        path.get_points(&mut result);
+        result
+    }
+
+    fn assert_near(actual: Point, expected: Point) {
+        assert!(
+            (actual - expected).length() < 1e-4,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn head_apex_lands_on_end_point() {
+        for style in [ArrowHead::Filled, ArrowHead::Open] {
+            let paths = arrow_paths(
+                Point::new(3.0, 4.0),
+                Point::new(53.0, 24.0),
+                HeadShape { style, ..HEAD },
+                false,
+            );
+            let head = match style {
+                ArrowHead::Filled => points(&paths.fill),
+                _ => points(&paths.stroke)[2..].to_vec(),
+            };
+            assert_eq!(head.len(), 3);
+            assert_near(head[1], Point::new(53.0, 24.0));
+        }
+    }
+
+    #[test]
+    fn head_rotates_with_segment() {
+        let to = Point::new(20.0, 20.0);
+        let paths = arrow_paths(Point::new(0.0, 0.0), to, HEAD, false);
+        let head = points(&paths.fill);
+        let direction = Vector::new(1.0, 1.0) * (1.0 / 2f32.sqrt());
+        let base = to - direction * HEAD.length;
+        // barbs are symmetric about the segment at its base
+        assert_near(head[0] + head[2], base * 2.0);
+        assert!(((head[0] - head[2]).length() - HEAD.width).abs() < 1e-4);
+        assert!((head[0] - head[2]).dot(direction).abs() < 1e-4);
+
+        // shaft ends inside the filled head
+        let shaft = points(&paths.stroke);
+        assert_near(shaft[0], Point::new(0.0, 0.0));
+        assert_near(shaft[1], to - direction * (HEAD.length / 2.0));
+    }
+
+    #[test]
+    fn heads_are_shortened_to_fit() {
+        let from = Point::new(0.0, 0.0);
+        let to = Point::new(10.0, 0.0);
+        let paths = arrow_paths(from, to, HEAD, true);
+        let head = points(&paths.fill);
+        assert_eq!(head.len(), 6);
+        assert_near(head[1], to);
+        assert_near(head[4], from);
+        // both heads meet in the middle
+        assert!((head[0].x - 5.0).abs() < 1e-4);
+        assert!((head[3].x - 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn zero_length_arrows_stay_finite() {
+        let point = Point::new(5.0, 5.0);
+        let paths = arrow_paths(point, point, HEAD, true);
+        assert!(paths.stroke.is_empty());
+        assert!(paths.fill.is_empty());
+
+        let dot = HeadShape {
+            style: ArrowHead::Dot,
+            ..HEAD
+        };
+        let paths = arrow_paths(point, point, dot, false);
+        assert!(points(&paths.fill).iter().all(|it| it.is_finite()));
+        assert_eq!(*paths.fill.bounds(), Rect::new(1.0, 1.0, 9.0, 9.0));
+    }
+
+    #[test]
+    fn markers_span_size() {
+        let center = Point::new(10.0, 20.0);
+        let expected = Rect::new(7.0, 17.0, 13.0, 23.0);
+        for style in [
+            MarkerStyle::Cross,
+            MarkerStyle::Circle,
+            MarkerStyle::Diamond,
+            MarkerStyle::Square,
+        ] {
+            let path = marker_path(style, center, 6.0);
+            assert_eq!(*path.bounds(), expected, "{:?}", style);
+        }
+    }
+}
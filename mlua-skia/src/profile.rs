@@ -0,0 +1,164 @@
+//! Opt-in counters of drawing work, started with `Skia.beginProfiling()` and
+//! reported by `Skia.endProfiling()`.
+//!
+//! Draw calls are counted per Skia canvas as they're made through `Canvas`
+//! methods, so debug canvases are counted too. Time spent in bound functions
+//! is measured around their dispatch, which is only coarse: it includes
+//! argument conversion and skips time spent in Lua in between calls.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use mlua::prelude::*;
+use skia_safe::{Canvas, Image, ImageInfo, Paint, Path, Rect};
+
+#[derive(Default)]
+struct CanvasCounters {
+    calls: BTreeMap<&'static str, usize>,
+    path_verbs: usize,
+    image_bytes: u64,
+    save_layers: usize,
+}
+
+#[derive(Default)]
+struct Profile {
+    /// Counters of canvases in order of their first call.
+    canvases: Vec<(usize, CanvasCounters)>,
+    binding_calls: u64,
+    binding_time: Duration,
+    /// Number of bound functions currently executing; only the outermost
+    /// one is timed, as Lua callbacks can call others.
+    depth: usize,
+}
+
+impl Profile {
+    fn canvas(&mut self, canvas: &Canvas) -> &mut CanvasCounters {
+        let key = canvas as *const Canvas as usize;
+        let index = match self.canvases.iter().position(|(it, _)| *it == key) {
+            Some(it) => it,
+            None => {
+                self.canvases.push((key, CanvasCounters::default()));
+                self.canvases.len() - 1
+            }
+        };
+        &mut self.canvases[index].1
+    }
+}
+
+thread_local! {
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+fn with_profile(f: impl FnOnce(&mut Profile)) {
+    PROFILE.with(|it| {
+        if let Some(profile) = it.borrow_mut().as_mut() {
+            f(profile)
+        }
+    })
+}
+
+/// Starts counting, discarding counters of a profile already in progress.
+pub fn begin() {
+    PROFILE.with(|it| *it.borrow_mut() = Some(Profile::default()));
+}
+
+pub fn is_active() -> bool {
+    PROFILE.with(|it| it.borrow().is_some())
+}
+
+/// Stops counting and returns the report table, or `nil` if profiling
+/// wasn't started.
+pub fn end(lua: &Lua) -> LuaResult<Option<LuaTable>> {
+    let profile = match PROFILE.with(|it| it.borrow_mut().take()) {
+        Some(it) => it,
+        None => return Ok(None),
+    };
+
+    let canvases = lua.create_table()?;
+    for (_, counters) in profile.canvases {
+        let calls = lua.create_table()?;
+        let mut total = 0;
+        for (op, count) in counters.calls {
+            calls.set(op, count)?;
+            total += count;
+        }
+        let entry = lua.create_table()?;
+        entry.set("calls", calls)?;
+        entry.set("totalCalls", total)?;
+        entry.set("pathVerbs", counters.path_verbs)?;
+        entry.set("imageBytes", counters.image_bytes)?;
+        entry.set("saveLayers", counters.save_layers)?;
+        canvases.raw_push(entry)?;
+    }
+
+    let result = lua.create_table()?;
+    result.set("canvases", canvases)?;
+    result.set("bindingCalls", profile.binding_calls)?;
+    result.set("bindingTime", profile.binding_time.as_secs_f64())?;
+    Ok(Some(result))
+}
+
+/// Counts a `Canvas` method call.
+pub(crate) fn count_call(canvas: &Canvas, op: &'static str) {
+    with_profile(|it| *it.canvas(canvas).calls.entry(op).or_default() += 1);
+}
+
+/// Draws `path`, counting its verbs. Every binding drawing paths should draw
+/// them through this function.
+pub(crate) fn draw_path(canvas: &Canvas, path: &Path, paint: &Paint) {
+    with_profile(|it| it.canvas(canvas).path_verbs += path.count_verbs());
+    canvas.draw_path(path, paint);
+}
+
+fn area_bytes(area: Rect, info: &ImageInfo) -> u64 {
+    let pixels = (area.width().max(0.0) * area.height().max(0.0)) as u64;
+    pixels * info.bytes_per_pixel() as u64
+}
+
+/// Counts bytes of `src` area (whole `image` by default) sampled by a draw.
+pub(crate) fn count_image(canvas: &Canvas, image: &Image, src: Option<Rect>) {
+    with_profile(|it| {
+        let src = src.unwrap_or_else(|| Rect::from_irect(image.bounds()));
+        it.canvas(canvas).image_bytes += area_bytes(src, image.image_info());
+    });
+}
+
+/// Counts bytes of `src` area of a surface with `info` sampled by a draw.
+pub(crate) fn count_surface(canvas: &Canvas, info: &ImageInfo, src: Rect) {
+    with_profile(|it| it.canvas(canvas).image_bytes += area_bytes(src, info));
+}
+
+pub(crate) fn count_save_layer(canvas: &Canvas) {
+    with_profile(|it| it.canvas(canvas).save_layers += 1);
+}
+
+/// Measures time until dropped if profiling is active. Created by every
+/// function generated with `lua_methods`.
+pub(crate) struct BindingTimer(Option<Instant>);
+
+pub(crate) fn time_binding() -> BindingTimer {
+    let mut start = None;
+    with_profile(|it| {
+        if it.depth == 0 {
+            start = Some(Instant::now());
+        }
+        it.depth += 1;
+        it.binding_calls += 1;
+    });
+    BindingTimer(start)
+}
+
+impl Drop for BindingTimer {
+    fn drop(&mut self) {
+        let start = self.0;
+        with_profile(|it| {
+            it.depth = it.depth.saturating_sub(1);
+            if let Some(start) = start {
+                it.binding_time += start.elapsed();
+            }
+        });
+    }
+}
@@ -0,0 +1,281 @@
+//! Construction metadata recorded for values built through Lua bindings.
+//!
+//! Skia doesn't expose node types of filter graphs, so constructors record
+//! their name and arguments which allows describing the graph from Lua.
+
+use std::fmt::Write;
+
+use mlua::{
+    Error as LuaError, IntoLua, Lua, Result as LuaResult, Table as LuaTable, Value as LuaValue,
+};
+use skia_safe::{Color4f, ImageFilter, Rect};
+
+use crate::{LuaColor, LuaImageFilter, LuaPoint, LuaRect};
+
+/// Recorded argument value.
+#[derive(Clone)]
+pub enum Param {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+    Numbers(Vec<f32>),
+    Rect(Rect),
+    Color(Color4f),
+    Filter(LuaImageFilter),
+    Filters(Vec<Option<LuaImageFilter>>),
+}
+
+macro_rules! param_from_number {
+    ($($t: ty),+) => {$(
+        impl From<$t> for Param {
+            #[inline]
+            fn from(value: $t) -> Self {
+                Param::Number(value as f64)
+            }
+        }
+    )+};
+}
+param_from_number![f32, f64, i32, u32, usize];
+
+impl From<bool> for Param {
+    fn from(value: bool) -> Self {
+        Param::Bool(value)
+    }
+}
+impl From<&str> for Param {
+    fn from(value: &str) -> Self {
+        Param::Text(value.to_string())
+    }
+}
+impl From<String> for Param {
+    fn from(value: String) -> Self {
+        Param::Text(value)
+    }
+}
+impl From<Vec<f32>> for Param {
+    fn from(value: Vec<f32>) -> Self {
+        Param::Numbers(value)
+    }
+}
+impl<const N: usize> From<LuaPoint<N>> for Param {
+    fn from(value: LuaPoint<N>) -> Self {
+        Param::Numbers(value.as_array().to_vec())
+    }
+}
+impl From<Rect> for Param {
+    fn from(value: Rect) -> Self {
+        Param::Rect(value)
+    }
+}
+impl From<LuaRect> for Param {
+    fn from(value: LuaRect) -> Self {
+        Param::Rect(value.into())
+    }
+}
+impl From<LuaColor> for Param {
+    fn from(value: LuaColor) -> Self {
+        Param::Color(value.into())
+    }
+}
+impl From<LuaImageFilter> for Param {
+    fn from(value: LuaImageFilter) -> Self {
+        Param::Filter(value)
+    }
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Param::Number(it) => write!(f, "{}", it),
+            Param::Bool(it) => write!(f, "{}", it),
+            Param::Text(it) => write!(f, "'{}'", it),
+            Param::Numbers(it) => {
+                let values: Vec<String> = it.iter().map(f32::to_string).collect();
+                write!(f, "{{{}}}", values.join(", "))
+            }
+            Param::Rect(it) => write!(
+                f,
+                "{{left={}, top={}, right={}, bottom={}}}",
+                it.left, it.top, it.right, it.bottom
+            ),
+            Param::Color(it) => write!(f, "{{r={}, g={}, b={}, a={}}}", it.r, it.g, it.b, it.a),
+            Param::Filter(_) => write!(f, "<filter>"),
+            Param::Filters(it) => write!(f, "<{} filters>", it.len()),
+        }
+    }
+}
+
+impl<'lua> IntoLua<'lua> for Param {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        match self {
+            Param::Number(it) => it.into_lua(lua),
+            Param::Bool(it) => it.into_lua(lua),
+            Param::Text(it) => it.into_lua(lua),
+            Param::Numbers(it) => it.into_lua(lua),
+            Param::Rect(it) => LuaRect::from(it).into_lua(lua),
+            Param::Color(it) => LuaColor::from(it).into_lua(lua),
+            Param::Filter(it) => it.provenance_table(lua)?.into_lua(lua),
+            Param::Filters(it) => {
+                let result = lua.create_table()?;
+                for (i, filter) in it.into_iter().enumerate() {
+                    if let Some(filter) = filter {
+                        result.set(i + 1, filter.provenance_table(lua)?)?;
+                    }
+                }
+                result.into_lua(lua)
+            }
+        }
+    }
+}
+
+/// Name and arguments of a filter constructor.
+#[derive(Clone)]
+pub struct FilterProvenance {
+    pub name: &'static str,
+    pub params: Vec<(&'static str, Param)>,
+}
+
+impl FilterProvenance {
+    pub fn new(name: &'static str) -> Self {
+        FilterProvenance {
+            name,
+            params: Vec::new(),
+        }
+    }
+
+    /// Records an argument.
+    pub fn param(mut self, name: &'static str, value: impl Into<Param>) -> Self {
+        self.params.push((name, value.into()));
+        self
+    }
+
+    /// Records an optional argument, skipping it if it's `None`.
+    pub fn opt_param(self, name: &'static str, value: Option<impl Into<Param>>) -> Self {
+        match value {
+            Some(value) => self.param(name, value),
+            None => self,
+        }
+    }
+
+    /// Returns `name=value` list of recorded arguments, excluding inputs.
+    pub fn describe_params(&self) -> String {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .filter(|(_, it)| !matches!(it, Param::Filter(_) | Param::Filters(_)))
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        params.join(", ")
+    }
+
+    fn inputs(&self) -> impl Iterator<Item = (String, &LuaImageFilter)> {
+        self.params.iter().flat_map(|(name, value)| {
+            let inputs: Vec<_> = match value {
+                Param::Filter(it) => vec![(name.to_string(), it)],
+                Param::Filters(it) => it
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, it)| {
+                        it.as_ref().map(|it| (format!("{}[{}]", name, i + 1), it))
+                    })
+                    .collect(),
+                _ => vec![],
+            };
+            inputs.into_iter()
+        })
+    }
+}
+
+/// Wraps filters returned by Skia constructors with their provenance.
+pub trait WithProvenance {
+    /// Returns the wrapped filter, or an error listing arguments Skia
+    /// rejected.
+    fn with_provenance(self, meta: FilterProvenance) -> LuaResult<LuaImageFilter>;
+}
+
+impl WithProvenance for Option<ImageFilter> {
+    fn with_provenance(self, meta: FilterProvenance) -> LuaResult<LuaImageFilter> {
+        match self {
+            Some(it) => Ok(LuaImageFilter::new(it).with_meta(meta)),
+            None => Err(LuaError::RuntimeError(format!(
+                "ImageFilter.{} failed: invalid arguments ({})",
+                meta.name,
+                meta.describe_params()
+            ))),
+        }
+    }
+}
+
+impl LuaImageFilter {
+    /// Writes a human readable tree of the filter graph into `out`.
+    pub fn describe_into(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        match self.meta() {
+            Some(meta) => {
+                let _ = writeln!(out, "{}{}({})", indent, meta.name, meta.describe_params());
+                for (name, input) in meta.inputs() {
+                    let _ = writeln!(out, "{}  {}:", indent, name);
+                    input.describe_into(out, depth + 2);
+                }
+            }
+            None => {
+                // not constructed through bindings, only the structure is known
+                let count = self.0.count_inputs();
+                let _ = writeln!(out, "{}<unknown>({} inputs)", indent, count);
+                for i in 0..count {
+                    let _ = writeln!(out, "{}  input[{}]:", indent, i + 1);
+                    match self.0.get_input(i) {
+                        Some(input) => LuaImageFilter::new(input).describe_into(out, depth + 2),
+                        None => {
+                            let _ = writeln!(out, "{}    <source>", indent);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the largest `cropRect` inflate margin of the filter graph,
+    /// or `0` if none was requested.
+    pub fn crop_margin(&self) -> f32 {
+        let meta = match self.meta() {
+            Some(it) => it,
+            None => return 0.0,
+        };
+        let own = meta
+            .params
+            .iter()
+            .find_map(|(name, value)| match (*name, value) {
+                ("cropRect", Param::Number(it)) => Some(*it as f32),
+                _ => None,
+            })
+            .unwrap_or_default();
+        meta.inputs()
+            .map(|(_, input)| input.crop_margin())
+            .fold(own, f32::max)
+    }
+
+    /// Returns a nested table of construction parameters.
+    pub fn provenance_table<'lua>(&self, lua: &'lua Lua) -> LuaResult<LuaTable<'lua>> {
+        let result = lua.create_table()?;
+        match self.meta() {
+            Some(meta) => {
+                result.set("type", meta.name)?;
+                for (name, value) in &meta.params {
+                    result.set(*name, value.clone())?;
+                }
+            }
+            None => {
+                result.set("type", "unknown")?;
+                let inputs = lua.create_table()?;
+                for i in 0..self.0.count_inputs() {
+                    if let Some(input) = self.0.get_input(i) {
+                        inputs.set(i + 1, LuaImageFilter::new(input).provenance_table(lua)?)?;
+                    }
+                }
+                result.set("inputs", inputs)?;
+            }
+        }
+        Ok(result)
+    }
+}
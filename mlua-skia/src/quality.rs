@@ -0,0 +1,125 @@
+//! Render quality chosen by the host, exposed to Lua as `Skia.quality()`.
+//!
+//! Hosts lower it to degrade visuals globally (e.g. while on battery) without
+//! scripts having to implement their own flag. Expensive helpers such as blur
+//! filters and backdrop blur check it when they're constructed, so effects
+//! created before a change keep their quality.
+
+use mlua::prelude::*;
+
+/// Registry key of the current quality name.
+pub(crate) const QUALITY_KEY: &str = "mlua_skia.quality";
+
+/// Largest blur sigma used with [`Quality::Reduced`].
+pub const REDUCED_MAX_SIGMA: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Quality {
+    /// Effects are rendered as requested.
+    #[default]
+    Full,
+    /// Blurs are clamped to [`REDUCED_MAX_SIGMA`] and backdrop blur is
+    /// replaced by a translucent fill.
+    Reduced,
+    /// Blurs are skipped and shapes drawn with paints chosen by the bindings
+    /// (such as borders) aren't anti-aliased.
+    Minimal,
+}
+
+impl Quality {
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Full => "full",
+            Quality::Reduced => "reduced",
+            Quality::Minimal => "minimal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "full" => Some(Quality::Full),
+            "reduced" => Some(Quality::Reduced),
+            "minimal" => Some(Quality::Minimal),
+            _ => None,
+        }
+    }
+
+    /// Returns sigma a blur should use at this quality, or `None` if it
+    /// should be skipped.
+    pub fn blur_sigma(self, sigma: f32) -> Option<f32> {
+        match self {
+            Quality::Full => Some(sigma),
+            Quality::Reduced => Some(sigma.min(REDUCED_MAX_SIGMA)),
+            Quality::Minimal => None,
+        }
+    }
+
+    /// Whether paints and clips chosen by the bindings should be
+    /// anti-aliased.
+    #[inline]
+    pub fn anti_alias(self) -> bool {
+        self != Quality::Minimal
+    }
+}
+
+impl<'lua> IntoLua<'lua> for Quality {
+    fn into_lua(self, lua: &'lua Lua) -> LuaResult<LuaValue<'lua>> {
+        self.name().into_lua(lua)
+    }
+}
+
+impl<'lua> FromLua<'lua> for Quality {
+    fn from_lua(value: LuaValue<'lua>, lua: &'lua Lua) -> LuaResult<Self> {
+        let name = String::from_lua(value, lua)?;
+        Quality::from_name(&name).ok_or_else(|| LuaError::FromLuaConversionError {
+            from: "string",
+            to: "Quality",
+            message: Some(format!("unknown quality '{}'", name)),
+        })
+    }
+}
+
+/// Returns quality last set by the host, or [`Quality::Full`] if
+/// [`set_quality`] wasn't called yet.
+pub fn quality(lua: &Lua) -> LuaResult<Quality> {
+    let quality: Option<Quality> = lua.named_registry_value(QUALITY_KEY)?;
+    Ok(quality.unwrap_or_default())
+}
+
+/// Sets render quality of effects created from now on.
+pub fn set_quality(lua: &Lua, quality: Quality) -> LuaResult<()> {
+    lua.set_named_registry_value(QUALITY_KEY, quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_name_round_trips_names() {
+        for quality in [Quality::Full, Quality::Reduced, Quality::Minimal] {
+            assert_eq!(Quality::from_name(quality.name()), Some(quality));
+        }
+        assert_eq!(Quality::from_name("Full"), None);
+        assert_eq!(Quality::from_name(""), None);
+    }
+
+    #[test]
+    fn blur_sigma_follows_tier() {
+        assert_eq!(Quality::Full.blur_sigma(10.), Some(10.));
+        assert_eq!(Quality::Reduced.blur_sigma(10.), Some(REDUCED_MAX_SIGMA));
+        assert_eq!(Quality::Reduced.blur_sigma(1.), Some(1.));
+        assert_eq!(Quality::Minimal.blur_sigma(1.), None);
+        assert!(Quality::Reduced.anti_alias());
+        assert!(!Quality::Minimal.anti_alias());
+    }
+
+    #[test]
+    fn quality_defaults_to_full_and_is_stored_per_state() {
+        let lua = Lua::new();
+        assert_eq!(quality(&lua).unwrap(), Quality::Full);
+        set_quality(&lua, Quality::Minimal).unwrap();
+        assert_eq!(quality(&lua).unwrap(), Quality::Minimal);
+        assert_eq!(quality(&Lua::new()).unwrap(), Quality::Full);
+    }
+}
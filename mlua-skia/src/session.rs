@@ -0,0 +1,94 @@
+//! Rendering state supplied by the host for the canvas being drawn.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+};
+
+use mlua::Lua;
+use skia_safe::Canvas;
+
+/// Properties of the output canvases are drawn to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSession {
+    /// Number of device pixels per logical pixel.
+    pub scale_factor: f32,
+}
+
+impl Default for CanvasSession {
+    fn default() -> Self {
+        CanvasSession { scale_factor: 1.0 }
+    }
+}
+
+thread_local! {
+    static SESSION: Cell<CanvasSession> = const { Cell::new(CanvasSession { scale_factor: 1.0 }) };
+}
+
+/// Save counts to restore to for each `Canvas:beginFrame` call, per canvas.
+/// Kept as app data of the context the calls were made from.
+#[derive(Default)]
+struct Frames(RefCell<HashMap<usize, Vec<usize>>>);
+
+/// Sets session used by canvases drawn on the current thread.
+///
+/// Non-finite or non-positive scale factors are replaced with `1.0`.
+pub fn set_session(mut session: CanvasSession) {
+    if !session.scale_factor.is_finite() || session.scale_factor <= 0.0 {
+        session.scale_factor = 1.0;
+    }
+    SESSION.with(|it| it.set(session));
+}
+
+#[inline]
+pub fn session() -> CanvasSession {
+    SESSION.with(Cell::get)
+}
+
+#[inline]
+fn key(canvas: &Canvas) -> usize {
+    canvas as *const Canvas as usize
+}
+
+/// Drops frames that were already restored past, either by `restore` calls
+/// or because they belong to a previous canvas at the same address.
+fn drop_restored(frames: &mut Vec<usize>, canvas: &Canvas) {
+    let save_count = canvas.save_count();
+    while matches!(frames.last(), Some(it) if *it >= save_count) {
+        frames.pop();
+    }
+}
+
+/// Saves `canvas` state and records the save count to restore to with
+/// [`pop_frame`] in frames of `lua`.
+pub(crate) fn push_frame(lua: &Lua, canvas: &Canvas) -> usize {
+    if lua.app_data_ref::<Frames>().is_none() {
+        lua.set_app_data(Frames::default());
+    }
+    let frames = lua.app_data_ref::<Frames>().expect("frames were just set");
+    let mut frames = frames.0.borrow_mut();
+    let frames = frames.entry(key(canvas)).or_default();
+    drop_restored(frames, canvas);
+    let save_count = canvas.save();
+    frames.push(save_count);
+    save_count
+}
+
+/// Returns the save count recorded by the innermost [`push_frame`] of
+/// `canvas` that is still in effect.
+pub(crate) fn pop_frame(lua: &Lua, canvas: &Canvas) -> Option<usize> {
+    let frames = lua.app_data_ref::<Frames>()?;
+    let mut frames = frames.0.borrow_mut();
+    let stack = frames.get_mut(&key(canvas))?;
+    drop_restored(stack, canvas);
+    let result = stack.pop();
+    if stack.is_empty() {
+        frames.remove(&key(canvas));
+    }
+    result
+}
+
+/// Forgets frames begun from `lua` that won't be ended anymore.
+pub(crate) fn clear_frames(lua: &Lua) {
+    lua.remove_app_data::<Frames>();
+}
@@ -0,0 +1,279 @@
+//! Live handle statistics, kept separately for each Lua context.
+//!
+//! Wrappers declared with [`wrap_skia_handle`](crate::wrap_skia_handle)
+//! hold a [`Tracked`] token counting them in statistics of the context that
+//! was running a binding when they were created. Pixel memory is counted per
+//! buffer rather than per handle, so a surface and images snapshotted from
+//! it, or several clones of one image, don't count the same pixels twice.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use mlua::{Function as LuaFunction, Lua};
+use skia_safe::{Image, Surface};
+
+/// Registry key under which the budget callback is stored.
+const BUDGET_CALLBACK_KEY: &str = "mlua_skia.budget_callback";
+
+/// Identity of pixel memory shared between handles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PixelKey {
+    /// Address of raster pixels.
+    Address(usize),
+    /// Unique ID of an image that isn't backed by raster pixels, such as a
+    /// lazily decoded one.
+    Image(u32),
+}
+
+/// Pixel memory held by a handle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pixels {
+    pub key: PixelKey,
+    pub bytes: usize,
+}
+
+/// Returns pixel memory of `image`, shared with the surface or image its
+/// pixels come from.
+pub fn image_pixels(image: &Image) -> Option<Pixels> {
+    let bytes = image.image_info().compute_min_byte_size();
+    let key = match image.peek_pixels() {
+        Some(pixmap) => PixelKey::Address(pixmap.addr() as usize),
+        None => PixelKey::Image(image.unique_id()),
+    };
+    Some(Pixels { key, bytes })
+}
+
+/// Returns raster pixel memory of `surface`, or `None` for surfaces without
+/// raster pixels.
+pub fn surface_pixels(surface: &mut Surface) -> Option<Pixels> {
+    let bytes = surface.image_info().compute_min_byte_size();
+    let pixmap = surface.peek_pixels()?;
+    Some(Pixels {
+        key: PixelKey::Address(pixmap.addr() as usize),
+        bytes,
+    })
+}
+
+/// Pixel buffer and types of handles holding it, in order of acquisition.
+struct Buffer {
+    bytes: usize,
+    holders: Vec<&'static str>,
+}
+
+/// Live handle counts and pixel memory of a single Lua context.
+#[derive(Default)]
+pub struct HandleStats {
+    live: RefCell<HashMap<&'static str, usize>>,
+    buffers: RefCell<HashMap<PixelKey, Buffer>>,
+    /// Byte budget set by the script, `0` when disabled.
+    budget: Cell<usize>,
+    /// Whether the current budget overrun was already reported.
+    budget_reported: Cell<bool>,
+}
+
+impl HandleStats {
+    fn acquire(&self, kind: &'static str, pixels: Option<Pixels>) {
+        *self.live.borrow_mut().entry(kind).or_default() += 1;
+        self.hold(kind, pixels);
+    }
+
+    fn release(&self, kind: &'static str, pixels: Option<Pixels>) {
+        if let Some(live) = self.live.borrow_mut().get_mut(kind) {
+            *live = live.saturating_sub(1);
+        }
+        self.unhold(kind, pixels);
+    }
+
+    fn hold(&self, kind: &'static str, pixels: Option<Pixels>) {
+        if let Some(pixels) = pixels {
+            self.buffers
+                .borrow_mut()
+                .entry(pixels.key)
+                .or_insert_with(|| Buffer {
+                    bytes: pixels.bytes,
+                    holders: Vec::new(),
+                })
+                .holders
+                .push(kind);
+        }
+    }
+
+    fn unhold(&self, kind: &'static str, pixels: Option<Pixels>) {
+        if let Some(pixels) = pixels {
+            let mut buffers = self.buffers.borrow_mut();
+            if let Some(buffer) = buffers.get_mut(&pixels.key) {
+                if let Some(index) = buffer.holders.iter().position(|it| *it == kind) {
+                    buffer.holders.remove(index);
+                }
+                if buffer.holders.is_empty() {
+                    buffers.remove(&pixels.key);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of live handles of `kind`.
+    pub fn live(&self, kind: &str) -> usize {
+        self.live.borrow().get(kind).copied().unwrap_or_default()
+    }
+
+    /// Returns bytes of pixel buffers held by handles of `kind`. Buffers
+    /// shared with other types count towards the type that acquired them
+    /// first.
+    pub fn bytes(&self, kind: &str) -> usize {
+        self.buffers
+            .borrow()
+            .values()
+            .filter(|it| it.holders.first().copied() == Some(kind))
+            .map(|it| it.bytes)
+            .sum()
+    }
+
+    /// Returns approximate number of bytes held by all live handles.
+    pub fn total_bytes(&self) -> usize {
+        self.buffers.borrow().values().map(|it| it.bytes).sum()
+    }
+}
+
+thread_local! {
+    /// Statistics of the context whose binding is currently running.
+    static CURRENT: RefCell<Option<Rc<HandleStats>>> = const { RefCell::new(None) };
+}
+
+/// Creates statistics of `lua` if they don't exist yet.
+pub fn install(lua: &Lua) {
+    if lua.app_data_ref::<Rc<HandleStats>>().is_none() {
+        lua.set_app_data(Rc::new(HandleStats::default()));
+    }
+}
+
+/// Returns statistics of `lua`, or `None` if [`setup`](crate::setup) wasn't
+/// called for it.
+pub fn context_stats(lua: &Lua) -> Option<Rc<HandleStats>> {
+    lua.app_data_ref::<Rc<HandleStats>>().map(|it| it.clone())
+}
+
+/// Restores previously entered statistics when dropped.
+pub struct Scope(Option<Rc<HandleStats>>);
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|it| *it.borrow_mut() = previous);
+    }
+}
+
+/// Makes handles wrapped until the returned scope is dropped count towards
+/// statistics of `lua`. Every generated binding enters its context.
+pub fn enter(lua: &Lua) -> Scope {
+    let stats = context_stats(lua);
+    Scope(CURRENT.with(|it| it.replace(stats)))
+}
+
+/// Counts a wrapped handle in statistics of the context it was created in
+/// until dropped.
+pub struct Tracked {
+    stats: Option<Rc<HandleStats>>,
+    kind: &'static str,
+    pixels: Option<Pixels>,
+}
+
+impl Tracked {
+    pub fn new(kind: &'static str, pixels: Option<Pixels>) -> Self {
+        let stats = CURRENT.with(|it| it.borrow().clone());
+        if let Some(stats) = &stats {
+            stats.acquire(kind, pixels);
+        }
+        Tracked {
+            stats,
+            kind,
+            pixels,
+        }
+    }
+
+    /// Replaces counted pixel memory, for handles whose pixels changed.
+    pub fn set_pixels(&mut self, pixels: Option<Pixels>) {
+        if let Some(stats) = &self.stats {
+            stats.unhold(self.kind, self.pixels);
+            stats.hold(self.kind, pixels);
+        }
+        self.pixels = pixels;
+    }
+}
+
+impl Clone for Tracked {
+    fn clone(&self) -> Self {
+        if let Some(stats) = &self.stats {
+            stats.acquire(self.kind, self.pixels);
+        }
+        Tracked {
+            stats: self.stats.clone(),
+            kind: self.kind,
+            pixels: self.pixels,
+        }
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        if let Some(stats) = &self.stats {
+            stats.release(self.kind, self.pixels);
+        }
+    }
+}
+
+/// Implemented by all types declared with
+/// [`wrap_skia_handle`](crate::wrap_skia_handle).
+pub trait TrackedHandle {
+    type Handle;
+
+    /// Name of the wrapped type in `Skia.stats()`.
+    const NAME: &'static str;
+
+    /// Returns pixel memory owned by the handle.
+    fn pixels(handle: &mut Self::Handle) -> Option<Pixels>;
+}
+
+pub fn set_budget(lua: &Lua, bytes: usize, callback: Option<LuaFunction>) -> mlua::Result<()> {
+    if let Some(stats) = context_stats(lua) {
+        stats.budget.set(bytes);
+        stats.budget_reported.set(false);
+    }
+    lua.set_named_registry_value(BUDGET_CALLBACK_KEY, callback)
+}
+
+/// Calls the budget callback if approximate memory use of `lua` exceeds the
+/// budget set by `Skia.onBudgetExceeded`.
+///
+/// The callback is called once per overrun; it will be called again only
+/// after memory use drops below the budget and exceeds it again.
+///
+/// Should be called by the host application between frames.
+pub fn check_budget(lua: &Lua) -> mlua::Result<()> {
+    let stats = match context_stats(lua) {
+        Some(it) => it,
+        None => return Ok(()),
+    };
+    let budget = stats.budget.get();
+    if budget == 0 {
+        return Ok(());
+    }
+
+    let total = stats.total_bytes();
+    if total <= budget {
+        stats.budget_reported.set(false);
+        return Ok(());
+    }
+    if stats.budget_reported.replace(true) {
+        return Ok(());
+    }
+
+    let callback: Option<LuaFunction> = lua.named_registry_value(BUDGET_CALLBACK_KEY)?;
+    match callback {
+        Some(callback) => callback.call((total, budget)),
+        None => Ok(()),
+    }
+}
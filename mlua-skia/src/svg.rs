@@ -0,0 +1,238 @@
+//! SVG document rendering, available with the `svg` feature.
+//!
+//! Only static rendering is supported; documents are parsed once by Skia's
+//! SVG module, which ignores CSS stylesheets and animations.
+
+use mlua::prelude::*;
+use mlua_skia_macros::lua_methods;
+use skia_safe::{svg::Dom, Size};
+
+use crate::{lua::ConstructorResult, LuaCanvas, LuaSize};
+
+/// Container size Skia resolves relative root dimensions against.
+const DEFAULT_CONTAINER_SIZE: Size = Size {
+    width: 100.0,
+    height: 100.0,
+};
+
+/// Parsed SVG document.
+#[derive(Clone)]
+pub struct LuaSvgDom {
+    pub dom: Dom,
+    /// Skia doesn't expose the container size, so it's tracked alongside.
+    container_size: Size,
+}
+
+impl LuaSvgDom {
+    pub fn from_source(source: &str) -> Option<Self> {
+        let mut dom = Dom::from_bytes(source.as_bytes()).ok()?;
+        let container_size = match root_size(source) {
+            (Some(width), Some(height)) => Size::new(width, height),
+            // icons often only specify a viewBox; size the container to it
+            // instead of Skia's default
+            _ => {
+                let size = view_box_size(source).unwrap_or(DEFAULT_CONTAINER_SIZE);
+                dom.set_container_size(size);
+                size
+            }
+        };
+        Some(LuaSvgDom {
+            dom,
+            container_size,
+        })
+    }
+
+    #[inline]
+    pub fn container_size(&self) -> Size {
+        self.container_size
+    }
+}
+
+/// Returns attributes of the root element if it's an `svg` element,
+/// skipping the XML declaration, comments and doctype preceding it.
+fn root_attributes(source: &str) -> Option<&str> {
+    let mut rest = source;
+    loop {
+        rest = &rest[rest.find('<')?..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = &comment[comment.find("-->")? + "-->".len()..];
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = &rest[rest.find('>')? + 1..];
+        } else {
+            break;
+        }
+    }
+    let element = &rest[1..];
+    let name_end = element
+        .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .unwrap_or(element.len());
+    let name = &element[..name_end];
+    if name != "svg" && !name.ends_with(":svg") {
+        return None;
+    }
+    let end = element.find('>')?;
+    Some(&element[name_end..end])
+}
+
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    while let Some(index) = rest.find(name) {
+        let preceded_by_space = rest[..index]
+            .chars()
+            .next_back()
+            .map_or(true, char::is_whitespace);
+        let after = rest[index + name.len()..].trim_start();
+        rest = &rest[index + name.len()..];
+        if !preceded_by_space {
+            continue;
+        }
+        let value = match after.strip_prefix('=') {
+            Some(it) => it.trim_start(),
+            None => continue,
+        };
+        let quote = value.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value = &value[1..];
+        return Some(&value[..value.find(quote)?]);
+    }
+    None
+}
+
+/// Parses absolute lengths in user units (plain numbers or pixels).
+fn parse_length(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let value = value.strip_suffix("px").unwrap_or(value);
+    value.parse::<f32>().ok().filter(|it| *it > 0.0)
+}
+
+/// Returns root `width` and `height` attributes if they are absolute.
+fn root_size(source: &str) -> (Option<f32>, Option<f32>) {
+    let attributes = match root_attributes(source) {
+        Some(it) => it,
+        None => return (None, None),
+    };
+    (
+        attribute(attributes, "width").and_then(parse_length),
+        attribute(attributes, "height").and_then(parse_length),
+    )
+}
+
+fn view_box_size(source: &str) -> Option<Size> {
+    let view_box = attribute(root_attributes(source)?, "viewBox")?;
+    let values: Vec<f32> = view_box
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|it| !it.is_empty())
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+    match values.as_slice() {
+        [_, _, width, height] if *width > 0.0 && *height > 0.0 => Some(Size::new(*width, *height)),
+        _ => None,
+    }
+}
+
+pub struct Svg;
+
+#[lua_methods(lua_name: Svg)]
+impl Svg {
+    /// Loads an SVG document from SVG source, or from a file path if the
+    /// argument doesn't parse as a document.
+    ///
+    /// Skia doesn't report where parsing failed, so errors only include the
+    /// file name.
+    pub fn load<'lua>(lua: &'lua Lua, path_or_data: String) -> LuaSvgDom {
+        if let Some(dom) = LuaSvgDom::from_source(&path_or_data) {
+            return Ok(dom);
+        }
+        // paths can't contain markup, report it as a parse error
+        if path_or_data.trim_start().starts_with('<') {
+            return Err(LuaError::RuntimeError(
+                "Svg.load failed: invalid SVG source".to_string(),
+            ));
+        }
+        let path = crate::fs_policy::check_read(lua, "Svg.load", &path_or_data)?;
+        let source = std::fs::read_to_string(path).map_err(|err| {
+            LuaError::RuntimeError(format!(
+                "Svg.load failed: unable to read '{}': {}",
+                path_or_data, err
+            ))
+        })?;
+        Ok(LuaSvgDom::from_source(&source).or_failed("Svg.load", || {
+            format!("invalid SVG file '{}'", path_or_data)
+        })?)
+    }
+}
+
+#[lua_methods(lua_name: SvgDom)]
+impl LuaSvgDom {
+    pub fn container_size(&self) -> LuaSize {
+        Ok(self.container_size.into())
+    }
+    pub fn set_container_size(&mut self, size: LuaSize) {
+        let size = Size::new(size.width(), size.height());
+        self.dom.set_container_size(size);
+        self.container_size = size;
+        Ok(())
+    }
+    /// Renders the document at its container size into `canvas`.
+    pub fn render(&self, canvas: &LuaCanvas) {
+        canvas.check_valid()?;
+        self.dom.render(canvas.canvas());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attribute_matches_whole_names_only() {
+        let attributes = r#" stroke-width="2" width = '24' data-height="5" height="12px""#;
+        assert_eq!(attribute(attributes, "width"), Some("24"));
+        assert_eq!(attribute(attributes, "height"), Some("12px"));
+        assert_eq!(attribute(attributes, "stroke"), None);
+        assert_eq!(attribute(" width=24", "width"), None);
+        assert_eq!(attribute(r#" width="24"#, "width"), None);
+    }
+
+    #[test]
+    fn root_size_only_reads_absolute_lengths() {
+        let source =
+            r#"<?xml version="1.0"?><svg width="32px" height="50%"><rect width="5"/></svg>"#;
+        assert_eq!(root_size(source), (Some(32.), None));
+        assert_eq!(root_size("<svg>"), (None, None));
+        assert_eq!(root_size("<html></html>"), (None, None));
+    }
+
+    #[test]
+    fn root_attributes_skip_prolog() {
+        let source = r#"<?xml version="1.0"?>
+            <!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "svg11.dtd">
+            <!-- <svg width="1" height="1"> -->
+            <svg width="8" height="6"><rect width="5"/></svg>"#;
+        assert_eq!(root_size(source), (Some(8.), Some(6.)));
+        assert_eq!(
+            root_size(r#"<svg:svg width="4" height="4"/>"#),
+            (Some(4.), Some(4.))
+        );
+        assert_eq!(root_size(r#"<svgx width="3" height="3">"#), (None, None));
+        assert_eq!(
+            root_size(r#"<g><svg width="3" height="3"/></g>"#),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn view_box_size_accepts_separators() {
+        let size = |view_box: &str| view_box_size(&format!(r#"<svg viewBox="{}">"#, view_box));
+        assert_eq!(size("0 0 24 16"), Some(Size::new(24., 16.)));
+        assert_eq!(size(" 0,0, 48,48 "), Some(Size::new(48., 48.)));
+        assert_eq!(size("0 0 0 16"), None);
+        assert_eq!(size("0 0 24"), None);
+        assert_eq!(size("0 0 a 16"), None);
+        assert_eq!(view_box_size("<svg>"), None);
+    }
+}
@@ -0,0 +1,75 @@
+//! Teardown of a Lua context before the host drops it, e.g. to reload a
+//! script.
+//!
+//! Userdata can outlive scripts when Rust code still references it, and
+//! finalizers run while the context is closing. [`teardown`] releases state
+//! this crate keeps for the context and poisons it, so methods called on
+//! surviving userdata fail with an error instead of touching Skia.
+//!
+//! Poisoning and released state are kept per context, so other contexts on
+//! the same thread keep working. A torn down context can't be set up again;
+//! create a new one instead.
+
+use mlua::prelude::*;
+
+use crate::{frame, input_region, quality, stats, theme};
+
+/// App data marking a torn down context.
+struct TornDown;
+
+/// Registry values holding state of a single context.
+const REGISTRY_KEYS: &[&str] = &[
+    frame::FRAME_KEY,
+    input_region::INPUT_REGION_KEY,
+    input_region::INPUT_REGION_CANVAS_KEY,
+    quality::QUALITY_KEY,
+    theme::THEME_STORAGE_KEY,
+    theme::THEME_PROXY_KEY,
+    theme::THEME_CALLBACKS_KEY,
+];
+
+/// Counterpart to [`setup`](crate::setup), to be called before dropping
+/// `lua`.
+///
+/// Clears registry state, the `Theme` global, frames begun on canvases,
+/// damage tracking started by scripts and preset caches, and makes every
+/// subsequent call of bound functions and methods of `lua` fail with a
+/// "context torn down" error.
+pub fn teardown(lua: &Lua) -> LuaResult<()> {
+    lua.set_app_data(TornDown);
+
+    let has_theme = lua
+        .named_registry_value::<Option<LuaTable>>(theme::THEME_PROXY_KEY)?
+        .is_some();
+    if has_theme {
+        lua.globals().set("Theme", LuaNil)?;
+    }
+    for key in REGISTRY_KEYS {
+        lua.unset_named_registry_value(key)?;
+    }
+    stats::set_budget(lua, 0, None)?;
+
+    crate::session::clear_frames(lua);
+    crate::damage::clear_context(lua);
+    // caches hold no context state, but may hold the last references to
+    // handles created by it
+    crate::args::clear_paint_pool();
+    crate::clear_shader_caches();
+
+    // release handles no longer referenced while the context is still intact
+    lua.gc_collect()
+}
+
+pub fn is_torn_down(lua: &Lua) -> bool {
+    lua.app_data_ref::<TornDown>().is_some()
+}
+
+/// Fails if [`teardown`] was called for `lua`. Called by every function
+/// generated with `lua_methods` and other functions bound to Lua.
+#[inline]
+pub fn ensure_alive(lua: &Lua) -> LuaResult<()> {
+    if is_torn_down(lua) {
+        return Err(LuaError::RuntimeError("context torn down".to_string()));
+    }
+    Ok(())
+}
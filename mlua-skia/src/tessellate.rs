@@ -0,0 +1,308 @@
+//! Polygonal approximation and triangulation of paths.
+//!
+//! skia-safe doesn't expose Skia's tessellators, so curves are subdivided
+//! here and the resulting polygons are triangulated by ear clipping. Ear
+//! clipping only handles simple polygons: each contour is triangulated on its
+//! own, so holes are filled and self-intersecting contours produce
+//! overlapping triangles.
+
+use skia_safe::{path, path::Verb, Path, Point};
+
+/// Flattening tolerance used when none is given, in path units.
+pub const DEFAULT_TOLERANCE: f32 = 0.25;
+
+/// Upper bound for segments a single curve is split into.
+const MAX_CURVE_SEGMENTS: f32 = 1024.0;
+
+/// Returns the number of line segments keeping a curve with second
+/// difference magnitude `deviation` within `tolerance` of its chords.
+fn segment_count(deviation: f32, tolerance: f32) -> usize {
+    (deviation / tolerance)
+        .sqrt()
+        .ceil()
+        .clamp(1.0, MAX_CURVE_SEGMENTS) as usize
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    a + (b - a) * t
+}
+
+fn quad_at(p: &[Point], t: f32) -> Point {
+    lerp(lerp(p[0], p[1], t), lerp(p[1], p[2], t), t)
+}
+
+fn conic_at(p: &[Point], w: f32, t: f32) -> Point {
+    let u = 1.0 - t;
+    let (a, b, c) = (u * u, 2.0 * w * t * u, t * t);
+    let denominator = a + b + c;
+    Point::new(
+        (p[0].x * a + p[1].x * b + p[2].x * c) / denominator,
+        (p[0].y * a + p[1].y * b + p[2].y * c) / denominator,
+    )
+}
+
+fn cubic_at(p: &[Point], t: f32) -> Point {
+    let a = lerp(p[0], p[1], t);
+    let b = lerp(p[1], p[2], t);
+    let c = lerp(p[2], p[3], t);
+    lerp(lerp(a, b, t), lerp(b, c, t), t)
+}
+
+/// Appends `count` evenly spaced points of a curve, excluding its start.
+fn subdivide(current: &mut Vec<Point>, count: usize, at: impl Fn(f32) -> Point) {
+    for i in 1..=count {
+        current.push(at(i as f32 / count as f32));
+    }
+}
+
+fn second_difference(a: Point, b: Point, c: Point) -> f32 {
+    (a - b * 2.0 + c).length()
+}
+
+/// Polygons approximating contours of a path, each flagged as closed or not.
+pub fn flatten_contours(path: &Path, tolerance: f32) -> Vec<(Vec<Point>, bool)> {
+    let mut contours = Vec::new();
+    let mut current: Vec<Point> = Vec::new();
+    let mut iter = path::Iter::new(path, false);
+
+    while let Some((verb, p)) = iter.next() {
+        match verb {
+            Verb::Move => {
+                if current.len() > 1 {
+                    contours.push((std::mem::take(&mut current), false));
+                }
+                current.clear();
+                current.push(p[0]);
+            }
+            Verb::Line => current.push(p[1]),
+            Verb::Quad => {
+                // chord error of n segments is at most |p0 - 2p1 + p2| / (4n²)
+                let count = segment_count(second_difference(p[0], p[1], p[2]) / 4.0, tolerance);
+                subdivide(&mut current, count, |t| quad_at(&p, t));
+            }
+            Verb::Conic => {
+                let w = iter.conic_weight().unwrap_or(1.0);
+                let deviation = second_difference(p[0], p[1], p[2]) * w.max(1.0) / 4.0;
+                let count = segment_count(deviation, tolerance);
+                subdivide(&mut current, count, |t| conic_at(&p, w, t));
+            }
+            Verb::Cubic => {
+                // chord error is at most 3 * max|second difference| / (4n²)
+                let deviation = second_difference(p[0], p[1], p[2])
+                    .max(second_difference(p[1], p[2], p[3]))
+                    * 3.0
+                    / 4.0;
+                let count = segment_count(deviation, tolerance);
+                subdivide(&mut current, count, |t| cubic_at(&p, t));
+            }
+            Verb::Close => {
+                // the closing line ends at the first point, which is implied
+                if current.len() > 1 && current.first() == current.last() {
+                    current.pop();
+                }
+                if current.len() > 1 {
+                    let start = current[0];
+                    contours.push((std::mem::take(&mut current), true));
+                    current.push(start);
+                }
+            }
+            _ => {}
+        }
+    }
+    if current.len() > 1 {
+        contours.push((current, false));
+    }
+    contours
+}
+
+/// Returns a path with curves of `path` replaced by line segments within
+/// `tolerance` of them.
+pub fn flatten(path: &Path, tolerance: f32) -> Path {
+    let mut result = Path::new();
+    result.set_fill_type(path.fill_type());
+    for (points, closed) in flatten_contours(path, tolerance) {
+        result.add_poly(&points, closed);
+    }
+    result
+}
+
+fn signed_area(points: &[Point]) -> f32 {
+    let mut area = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a - o).cross(b - o)
+}
+
+fn contains(a: Point, b: Point, c: Point, p: Point) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// Triangulates a simple polygon, appending indices offset by `base` into
+/// `indices`.
+fn ear_clip(points: &[Point], base: u32, indices: &mut Vec<u32>) {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    // ears are found by convexity, which requires counter-clockwise order
+    if signed_area(points) < 0.0 {
+        remaining.reverse();
+    }
+
+    let mut misses = 0;
+    let mut i = 0;
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let (prev, current, next) = (
+            remaining[(i + count - 1) % count],
+            remaining[i % count],
+            remaining[(i + 1) % count],
+        );
+        let (a, b, c) = (points[prev], points[current], points[next]);
+        let is_ear = cross(a, b, c) > 0.0
+            && !remaining.iter().any(|&other| {
+                other != prev
+                    && other != current
+                    && other != next
+                    && contains(a, b, c, points[other])
+            });
+
+        if is_ear {
+            indices.extend([prev, current, next].map(|it| base + it as u32));
+            remaining.remove(i % count);
+            misses = 0;
+        } else {
+            misses += 1;
+            i += 1;
+            // no ear left means the polygon isn't simple; fall back to a fan
+            if misses > count {
+                break;
+            }
+        }
+        i %= remaining.len();
+    }
+
+    let first = remaining[0];
+    for pair in remaining[1..].windows(2) {
+        indices.extend([first, pair[0], pair[1]].map(|it| base + it as u32));
+    }
+}
+
+/// Triangulates the area of `path` after flattening it with `tolerance`.
+///
+/// Returns vertex positions and indices of triangles into them.
+pub fn triangulate(path: &Path, tolerance: f32) -> (Vec<Point>, Vec<u32>) {
+    let mut positions = Vec::new();
+    let mut indices = Vec::new();
+    for (mut points, _) in flatten_contours(path, tolerance) {
+        points.dedup();
+        if points.len() < 3 {
+            continue;
+        }
+        let base = positions.len() as u32;
+        ear_clip(&points, base, &mut indices);
+        positions.extend(points);
+    }
+    (positions, indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skia_safe::Rect;
+
+    fn triangle_area(positions: &[Point], indices: &[u32]) -> f32 {
+        indices
+            .chunks(3)
+            .map(|it| {
+                let [a, b, c] = [0, 1, 2].map(|i| positions[it[i] as usize]);
+                cross(a, b, c).abs() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn flattened_circles_stay_within_tolerance() {
+        let circle = Path::circle((50.0, 50.0), 40.0, None);
+        let contours = flatten_contours(&circle, 0.25);
+        assert_eq!(contours.len(), 1);
+        let (points, closed) = &contours[0];
+        assert!(closed);
+        assert!(points.len() > 8);
+        let center = Point::new(50.0, 50.0);
+        for (i, a) in points.iter().enumerate() {
+            let b = points[(i + 1) % points.len()];
+            assert!(((*a - center).length() - 40.0).abs() < 1e-3);
+            let midpoint = lerp(*a, b, 0.5);
+            assert!(40.0 - (midpoint - center).length() <= 0.25);
+        }
+        // coarser tolerance needs fewer points
+        assert!(flatten_contours(&circle, 4.0)[0].0.len() < points.len());
+    }
+
+    #[test]
+    fn flatten_contours_drops_implied_closing_point() {
+        let rect = Path::rect(Rect::from_xywh(0.0, 0.0, 10.0, 20.0), None);
+        let contours = flatten_contours(&rect, DEFAULT_TOLERANCE);
+        assert_eq!(contours.len(), 1);
+        assert_eq!(contours[0].0.len(), 4);
+        assert!(contours[0].1);
+
+        let mut open = Path::new();
+        open.move_to((0.0, 0.0)).line_to((10.0, 0.0));
+        open.move_to((0.0, 5.0)).quad_to((5.0, 10.0), (10.0, 5.0));
+        let contours = flatten_contours(&open, DEFAULT_TOLERANCE);
+        assert_eq!(contours.len(), 2);
+        assert!(contours.iter().all(|(_, closed)| !closed));
+        assert_eq!(
+            contours[0].0,
+            vec![Point::new(0.0, 0.0), Point::new(10.0, 0.0)]
+        );
+        assert_eq!(contours[1].0.last(), Some(&Point::new(10.0, 5.0)));
+    }
+
+    #[test]
+    fn ear_clip_covers_concave_polygons_once() {
+        // L shape, in both orientations
+        let mut points = vec![
+            Point::new(0.0, 0.0),
+            Point::new(20.0, 0.0),
+            Point::new(20.0, 10.0),
+            Point::new(10.0, 10.0),
+            Point::new(10.0, 20.0),
+            Point::new(0.0, 20.0),
+        ];
+        for _ in 0..2 {
+            let mut indices = Vec::new();
+            ear_clip(&points, 3, &mut indices);
+            assert_eq!(indices.len(), 4 * 3);
+            assert!(indices.iter().all(|it| (3..9).contains(it)));
+            let local: Vec<u32> = indices.iter().map(|it| it - 3).collect();
+            assert!((triangle_area(&points, &local) - 300.0).abs() < 1e-3);
+            points.reverse();
+        }
+    }
+
+    #[test]
+    fn circle_triangles_index_their_positions() {
+        let circle = Path::circle((50.0, 50.0), 40.0, None);
+        let (positions, indices) = triangulate(&circle, DEFAULT_TOLERANCE);
+        assert!(!indices.is_empty());
+        assert_eq!(indices.len() % 3, 0);
+        assert_eq!(indices.len() / 3, positions.len() - 2);
+        assert!(indices.iter().all(|it| (*it as usize) < positions.len()));
+        let area = triangle_area(&positions, &indices);
+        assert!((area - std::f32::consts::PI * 1600.0).abs() < 50.0);
+
+        // each contour is triangulated separately
+        let mut path = Path::rect(Rect::from_xywh(0.0, 0.0, 10.0, 10.0), None);
+        path.add_rect(Rect::from_xywh(20.0, 0.0, 10.0, 10.0), None);
+        let (positions, indices) = triangulate(&path, DEFAULT_TOLERANCE);
+        assert_eq!(positions.len(), 8);
+        assert_eq!(indices.len(), 4 * 3);
+        assert!(indices[6..].iter().all(|it| *it >= 4));
+    }
+}
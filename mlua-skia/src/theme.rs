@@ -0,0 +1,356 @@
+//! Host provided color theme, exposed to Lua as a read-only `Theme` table.
+//!
+//! Paints and shaders in the table are created once and updated in place by
+//! [`update_theme`], so references held by scripts always reflect the current
+//! theme. Paints are frozen so scripts can't change them for other widgets;
+//! `Paint:copy()` returns a mutable copy.
+
+use mlua::prelude::*;
+use skia_safe::{paint::Style as PaintStyle, Color4f, ColorSpace, Paint, Shader, TileMode};
+
+use crate::{
+    help::{MethodHelp, ParamHelp, TypeHelp},
+    teardown::ensure_alive,
+    FrozenPaint, LuaColor, LuaPaint, LuaShader,
+};
+
+/// Registry key of the table `Theme` global proxies.
+pub(crate) const THEME_STORAGE_KEY: &str = "mlua_skia.theme";
+/// Registry key of the `Theme` proxy passed to change callbacks.
+pub(crate) const THEME_PROXY_KEY: &str = "mlua_skia.theme_proxy";
+/// Registry key of the array of `Theme.onChange` callbacks.
+pub(crate) const THEME_CALLBACKS_KEY: &str = "mlua_skia.theme_callbacks";
+/// Theme entries that aren't colors, kept across updates.
+const HANDLES: [&str; 4] = ["accentFill", "subtleBorder", "panelGradient", "onChange"];
+
+/// Documentation of the `Theme` global; custom colors of [`ThemeSpec`]
+/// aren't listed.
+pub const LUA_HELP: TypeHelp = TypeHelp::table(
+    "Theme",
+    &[
+        MethodHelp::value("accent", "Theme.accent: Color", "Color", "Accent color."),
+        MethodHelp::value(
+            "background",
+            "Theme.background: Color",
+            "Color",
+            "Window background color.",
+        ),
+        MethodHelp::value(
+            "surface",
+            "Theme.surface: Color",
+            "Color",
+            "Color of panels drawn over the background.",
+        ),
+        MethodHelp::value("border", "Theme.border: Color", "Color", "Border color."),
+        MethodHelp::value("text", "Theme.text: Color", "Color", "Text color."),
+        MethodHelp::value(
+            "accentFill",
+            "Theme.accentFill: Paint",
+            "Paint",
+            "Frozen anti-aliased fill paint of the accent color.",
+        ),
+        MethodHelp::value(
+            "subtleBorder",
+            "Theme.subtleBorder: Paint",
+            "Paint",
+            "Frozen 1px stroke paint of the border color.",
+        ),
+        MethodHelp::value(
+            "panelGradient",
+            "Theme.panelGradient: Shader",
+            "Shader",
+            "Vertical surface to background gradient spanning unit height.",
+        ),
+        MethodHelp::function(
+            "onChange",
+            "Theme.onChange(callback: function)",
+            &[ParamHelp {
+                name: "callback",
+                ty: "function",
+            }],
+            None,
+            "Registers `callback` called with the `Theme` table whenever the host \
+             updates the theme.",
+        ),
+    ],
+);
+
+/// Palette provided by the host application.
+#[derive(Clone)]
+pub struct ThemeSpec {
+    pub accent: Color4f,
+    pub background: Color4f,
+    pub surface: Color4f,
+    pub border: Color4f,
+    pub text: Color4f,
+    /// Additional named colors exposed alongside the standard ones.
+    pub colors: Vec<(String, Color4f)>,
+}
+
+impl Default for ThemeSpec {
+    fn default() -> Self {
+        ThemeSpec {
+            accent: Color4f::new(0.26, 0.52, 0.96, 1.0),
+            background: Color4f::new(0.08, 0.08, 0.1, 1.0),
+            surface: Color4f::new(0.14, 0.14, 0.17, 1.0),
+            border: Color4f::new(1.0, 1.0, 1.0, 0.12),
+            text: Color4f::new(0.92, 0.92, 0.94, 1.0),
+            colors: Vec::new(),
+        }
+    }
+}
+
+impl ThemeSpec {
+    fn accent_fill(&self) -> Paint {
+        let mut paint = Paint::new(self.accent, None);
+        paint.set_anti_alias(true);
+        paint
+    }
+
+    fn subtle_border(&self) -> Paint {
+        let mut paint = Paint::new(self.border, None);
+        paint.set_anti_alias(true);
+        paint.set_style(PaintStyle::Stroke);
+        paint.set_stroke_width(1.0);
+        paint
+    }
+
+    /// Vertical surface to background gradient spanning unit height; scripts
+    /// scale it to panel bounds with `Shader:makeWithLocalMatrix`.
+    fn panel_gradient(&self) -> LuaResult<Shader> {
+        let colors = [self.surface, self.background];
+        Shader::linear_gradient(
+            ((0.0, 0.0), (0.0, 1.0)),
+            (&colors[..], None::<ColorSpace>),
+            None,
+            TileMode::Clamp,
+            None,
+            None,
+        )
+        .ok_or_else(|| LuaError::RuntimeError("unable to create theme panel gradient".to_string()))
+    }
+
+    fn write_colors(&self, storage: &LuaTable) -> LuaResult<()> {
+        storage.set("accent", LuaColor::from(self.accent))?;
+        storage.set("background", LuaColor::from(self.background))?;
+        storage.set("surface", LuaColor::from(self.surface))?;
+        storage.set("border", LuaColor::from(self.border))?;
+        storage.set("text", LuaColor::from(self.text))?;
+        for (name, color) in &self.colors {
+            storage.set(name.as_str(), LuaColor::from(*color))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns theme storage registered by [`setup_with_theme`].
+fn theme_storage(lua: &Lua) -> LuaResult<LuaTable> {
+    let storage: Option<LuaTable> = lua.named_registry_value(THEME_STORAGE_KEY)?;
+    storage.ok_or_else(|| {
+        LuaError::RuntimeError("theme wasn't registered with setup_with_theme".to_string())
+    })
+}
+
+/// Builds storage holding colors of `theme` and the shared `handles`.
+fn build_storage<'lua>(
+    lua: &'lua Lua,
+    theme: &ThemeSpec,
+    handles: impl IntoIterator<Item = (&'static str, LuaValue<'lua>)>,
+) -> LuaResult<LuaTable<'lua>> {
+    let storage = lua.create_table()?;
+    theme.write_colors(&storage)?;
+    for (name, value) in handles {
+        storage.set(name, value)?;
+    }
+    Ok(storage)
+}
+
+/// Calls [`setup`](crate::setup) and registers the `Theme` global built from
+/// `theme`.
+pub fn setup_with_theme(lua: &Lua, theme: ThemeSpec) -> LuaResult<()> {
+    crate::setup(lua)?;
+    let _stats = crate::stats::enter(lua);
+
+    let handles = [
+        (
+            "accentFill",
+            LuaPaint::new(theme.accent_fill())
+                .with_meta(FrozenPaint)
+                .into_lua(lua)?,
+        ),
+        (
+            "subtleBorder",
+            LuaPaint::new(theme.subtle_border())
+                .with_meta(FrozenPaint)
+                .into_lua(lua)?,
+        ),
+        (
+            "panelGradient",
+            LuaShader::new(theme.panel_gradient()?).into_lua(lua)?,
+        ),
+        (
+            "onChange",
+            LuaValue::Function(lua.create_function(|lua, callback: LuaFunction| {
+                ensure_alive(lua)?;
+                let callbacks: LuaTable = lua.named_registry_value(THEME_CALLBACKS_KEY)?;
+                callbacks.raw_push(callback)
+            })?),
+        ),
+    ];
+    let storage = build_storage(lua, &theme, handles)?;
+
+    let meta = lua.create_table()?;
+    meta.set(
+        "__index",
+        lua.create_function(|lua, (_, key): (LuaValue, LuaValue)| {
+            ensure_alive(lua)?;
+            theme_storage(lua)?.get::<_, LuaValue>(key)
+        })?,
+    )?;
+    meta.set(
+        "__newindex",
+        lua.create_function(|lua, (_, key): (LuaValue, LuaValue)| -> LuaResult<()> {
+            ensure_alive(lua)?;
+            Err(LuaError::RuntimeError(format!(
+                "Theme is read-only; can't assign '{}'",
+                key.to_string()?
+            )))
+        })?,
+    )?;
+    meta.set("__metatable", false)?;
+    let proxy = lua.create_table()?;
+    proxy.set_metatable(Some(meta));
+
+    lua.set_named_registry_value(THEME_STORAGE_KEY, storage)?;
+    lua.set_named_registry_value(THEME_PROXY_KEY, proxy.clone())?;
+    lua.set_named_registry_value(THEME_CALLBACKS_KEY, lua.create_table()?)?;
+    lua.globals().set("Theme", proxy)
+}
+
+/// Replaces theme registered by [`setup_with_theme`] and invokes
+/// `Theme.onChange` callbacks with the `Theme` table.
+///
+/// Storage is rebuilt from `theme`, so custom colors it doesn't define are
+/// removed.
+pub fn update_theme(lua: &Lua, theme: ThemeSpec) -> LuaResult<()> {
+    let _stats = crate::stats::enter(lua);
+    let old = theme_storage(lua)?;
+
+    // swap handles in place so existing references see the new theme
+    old.get::<_, LuaAnyUserData>("accentFill")?
+        .borrow_mut::<LuaPaint>()?
+        .0 = theme.accent_fill();
+    old.get::<_, LuaAnyUserData>("subtleBorder")?
+        .borrow_mut::<LuaPaint>()?
+        .0 = theme.subtle_border();
+    old.get::<_, LuaAnyUserData>("panelGradient")?
+        .borrow_mut::<LuaShader>()?
+        .0 = theme.panel_gradient()?;
+
+    let mut handles = Vec::with_capacity(HANDLES.len());
+    for name in HANDLES {
+        handles.push((name, old.get::<_, LuaValue>(name)?));
+    }
+    let storage = build_storage(lua, &theme, handles)?;
+    lua.set_named_registry_value(THEME_STORAGE_KEY, storage)?;
+
+    let proxy: LuaTable = lua.named_registry_value(THEME_PROXY_KEY)?;
+    let callbacks: LuaTable = lua.named_registry_value(THEME_CALLBACKS_KEY)?;
+    for callback in callbacks.sequence_values::<LuaFunction>() {
+        callback?.call::<_, ()>(proxy.clone())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_swaps_values_behind_held_references() {
+        let lua = Lua::new();
+        setup_with_theme(&lua, ThemeSpec::default()).unwrap();
+        lua.load(
+            r#"
+            gradient = Theme.panelGradient
+            fill = Theme.accentFill
+            changes = 0
+            Theme.onChange(function(theme)
+                changes = changes + 1
+                assert(theme == Theme, "callback should receive the Theme table")
+            end)
+            assert(not pcall(function() Theme.accent = {1, 0, 0, 1} end), "Theme should be read-only")
+        "#,
+        )
+        .exec()
+        .unwrap();
+
+        let theme = ThemeSpec {
+            accent: Color4f::new(1.0, 0.0, 0.0, 1.0),
+            colors: vec![("warning".to_string(), Color4f::new(1.0, 0.5, 0.0, 1.0))],
+            ..ThemeSpec::default()
+        };
+        update_theme(&lua, theme).unwrap();
+
+        lua.load(
+            r#"
+            assert(changes == 1, ("onChange called %d times"):format(changes))
+            assert(rawequal(gradient, Theme.panelGradient), "shader userdata was replaced")
+            assert(rawequal(fill, Theme.accentFill), "paint userdata was replaced")
+            local color = fill:getColor()
+            assert(color.r == 1 and color.g == 0 and color.b == 0, "theme paint wasn't updated")
+            assert(fill:isFrozen(), "updated theme paint isn't frozen")
+            assert(Theme.accent.r == 1 and Theme.accent.g == 0, "accent color wasn't updated")
+            assert(Theme.warning.g == 0.5, "custom colors aren't exposed")
+        "#,
+        )
+        .exec()
+        .unwrap();
+
+        update_theme(&lua, ThemeSpec::default()).unwrap();
+        lua.load(r#"assert(Theme.warning == nil, "removed custom color is still exposed")"#)
+            .exec()
+            .unwrap();
+    }
+
+    #[test]
+    fn theme_paints_are_frozen() {
+        let lua = Lua::new();
+        setup_with_theme(&lua, ThemeSpec::default()).unwrap();
+        lua.load(
+            r#"
+            local fill = Theme.accentFill
+            assert(not pcall(fill.setColor, fill, {0, 1, 0, 1}), "theme paint can be changed")
+            assert(not pcall(fill.setStrokeWidth, Theme.subtleBorder, 8))
+
+            local copy = fill:copy()
+            copy:setColor({0, 1, 0, 1})
+            assert(copy:getColor().g == 1)
+            assert(Theme.accentFill:getColor().g ~= 1, "paint change leaked into the theme")
+        "#,
+        )
+        .exec()
+        .unwrap();
+    }
+
+    #[test]
+    fn held_theme_fails_after_teardown() {
+        let lua = Lua::new();
+        setup_with_theme(&lua, ThemeSpec::default()).unwrap();
+        lua.load("theme = Theme; onChange = Theme.onChange")
+            .exec()
+            .unwrap();
+        crate::teardown(&lua).unwrap();
+        for script in ["return theme.accent", "onChange(print)"] {
+            let err = lua.load(script).exec().unwrap_err().to_string();
+            assert!(err.contains("context torn down"), "{}: {}", script, err);
+        }
+    }
+
+    #[test]
+    fn update_requires_registered_theme() {
+        let lua = Lua::new();
+        crate::setup(&lua).unwrap();
+        assert!(update_theme(&lua, ThemeSpec::default()).is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Large surfaces split into fixed size tiles.
+//!
+//! Draw calls are recorded into a picture which is replayed into the tiles
+//! they hit when the surface is flushed. Tiles are only allocated once
+//! something is rendered into them, and resizing keeps tiles that are still in
+//! bounds.
+
+use std::cell::RefCell;
+
+use skia_safe::{
+    surfaces, Canvas, Color, IPoint, IRect, ISize, Image, Paint, Picture, PictureRecorder, Rect,
+    Surface,
+};
+use thiserror::Error;
+
+use crate::util::draw_device_bounds;
+
+/// Tile size used when none is requested.
+pub const DEFAULT_TILE_SIZE: i32 = 512;
+
+struct TileGrid {
+    size: ISize,
+    tile_size: i32,
+    columns: i32,
+    rows: i32,
+    tiles: Vec<Option<Surface>>,
+    /// Tiles cleared on the next flush.
+    dirty: Vec<bool>,
+    /// Tiles hit by draw calls recorded since the last flush.
+    drawn: Vec<bool>,
+}
+
+impl TileGrid {
+    fn new(size: ISize, tile_size: i32) -> Self {
+        let columns = (size.width + tile_size - 1) / tile_size;
+        let rows = (size.height + tile_size - 1) / tile_size;
+        let count = (columns * rows) as usize;
+        TileGrid {
+            size,
+            tile_size,
+            columns,
+            rows,
+            tiles: (0..count).map(|_| None).collect(),
+            dirty: vec![false; count],
+            drawn: vec![false; count],
+        }
+    }
+
+    fn index(&self, ix: i32, iy: i32) -> Option<usize> {
+        if ix < 0 || iy < 0 || ix >= self.columns || iy >= self.rows {
+            return None;
+        }
+        Some((iy * self.columns + ix) as usize)
+    }
+
+    fn tile_bounds(&self, ix: i32, iy: i32) -> IRect {
+        IRect::from_xywh(
+            ix * self.tile_size,
+            iy * self.tile_size,
+            self.tile_size,
+            self.tile_size,
+        )
+    }
+
+    /// Returns the range of tiles intersecting `rect` as
+    /// `(first_x, first_y, last_x, last_y)`, inclusive.
+    fn tile_range(&self, rect: &Rect) -> Option<(i32, i32, i32, i32)> {
+        let bounds = Rect::from_isize(self.size);
+        let mut rect = *rect;
+        if !rect.intersect(bounds) {
+            return None;
+        }
+        let tile = self.tile_size as f32;
+        let last = |value: f32, count: i32| ((value / tile).ceil() as i32 - 1).clamp(0, count - 1);
+        Some((
+            ((rect.left / tile).floor() as i32).clamp(0, self.columns - 1),
+            ((rect.top / tile).floor() as i32).clamp(0, self.rows - 1),
+            last(rect.right, self.columns),
+            last(rect.bottom, self.rows),
+        ))
+    }
+
+    /// Returns indices of tiles intersecting `rect`.
+    fn indices(&self, rect: &Rect) -> Vec<usize> {
+        let mut result = Vec::new();
+        if let Some((x0, y0, x1, y1)) = self.tile_range(rect) {
+            for iy in y0..=y1 {
+                for ix in x0..=x1 {
+                    result.push((iy * self.columns + ix) as usize);
+                }
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TiledError {
+    #[error("tiled surface can't be drawn into its own canvas")]
+    OwnCanvas,
+    #[error("tiled surface is already being flushed")]
+    Busy,
+}
+
+/// Surface split into tiles which presents a single recording canvas.
+pub struct TiledSurface {
+    recorder: RefCell<PictureRecorder>,
+    grid: RefCell<TileGrid>,
+}
+
+impl TiledSurface {
+    pub fn new(size: ISize, tile_size: Option<i32>) -> Option<Self> {
+        let tile_size = tile_size.unwrap_or(DEFAULT_TILE_SIZE);
+        if size.width <= 0 || size.height <= 0 || tile_size <= 0 {
+            return None;
+        }
+        let result = TiledSurface {
+            recorder: RefCell::new(PictureRecorder::new()),
+            grid: RefCell::new(TileGrid::new(size, tile_size)),
+        };
+        result
+            .recorder
+            .borrow_mut()
+            .begin_recording(Rect::from_isize(size), None);
+        Some(result)
+    }
+
+    /// Canvas recording draw calls until the next [`flush`](Self::flush).
+    pub fn canvas(&self) -> &Canvas {
+        let mut recorder = self.recorder.borrow_mut();
+        let canvas: *const Canvas = recorder
+            .recording_canvas()
+            .expect("tiled surface is always recording");
+        // SAFETY: The recording canvas is allocated by the native recorder
+        // instead of being stored in `self.recorder`, and stays alive until
+        // the recording is finished. Only `flush` and `resize` finish it,
+        // and `draw_to` refuses to flush into this canvas, so it outlives
+        // any binding call drawing into it.
+        unsafe { &*canvas }
+    }
+
+    /// Returns whether `canvas` is the recording canvas of this surface.
+    fn is_own_canvas(&self, canvas: &Canvas) -> Result<bool, TiledError> {
+        let mut recorder = self
+            .recorder
+            .try_borrow_mut()
+            .map_err(|_| TiledError::Busy)?;
+        Ok(recorder
+            .recording_canvas()
+            .map_or(false, |it| std::ptr::eq(it, canvas)))
+    }
+
+    /// Marks tiles hit by drawing local `bounds` (whole clip if `None`) of
+    /// the recording canvas with `paint` to be redrawn on the next flush.
+    pub fn mark_drawn(&self, bounds: Option<Rect>, paint: Option<&Paint>) {
+        let device = match draw_device_bounds(self.canvas(), bounds, paint) {
+            Some(it) => Rect::from(it),
+            None => return,
+        };
+        let mut grid = self.grid.borrow_mut();
+        for index in grid.indices(&device) {
+            grid.drawn[index] = true;
+        }
+    }
+
+    pub fn size(&self) -> ISize {
+        self.grid.borrow().size
+    }
+
+    pub fn tile_size(&self) -> i32 {
+        self.grid.borrow().tile_size
+    }
+
+    /// Returns number of tile columns and rows.
+    pub fn tile_count(&self) -> (i32, i32) {
+        let grid = self.grid.borrow();
+        (grid.columns, grid.rows)
+    }
+
+    /// Number of tiles that currently hold pixel memory.
+    pub fn allocated_tiles(&self) -> usize {
+        self.grid.borrow().tiles.iter().flatten().count()
+    }
+
+    /// Marks tiles intersecting `rect` (all tiles if `None`) to be cleared
+    /// on the next flush, before draw calls recorded since the last flush
+    /// are replayed into them.
+    pub fn mark_dirty(&self, rect: Option<Rect>) {
+        let mut grid = self.grid.borrow_mut();
+        match rect {
+            Some(rect) => {
+                for index in grid.indices(&rect) {
+                    grid.dirty[index] = true;
+                }
+            }
+            None => grid.dirty.fill(true),
+        }
+    }
+
+    /// Finishes recording and starts a new one covering the surface.
+    fn take_picture(&self) -> Result<Option<Picture>, TiledError> {
+        let mut recorder = self
+            .recorder
+            .try_borrow_mut()
+            .map_err(|_| TiledError::Busy)?;
+        let picture = recorder.finish_recording_as_picture(None);
+        recorder.begin_recording(Rect::from_isize(self.grid.borrow().size), None);
+        Ok(picture)
+    }
+
+    /// Replays draw calls recorded since the last flush into tiles they hit,
+    /// after clearing tiles marked dirty. Returns number of redrawn tiles.
+    ///
+    /// Dirty tiles that weren't drawn into are released.
+    pub fn flush(&self) -> Result<usize, TiledError> {
+        let picture = self.take_picture()?;
+
+        let mut grid = self.grid.borrow_mut();
+        let tile_size = grid.tile_size;
+        let mut redrawn = 0;
+        for iy in 0..grid.rows {
+            for ix in 0..grid.columns {
+                let index = (iy * grid.columns + ix) as usize;
+                let dirty = std::mem::take(&mut grid.dirty[index]);
+                let drawn = std::mem::take(&mut grid.drawn[index]) && picture.is_some();
+                if !drawn {
+                    if dirty {
+                        grid.tiles[index] = None;
+                    }
+                    continue;
+                }
+                let bounds = grid.tile_bounds(ix, iy);
+                if grid.tiles[index].is_none() {
+                    grid.tiles[index] = surfaces::raster_n32_premul((tile_size, tile_size));
+                }
+                let (tile, picture) = match (&mut grid.tiles[index], &picture) {
+                    (Some(tile), Some(picture)) => (tile, picture),
+                    _ => continue,
+                };
+                let canvas = tile.canvas();
+                if dirty {
+                    canvas.clear(Color::TRANSPARENT);
+                }
+                canvas.save();
+                canvas.translate((-bounds.left as f32, -bounds.top as f32));
+                picture.playback(canvas);
+                canvas.restore();
+                redrawn += 1;
+            }
+        }
+        Ok(redrawn)
+    }
+
+    /// Returns contents of tile at column `ix` and row `iy`, or `None` if
+    /// it's out of bounds or was never drawn.
+    pub fn snapshot_tile(&self, ix: i32, iy: i32) -> Result<Option<Image>, TiledError> {
+        self.flush()?;
+        let mut grid = self.grid.borrow_mut();
+        let index = match grid.index(ix, iy) {
+            Some(it) => it,
+            None => return Ok(None),
+        };
+        Ok(grid.tiles[index].as_mut().map(|it| it.image_snapshot()))
+    }
+
+    /// Flushes pending draws and composes tiles onto `canvas` at `offset`.
+    ///
+    /// Fails if `canvas` is the recording canvas of this surface.
+    pub fn draw_to(
+        &self,
+        canvas: &Canvas,
+        offset: IPoint,
+        paint: Option<&Paint>,
+    ) -> Result<(), TiledError> {
+        if self.is_own_canvas(canvas)? {
+            return Err(TiledError::OwnCanvas);
+        }
+        self.flush()?;
+        let mut grid = self.grid.borrow_mut();
+        let columns = grid.columns;
+        let tile_size = grid.tile_size;
+        // edge tiles extend past surface bounds
+        canvas.save();
+        canvas.clip_irect(IRect::from_pt_size(offset, grid.size), None);
+        for (index, tile) in grid.tiles.iter_mut().enumerate() {
+            if let Some(tile) = tile {
+                let (ix, iy) = (index as i32 % columns, index as i32 / columns);
+                let position = (
+                    (offset.x + ix * tile_size) as f32,
+                    (offset.y + iy * tile_size) as f32,
+                );
+                tile.draw(canvas, position, Default::default(), paint);
+            }
+        }
+        canvas.restore();
+        Ok(())
+    }
+
+    /// Changes the surface size, keeping tiles that remain in bounds.
+    ///
+    /// Pending draw calls are flushed before resizing.
+    pub fn resize(&self, size: ISize) -> Result<(), TiledError> {
+        if size.width <= 0 || size.height <= 0 {
+            return Ok(());
+        }
+        self.flush()?;
+        {
+            let mut grid = self.grid.borrow_mut();
+            let mut resized = TileGrid::new(size, grid.tile_size);
+            for iy in 0..resized.rows.min(grid.rows) {
+                for ix in 0..resized.columns.min(grid.columns) {
+                    let (from, to) = (iy * grid.columns + ix, iy * resized.columns + ix);
+                    resized.tiles[to as usize] = grid.tiles[from as usize].take();
+                    resized.dirty[to as usize] = grid.dirty[from as usize];
+                }
+            }
+            *grid = resized;
+        }
+
+        // restart recording with new bounds
+        self.take_picture()?;
+        Ok(())
+    }
+}
@@ -1,3 +1,9 @@
+use skia_safe::{
+    paint::{Join, Style as PaintStyle},
+    Canvas, IRect, Paint, Rect, RoundOut,
+};
+use unicode_segmentation::UnicodeSegmentation;
+
 /// Takes in a HSL color and converts it into sRGB.
 ///
 /// Expected input ranges are:
@@ -59,3 +65,216 @@ impl OptionStrOwned for Option<&str> {
         self.map(|it| it.to_string())
     }
 }
+
+/// Returns `text` truncated with `ellipsis` appended so that the result
+/// `fits`, or unchanged `text` if it already does.
+///
+/// Text is only cut at grapheme cluster boundaries, so combining characters
+/// and emoji sequences are kept whole. Returns an empty string if even the
+/// ellipsis doesn't fit.
+pub fn ellipsize(text: &str, ellipsis: &str, fits: impl Fn(&str) -> bool) -> String {
+    if fits(text) {
+        return text.to_string();
+    }
+    if !fits(ellipsis) {
+        return String::new();
+    }
+
+    let boundaries: Vec<usize> = text.grapheme_indices(true).map(|(i, _)| i).collect();
+    let truncated = |count: usize| {
+        let end = boundaries.get(count).copied().unwrap_or(text.len());
+        format!("{}{}", text[..end].trim_end(), ellipsis)
+    };
+
+    // number of graphemes kept before the ellipsis
+    let (mut low, mut high) = (0, boundaries.len());
+    while low < high {
+        let mid = (low + high + 1) / 2;
+        if fits(&truncated(mid)) {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    truncated(low)
+}
+
+/// Hashes `bytes` with 64-bit FNV-1a, continuing from `state`.
+///
+/// Unlike std hashers, the result is stable across builds and platforms.
+pub fn fnv1a_64(state: u64, bytes: &[u8]) -> u64 {
+    const PRIME: u64 = 0x100000001b3;
+    bytes.iter().fold(state, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Initial state of [`fnv1a_64`].
+pub const FNV1A_64_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// Source of encoded data passed to loading functions as either a file path
+/// or the data itself.
+pub enum PathOrData<'a> {
+    Path(std::path::PathBuf),
+    Data(&'a [u8]),
+}
+
+impl<'a> PathOrData<'a> {
+    /// Treats `value` as a path if it's valid UTF-8 and names an existing
+    /// file, and as data otherwise.
+    pub fn new(value: &'a [u8]) -> Self {
+        match std::str::from_utf8(value) {
+            Ok(path) if !path.contains('\0') && std::path::Path::new(path).is_file() => {
+                PathOrData::Path(path.into())
+            }
+            _ => PathOrData::Data(value),
+        }
+    }
+
+    /// Fails if `self` is a path the file access policy of `lua` doesn't
+    /// allow `call` to read, and replaces it with the checked path otherwise.
+    pub fn check_read(self, lua: &mlua::Lua, call: &str) -> mlua::Result<Self> {
+        match self {
+            PathOrData::Path(path) => {
+                crate::fs_policy::check_read(lua, call, path).map(PathOrData::Path)
+            }
+            data => Ok(data),
+        }
+    }
+
+    /// Returns the whole data, reading the file if needed.
+    pub fn read(&self) -> std::io::Result<std::borrow::Cow<'a, [u8]>> {
+        match self {
+            PathOrData::Path(path) => std::fs::read(path).map(Into::into),
+            PathOrData::Data(data) => Ok((*data).into()),
+        }
+    }
+}
+
+/// Returns device space bounds of drawing `bounds` in local coordinates of
+/// `canvas` with `paint`, clipped to the canvas clip.
+///
+/// `None` bounds, or paints with effects whose bounds can't be computed,
+/// cover the whole clip. Returns `None` if the draw is clipped out.
+pub fn draw_device_bounds(
+    canvas: &Canvas,
+    bounds: Option<Rect>,
+    paint: Option<&Paint>,
+) -> Option<IRect> {
+    let clip = canvas.device_clip_bounds()?;
+    let local = match (bounds, paint) {
+        (Some(bounds), Some(paint)) => paint_bounds(bounds, paint),
+        (bounds, None) => bounds,
+        (None, _) => None,
+    };
+    let local = match local {
+        Some(it) => it,
+        None => return Some(clip),
+    };
+    let mut device = canvas.local_to_device_as_3x3().map_rect(local).0;
+    if paint.map_or(false, Paint::is_anti_alias) {
+        // anti-aliased edges can touch one extra pixel
+        device = device.with_outset((1.0, 1.0));
+    }
+    IRect::intersect(&device.round_out(), &clip)
+}
+
+/// Returns local bounds of drawing `bounds` with `paint`, or `None` if
+/// `paint` has effects that can spread it arbitrarily.
+fn paint_bounds(bounds: Rect, paint: &Paint) -> Option<Rect> {
+    if paint.path_effect().is_some() || paint.mask_filter().is_some() {
+        return None;
+    }
+    let mut result = bounds;
+    if paint.style() != PaintStyle::Fill {
+        let radius = paint.stroke_width().max(1.0) / 2.0;
+        // miter joins can extend past the stroke radius, square caps by
+        // up to a half diagonal
+        let scale = match paint.stroke_join() {
+            Join::Miter => paint.stroke_miter().max(std::f32::consts::SQRT_2),
+            _ => std::f32::consts::SQRT_2,
+        };
+        result = result.with_outset((radius * scale, radius * scale));
+    }
+    match paint.image_filter() {
+        Some(filter) if filter.can_compute_fast_bounds() => {
+            Some(filter.compute_fast_bounds(result))
+        }
+        Some(_) => None,
+        None => Some(result),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a check that text is at most `width` characters wide.
+    fn chars(width: usize) -> impl Fn(&str) -> bool {
+        move |it| it.chars().count() <= width
+    }
+
+    #[test]
+    fn fnv1a_64_matches_reference_values() {
+        assert_eq!(fnv1a_64(FNV1A_64_OFFSET, b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(FNV1A_64_OFFSET, b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a_64(FNV1A_64_OFFSET, b"foobar"), 0x85944171f73967e8);
+        // hashing can continue from previous state
+        let partial = fnv1a_64(FNV1A_64_OFFSET, b"foo");
+        assert_eq!(fnv1a_64(partial, b"bar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn ellipsize_keeps_fitting_text() {
+        assert_eq!(ellipsize("", "\u{2026}", chars(0)), "");
+        assert_eq!(ellipsize("short", "\u{2026}", chars(5)), "short");
+    }
+
+    #[test]
+    fn ellipsize_drops_text_narrower_than_ellipsis() {
+        assert_eq!(ellipsize("text", "...", chars(2)), "");
+    }
+
+    #[test]
+    fn ellipsize_cuts_cjk_and_trims_spaces() {
+        assert_eq!(
+            ellipsize(
+                "\u{6F22}\u{5B57}\u{30C6}\u{30B9}\u{30C8}",
+                "\u{2026}",
+                chars(3)
+            ),
+            "\u{6F22}\u{5B57}\u{2026}"
+        );
+        assert_eq!(ellipsize("ab cd", "\u{2026}", chars(4)), "ab\u{2026}");
+    }
+
+    #[test]
+    fn ellipsize_keeps_grapheme_clusters_whole() {
+        // "e" with a combining acute accent is a single cluster
+        assert_eq!(
+            ellipsize("he\u{301}llo", "\u{2026}", chars(4)),
+            "he\u{301}\u{2026}"
+        );
+        assert_eq!(ellipsize("he\u{301}llo", "\u{2026}", chars(3)), "h\u{2026}");
+
+        // family emoji joined with zero width joiners is five code points
+        let family = "\u{1F469}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let text = format!("{} family", family);
+        assert_eq!(ellipsize(&text, "\u{2026}", chars(5)), "\u{2026}");
+        assert_eq!(
+            ellipsize(&text, "\u{2026}", chars(7)),
+            format!("{}\u{2026}", family)
+        );
+
+        for width in 1..text.chars().count() {
+            let result = ellipsize(&text, "\u{2026}", chars(width));
+            let kept = result.trim_end_matches('\u{2026}');
+            assert!(
+                text.grapheme_indices(true)
+                    .any(|(i, _)| text[..i].trim_end() == kept),
+                "{:?} splits a grapheme cluster",
+                result
+            );
+        }
+    }
+}
@@ -12,7 +12,10 @@ use glam::{IVec2, UVec2};
 use mlua::prelude::*;
 use notify::Watcher;
 use render::{
-    frontend::{bindings::LuaCanvas, FrameBufferSurface},
+    frontend::{
+        bindings::{self, LuaCanvas},
+        FrameBufferSurface,
+    },
     RenderTarget, RenderTargetImpl, TargetConfig,
 };
 use script::{data::DataCollectors, events::EventBuffer};
@@ -36,6 +39,11 @@ pub struct MainState {
     script: Option<ScriptContext>,
     collectors: DataCollectors,
     evb: EventBuffer,
+    /// Origin of `Frame.time` reported to scripts.
+    clock: Instant,
+    frame_index: u64,
+    /// Whether scripts were told frames aren't being rendered.
+    paused: bool,
 }
 
 impl MainState {
@@ -57,10 +65,15 @@ impl MainState {
             script,
             collectors,
             evb,
+            clock: Instant::now(),
+            frame_index: 0,
+            paused: false,
         }
     }
 
     pub fn reload(&mut self, script_path: impl AsRef<Path>) {
+        // reloaded scripts start with a fresh frame state
+        self.paused = false;
         let script = match &mut self.script {
             Some(script) => {
                 script
@@ -95,12 +108,33 @@ impl MainState {
             .expect("can't update state");
     }
 
+    /// Reports to scripts that frames aren't being rendered.
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.paused = true;
+        if let Some(script) = &self.script {
+            bindings::frame::set_animating(script.lua(), false)
+                .some_or_log(Some("unable to update frame state".to_string()));
+        }
+    }
+
     pub fn draw_frame<Q, T: RenderTarget<Q>>(&mut self, target: &mut T, qh: T::QH) {
         let script = match &self.script {
             Some(it) => it,
             None => return,
         };
 
+        bindings::frame::set_frame_time(
+            script.lua(),
+            self.clock.elapsed().as_secs_f64(),
+            self.frame_index,
+        )
+        .some_or_log(Some("unable to update frame state".to_string()));
+        self.frame_index += 1;
+        self.paused = false;
+
         let draw_fn: LuaFunction = match script.draw_fn() {
             Some(it) => it,
             None => return,
@@ -127,6 +161,9 @@ impl MainState {
             .call::<(LuaCanvas, LuaTable), ()>((canvas, state_value))
             .some_or_log(Some("render function error".to_string()));
 
+        bindings::stats::check_budget(script.lua())
+            .some_or_log(Some("skia budget callback error".to_string()));
+
         target.push_frame(qh);
     }
 }
@@ -210,6 +247,7 @@ fn main() {
         if target.can_render() {
             state.draw_frame(&mut target, queue.handle());
         } else {
+            state.pause();
             sleep(Duration::from_millis(1));
         }
     }
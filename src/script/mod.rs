@@ -68,25 +68,19 @@ impl ScriptContext {
         })
     }
 
+    /// Replaces the Lua context with a new one running the script again.
+    ///
+    /// The previous context is torn down, so userdata it leaves behind
+    /// can't be used with the new one.
     pub fn reload(&mut self, path: impl AsRef<Path>) -> Result<(), ClunkyError> {
-        self.lua.expire_registry_values();
-        let init_script = std::fs::read_to_string(&self.source)
-            .map_err(|_| ClunkyError::InvalidScript(path.as_ref().to_path_buf()))?;
-
-        self.lua
-            .load(&init_script)
-            .set_name(self.source.to_str().unwrap_or("user script"))
-            .exec()
-            .some_or_log(None);
-
-        self.settings = self
-            .lua
-            .globals()
-            .get("settings")
-            .and_then(|it| Settings::load(&self.lua, it))
-            .some_or_log(Some("script missing 'settings' global".to_string()))
-            .unwrap_or_default();
-
+        let reloaded = ScriptContext::new(&self.source).map_err(|err| match err {
+            ClunkyError::InvalidScript(_) => {
+                ClunkyError::InvalidScript(path.as_ref().to_path_buf())
+            }
+            other => other,
+        })?;
+        // dropping the previous context tears it down
+        *self = reloaded;
         Ok(())
     }
 
@@ -114,6 +108,7 @@ impl ScriptContext {
 
 impl Drop for ScriptContext {
     fn drop(&mut self) {
+        crate::render::frontend::bindings::teardown(&self.lua).some_or_log(None);
         self.lua.expire_registry_values();
     }
 }